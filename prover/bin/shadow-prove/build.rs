@@ -0,0 +1,22 @@
+use std::process::Command;
+
+// Embed the git commit as `SHADOW_PROVING_GIT_SHA`, labelling `shadow_build_info`. A build
+// without the repository, e.g. in a docker context, passes it as `GIT_SHA` instead.
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/refs", git_dir);
+    }
+    let sha = std::env::var("GIT_SHA")
+        .ok()
+        .filter(|sha| !sha.is_empty())
+        .or_else(|| git(&["rev-parse", "--short=12", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=SHADOW_PROVING_GIT_SHA={}", sha);
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -17,4 +17,99 @@ sol!(
     #[sol(rpc)]
     SP1Verifier,
     "abi/SP1Verifier.json"
-);
\ No newline at end of file
+);
+
+sol! {
+    /// Optional batched getter for the block range and transaction count of a committed batch,
+    /// served by the rollup or a helper contract (`SHADOW_PROVING_BATCH_BLOCKS_GETTER`).
+    /// `startBlock..=endBlock` are the L2 blocks of the batch, `txnCount` their transactions.
+    #[sol(rpc)]
+    interface BatchBlocksGetter {
+        function batchBlocks(uint256 batchIndex)
+            external
+            view
+            returns (uint64 startBlock, uint64 endBlock, uint64 txnCount);
+    }
+}
+
+sol! {
+    /// Multicall3, batching the rollup reads of a per-block inspection
+    /// (`SHADOW_PROVING_MULTICALL`).
+    #[sol(rpc)]
+    interface Multicall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        struct Call3Result {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls)
+            external
+            payable
+            returns (Call3Result[] memory returnData);
+    }
+}
+
+sol! {
+    /// Morph L2 predeploy maintaining the withdrawal tree, at `0x5300..01`.
+    #[sol(rpc)]
+    interface L2ToL1MessagePasser {
+        function messageRoot() external view returns (bytes32);
+    }
+}
+
+sol! {
+    /// Optional batch header codec version getters of the rollup contract.
+    #[sol(rpc)]
+    interface RollupVersion {
+        function batchHeaderVersion() external view returns (uint8);
+        function version() external view returns (uint8);
+    }
+}
+
+sol! {
+    /// Optional multi-batch prove of the shadow rollup (`SHADOW_PROVING_MULTI_PROVE_MAX`).
+    /// `batchIndexes` are contiguous and ascending, `proofs[i]` proves `batchIndexes[i]`.
+    #[sol(rpc)]
+    interface MultiProve {
+        function proveStates(uint64[] calldata batchIndexes, bytes[] calldata proofs) external;
+    }
+}
+
+sol! {
+    /// Alarm contract recording detected mismatches (`SHADOW_PROVING_ALARM_CONTRACT`).
+    #[sol(rpc)]
+    interface ShadowAlarm {
+        function raiseAlarm(uint256 batchIndex, bytes32 expected, bytes32 actual) external;
+    }
+}
+
+sol! {
+    /// Optional finalization window of the shadow rollup, laid out like the rollup's
+    /// (`SHADOW_PROVING_CHALLENGE_WINDOW`).
+    #[sol(rpc)]
+    interface ChallengeWindow {
+        function batchDataStore(uint256)
+            external
+            view
+            returns (
+                uint256 originTimestamp,
+                uint256 finalizeTimestamp,
+                uint256 blockNumber,
+                uint256 signedSequencersBitmap
+            );
+    }
+}
+
+sol! {
+    /// Optional OpenZeppelin-style pause getter of the shadow rollup.
+    #[sol(rpc)]
+    interface Pausable {
+        function paused() external view returns (bool);
+    }
+}
@@ -0,0 +1,98 @@
+//! On-chain attestation of detected mismatches.
+//!
+//! With `SHADOW_PROVING_ALARM_CONTRACT` set, every mismatch found by the withdrawal root and state
+//! chain checks (in observe or strict mode) is also sent by the prover's signer to the alarm
+//! contract, leaving an immutable record beside the logs and metrics. The contract is expected to
+//! expose:
+//!
+//! ```solidity
+//! /// `expected` is the reference value (L2 state, proven previous batch), `actual` the value
+//! /// found in the batch header. Must accept calls from the prover's signer.
+//! function raiseAlarm(uint256 batchIndex, bytes32 expected, bytes32 actual) external;
+//! ```
+//!
+//! It would typically emit an event for monitors to pick up. A failed alarm is logged and never
+//! affects the batch being checked.
+
+use crate::abi::ShadowAlarm::{self, ShadowAlarmInstance};
+use alloy::{
+    network::{Network, ReceiptResponse},
+    primitives::{Address, B256, U256},
+    providers::Provider,
+    transports::Transport,
+};
+
+/// Sender of `raiseAlarm` transactions, a no-op without an alarm contract.
+#[derive(Clone, Debug)]
+pub struct Alarm<T, P, N> {
+    contract: Option<ShadowAlarmInstance<T, P, N>>,
+}
+
+impl<T, P, N> Alarm<T, P, N>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    pub fn new(alarm_contract: Option<Address>, wallet: P) -> Self {
+        Self { contract: alarm_contract.map(|address| ShadowAlarm::new(address, wallet)) }
+    }
+
+    pub async fn raise(&self, batch_index: u64, expected: B256, actual: B256) {
+        let Some(contract) = &self.contract else {
+            return;
+        };
+        log::info!("raise alarm of batch {:?} on {:?}", batch_index, contract.address());
+        let pending_tx =
+            match contract.raiseAlarm(U256::from(batch_index), expected, actual).send().await {
+                Ok(pending_tx) => pending_tx,
+                Err(e) => {
+                    log::error!("send tx of alarm.raise_alarm error: {:#?}", e);
+                    return;
+                }
+            };
+        match pending_tx.get_receipt().await {
+            Ok(receipt) if receipt.status() => {
+                log::info!("alarm raised, tx hash: {:?}", receipt.transaction_hash())
+            }
+            Ok(receipt) => {
+                log::error!(
+                    "tx of alarm.raise_alarm failed, tx hash: {:?}",
+                    receipt.transaction_hash()
+                )
+            }
+            Err(e) => log::error!("get receipt of alarm.raise_alarm error: {:#?}", e),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_raise_alarm() {
+    use crate::mock_rpc::{call_selector, receipt, MockRpc};
+    use alloy::{primitives::Bytes, sol_types::SolCall};
+    use serde_json::json;
+
+    let tx_hash = B256::repeat_byte(0x42);
+    let rpc = MockRpc::start(move |method, _| match method {
+        "eth_sendTransaction" => Some(json!(tx_hash)),
+        "eth_getTransactionReceipt" => receipt(tx_hash, true),
+        "eth_blockNumber" => Some(json!("0x1")),
+        _ => None,
+    });
+    let alarm_contract = Address::repeat_byte(0xa1);
+    let (expected, actual) = (B256::repeat_byte(1), B256::repeat_byte(2));
+
+    Alarm::new(Some(alarm_contract), rpc.provider()).raise(7, expected, actual).await;
+    let sent = rpc.requests("eth_sendTransaction");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0][0]["to"], json!(alarm_contract));
+    assert_eq!(call_selector(&sent[0]), ShadowAlarm::raiseAlarmCall::SELECTOR);
+    let input = sent[0][0]["input"].as_str().or(sent[0][0]["data"].as_str()).unwrap();
+    let input: Bytes = input.parse().unwrap();
+    let call = ShadowAlarm::raiseAlarmCall::abi_decode(&input, false).unwrap();
+    assert_eq!((call.batchIndex, call.expected, call.actual), (U256::from(7), expected, actual));
+
+    // Disabled without an alarm contract.
+    Alarm::new(None, rpc.provider()).raise(7, expected, actual).await;
+    assert_eq!(rpc.requests("eth_sendTransaction").len(), 1);
+}
@@ -0,0 +1,189 @@
+//! Failed stages, posted to `SHADOW_PROVING_ALERT_WEBHOOK`.
+//!
+//! A failure of the commit or the prove stage is posted as a JSON [`Alert`], e.g. to a chat
+//! incoming webhook, so on-call hears of it without standing up Alertmanager. During an outage
+//! every tick fails, so a stage alerts at most once per
+//! `SHADOW_PROVING_ALERT_WEBHOOK_DEBOUNCE_SECS`; the next alert of the stage tells how many
+//! failures were suppressed meanwhile.
+//!
+//! Posting never blocks the loop. An alert finding the queue full, or whose post fails, is
+//! dropped and counted in `shadow_alerts_dropped_total`.
+
+use crate::{
+    audit::{Decision, Outcome, Tick},
+    config::Config,
+    failure::Stage,
+    metrics::{self, METRICS},
+};
+use serde::Serialize;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+/// Alerts waiting to be posted before new ones are dropped.
+const QUEUE_SIZE: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Alert {
+    pub stage: &'static str,
+    /// The batch the stage failed on, if it got to select one.
+    pub batch_index: Option<u64>,
+    pub error: String,
+    /// Unix timestamp of the tick the stage failed in.
+    pub timestamp: u64,
+    /// Failures of the stage not alerted since its previous alert.
+    pub suppressed: u64,
+}
+
+#[derive(Debug, Default)]
+struct Debounce {
+    last_alert: Option<u64>,
+    suppressed: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    commit: Debounce,
+    prove: Debounce,
+}
+
+/// Emitter of the alerts of the failed stages, shared by the loops of the stages.
+#[derive(Clone, Debug)]
+pub struct AlertWebhook {
+    debounce: Duration,
+    state: Arc<Mutex<State>>,
+    queue: mpsc::Sender<Alert>,
+}
+
+impl AlertWebhook {
+    /// Start posting to the configured webhook; None when no webhook is configured.
+    pub fn spawn(config: &Config) -> Option<Self> {
+        let url = config.alert_webhook.clone()?;
+        let (alerts, mut queue) = Self::new(config.alert_webhook_debounce);
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            while let Some(alert) = queue.recv().await {
+                post(&client, &url, &alert).await;
+            }
+        });
+        Some(alerts)
+    }
+
+    fn new(debounce: Duration) -> (Self, mpsc::Receiver<Alert>) {
+        let (queue, receiver) = mpsc::channel(QUEUE_SIZE);
+        (Self { debounce, state: Arc::default(), queue }, receiver)
+    }
+
+    /// Queue an alert for each stage of `tick` that failed, unless debounced.
+    pub fn record_tick(&self, tick: &Tick) {
+        let mut state = self.state.lock().unwrap();
+        let State { commit, prove } = &mut *state;
+        let stages = [(Stage::Commit, &tick.commit, commit), (Stage::Prove, &tick.prove, prove)];
+        for (stage, decision, debounce) in stages {
+            let Some(decision) = decision.as_ref().filter(|d| d.outcome == Outcome::Failed) else {
+                continue;
+            };
+            let debounced = debounce
+                .last_alert
+                .is_some_and(|last| tick.timestamp < last + self.debounce.as_secs());
+            if debounced {
+                debounce.suppressed += 1;
+                continue;
+            }
+            let alert = alert(stage, decision, tick.timestamp, debounce.suppressed);
+            debounce.last_alert = Some(tick.timestamp);
+            debounce.suppressed = 0;
+            if self.queue.try_send(alert).is_err() {
+                log::warn!("alert queue full, drop the alert of a {} stage", stage.as_str());
+                metrics::inc(&METRICS.shadow_alerts_dropped_total);
+            }
+        }
+    }
+}
+
+fn alert(stage: Stage, decision: &Decision, timestamp: u64, suppressed: u64) -> Alert {
+    Alert {
+        stage: stage.as_str(),
+        batch_index: decision.batch_index,
+        error: decision.reason.clone().unwrap_or_default(),
+        timestamp,
+        suppressed,
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, alert: &Alert) {
+    let body = serde_json::to_vec(alert).expect("alert serializes");
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => {
+            metrics::inc(&METRICS.shadow_alerts_sent_total);
+            return;
+        }
+        Ok(response) => log::warn!("alert rejected by the webhook: {}", response.status()),
+        Err(e) => log::warn!("post alert error: {}", e),
+    }
+    metrics::inc(&METRICS.shadow_alerts_dropped_total);
+}
+
+#[test]
+fn test_record_tick() {
+    let (alerts, mut queue) = AlertWebhook::new(Duration::from_secs(300));
+    let failed = |index: u64, reason: &str| {
+        let mut decision = Decision { batch_index: Some(index), ..Default::default() };
+        decision.fail(reason);
+        Some(decision)
+    };
+
+    alerts.record_tick(&Tick {
+        timestamp: 100,
+        commit: failed(7, "commit_batch tx failed"),
+        prove: None,
+    });
+    // Within the debounce of the commit stage, not of the prove one.
+    alerts.record_tick(&Tick {
+        timestamp: 110,
+        commit: failed(7, "commit_batch tx failed"),
+        prove: failed(6, "prove batch 6 error: timeout"),
+    });
+    alerts.record_tick(&Tick { timestamp: 200, commit: failed(7, "nonce too low"), prove: None });
+    // A stage that did not fail is no alert.
+    let mut proven = Decision { batch_index: Some(6), ..Default::default() };
+    proven.prove(alloy::primitives::B256::repeat_byte(1));
+    alerts.record_tick(&Tick { timestamp: 300, commit: None, prove: Some(proven) });
+    alerts.record_tick(&Tick { timestamp: 400, commit: failed(7, "nonce too low"), prove: None });
+
+    let sent: Vec<Alert> = std::iter::from_fn(|| queue.try_recv().ok()).collect();
+    assert_eq!(
+        sent,
+        vec![
+            Alert {
+                stage: "commit",
+                batch_index: Some(7),
+                error: String::from("commit_batch tx failed"),
+                timestamp: 100,
+                suppressed: 0,
+            },
+            Alert {
+                stage: "prove",
+                batch_index: Some(6),
+                error: String::from("prove batch 6 error: timeout"),
+                timestamp: 110,
+                suppressed: 0,
+            },
+            Alert {
+                stage: "commit",
+                batch_index: Some(7),
+                error: String::from("nonce too low"),
+                timestamp: 400,
+                suppressed: 2,
+            },
+        ]
+    );
+}
@@ -0,0 +1,164 @@
+//! Proof-request artifact carrying a committed batch to an offline prover.
+//!
+//! `shadow-proving export-request <index>` writes the artifact of a batch committed to the shadow
+//! rollup; the proof generated offline is submitted with `shadow-proving submit-proof <artifact>
+//! <proof>`, which checks the artifact still matches the on-chain commit.
+//!
+//! The artifact is a JSON object:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "shadow_rollup": "0x…",
+//!   "layer2_chain_id": 2818,
+//!   "batch": { "batch_index": 7, "start_block": 101, "end_block": 110 },
+//!   "batch_store": {
+//!     "prev_state_root": "0x…", "post_state_root": "0x…", "withdrawal_root": "0x…",
+//!     "data_hash": "0x…", "blob_versioned_hash": "0x…", "sequencer_set_verify_hash": "0x…"
+//!   }
+//! }
+//! ```
+//!
+//! `batch_store` is the batch as committed to the shadow rollup, i.e. the public inputs of the
+//! proof. Adding an optional field keeps the version; renaming, removing or changing the meaning
+//! of a field bumps [`ARTIFACT_VERSION`], and artifacts of another version are rejected.
+
+use crate::{BatchInfo, ShadowRollup};
+use alloy::primitives::{Address, B256};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// Version of the artifact format written by this build.
+pub const ARTIFACT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProveArtifact {
+    pub version: u32,
+    pub shadow_rollup: Address,
+    pub layer2_chain_id: u64,
+    pub batch: BatchInfo,
+    pub batch_store: BatchStoreFields,
+}
+
+/// Fields of the batch committed to the shadow rollup.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchStoreFields {
+    pub prev_state_root: B256,
+    pub post_state_root: B256,
+    pub withdrawal_root: B256,
+    pub data_hash: B256,
+    pub blob_versioned_hash: B256,
+    pub sequencer_set_verify_hash: B256,
+}
+
+impl From<ShadowRollup::committedBatchStoresReturn> for BatchStoreFields {
+    fn from(store: ShadowRollup::committedBatchStoresReturn) -> Self {
+        Self {
+            prev_state_root: store.prevStateRoot,
+            post_state_root: store.postStateRoot,
+            withdrawal_root: store.withdrawalRoot,
+            data_hash: store.dataHash,
+            blob_versioned_hash: store.blobVersionedHash,
+            sequencer_set_verify_hash: store.sequencerSetVerifyHash,
+        }
+    }
+}
+
+impl From<&ShadowRollup::BatchStore> for BatchStoreFields {
+    fn from(store: &ShadowRollup::BatchStore) -> Self {
+        Self {
+            prev_state_root: store.prevStateRoot,
+            post_state_root: store.postStateRoot,
+            withdrawal_root: store.withdrawalRoot,
+            data_hash: store.dataHash,
+            blob_versioned_hash: store.blobVersionedHash,
+            sequencer_set_verify_hash: store.sequencerSetVerifyHash,
+        }
+    }
+}
+
+impl ProveArtifact {
+    pub fn new(
+        shadow_rollup: Address,
+        layer2_chain_id: u64,
+        batch: BatchInfo,
+        batch_store: BatchStoreFields,
+    ) -> Self {
+        Self { version: ARTIFACT_VERSION, shadow_rollup, layer2_chain_id, batch, batch_store }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).expect("artifact serializes to json")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        // Check the version first, other versions may not parse as this one.
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+        let Versioned { version } =
+            serde_json::from_slice(bytes).context("not a prove request artifact")?;
+        if version != ARTIFACT_VERSION {
+            return Err(anyhow!(
+                "unsupported artifact version {}, expected {}",
+                version,
+                ARTIFACT_VERSION
+            ));
+        }
+        serde_json::from_slice(bytes).context("malformed prove request artifact")
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), anyhow::Error> {
+        fs::write(path, self.to_bytes()).with_context(|| format!("write artifact {:?}", path))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, anyhow::Error> {
+        let bytes = fs::read(path).with_context(|| format!("read artifact {:?}", path))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+#[test]
+fn test_artifact_round_trip() {
+    use crate::size_class::SizeClass;
+
+    let artifact = ProveArtifact::new(
+        Address::repeat_byte(0x53),
+        2818,
+        BatchInfo {
+            batch_index: 7,
+            start_block: 101,
+            end_block: 110,
+            size_class: SizeClass::Small,
+        },
+        BatchStoreFields {
+            prev_state_root: B256::repeat_byte(1),
+            post_state_root: B256::repeat_byte(2),
+            withdrawal_root: B256::repeat_byte(3),
+            data_hash: B256::repeat_byte(4),
+            blob_versioned_hash: B256::repeat_byte(5),
+            sequencer_set_verify_hash: B256::repeat_byte(6),
+        },
+    );
+    assert_eq!(ProveArtifact::from_bytes(&artifact.to_bytes()).unwrap(), artifact);
+
+    // The field names are the stable format.
+    let json: serde_json::Value = serde_json::from_slice(&artifact.to_bytes()).unwrap();
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["layer2_chain_id"], 2818);
+    assert_eq!(json["batch"]["end_block"], 110);
+    assert_eq!(json["batch_store"]["withdrawal_root"], B256::repeat_byte(3).to_string());
+
+    let path = std::env::temp_dir().join(format!("shadow-artifact-{}.json", std::process::id()));
+    artifact.write(&path).unwrap();
+    assert_eq!(ProveArtifact::read(&path).unwrap(), artifact);
+    fs::remove_file(path).unwrap();
+
+    let mut json = json;
+    json["version"] = 2.into();
+    let err = ProveArtifact::from_bytes(&serde_json::to_vec(&json).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("unsupported artifact version 2"));
+    assert!(ProveArtifact::from_bytes(b"{\"version\": 1}").is_err());
+}
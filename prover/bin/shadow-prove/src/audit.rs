@@ -0,0 +1,238 @@
+//! Tamper-evident ledger of the proving decisions.
+//!
+//! With `SHADOW_PROVING_AUDIT_FILE` set, every tick appends one JSON line to the file holding the
+//! commit decision (batch, roots decoded from its header, outcome and reason, tx hash) and the
+//! prove decision of the tick. Stages not run by the process are absent.
+//!
+//! ```json
+//! {"seq":7,"prev_hash":"0x…","tick":{"timestamp":1718000000,"commit":{…},"prove":{…}},"hash":"0x…"}
+//! ```
+//!
+//! `hash` is `keccak256` of the JSON array `[seq, prev_hash, tick]` and `prev_hash` the hash of
+//! the previous entry, zero for the first one. Editing, inserting, reordering or removing an entry
+//! breaks the chain from there on, which `shadow-proving verify-audit <file>` reports. Dropping
+//! the last entries cannot be told from a shorter log: operators needing that guarantee should
+//! record the head hash printed by `verify-audit` elsewhere.
+//!
+//! The file is only ever appended to. An existing log is verified when opened and the prover
+//! refuses to extend a broken chain.
+
+use crate::artifact::BatchStoreFields;
+use alloy::primitives::{keccak256, B256};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Committed,
+    Proven,
+    #[default]
+    Skipped,
+    Failed,
+}
+
+/// What a stage decided for a batch.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decision {
+    pub batch_index: Option<u64>,
+    pub outcome: Outcome,
+    pub reason: Option<String>,
+    /// Roots decoded from the batch header, commit decisions only.
+    pub roots: Option<BatchStoreFields>,
+    /// Version of the decoded batch header, commit decisions only. Absent from entries written
+    /// before it was recorded, which keeps them verifiable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_version: Option<u8>,
+    pub tx_hash: Option<B256>,
+}
+
+impl Decision {
+    pub fn skip(&mut self, reason: impl Into<String>) {
+        self.outcome = Outcome::Skipped;
+        self.reason = Some(reason.into());
+    }
+
+    pub fn fail(&mut self, reason: impl Into<String>) {
+        self.outcome = Outcome::Failed;
+        self.reason = Some(reason.into());
+    }
+
+    pub fn commit(&mut self, tx_hash: B256) {
+        self.outcome = Outcome::Committed;
+        self.tx_hash = Some(tx_hash);
+    }
+
+    pub fn prove(&mut self, tx_hash: B256) {
+        self.outcome = Outcome::Proven;
+        self.tx_hash = Some(tx_hash);
+    }
+}
+
+/// Decisions of one tick.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tick {
+    /// Unix timestamp of the tick start.
+    pub timestamp: u64,
+    pub commit: Option<Decision>,
+    pub prove: Option<Decision>,
+}
+
+/// A line of the audit log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub prev_hash: B256,
+    pub tick: Tick,
+    pub hash: B256,
+}
+
+impl AuditEntry {
+    fn new(seq: u64, prev_hash: B256, tick: Tick) -> Self {
+        let hash = entry_hash(seq, prev_hash, &tick);
+        Self { seq, prev_hash, tick, hash }
+    }
+}
+
+fn entry_hash(seq: u64, prev_hash: B256, tick: &Tick) -> B256 {
+    keccak256(serde_json::to_vec(&(seq, prev_hash, tick)).expect("audit tick serializes"))
+}
+
+/// Number of entries of a valid log and hash of the last one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainHead {
+    pub len: u64,
+    pub hash: B256,
+}
+
+/// Check every entry of a log, failing at the first one breaking the chain.
+///
+/// Entries must be exactly as written: a line that parses but differs from its serialization,
+/// e.g. with an added field, is rejected as well.
+pub fn verify_chain(content: &str) -> Result<ChainHead, anyhow::Error> {
+    let mut head = ChainHead { len: 0, hash: B256::ZERO };
+    for (line, entry) in content.lines().enumerate() {
+        let line = line + 1;
+        let parsed: AuditEntry = serde_json::from_str(entry)
+            .with_context(|| format!("line {}: not an audit entry", line))?;
+        if serde_json::to_string(&parsed)? != entry {
+            anyhow::bail!("line {}: entry was rewritten", line);
+        }
+        if parsed.seq != head.len {
+            anyhow::bail!("line {}: seq {} where {} was expected", line, parsed.seq, head.len);
+        }
+        if parsed.prev_hash != head.hash {
+            anyhow::bail!("line {}: prev_hash does not match the previous entry", line);
+        }
+        if parsed.hash != entry_hash(parsed.seq, parsed.prev_hash, &parsed.tick) {
+            anyhow::bail!("line {}: hash does not match the entry", line);
+        }
+        head = ChainHead { len: head.len + 1, hash: parsed.hash };
+    }
+    Ok(head)
+}
+
+/// Verify the audit log file; a missing file is an empty log.
+pub fn verify_file(path: &Path) -> Result<ChainHead, anyhow::Error> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(anyhow!("read audit log {:?}: {}", path, e)),
+    };
+    verify_chain(&content).with_context(|| format!("audit log {:?} is broken", path))
+}
+
+/// Append-only audit log file.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    head: ChainHead,
+}
+
+impl AuditLog {
+    /// Open the log, verifying the entries already in it.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, anyhow::Error> {
+        let path = path.into();
+        let head = verify_file(&path)?;
+        Ok(Self { path, head })
+    }
+
+    pub fn append(&mut self, tick: Tick) -> Result<AuditEntry, anyhow::Error> {
+        let entry = AuditEntry::new(self.head.len, self.head.hash, tick);
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("open audit log {:?}", self.path))?;
+        file.write_all(line.as_bytes())
+            .and_then(|()| file.sync_data())
+            .with_context(|| format!("append to audit log {:?}", self.path))?;
+        self.head = ChainHead { len: self.head.len + 1, hash: entry.hash };
+        Ok(entry)
+    }
+}
+
+#[test]
+fn test_audit_chain() {
+    let path = std::env::temp_dir().join(format!("shadow-audit-{}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let tick = |timestamp: u64, outcome: Outcome| Tick {
+        timestamp,
+        commit: Some(Decision {
+            batch_index: Some(timestamp),
+            outcome,
+            reason: (outcome == Outcome::Skipped).then(|| String::from("too many txn")),
+            roots: None,
+            header_version: (outcome == Outcome::Committed).then_some(1),
+            tx_hash: (outcome == Outcome::Committed).then_some(B256::repeat_byte(timestamp as u8)),
+        }),
+        prove: None,
+    };
+
+    let mut audit = AuditLog::open(&path).unwrap();
+    let first = audit.append(tick(1, Outcome::Committed)).unwrap();
+    assert_eq!(first.prev_hash, B256::ZERO);
+    audit.append(tick(2, Outcome::Skipped)).unwrap();
+    // Reopening continues the chain.
+    let mut audit = AuditLog::open(&path).unwrap();
+    let last = audit.append(tick(3, Outcome::Committed)).unwrap();
+    assert_eq!(last.seq, 2);
+    assert_eq!(verify_file(&path).unwrap(), ChainHead { len: 3, hash: last.hash });
+
+    let content = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    let broken_at = |lines: &[String]| {
+        let err = verify_chain(&lines.join("\n")).unwrap_err().to_string();
+        err.split(':').next().unwrap().to_string()
+    };
+
+    // An edited decision no longer matches its hash.
+    let mut tampered: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    tampered[1] = tampered[1].replace("\"skipped\"", "\"committed\"");
+    assert_eq!(broken_at(&tampered), "line 2");
+
+    // Rehashing the edited entry breaks the link of the next one.
+    let mut entry: AuditEntry = serde_json::from_str(&tampered[1]).unwrap();
+    entry.hash = entry_hash(entry.seq, entry.prev_hash, &entry.tick);
+    tampered[1] = serde_json::to_string(&entry).unwrap();
+    assert_eq!(broken_at(&tampered), "line 3");
+
+    // Removed and added entries and fields are caught too.
+    let removed = vec![lines[0].to_string(), lines[2].to_string()];
+    assert_eq!(broken_at(&removed), "line 2");
+    let mut added: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    added[0] = added[0].replacen('{', "{\"note\":\"ok\",", 1);
+    assert_eq!(broken_at(&added), "line 1");
+
+    // A broken log is not extended.
+    fs::write(&path, tampered.join("\n")).unwrap();
+    assert!(AuditLog::open(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,103 @@
+//! Balance of the signer, served in ETH as `shadow_wallet_balance`.
+//!
+//! The balance is read before every proof, and by a background task every
+//! `SHADOW_PROVING_BALANCE_REFRESH_SECS` so the gauge stays current while no batch is proven.
+//! A warning is logged when the balance falls below `SHADOW_PROVING_LOW_BALANCE_ETH`, and again
+//! only after it was topped up above it.
+
+use crate::{config::Config, failover::FailoverTransport, metrics::METRICS};
+use alloy::{
+    primitives::{utils::format_ether, Address},
+    providers::{Provider, RootProvider},
+    transports::TransportResult,
+};
+use std::time::Duration;
+
+/// Read the balance of `wallet` and set the gauge; returns the balance in ETH.
+pub async fn record_wallet_balance(
+    provider: &RootProvider<FailoverTransport>,
+    wallet: Address,
+) -> TransportResult<f64> {
+    let balance = provider.get_balance(wallet).await?;
+    let eth = format_ether(balance).parse().unwrap_or(0.0);
+    METRICS.shadow_wallet_balance.set(eth);
+    Ok(eth)
+}
+
+/// Whether the balance is below the threshold, warning once per drop.
+#[derive(Clone, Copy, Debug)]
+pub struct LowBalance {
+    threshold: f64,
+    low: bool,
+}
+
+impl LowBalance {
+    pub fn new(threshold: f64) -> Self {
+        Self { threshold, low: false }
+    }
+
+    /// Record a balance, returning whether it just fell below the threshold.
+    pub fn observe(&mut self, wallet: Address, balance: f64) -> bool {
+        let low = balance < self.threshold;
+        let dropped = low && !self.low;
+        if dropped {
+            log::warn!(
+                "balance of the signer {:?} is {} ETH, below {} ETH, top it up",
+                wallet,
+                balance,
+                self.threshold
+            );
+        } else if self.low && !low {
+            log::info!("balance of the signer {:?} is back to {} ETH", wallet, balance);
+        }
+        self.low = low;
+        dropped
+    }
+}
+
+/// Refresh the balance of `wallet` in the background; no-op with a zero refresh interval.
+pub fn spawn(config: &Config, provider: RootProvider<FailoverTransport>, wallet: Address) {
+    if config.balance_refresh.is_zero() {
+        return;
+    }
+    let refresh = config.balance_refresh;
+    let mut low_balance = LowBalance::new(config.low_balance_threshold);
+    tokio::spawn(async move {
+        loop {
+            match record_wallet_balance(&provider, wallet).await {
+                Ok(balance) => {
+                    low_balance.observe(wallet, balance);
+                }
+                Err(e) => log::error!("get balance of the signer {:?} error: {}", wallet, e),
+            }
+            tokio::time::sleep(refresh).await;
+        }
+    });
+}
+
+#[tokio::test]
+async fn test_record_wallet_balance() {
+    use crate::mock_rpc::MockRpc;
+    use serde_json::json;
+
+    // 1.5 ETH.
+    let rpc = MockRpc::start(|method, _| match method {
+        "eth_getBalance" => Some(json!("0x14d1120d7b160000")),
+        _ => None,
+    });
+    let wallet = Address::repeat_byte(0x33);
+    assert_eq!(record_wallet_balance(&rpc.provider(), wallet).await.unwrap(), 1.5);
+    assert_eq!(rpc.requests("eth_getBalance")[0][0], json!(wallet));
+}
+
+#[test]
+fn test_low_balance() {
+    let wallet = Address::repeat_byte(0x33);
+    let mut low_balance = LowBalance::new(0.1);
+    assert!(!low_balance.observe(wallet, 1.5));
+    assert!(low_balance.observe(wallet, 0.09));
+    // Warned once per drop.
+    assert!(!low_balance.observe(wallet, 0.05));
+    assert!(!low_balance.observe(wallet, 0.2));
+    assert!(low_balance.observe(wallet, 0.01));
+}
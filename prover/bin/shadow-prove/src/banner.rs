@@ -0,0 +1,284 @@
+//! Startup banner summarizing what the prover is configured to do.
+//!
+//! URLs are reduced to scheme, host and port: RPC providers carry API keys in the path or query
+//! and queue URLs may carry credentials. The signer is shown by address only.
+
+use crate::config::Config;
+use alloy::{primitives::Address, transports::http::reqwest::header::HeaderMap};
+use std::fmt::Write;
+
+/// Deployment facts resolved at startup, shown beside the config.
+#[derive(Clone, Debug)]
+pub struct Deployment {
+    pub l1_chain_id: Option<u64>,
+    /// Chain of the shadow rollup, which may differ from the rollup's.
+    pub verify_l1_chain_id: Option<u64>,
+    pub l2_chain_id: Option<u64>,
+    pub l1_rpc: String,
+    pub l1_verify_rpc: String,
+    pub l2_rpc: String,
+    pub rollup: Address,
+    pub shadow_rollup: Address,
+    pub signer: Address,
+}
+
+pub fn startup_banner(config: &Config, deployment: &Deployment) -> String {
+    let chain = |id: Option<u64>| id.map_or_else(|| "unknown".to_string(), |id| id.to_string());
+    let url = |url: Option<&str>| url.map_or_else(|| "off".to_string(), redact_url);
+    let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+    // Names only, the values carry API keys.
+    let headers = |headers: &HeaderMap| match headers.is_empty() {
+        true => "off".to_string(),
+        false => headers.keys().map(|name| name.as_str()).collect::<Vec<_>>().join(" "),
+    };
+
+    let mut banner = String::new();
+    let mut line = |label: &str, value: String| {
+        let _ = writeln!(banner, "  {:<22}{}", format!("{}:", label), value);
+    };
+    line("version", format!("{} ({})", env!("CARGO_PKG_VERSION"), env!("SHADOW_PROVING_GIT_SHA")));
+    line("role", format!("{:?}", config.role));
+    line(
+        "l1",
+        format!(
+            "chain {}, rpc {}, verify chain {}, verify rpc {}",
+            chain(deployment.l1_chain_id),
+            redact_urls(&deployment.l1_rpc),
+            chain(deployment.verify_l1_chain_id),
+            redact_urls(&deployment.l1_verify_rpc)
+        ),
+    );
+    line(
+        "l2",
+        format!("chain {}, rpc {}", chain(deployment.l2_chain_id), redact_urls(&deployment.l2_rpc)),
+    );
+    line("rollup", format!("{:?}", deployment.rollup));
+    line("shadow rollup", format!("{:?}", deployment.shadow_rollup));
+    line("signer", format!("{:?}", deployment.signer));
+    line("loop interval", format!("{:?}", config.loop_interval));
+    line("commit subscription", url(config.l1_ws.as_deref()));
+    line(
+        "lookback blocks",
+        match config.log_chunk_blocks {
+            0 => config.lookback_blocks.to_string(),
+            chunk => format!("{}, in chunks of {}", config.lookback_blocks, chunk),
+        },
+    );
+    line("batch buffer", config.batch_buffer.to_string());
+    line("confirmations", format!("{} blocks", config.confirmations));
+    line("size limits", format!("{} blocks, {} txns", config.max_block, config.max_txn));
+    line(
+        "block fetch",
+        format!(
+            "{} concurrent, multicall {}, block receipts {}",
+            config.block_fetch_concurrency,
+            config.multicall.map_or("off".to_string(), |a| format!("{:?}", a)),
+            on_off(config.block_receipts)
+        ),
+    );
+    line(
+        "checks",
+        format!(
+            "withdrawal root {:?}, state chain {:?}, sequencer {}, authorized signer {}, pause {}",
+            config.verify_withdrawal_root,
+            config.verify_state_chain,
+            if config.sequencer_allowlist.is_empty() {
+                "off".to_string()
+            } else {
+                format!("{:?} of {}", config.verify_sequencer, config.sequencer_allowlist.len())
+            },
+            on_off(config.check_authorized_signer),
+            on_off(config.pause_check)
+        ),
+    );
+    line(
+        "blob hash check",
+        format!("{:?}, beacon {}", config.verify_blob_hash, url(config.beacon_rpc.as_deref())),
+    );
+    line(
+        "batch range",
+        match (config.min_batch, config.max_batch) {
+            (None, None) => "all".to_string(),
+            (min, max) => format!(
+                "{}..{}",
+                min.map_or(String::new(), |min| min.to_string()),
+                max.map_or(String::new(), |max| format!("={}", max))
+            ),
+        },
+    );
+    line("startup catch-up", on_off(config.startup_catchup).to_string());
+    line(
+        "catch-up",
+        if config.catchup {
+            format!("every tick, up to {} batches", config.catchup_max_batches)
+        } else {
+            "off".to_string()
+        },
+    );
+    line("multi prove", format!("up to {} batches", config.multi_prove_max));
+    line("in-flight commits", config.max_inflight_commits.to_string());
+    line("commit simulation", on_off(config.simulate_before_send).to_string());
+    line("dry run", on_off(config.dry_run).to_string());
+    line(
+        "commit receipt",
+        format!(
+            "timeout {:?}, {} fee bumps of {}% up to {} gwei",
+            config.commit_receipt_timeout,
+            config.commit_fee_bumps,
+            config.commit_fee_bump_percent,
+            config.commit_max_fee_per_gas / 1_000_000_000
+        ),
+    );
+    line(
+        "circuit breaker",
+        match config.circuit_revert_threshold {
+            0 => "off".to_string(),
+            threshold => {
+                format!("open after {} reverts, cooldown {:?}", threshold, config.circuit_cooldown)
+            }
+        },
+    );
+    line(
+        "loop backoff",
+        match config.loop_failure_threshold {
+            0 => "off".to_string(),
+            threshold => format!("{:?} after {} failed ticks", config.loop_backoff, threshold),
+        },
+    );
+    line("rpc failover", format!("after {} failed requests", config.rpc_failover_errors));
+    line("rpc timeout", format!("{:?}", config.rpc_timeout));
+    line(
+        "rpc headers",
+        format!(
+            "l1 {}, verify {}, l2 {}",
+            headers(&config.l1_rpc_headers),
+            headers(&config.verify_l1_rpc_headers),
+            headers(&config.l2_rpc_headers)
+        ),
+    );
+    line(
+        "batch queue",
+        config
+            .queue_url
+            .as_deref()
+            .map_or_else(|| format!("in-process, {} batches", config.queue_size), redact_url),
+    );
+    line("private tx relay", url(config.private_tx_rpc.as_deref()));
+    line("receipt webhook", url(config.receipt_webhook_url.as_deref()));
+    line(
+        "alert webhook",
+        match &config.alert_webhook {
+            Some(webhook) => {
+                format!("{}, debounce {:?}", redact_url(webhook), config.alert_webhook_debounce)
+            }
+            None => "off".to_string(),
+        },
+    );
+    line("alarm contract", config.alarm_contract.map_or("off".to_string(), |a| format!("{:?}", a)));
+    line(
+        "checkpoint",
+        config.checkpoint_file.as_ref().map_or("off".to_string(), |f| format!("{:?}", f)),
+    );
+    line("audit log", config.audit_file.as_ref().map_or("off".to_string(), |f| format!("{:?}", f)));
+    line("metrics", config.metric_address.to_string());
+    line("statsd", config.statsd_address.clone().unwrap_or_else(|| "off".to_string()));
+    line("metrics push", url(config.metrics_push_url.as_deref()));
+    line("prove endpoint", on_off(config.admin_token.is_some()).to_string());
+    line(
+        "signer balance",
+        format!(
+            "refresh {}, warn below {} ETH",
+            match config.balance_refresh.is_zero() {
+                true => "before proving".to_string(),
+                false => format!("{:?}", config.balance_refresh),
+            },
+            config.low_balance_threshold
+        ),
+    );
+    format!("shadow proving configuration:\n{}", banner.trim_end())
+}
+
+/// Scheme, host and port of a URL, hiding credentials, path and query.
+pub fn redact_url(url: &str) -> String {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return String::from("<redacted>");
+    };
+    let mut redacted = format!("{}://{}", parsed.scheme(), parsed.host_str().unwrap_or_default());
+    if let Some(port) = parsed.port() {
+        redacted.push_str(&format!(":{}", port));
+    }
+    let hidden = !parsed.username().is_empty() ||
+        parsed.password().is_some() ||
+        !parsed.path().trim_start_matches('/').is_empty() ||
+        parsed.query().is_some();
+    if hidden {
+        redacted.push_str("/***");
+    }
+    redacted
+}
+
+/// Each of comma separated URLs, redacted.
+fn redact_urls(urls: &str) -> String {
+    urls.split(',').map(|url| redact_url(url.trim())).collect::<Vec<_>>().join(",")
+}
+
+#[test]
+fn test_startup_banner() {
+    use alloy::transports::http::reqwest::header::{HeaderValue, AUTHORIZATION};
+
+    let config = Config {
+        queue_url: Some(String::from("redis://:queue-secret@redis.internal:6379/0")),
+        private_tx_rpc: Some(String::from("https://relay.example?apikey=relay-secret")),
+        l1_rpc_headers: [(AUTHORIZATION, HeaderValue::from_static("Bearer l1-header-secret"))]
+            .into_iter()
+            .collect(),
+        ..Config::default()
+    };
+    let deployment = Deployment {
+        l1_chain_id: Some(17000),
+        verify_l1_chain_id: Some(10),
+        l2_chain_id: None,
+        l1_rpc: String::from(concat!(
+            "https://eth-holesky.g.alchemy.com/v2/l1-secret,",
+            " https://rpc.ankr.com/eth_holesky/l1-secret"
+        )),
+        l1_verify_rpc: String::from("http://127.0.0.1:8545"),
+        l2_rpc: String::from("not a url l2-secret"),
+        rollup: Address::repeat_byte(0x11),
+        shadow_rollup: Address::repeat_byte(0x22),
+        signer: Address::repeat_byte(0x33),
+    };
+    let banner = startup_banner(&config, &deployment);
+
+    for expected in [
+        concat!(
+            "chain 17000, rpc https://eth-holesky.g.alchemy.com/***,https://rpc.ankr.com/***, ",
+            "verify chain 10, verify rpc http://127.0.0.1:8545"
+        ),
+        "chain unknown, rpc <redacted>",
+        &format!("{:?}", Address::repeat_byte(0x11)),
+        &format!("{:?}", Address::repeat_byte(0x22)),
+        &format!("{:?}", Address::repeat_byte(0x33)),
+        "loop interval:        12s",
+        "lookback blocks:      600, in chunks of 1000",
+        "confirmations:        0 blocks",
+        "size limits:          300 blocks, 600 txns",
+        "block fetch:          8 concurrent, multicall off, block receipts off",
+        "loop backoff:         600s after 10 failed ticks",
+        "rpc failover:         after 3 failed requests",
+        "rpc timeout:          30s",
+        "rpc headers:          l1 authorization, verify off, l2 off",
+        "batch queue:          redis://redis.internal:6379/***",
+        "private tx relay:     https://relay.example/***",
+        "alarm contract:       off",
+        "alert webhook:        off",
+        "blob hash check:      Off, beacon off",
+        "dry run:              off",
+        "catch-up:             off",
+        "batch range:          all",
+        "prove endpoint:       off",
+    ] {
+        assert!(banner.contains(expected), "{:?} missing from\n{}", expected, banner);
+    }
+    assert!(!banner.contains("secret"), "secret leaked into\n{}", banner);
+}
@@ -0,0 +1,366 @@
+//! Versioned decoder of the batch headers committed to the rollup.
+//!
+//! Every version starts with the [`HeaderFields`] up to `parentBatchHash`; what follows them
+//! depends on the version, so each version is a variant of [`BatchHeader`] and supporting a new
+//! one means adding its variant. A header of a version above the newest known one fails to decode
+//! with "unsupported batch header version N, upgrade the prover" rather than being sliced with an
+//! older layout. V0 headers of the historical batches are still decoded for backfill proving.
+//!
+//! Reference: morph-repo/contracts/contracts/libraries/codec/BatchHeaderCodecV0.sol and
+//! BatchHeaderCodecV1.sol
+//!
+//! ```text
+//! Field                   Bytes       Type        Index   Comments
+//! version                 1           uint8       0       The batch version
+//! batchIndex              8           uint64      1       The index of the batch
+//! l1MessagePopped         8           uint64      9       Number of L1 messages popped in the batch
+//! totalL1MessagePopped    8           uint64      17      Number of total L1 messages popped after the batch
+//! dataHash                32          bytes32     25      The data hash of the batch
+//! blobVersionedHash       32          bytes32     57      The versioned hash of the blob with this batch’s data
+//! prevStateHash           32          bytes32     89      Preview state root
+//! postStateHash           32          bytes32     121     Post state root
+//! withdrawRootHash        32          bytes32     153     L2 withdrawal tree root hash
+//! sequencerSetVerifyHash  32          bytes32     185     L2 sequencers set verify hash
+//! parentBatchHash         32          bytes32     217     The parent batch hash
+//! skippedL1MessageBitmap  dynamic     uint256[]   249     V0: which L1 messages are skipped in the batch
+//! lastBlockNumber         8           uint64      249     V1: the last block number in this batch
+//! ```
+
+use crate::ShadowRollup;
+use alloy::primitives::{B256, U256};
+use std::fmt;
+
+/// Length of the fields shared by every version, the whole V0 header without its bitmap.
+pub const V0_LEN: usize = 249;
+
+/// Length of a V1 header.
+pub const V1_LEN: usize = 257;
+
+/// Fields shared by every header version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderFields {
+    pub batch_index: u64,
+    pub l1_message_popped: u64,
+    pub total_l1_message_popped: u64,
+    pub data_hash: B256,
+    pub blob_versioned_hash: B256,
+    pub prev_state_root: B256,
+    pub post_state_root: B256,
+    pub withdrawal_root: B256,
+    pub sequencer_set_verify_hash: B256,
+    pub parent_batch_hash: B256,
+}
+
+/// A decoded batch header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BatchHeader {
+    V0 {
+        fields: HeaderFields,
+        /// Which L1 messages are skipped, one bit per message popped by the batch from the least
+        /// significant bit of the first word; empty in the later V0 commits.
+        skipped_l1_message_bitmap: Vec<U256>,
+    },
+    V1 {
+        fields: HeaderFields,
+        last_block_number: u64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeaderError {
+    Empty,
+    UnsupportedVersion(u8),
+    TooShort {
+        version: u8,
+        len: usize,
+        min_len: usize,
+    },
+    /// The V0 bitmap is not made of whole 32-byte words.
+    BitmapLength(usize),
+    /// Not the codec version of the rollup.
+    VersionMismatch {
+        version: u8,
+        expected: u8,
+    },
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "batch header is empty"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported batch header version {}, upgrade the prover", version)
+            }
+            Self::TooShort { version, len, min_len } => write!(
+                f,
+                "batch header version {} is {} bytes, at least {} expected",
+                version, len, min_len
+            ),
+            Self::BitmapLength(len) => {
+                write!(f, "skipped L1 message bitmap of {} bytes is not made of 32-byte words", len)
+            }
+            Self::VersionMismatch { version, expected } => write!(
+                f,
+                "batch header version {} differs from rollup header version {}",
+                version, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+/// Error of [`crate::shadow_rollup::batch_store_from_header`].
+pub type DecodeError = HeaderError;
+
+impl BatchHeader {
+    pub fn decode(header: &[u8]) -> Result<Self, HeaderError> {
+        let version = *header.first().ok_or(HeaderError::Empty)?;
+        let min_len = match version {
+            0 => V0_LEN,
+            1 => V1_LEN,
+            _ => return Err(HeaderError::UnsupportedVersion(version)),
+        };
+        if header.len() < min_len {
+            return Err(HeaderError::TooShort { version, len: header.len(), min_len });
+        }
+
+        let u64_at = |offset: usize| {
+            u64::from_be_bytes(header[offset..offset + 8].try_into().expect("8 bytes"))
+        };
+        let b256_at = |offset: usize| B256::from_slice(&header[offset..offset + 32]);
+        let fields = HeaderFields {
+            batch_index: u64_at(1),
+            l1_message_popped: u64_at(9),
+            total_l1_message_popped: u64_at(17),
+            data_hash: b256_at(25),
+            blob_versioned_hash: b256_at(57),
+            prev_state_root: b256_at(89),
+            post_state_root: b256_at(121),
+            withdrawal_root: b256_at(153),
+            sequencer_set_verify_hash: b256_at(185),
+            parent_batch_hash: b256_at(217),
+        };
+        Ok(match version {
+            0 => {
+                let bitmap = &header[V0_LEN..];
+                if bitmap.len() % 32 != 0 {
+                    return Err(HeaderError::BitmapLength(bitmap.len()));
+                }
+                let skipped_l1_message_bitmap =
+                    bitmap.chunks(32).map(U256::from_be_slice).collect();
+                Self::V0 { fields, skipped_l1_message_bitmap }
+            }
+            _ => Self::V1 { fields, last_block_number: u64_at(V0_LEN) },
+        })
+    }
+
+    /// Version of `header`, refused unless it is the `expected` codec version of the rollup.
+    pub fn check_version(header: &[u8], expected: u8) -> Result<u8, HeaderError> {
+        let version = *header.first().ok_or(HeaderError::Empty)?;
+        if version != expected {
+            return Err(HeaderError::VersionMismatch { version, expected });
+        }
+        Ok(version)
+    }
+
+    pub fn version(&self) -> u8 {
+        match self {
+            Self::V0 { .. } => 0,
+            Self::V1 { .. } => 1,
+        }
+    }
+
+    pub fn fields(&self) -> &HeaderFields {
+        match self {
+            Self::V0 { fields, .. } | Self::V1 { fields, .. } => fields,
+        }
+    }
+
+    /// From version 1.
+    pub fn last_block_number(&self) -> Option<u64> {
+        match self {
+            Self::V0 { .. } => None,
+            Self::V1 { last_block_number, .. } => Some(*last_block_number),
+        }
+    }
+
+    /// Queue indexes of the L1 messages popped by the batch but skipped, ascending. None are
+    /// skipped from version 1.
+    pub fn skipped_l1_messages(&self) -> Vec<u64> {
+        let Self::V0 { fields, skipped_l1_message_bitmap } = self else {
+            return Vec::new();
+        };
+        let first = fields.total_l1_message_popped.saturating_sub(fields.l1_message_popped);
+        (0..fields.l1_message_popped)
+            .filter(|i| {
+                let word = skipped_l1_message_bitmap.get((i / 256) as usize);
+                word.is_some_and(|word| word.bit((i % 256) as usize))
+            })
+            .map(|i| first + i)
+            .collect()
+    }
+}
+
+/// Roots the shadow rollup is committed with.
+impl From<&BatchHeader> for ShadowRollup::BatchStore {
+    fn from(header: &BatchHeader) -> Self {
+        let fields = header.fields();
+        Self {
+            prevStateRoot: fields.prev_state_root,
+            postStateRoot: fields.post_state_root,
+            withdrawalRoot: fields.withdrawal_root,
+            dataHash: fields.data_hash,
+            blobVersionedHash: fields.blob_versioned_hash,
+            sequencerSetVerifyHash: fields.sequencer_set_verify_hash,
+        }
+    }
+}
+
+#[test]
+fn test_decode() {
+    // Every 32-byte field is filled with its offset.
+    let header = |version: u8, len: usize| {
+        let mut header = vec![0u8; len];
+        header[0] = version;
+        header[1..9].copy_from_slice(&7u64.to_be_bytes());
+        header[9..17].copy_from_slice(&2u64.to_be_bytes());
+        header[17..25].copy_from_slice(&40u64.to_be_bytes());
+        for offset in (25..249).step_by(32) {
+            header[offset..offset + 32].fill(offset as u8);
+        }
+        if version == 1 && len >= 257 {
+            header[249..257].copy_from_slice(&110u64.to_be_bytes());
+        }
+        header
+    };
+    let fields = HeaderFields {
+        batch_index: 7,
+        l1_message_popped: 2,
+        total_l1_message_popped: 40,
+        data_hash: B256::repeat_byte(25),
+        blob_versioned_hash: B256::repeat_byte(57),
+        prev_state_root: B256::repeat_byte(89),
+        post_state_root: B256::repeat_byte(121),
+        withdrawal_root: B256::repeat_byte(153),
+        sequencer_set_verify_hash: B256::repeat_byte(185),
+        parent_batch_hash: B256::repeat_byte(217),
+    };
+
+    // The newest known version.
+    let decoded = BatchHeader::decode(&header(1, 257)).unwrap();
+    assert_eq!(decoded, BatchHeader::V1 { fields: fields.clone(), last_block_number: 110 });
+    assert_eq!((decoded.version(), decoded.last_block_number()), (1, Some(110)));
+    assert!(decoded.skipped_l1_messages().is_empty());
+    let store = ShadowRollup::BatchStore::from(&decoded);
+    assert_eq!(store.prevStateRoot, B256::repeat_byte(89));
+    assert_eq!(store.sequencerSetVerifyHash, B256::repeat_byte(185));
+
+    // V0 has no last block number; what follows the fixed part is the skipped message bitmap.
+    let mut v0 = header(0, 249 + 64);
+    v0[249 + 31] = 0b101;
+    let decoded = BatchHeader::decode(&v0).unwrap();
+    assert_eq!(
+        decoded,
+        BatchHeader::V0 { fields, skipped_l1_message_bitmap: vec![U256::from(0b101), U256::ZERO] }
+    );
+    assert_eq!((decoded.version(), decoded.last_block_number()), (0, None));
+    assert_eq!(ShadowRollup::BatchStore::from(&decoded).postStateRoot, B256::repeat_byte(121));
+    assert_eq!(
+        BatchHeader::decode(&header(0, 249 + 33)).unwrap_err(),
+        HeaderError::BitmapLength(33)
+    );
+
+    // A future version is refused, not decoded with an older layout.
+    let err = BatchHeader::decode(&header(2, 300)).unwrap_err();
+    assert_eq!(err, HeaderError::UnsupportedVersion(2));
+    assert_eq!(err.to_string(), "unsupported batch header version 2, upgrade the prover");
+
+    assert_eq!(
+        BatchHeader::decode(&header(1, 249)).unwrap_err(),
+        HeaderError::TooShort { version: 1, len: 249, min_len: 257 }
+    );
+    assert_eq!(
+        BatchHeader::decode(&header(0, 248)).unwrap_err(),
+        HeaderError::TooShort { version: 0, len: 248, min_len: 249 }
+    );
+    assert_eq!(BatchHeader::decode(&[]).unwrap_err(), HeaderError::Empty);
+
+    assert_eq!(BatchHeader::check_version(&header(1, 257), 1), Ok(1));
+    assert_eq!(
+        BatchHeader::check_version(&header(0, 249), 1).unwrap_err(),
+        HeaderError::VersionMismatch { version: 0, expected: 1 }
+    );
+    assert_eq!(BatchHeader::check_version(&[], 1).unwrap_err(), HeaderError::Empty);
+}
+
+#[test]
+fn test_decode_v0_genesis() {
+    use alloy::primitives::Bytes;
+
+    // A genesis batch header as the node's `GenesisBatchHeader` encodes it: V0, nothing popped,
+    // the empty blob and no bitmap.
+    let header: Bytes = concat!(
+        "0x00",
+        "0000000000000000",
+        "0000000000000000",
+        "0000000000000000",
+        "9ad8c6bc8bd6d0d6ec7d62b2e5bef0c1fa7e3a2a3c0e8b4c1b9ad0f9c07c23e4",
+        "010657f37554c781402a22917dee2f75def7ab966d7b770905398eba3c444014",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "e23a1fa2de7c7b8a7b7fb0e5c1e1d2ec6e2a6ddf0ddbf2dd6ebc2d15c8c3f0b1",
+        "27ae5ba08d7291c96c8cbddcc148bf48a6d68c7974b94356f53754ef6171d757",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+    )
+    .parse()
+    .unwrap();
+    let decoded = BatchHeader::decode(&header).unwrap();
+    let BatchHeader::V0 { fields, skipped_l1_message_bitmap } = &decoded else {
+        panic!("V0 expected, got {:?}", decoded);
+    };
+    assert!(skipped_l1_message_bitmap.is_empty());
+    assert_eq!(
+        (fields.batch_index, fields.l1_message_popped, fields.total_l1_message_popped),
+        (0, 0, 0)
+    );
+    // The empty blob of the genesis batch.
+    assert_eq!(
+        fields.blob_versioned_hash.to_string(),
+        "0x010657f37554c781402a22917dee2f75def7ab966d7b770905398eba3c444014"
+    );
+    assert!(fields.prev_state_root.is_zero());
+    assert_eq!(
+        ShadowRollup::BatchStore::from(&decoded).postStateRoot.to_string(),
+        "0xe23a1fa2de7c7b8a7b7fb0e5c1e1d2ec6e2a6ddf0ddbf2dd6ebc2d15c8c3f0b1"
+    );
+    assert_eq!(
+        fields.withdrawal_root.to_string(),
+        "0x27ae5ba08d7291c96c8cbddcc148bf48a6d68c7974b94356f53754ef6171d757"
+    );
+    assert!(fields.parent_batch_hash.is_zero());
+}
+
+#[test]
+fn test_skipped_l1_messages() {
+    // The batch pops messages 30 to 299 of the queue and skips the 1st, 3rd and 258th of them.
+    let mut header = vec![0u8; V0_LEN + 64];
+    header[9..17].copy_from_slice(&270u64.to_be_bytes());
+    header[17..25].copy_from_slice(&300u64.to_be_bytes());
+    let bitmap = [U256::from(0b101), U256::from(0b10)];
+    for (word, value) in header[V0_LEN..].chunks_mut(32).zip(bitmap) {
+        word.copy_from_slice(&value.to_be_bytes::<32>());
+    }
+    let decoded = BatchHeader::decode(&header).unwrap();
+    assert_eq!(decoded.skipped_l1_messages(), vec![30, 32, 287]);
+
+    // Bits past the popped messages are not messages.
+    header[V0_LEN..V0_LEN + 32].copy_from_slice(&U256::MAX.to_be_bytes::<32>());
+    header[9..17].copy_from_slice(&2u64.to_be_bytes());
+    let decoded = BatchHeader::decode(&header).unwrap();
+    assert_eq!(decoded.skipped_l1_messages(), vec![298, 299]);
+
+    // The later V0 commits leave the bitmap out.
+    let decoded = BatchHeader::decode(&header[..V0_LEN]).unwrap();
+    assert!(decoded.skipped_l1_messages().is_empty());
+}
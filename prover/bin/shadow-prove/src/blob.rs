@@ -0,0 +1,127 @@
+//! Blobs a batch header commits to, read from the beacon node API.
+//!
+//! The blob sidecars of a block are served by slot, `/eth/v1/beacon/blob_sidecars/{slot}`, the
+//! slot being derived from the L1 block timestamp and the beacon chain genesis time. The beacon
+//! node checks a blob against its KZG commitment before serving it, so the versioned hash is
+//! recomputed from the commitment alone.
+
+use alloy::{
+    eips::eip4844::VERSIONED_HASH_VERSION_KZG,
+    primitives::{b256, Bytes, B256},
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use sha2::{Digest, Sha256};
+
+/// Versioned hash of the empty blob, carried by the header of a batch committed without one.
+pub const EMPTY_BLOB_VERSIONED_HASH: B256 =
+    b256!("010657f37554c781402a22917dee2f75def7ab966d7b770905398eba3c444014");
+
+const SECONDS_PER_SLOT: u64 = 12;
+
+/// EIP-4844 versioned hash of a KZG commitment.
+pub fn kzg_versioned_hash(commitment: &[u8]) -> B256 {
+    let mut hash: [u8; 32] = Sha256::digest(commitment).into();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    B256::from(hash)
+}
+
+/// Slot of the beacon block proposed at `timestamp`.
+pub fn slot_at(genesis_time: u64, timestamp: u64) -> u64 {
+    timestamp.saturating_sub(genesis_time) / SECONDS_PER_SLOT
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct Genesis {
+    genesis_time: String,
+}
+
+#[derive(Deserialize)]
+struct BlobSidecar {
+    kzg_commitment: Bytes,
+}
+
+/// Client of the beacon node API at `url`.
+#[derive(Clone, Debug)]
+pub struct Beacon {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Beacon {
+    pub fn new(url: &str) -> Self {
+        Self { client: reqwest::Client::new(), url: url.trim_end_matches('/').to_string() }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, anyhow::Error> {
+        let response =
+            self.client.get(format!("{}{}", self.url, path)).send().await?.error_for_status()?;
+        Ok(response.json::<Response<T>>().await?.data)
+    }
+
+    /// Unix time of the beacon chain genesis.
+    pub async fn genesis_time(&self) -> Result<u64, anyhow::Error> {
+        let genesis: Genesis = self.get("/eth/v1/beacon/genesis").await?;
+        Ok(genesis.genesis_time.parse()?)
+    }
+
+    /// Versioned hashes of the blobs of the beacon block at `slot`.
+    pub async fn blob_hashes(&self, slot: u64) -> Result<Vec<B256>, anyhow::Error> {
+        let sidecars: Vec<BlobSidecar> =
+            self.get(&format!("/eth/v1/beacon/blob_sidecars/{}", slot)).await?;
+        Ok(sidecars.iter().map(|sidecar| kzg_versioned_hash(&sidecar.kzg_commitment)).collect())
+    }
+
+    /// Versioned hashes of the blobs of the L1 block mined at `timestamp`.
+    pub async fn blob_hashes_at(&self, timestamp: u64) -> Result<Vec<B256>, anyhow::Error> {
+        let slot = slot_at(self.genesis_time().await?, timestamp);
+        self.blob_hashes(slot).await
+    }
+}
+
+#[test]
+fn test_kzg_versioned_hash() {
+    // The commitment of the empty blob, the compressed point at infinity.
+    let mut commitment = [0u8; 48];
+    commitment[0] = 0xc0;
+    assert_eq!(kzg_versioned_hash(&commitment), EMPTY_BLOB_VERSIONED_HASH);
+}
+
+#[test]
+fn test_slot_at() {
+    // Mainnet genesis.
+    let genesis_time = 1606824023;
+    assert_eq!(slot_at(genesis_time, genesis_time), 0);
+    assert_eq!(slot_at(genesis_time, genesis_time + 12 * 100 + 11), 100);
+    assert_eq!(slot_at(genesis_time, genesis_time - 1), 0);
+}
+
+#[tokio::test]
+async fn test_blob_hashes_at() {
+    use axum::{routing::get, Json, Router};
+    use serde_json::json;
+
+    let app = Router::new()
+        .route(
+            "/eth/v1/beacon/genesis",
+            get(|| async { Json(json!({ "data": { "genesis_time": "1000" } })) }),
+        )
+        .route(
+            "/eth/v1/beacon/blob_sidecars/:slot",
+            get(|axum::extract::Path(slot): axum::extract::Path<u64>| async move {
+                assert_eq!(slot, 5);
+                let commitment = format!("0xc0{}", "00".repeat(47));
+                Json(json!({ "data": [{ "index": "0", "kzg_commitment": commitment }] }))
+            }),
+        );
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+    let hashes = Beacon::new(&url).blob_hashes_at(1000 + 12 * 5 + 3).await.unwrap();
+    assert_eq!(hashes, vec![EMPTY_BLOB_VERSIONED_HASH]);
+}
@@ -0,0 +1,95 @@
+//! Block number arithmetic.
+//!
+//! Block numbers are plain `u64` everywhere in the prover. Values read from contracts as `uint256`
+//! are converted with [`from_u256`], which fails instead of truncating. Subtractions never go
+//! below genesis, and a range bound derived from `latest` never goes past it.
+
+use alloy::primitives::U256;
+use anyhow::anyhow;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// First block of the `lookback`-block log window ending at `latest`.
+///
+/// The window starts at block 1 at the earliest, genesis holds no logs, and never after `latest`,
+/// so `from_block <= to_block` holds even on a fresh chain.
+pub fn lookback_start(latest: u64, lookback: u64) -> u64 {
+    latest.saturating_sub(lookback).max(1).min(latest)
+}
+
+/// L2 blocks `(start, end)` of a batch whose predecessor ended at `prev_end` and which ends at
+/// `end`. `start > end` for an empty batch, see `check_block_range`.
+pub fn batch_blocks(prev_end: u64, end: u64) -> (u64, u64) {
+    (prev_end.saturating_add(1), end)
+}
+
+/// Highest `latest` block number answered by the L1 endpoint.
+///
+/// Behind a load balancer a request may reach a lagging node, whose `latest` is behind one seen
+/// before. Acting on it would make the prover go backwards, so such an answer is reported stale.
+#[derive(Clone, Debug, Default)]
+pub struct LatestBlock {
+    highest: Arc<AtomicU64>,
+}
+
+impl LatestBlock {
+    /// Record `latest`, failing when it is more than `tolerance` blocks behind the highest seen.
+    pub fn observe(&self, latest: u64, tolerance: u64) -> Result<(), anyhow::Error> {
+        let highest = self.highest.fetch_max(latest, Ordering::Relaxed);
+        if latest.saturating_add(tolerance) < highest {
+            return Err(anyhow!("latest block {} is behind {} seen before", latest, highest));
+        }
+        Ok(())
+    }
+
+    pub fn highest(&self) -> u64 {
+        self.highest.load(Ordering::Relaxed)
+    }
+}
+
+/// Block number read from a `uint256` contract field.
+pub fn from_u256(value: U256) -> Result<u64, anyhow::Error> {
+    u64::try_from(value).map_err(|_| anyhow!("block number {} does not fit in u64", value))
+}
+
+#[test]
+fn test_lookback_start() {
+    for (latest, start) in [(0, 0), (1, 1), (2, 1), (600, 1), (601, 1), (602, 2), (1000, 400)] {
+        assert_eq!(lookback_start(latest, 600), start, "latest = {}", latest);
+        assert!(lookback_start(latest, 600) <= latest);
+    }
+    // No lookback: the latest block only.
+    assert_eq!(lookback_start(601, 0), 601);
+    assert_eq!(lookback_start(u64::MAX, u64::MAX), 1);
+}
+
+#[test]
+fn test_batch_blocks() {
+    assert_eq!(batch_blocks(100, 110), (101, 110));
+    assert_eq!(batch_blocks(0, 1), (1, 1));
+    // Empty batch.
+    assert_eq!(batch_blocks(110, 110), (111, 110));
+}
+
+#[test]
+fn test_latest_block() {
+    let latest = LatestBlock::default();
+    latest.observe(100, 2).unwrap();
+    // Lagging within the tolerance.
+    latest.observe(98, 2).unwrap();
+    assert!(latest.observe(97, 2).is_err());
+    assert!(latest.observe(50, 0).is_err());
+    assert_eq!(latest.highest(), 100);
+    latest.observe(101, 0).unwrap();
+    assert!(latest.observe(100, 0).is_err());
+    assert_eq!(latest.highest(), 101);
+}
+
+#[test]
+fn test_from_u256() {
+    assert_eq!(from_u256(U256::ZERO).unwrap(), 0);
+    assert_eq!(from_u256(U256::from(u64::MAX)).unwrap(), u64::MAX);
+    assert!(from_u256(U256::from(u64::MAX) + U256::from(1)).is_err());
+}
@@ -0,0 +1,156 @@
+//! Challenge window state of recently proven batches.
+//!
+//! Deployments whose shadow rollup keeps a finalization window expose it like the rollup does,
+//! through `batchDataStore(batchIndex).finalizeTimestamp`. With `SHADOW_PROVING_CHALLENGE_WINDOW`
+//! set, the window of the last proven batches is read every tick, the remaining time of the most
+//! recent one is exported as `shadow_batch_challenge_remaining_secs` and the whole list is served
+//! at `/debug/challenge-window`.
+
+use crate::{
+    abi::ChallengeWindow::{self, ChallengeWindowInstance},
+    failover::FailoverTransport,
+    metrics::{self, METRICS},
+};
+use alloy::{
+    primitives::{Address, U256},
+    providers::RootProvider,
+};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+/// Number of the most recently proven batches followed.
+const RECENT_BATCHES: usize = 16;
+
+/// Where a proven batch sits in the challenge window.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct WindowStatus {
+    pub batch_index: u64,
+    /// Unix time the batch leaves the window.
+    pub finalize_timestamp: u64,
+    pub remaining_secs: u64,
+    pub in_window: bool,
+}
+
+/// Latest window statuses, shared with the debug endpoint.
+#[derive(Clone, Debug, Default)]
+pub struct WindowStatuses(Arc<Mutex<Vec<WindowStatus>>>);
+
+impl WindowStatuses {
+    pub fn get(&self) -> Vec<WindowStatus> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChallengeWindowTracker {
+    shadow_rollup: ChallengeWindowInstance<FailoverTransport, RootProvider<FailoverTransport>>,
+    recent: Arc<Mutex<VecDeque<u64>>>,
+    statuses: WindowStatuses,
+}
+
+impl ChallengeWindowTracker {
+    pub fn new(
+        shadow_rollup_address: Address,
+        l1_provider: RootProvider<FailoverTransport>,
+        statuses: WindowStatuses,
+    ) -> Self {
+        let shadow_rollup = ChallengeWindow::new(shadow_rollup_address, l1_provider);
+        Self { shadow_rollup, recent: Arc::default(), statuses }
+    }
+
+    /// Follow a newly proven batch, dropping the oldest beyond the recent ones.
+    pub fn track(&self, batch_index: u64) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.contains(&batch_index) {
+            return;
+        }
+        recent.push_back(batch_index);
+        if recent.len() > RECENT_BATCHES {
+            recent.pop_front();
+        }
+    }
+
+    /// Re-read the window of the followed batches as of `now` (unix time).
+    pub async fn refresh(&self, now: u64) {
+        let recent: Vec<u64> = self.recent.lock().unwrap().iter().copied().collect();
+        let mut statuses = Vec::with_capacity(recent.len());
+        for batch_index in recent {
+            let store =
+                match self.shadow_rollup.batchDataStore(U256::from(batch_index)).call().await {
+                    Ok(store) => store,
+                    Err(e) => {
+                        log::debug!("challenge window of batch {} unavailable: {}", batch_index, e);
+                        continue;
+                    }
+                };
+            let finalize_timestamp = store.finalizeTimestamp.saturating_to::<u64>();
+            let remaining_secs = finalize_timestamp.saturating_sub(now);
+            statuses.push(WindowStatus {
+                batch_index,
+                finalize_timestamp,
+                remaining_secs,
+                in_window: remaining_secs > 0,
+            });
+        }
+        statuses.sort_by_key(|status| status.batch_index);
+
+        if let Some(latest) = statuses.last() {
+            metrics::set(
+                &METRICS.shadow_batch_challenge_remaining_secs,
+                latest.remaining_secs as i64,
+            );
+        }
+        *self.statuses.0.lock().unwrap() = statuses;
+    }
+}
+
+#[tokio::test]
+async fn test_challenge_window() {
+    use crate::mock_rpc::{call_result, call_selector, MockRpc};
+    use alloy::{
+        primitives::Bytes,
+        sol_types::{SolCall, SolValue},
+    };
+
+    // Batch n finalizes at 1000 + 100 * n; batch 9 has no window state.
+    let rpc = MockRpc::start(|method, params| {
+        if method != "eth_call" ||
+            call_selector(params) != ChallengeWindow::batchDataStoreCall::SELECTOR
+        {
+            return None;
+        }
+        let input = params[0]["input"].as_str().or(params[0]["data"].as_str()).unwrap();
+        let input: Bytes = input.parse().unwrap();
+        let call = ChallengeWindow::batchDataStoreCall::abi_decode(&input, false).unwrap();
+        let batch_index = call._0.to::<u64>();
+        if batch_index == 9 {
+            return None;
+        }
+        let finalize = U256::from(1000 + 100 * batch_index);
+        call_result((U256::from(1), finalize, U256::from(2), U256::ZERO).abi_encode())
+    });
+    let statuses = WindowStatuses::default();
+    let tracker = ChallengeWindowTracker::new(Address::ZERO, rpc.provider(), statuses.clone());
+    for batch_index in [6, 8, 7, 8, 9] {
+        tracker.track(batch_index);
+    }
+
+    tracker.refresh(1750).await;
+    let status = |batch_index: u64, remaining_secs: u64| WindowStatus {
+        batch_index,
+        finalize_timestamp: 1000 + 100 * batch_index,
+        remaining_secs,
+        in_window: remaining_secs > 0,
+    };
+    assert_eq!(statuses.get(), vec![status(6, 0), status(7, 0), status(8, 50)]);
+    assert_eq!(METRICS.shadow_batch_challenge_remaining_secs.get(), 50);
+
+    // Only the recent batches are followed.
+    for batch_index in 10..10 + RECENT_BATCHES as u64 {
+        tracker.track(batch_index);
+    }
+    assert_eq!(tracker.recent.lock().unwrap().front(), Some(&10));
+}
@@ -0,0 +1,212 @@
+use crate::artifact::BatchStoreFields;
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// Progress of the shadow prover, persisted across restarts.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Latest batch committed to the shadow rollup.
+    pub last_committed_index: Option<u64>,
+    /// Latest batch proven on the shadow rollup.
+    pub last_proven_index: Option<u64>,
+    /// Batches committed to the shadow rollup whose prove has not succeeded yet.
+    #[serde(default)]
+    pub committed_unproven: BTreeSet<u64>,
+    /// Batches abandoned after exhausting their prove attempts, awaiting manual attention.
+    #[serde(default)]
+    pub dead_letter: Vec<DeadLetter>,
+}
+
+/// A batch that permanently failed to prove.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub batch_index: u64,
+    pub error: String,
+    /// Unix timestamp of the failure.
+    pub timestamp: u64,
+    /// Roots committed to the shadow rollup, for a proof the verifier rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roots: Option<BatchStoreFields>,
+}
+
+impl Checkpoint {
+    pub fn record_committed(&mut self, batch_index: u64) {
+        self.last_committed_index = self.last_committed_index.max(Some(batch_index));
+        self.committed_unproven.insert(batch_index);
+    }
+
+    pub fn record_proven(&mut self, batch_index: u64) {
+        self.last_proven_index = self.last_proven_index.max(Some(batch_index));
+        self.committed_unproven.remove(&batch_index);
+        self.dead_letter.retain(|d| d.batch_index != batch_index);
+    }
+
+    /// Record an abandoned batch, replacing an earlier entry of the same batch.
+    pub fn record_dead_letter(
+        &mut self,
+        batch_index: u64,
+        error: String,
+        roots: Option<BatchStoreFields>,
+        timestamp: u64,
+    ) {
+        self.dead_letter.retain(|d| d.batch_index != batch_index);
+        self.dead_letter.push(DeadLetter { batch_index, error, timestamp, roots });
+        self.dead_letter.sort_by_key(|d| d.batch_index);
+    }
+
+    /// Clear the dead-letter entry of `batch_index`, or all entries; returns how many were removed.
+    pub fn clear_dead_letter(&mut self, batch_index: Option<u64>) -> usize {
+        let len = self.dead_letter.len();
+        self.dead_letter.retain(|d| batch_index.is_some_and(|index| d.batch_index != index));
+        len - self.dead_letter.len()
+    }
+
+    /// Manual override for recovery: treat everything up to `batch_index` as handled.
+    pub fn reset_to(&mut self, batch_index: u64) {
+        self.last_committed_index = Some(batch_index);
+        self.last_proven_index = Some(batch_index);
+        self.committed_unproven.retain(|index| *index > batch_index);
+    }
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let index = |i: Option<u64>| i.map_or_else(|| "none".to_string(), |i| i.to_string());
+        writeln!(f, "last committed batch: {}", index(self.last_committed_index))?;
+        writeln!(f, "last proven batch:    {}", index(self.last_proven_index))?;
+        writeln!(f, "committed unproven:   {:?}", self.committed_unproven)?;
+        write!(f, "dead letter:          {} batches", self.dead_letter.len())
+    }
+}
+
+impl fmt::Display for DeadLetter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "batch {} at {}: {}", self.batch_index, self.timestamp, self.error)?;
+        if let Some(roots) = &self.roots {
+            write!(
+                f,
+                "\n  prevStateRoot {:?}, postStateRoot {:?}, withdrawalRoot {:?},\n  \
+                 dataHash {:?}, blobVersionedHash {:?}, sequencerSetVerifyHash {:?}",
+                roots.prev_state_root,
+                roots.post_state_root,
+                roots.withdrawal_root,
+                roots.data_hash,
+                roots.blob_versioned_hash,
+                roots.sequencer_set_verify_hash
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// JSON file holding the [`Checkpoint`].
+#[derive(Clone, Debug)]
+pub struct CheckpointStore {
+    path: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the checkpoint; a missing file is an empty checkpoint, a corrupted one an error.
+    pub fn load(&self) -> Result<Checkpoint, anyhow::Error> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Checkpoint::default()),
+            Err(e) => return Err(anyhow!("read checkpoint {:?}: {}", self.path, e)),
+        };
+        serde_json::from_str(&content)
+            .with_context(|| format!("checkpoint file {:?} is corrupted", self.path))
+    }
+
+    /// Write the checkpoint through a temp file and rename, so a crash never leaves it truncated.
+    pub fn save(&self, checkpoint: &Checkpoint) -> Result<(), anyhow::Error> {
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(checkpoint)?)
+            .with_context(|| format!("write checkpoint {:?}", tmp))?;
+        fs::rename(&tmp, &self.path).with_context(|| format!("rename checkpoint {:?}", tmp))
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut Checkpoint)) -> Result<Checkpoint, anyhow::Error> {
+        let mut checkpoint = self.load()?;
+        f(&mut checkpoint);
+        self.save(&checkpoint)?;
+        Ok(checkpoint)
+    }
+}
+
+#[test]
+fn test_checkpoint_store() {
+    let dir = std::env::temp_dir().join(format!("shadow-checkpoint-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let store = CheckpointStore::new(dir.join("checkpoint.json"));
+
+    // Missing file shows as empty.
+    assert_eq!(store.load().unwrap(), Checkpoint::default());
+
+    store.update(|c| c.record_committed(7)).unwrap();
+    store.update(|c| c.record_committed(8)).unwrap();
+    store.update(|c| c.record_proven(7)).unwrap();
+    let checkpoint = store.load().unwrap();
+    assert_eq!(checkpoint.last_committed_index, Some(8));
+    assert_eq!(checkpoint.last_proven_index, Some(7));
+    assert_eq!(checkpoint.committed_unproven, BTreeSet::from([8]));
+    assert!(checkpoint.to_string().contains("last proven batch:    7"));
+
+    // Manual override.
+    store.update(|c| c.reset_to(10)).unwrap();
+    let checkpoint = store.load().unwrap();
+    assert_eq!(checkpoint.last_proven_index, Some(10));
+    assert!(checkpoint.committed_unproven.is_empty());
+
+    fs::write(store.path(), "{\"last_proven_index\": \"x\"").unwrap();
+    assert!(store.load().unwrap_err().to_string().contains("corrupted"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_dead_letter() {
+    let dir = std::env::temp_dir().join(format!("shadow-dead-letter-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let store = CheckpointStore::new(dir.join("checkpoint.json"));
+
+    // Add.
+    store
+        .update(|c| c.record_dead_letter(9, "proof not generated".to_string(), None, 100))
+        .unwrap();
+    store
+        .update(|c| c.record_dead_letter(5, "prove_state reverted".to_string(), None, 101))
+        .unwrap();
+    store.update(|c| c.record_dead_letter(9, "query proof failed".to_string(), None, 102)).unwrap();
+
+    // List, ordered by batch index with the latest error of each batch.
+    let checkpoint = store.load().unwrap();
+    let indexes: Vec<u64> = checkpoint.dead_letter.iter().map(|d| d.batch_index).collect();
+    assert_eq!(indexes, vec![5, 9]);
+    assert_eq!(checkpoint.dead_letter[1].to_string(), "batch 9 at 102: query proof failed");
+    assert!(checkpoint.to_string().contains("dead letter:          2 batches"));
+
+    // Clear one, a proven batch, then all.
+    let mut checkpoint = checkpoint;
+    assert_eq!(checkpoint.clear_dead_letter(Some(7)), 0);
+    assert_eq!(checkpoint.clear_dead_letter(Some(5)), 1);
+    checkpoint.record_dead_letter(6, "proof not generated".to_string(), None, 103);
+    checkpoint.record_proven(6);
+    assert_eq!(checkpoint.dead_letter.len(), 1);
+    assert_eq!(checkpoint.clear_dead_letter(None), 1);
+    assert!(checkpoint.dead_letter.is_empty());
+
+    fs::remove_dir_all(dir).unwrap();
+}
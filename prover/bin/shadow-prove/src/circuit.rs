@@ -0,0 +1,234 @@
+//! Circuit breakers of the `commitBatch` sends and of the main loop.
+//!
+//! A `commitBatch` reverting for several batches in a row is a systemic problem, e.g. a
+//! misconfigured signer or an upgraded shadow rollup, and every further send burns gas for
+//! nothing. After `SHADOW_PROVING_CIRCUIT_REVERT_THRESHOLD` consecutive reverts the circuit opens:
+//! `shadow_circuit_open` is set, a critical commit alert is raised and no commit is sent.
+//!
+//! The circuit closes again on `POST /admin/reset-circuit`, or on its own once
+//! `SHADOW_PROVING_CIRCUIT_COOLDOWN_SECS` have passed and the next commit simulates without
+//! reverting; until that simulation succeeds nothing is sent. A threshold of 0 disables the
+//! breaker.
+//!
+//! Other systemic failures, e.g. a bad RPC URL or a rollup ABI change, fail every tick. After
+//! `SHADOW_PROVING_LOOP_FAILURE_THRESHOLD` failed ticks in a row the loop circuit opens: the
+//! loop ticks every `SHADOW_PROVING_LOOP_BACKOFF_SECS` instead of every poll interval, sparing the
+//! providers and the logs, and counts in `shadow_loop_backoff`. The first tick without a failure
+//! closes it. The sync and prove loops of a single process have a circuit each.
+
+use crate::{
+    config::{Config, Severity},
+    failure::Stage,
+    metrics::{self, METRICS},
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// What the circuit lets through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Circuit {
+    /// Commits are sent.
+    Closed,
+    /// Commits are not sent.
+    Open,
+    /// The cooldown is over, a commit is sent once its simulation succeeds.
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    consecutive_reverts: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Consecutive reverts of the sends, shared with the admin endpoint.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            threshold: config.circuit_revert_threshold,
+            cooldown: config.circuit_cooldown,
+            state: Arc::default(),
+        }
+    }
+
+    pub fn circuit(&self, now: Instant) -> Circuit {
+        match self.state.lock().unwrap().opened_at {
+            None => Circuit::Closed,
+            Some(opened_at) if now < opened_at + self.cooldown => Circuit::Open,
+            Some(_) => Circuit::HalfOpen,
+        }
+    }
+
+    /// Record a reverted send, returning whether it opened the circuit.
+    pub fn revert(&self, reason: &str, now: Instant) -> bool {
+        if self.threshold == 0 {
+            return false;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_reverts += 1;
+        if state.opened_at.is_some() || state.consecutive_reverts < self.threshold {
+            return false;
+        }
+        state.opened_at = Some(now);
+        METRICS.shadow_circuit_open.set(1);
+        metrics::inc(
+            &METRICS
+                .shadow_stage_alerts_total
+                .with_label_values(&[Stage::Commit.as_str(), Severity::Critical.as_str()]),
+        );
+        log::error!(
+            "commit_batch reverted {} times in a row, circuit open, no commit is sent until \
+             POST /admin/reset-circuit or a successful simulation after {:?}: {}",
+            state.consecutive_reverts,
+            self.cooldown,
+            reason
+        );
+        true
+    }
+
+    /// Record a send that did not revert.
+    pub fn success(&self) {
+        self.state.lock().unwrap().consecutive_reverts = 0;
+    }
+
+    /// Close the circuit, returning whether it was open.
+    pub fn reset(&self, why: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let was_open = state.opened_at.take().is_some();
+        state.consecutive_reverts = 0;
+        METRICS.shadow_circuit_open.set(0);
+        if was_open {
+            log::info!("circuit closed: {}", why);
+        }
+        was_open
+    }
+}
+
+/// Consecutive failed ticks of the main loop.
+#[derive(Debug)]
+pub struct LoopBreaker {
+    threshold: u32,
+    backoff: Duration,
+    consecutive_failures: u32,
+}
+
+impl LoopBreaker {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            threshold: config.loop_failure_threshold,
+            backoff: config.loop_backoff,
+            consecutive_failures: 0,
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.threshold > 0 && self.consecutive_failures >= self.threshold
+    }
+
+    /// Record a tick, `failed` when one of its stages failed; returns whether it opened the
+    /// circuit.
+    pub fn tick(&mut self, failed: bool) -> bool {
+        if !failed {
+            if self.is_open() {
+                log::info!("tick succeeded, circuit closed, back to the poll interval");
+                METRICS.shadow_loop_backoff.dec();
+            }
+            self.consecutive_failures = 0;
+            return false;
+        }
+        if self.threshold == 0 {
+            return false;
+        }
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.consecutive_failures != self.threshold {
+            return false;
+        }
+        METRICS.shadow_loop_backoff.inc();
+        log::error!(
+            "{} ticks failed in a row, circuit open, ticking every {:?} until one succeeds",
+            self.consecutive_failures,
+            self.backoff
+        );
+        true
+    }
+
+    /// Sleep before the next tick while the circuit is open.
+    pub fn backoff(&self) -> Option<Duration> {
+        self.is_open().then_some(self.backoff)
+    }
+}
+
+#[test]
+fn test_circuit_breaker() {
+    let config = Config {
+        circuit_revert_threshold: 3,
+        circuit_cooldown: Duration::from_secs(60),
+        ..Config::default()
+    };
+    let breaker = CircuitBreaker::new(&config);
+    let now = Instant::now();
+
+    // A success in between starts the count over.
+    assert!(!breaker.revert("Ownable: caller is not the owner", now));
+    breaker.success();
+    assert!(!breaker.revert("Ownable: caller is not the owner", now));
+    assert!(!breaker.revert("Ownable: caller is not the owner", now));
+    assert_eq!(breaker.circuit(now), Circuit::Closed);
+    assert!(breaker.revert("Ownable: caller is not the owner", now));
+    assert_eq!(breaker.circuit(now), Circuit::Open);
+    // Already open.
+    assert!(!breaker.revert("Ownable: caller is not the owner", now));
+    assert_eq!(breaker.circuit(now + Duration::from_secs(60)), Circuit::HalfOpen);
+
+    assert!(breaker.reset("reset by the admin endpoint"));
+    assert_eq!(breaker.circuit(now), Circuit::Closed);
+    assert!(!breaker.reset("reset by the admin endpoint"));
+
+    // Disabled.
+    let breaker = CircuitBreaker::new(&Config { circuit_revert_threshold: 0, ..config });
+    for _ in 0..10 {
+        assert!(!breaker.revert("paused", now));
+    }
+    assert_eq!(breaker.circuit(now), Circuit::Closed);
+}
+
+#[test]
+fn test_loop_breaker() {
+    let config = Config {
+        loop_failure_threshold: 3,
+        loop_backoff: Duration::from_secs(600),
+        ..Config::default()
+    };
+    let mut breaker = LoopBreaker::new(&config);
+
+    // A success in between starts the count over.
+    assert!(!breaker.tick(true));
+    assert!(!breaker.tick(false));
+    assert!(!breaker.tick(true));
+    assert!(!breaker.tick(true));
+    assert_eq!(breaker.backoff(), None);
+    assert!(breaker.tick(true));
+    assert_eq!(breaker.backoff(), Some(Duration::from_secs(600)));
+    // Already open.
+    assert!(!breaker.tick(true));
+    assert_eq!(breaker.backoff(), Some(Duration::from_secs(600)));
+
+    assert!(!breaker.tick(false));
+    assert_eq!(breaker.backoff(), None);
+
+    // Disabled.
+    let mut breaker = LoopBreaker::new(&Config { loop_failure_threshold: 0, ..config });
+    for _ in 0..10 {
+        assert!(!breaker.tick(true));
+    }
+    assert_eq!(breaker.backoff(), None);
+}
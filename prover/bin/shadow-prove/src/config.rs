@@ -0,0 +1,882 @@
+use crate::util::{read_env_var, Backoff};
+use alloy::{
+    primitives::Address,
+    transports::http::reqwest::header::{HeaderMap, HeaderName, HeaderValue},
+};
+use anyhow::Context;
+use std::{net::SocketAddr, path::PathBuf, str::FromStr, time::Duration};
+
+const DEFAULT_METRIC_ADDRESS: &str = "0.0.0.0:6060";
+
+/// How an optional consistency check reacts to a mismatch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CheckMode {
+    /// The check is not performed.
+    #[default]
+    Off,
+    /// Mismatches are logged and counted, the batch is still committed.
+    Observe,
+    /// Mismatches are logged and counted, the batch is not committed.
+    Strict,
+}
+
+impl FromStr for CheckMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "false" => Ok(Self::Off),
+            "observe" | "true" => Ok(Self::Observe),
+            "strict" => Ok(Self::Strict),
+            _ => Err(format!("unknown check mode: {}", s)),
+        }
+    }
+}
+
+/// Severity of the alert raised when a stage keeps failing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// Likely to heal by itself, e.g. a gas or nonce issue.
+    Warning,
+    /// Needs attention, e.g. a proof the shadow rollup rejects.
+    Critical,
+}
+
+impl Severity {
+    /// Value of the `severity` metric label.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "warning" | "warn" => Ok(Self::Warning),
+            "critical" => Ok(Self::Critical),
+            _ => Err(format!("unknown severity: {}", s)),
+        }
+    }
+}
+
+/// Stages run by this process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Role {
+    /// Sync and prove in one process.
+    #[default]
+    All,
+    /// Commit batches to the shadow rollup and publish them to the batch queue.
+    Syncer,
+    /// Prove batches taken from the batch queue.
+    Prover,
+}
+
+impl Role {
+    pub fn syncs(self) -> bool {
+        self != Self::Prover
+    }
+
+    pub fn proves(self) -> bool {
+        self != Self::Syncer
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "all" => Ok(Self::All),
+            "syncer" => Ok(Self::Syncer),
+            "prover" => Ok(Self::Prover),
+            _ => Err(format!("unknown role: {}", s)),
+        }
+    }
+}
+
+/// Tuning parameters of the shadow prover, resolved once at startup.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Sleep between two sync & prove iterations.
+    pub loop_interval: Duration,
+    /// WebSocket endpoint of the read chain whose `CommitBatch` logs cut the sleep short, if any.
+    pub l1_ws: Option<String>,
+    /// Chain id the rollup is read from, checked at startup when set.
+    pub l1_chain_id: Option<u64>,
+    /// Chain id the shadow rollup is written to, checked at startup when set.
+    pub verify_l1_chain_id: Option<u64>,
+    /// Batches below this index are not shadow proven.
+    pub min_batch: Option<u64>,
+    /// Batches above this index are not shadow proven; a run-once process ticks until it is
+    /// proven.
+    pub max_batch: Option<u64>,
+    /// Batches spanning more blocks than this are not shadow proven.
+    pub max_block: u64,
+    /// Batches containing more transactions than this are not shadow proven.
+    pub max_txn: u64,
+    /// Batches from this many blocks are labelled `medium` in the prove metrics.
+    pub medium_batch_blocks: u64,
+    /// Batches from this many transactions are labelled `medium` in the prove metrics.
+    pub medium_batch_txns: u64,
+    /// Batches from this many blocks are labelled `large` in the prove metrics.
+    pub large_batch_blocks: u64,
+    /// Batches from this many transactions are labelled `large` in the prove metrics.
+    pub large_batch_txns: u64,
+    /// Number of L1 blocks scanned back from the latest for `CommitBatch` logs. The logs are kept
+    /// across ticks, which only scan the new blocks.
+    pub lookback_blocks: u64,
+    /// Blocks of a single `eth_getLogs` of the scan, within the block range limit of the
+    /// provider; 0 scans the blocks in one request.
+    pub log_chunk_blocks: u64,
+    /// Blocks the L1 `latest` may fall behind the highest one seen before the tick is skipped.
+    pub stale_block_tolerance: u64,
+    /// Blocks a `CommitBatch` log must be buried under before its batch is selected; 0 takes the
+    /// logs up to the latest block.
+    pub confirmations: u64,
+    /// Number of the most recent committed batches skipped when selecting the batch to prove, at
+    /// least 1 as the header of a batch is read from the commit tx of its successor. The
+    /// selection waits for `batch_buffer + 2` commit logs in the lookback window.
+    /// `SHADOW_PROVING_BATCH_LAG` is read as an alias.
+    pub batch_buffer: u64,
+    /// First backoff of a rate-limited `eth_getLogs` without `Retry-After`, doubled per retry.
+    pub rate_limit_backoff: Duration,
+    /// Upper bound of the exponential rate-limit backoff.
+    pub rate_limit_max_backoff: Duration,
+    /// Retries of a rate-limited `eth_getLogs` before giving up.
+    pub rate_limit_retries: u32,
+    /// Retries of an L1 read failing on a transient RPC error.
+    pub rpc_retries: u32,
+    /// First backoff of a transient RPC error retry, doubled per retry.
+    pub rpc_retry_backoff: Duration,
+    /// Consecutive failed requests to the active endpoint of an RPC after which it fails over to
+    /// the next of its comma separated URLs.
+    pub rpc_failover_errors: u32,
+    /// Bound of a single RPC request, after which it fails as a transient error.
+    pub rpc_timeout: Duration,
+    /// Headers sent with every request to the L1 RPC, e.g. the `Authorization` of a provider
+    /// taking no key in the URL.
+    pub l1_rpc_headers: HeaderMap,
+    /// Headers sent with every request to the L1 of the shadow rollup.
+    pub verify_l1_rpc_headers: HeaderMap,
+    /// Headers sent with every request to the L2 RPC.
+    pub l2_rpc_headers: HeaderMap,
+    /// Drop all but the newest of several `CommitBatch` logs for the same batch index.
+    pub dedupe_commit_logs: bool,
+    /// Prove every unproven batch of the lookback window once at startup.
+    pub startup_catchup: bool,
+    /// Catch up on the unproven batches of the lookback window, oldest first, on every tick in
+    /// place of syncing the latest batch.
+    pub catchup: bool,
+    /// Upper bound of the batches a tick catches up on.
+    pub catchup_max_batches: usize,
+    /// Upper bound of the startup dry run gating readiness.
+    pub warmup_timeout: Duration,
+    /// Listen address of the metrics server.
+    pub metric_address: SocketAddr,
+    /// StatsD/DogStatsD daemon the metrics are mirrored to, if any.
+    pub statsd_address: Option<String>,
+    /// Prometheus Pushgateway the metrics are pushed to on exit, if any.
+    pub metrics_push_url: Option<String>,
+    /// Bearer token of `POST /prove/{index}`, which is off without one.
+    pub admin_token: Option<String>,
+    /// Interval between two background reads of the signer balance; zero only reads it before
+    /// proving.
+    pub balance_refresh: Duration,
+    /// Signer balance, in ETH, below which a warning is logged.
+    pub low_balance_threshold: f64,
+    /// File persisting the committed/proven progress, if any.
+    pub checkpoint_file: Option<PathBuf>,
+    /// Append-only, hash-chained log of the decision of every tick, if any.
+    pub audit_file: Option<PathBuf>,
+    /// Age after which the auxiliary files beside the checkpoint are pruned; zero keeps them.
+    pub state_retention: Duration,
+    /// Contract serving `BatchBlocksGetter.batchBlocks`, preferred over per-block inspection.
+    pub batch_blocks_getter: Option<Address>,
+    /// Concurrent `eth_getBlockTransactionCountByNumber` requests of a per-block inspection.
+    pub block_fetch_concurrency: usize,
+    /// Multicall3 contract reading both `batchDataStore` of a per-block inspection in one call.
+    pub multicall: Option<Address>,
+    /// Count the transactions of a block from `eth_getBlockReceipts`, falling back to
+    /// `eth_getBlockTransactionCountByNumber` where the node does not serve it.
+    pub block_receipts: bool,
+    /// Skip the ticks while the shadow rollup is paused.
+    pub pause_check: bool,
+    /// Follow the challenge window of the recently proven batches.
+    pub challenge_window: bool,
+    /// Refuse to start unless the signer is the shadow rollup owner.
+    pub check_authorized_signer: bool,
+    /// Compare the header's withdrawalRoot with the L2 withdrawal tree at the batch end block.
+    pub verify_withdrawal_root: CheckMode,
+    /// Compare the header's prevStateRoot with the postStateRoot of the proven previous batch.
+    pub verify_state_chain: CheckMode,
+    /// Senders accepted for the commit transaction a batch header is read from; empty accepts
+    /// any sender.
+    pub sequencer_allowlist: Vec<Address>,
+    /// Compare the commit transaction sender with `sequencer_allowlist`, when set.
+    pub verify_sequencer: CheckMode,
+    /// Compare the header's blobVersionedHash with the blobs of the batch's commit transaction,
+    /// and with the blob sidecars of `beacon_rpc`, when set.
+    pub verify_blob_hash: CheckMode,
+    /// Beacon node API the blob sidecars are fetched from, if any.
+    pub beacon_rpc: Option<String>,
+    /// Contract the mismatches found by the checks are reported to with `raiseAlarm`, if any.
+    pub alarm_contract: Option<Address>,
+    /// Batch header codec version assumed when the rollup does not expose one.
+    pub batch_header_version: u8,
+    /// Interval to re-read the rollup's batch header version.
+    pub header_version_refresh: Duration,
+    /// Upper bound of `commitBatch` transactions sent but not yet confirmed.
+    pub max_inflight_commits: usize,
+    /// Simulate `commitBatch` with `eth_call` and skip the send when it would revert.
+    pub simulate_before_send: bool,
+    /// Simulate `commitBatch` and `proveState` with `eth_call` instead of sending them.
+    pub dry_run: bool,
+    /// Consecutive reverted commits opening the circuit breaker; 0 disables it.
+    pub circuit_revert_threshold: u32,
+    /// Time the circuit stays open before a successful simulation may close it.
+    pub circuit_cooldown: Duration,
+    /// Consecutive failed ticks after which the loop backs off; 0 disables it.
+    pub loop_failure_threshold: u32,
+    /// Sleep between ticks while the loop backs off.
+    pub loop_backoff: Duration,
+    /// Upper bound of contiguous batches proven in one `proveStates` transaction during the
+    /// catch-up; 1 proves each batch on its own.
+    pub multi_prove_max: usize,
+    /// Time to wait for the receipt of a sent `commitBatch` before failing the commit stage.
+    pub commit_receipt_timeout: Duration,
+    /// Replacements of a `commitBatch` still without a receipt after `commit_receipt_timeout`,
+    /// same nonce and bumped fees; 0 disables them.
+    pub commit_fee_bumps: u32,
+    /// Fee increase of each replacement, in percent.
+    pub commit_fee_bump_percent: u64,
+    /// Cap of the max fee per gas of the replacements, in wei.
+    pub commit_max_fee_per_gas: u128,
+    /// Private relay (e.g. Flashbots Protect) the signed transactions are sent to, if any.
+    pub private_tx_rpc: Option<String>,
+    /// Time to wait for a transaction sent through the private relay to be included.
+    pub private_tx_timeout: Duration,
+    /// Webhook the signed receipts of the processed batches are posted to, if any.
+    pub receipt_webhook_url: Option<String>,
+    /// HMAC key signing the receipts, required with a receipt webhook.
+    pub receipt_webhook_secret: Option<String>,
+    /// Receipts waiting for delivery before new ones are dropped.
+    pub receipt_queue_size: usize,
+    /// Retries of a failed receipt delivery.
+    pub receipt_retries: u32,
+    /// Webhook the failures of the commit and prove stages are posted to, if any.
+    pub alert_webhook: Option<String>,
+    /// Least time between two alerts of a stage.
+    pub alert_webhook_debounce: Duration,
+    /// Consecutive failed commit stages retried before alerting.
+    pub commit_failure_retries: u32,
+    /// Ticks without a commit stage after a commit alert.
+    pub commit_failure_cooldown: Duration,
+    /// Severity of a commit alert.
+    pub commit_failure_severity: Severity,
+    /// Consecutive failed prove stages retried before alerting.
+    pub prove_failure_retries: u32,
+    /// Ticks without a prove stage after a prove alert.
+    pub prove_failure_cooldown: Duration,
+    /// Severity of a prove alert.
+    pub prove_failure_severity: Severity,
+    /// Stages run by this process.
+    pub role: Role,
+    /// External batch queue shared by syncer and prover processes; in-process if unset.
+    pub queue_url: Option<String>,
+    /// Name of the batch list in the external queue.
+    pub queue_key: String,
+    /// Batches the in-process queue holds before the sync stage waits for the prove stage.
+    pub queue_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            loop_interval: Duration::from_secs(12),
+            l1_ws: None,
+            l1_chain_id: None,
+            verify_l1_chain_id: None,
+            min_batch: None,
+            max_batch: None,
+            max_block: 300,
+            max_txn: 600,
+            medium_batch_blocks: 100,
+            medium_batch_txns: 200,
+            large_batch_blocks: 200,
+            large_batch_txns: 400,
+            lookback_blocks: 600,
+            log_chunk_blocks: 1000,
+            stale_block_tolerance: 0,
+            confirmations: 0,
+            batch_buffer: 1,
+            rate_limit_backoff: Duration::from_secs(2),
+            rate_limit_max_backoff: Duration::from_secs(60),
+            rate_limit_retries: 5,
+            rpc_retries: 3,
+            rpc_retry_backoff: Duration::from_millis(500),
+            rpc_failover_errors: 3,
+            rpc_timeout: Duration::from_secs(30),
+            l1_rpc_headers: HeaderMap::new(),
+            verify_l1_rpc_headers: HeaderMap::new(),
+            l2_rpc_headers: HeaderMap::new(),
+            dedupe_commit_logs: true,
+            startup_catchup: false,
+            catchup: false,
+            catchup_max_batches: 10,
+            warmup_timeout: Duration::from_secs(60),
+            metric_address: DEFAULT_METRIC_ADDRESS.parse().unwrap(),
+            statsd_address: None,
+            metrics_push_url: None,
+            admin_token: None,
+            balance_refresh: Duration::from_secs(60),
+            low_balance_threshold: 0.1,
+            checkpoint_file: None,
+            audit_file: None,
+            state_retention: Duration::from_secs(7 * 86400),
+            batch_blocks_getter: None,
+            block_fetch_concurrency: 8,
+            multicall: None,
+            block_receipts: false,
+            pause_check: true,
+            challenge_window: false,
+            check_authorized_signer: true,
+            verify_withdrawal_root: CheckMode::Off,
+            verify_state_chain: CheckMode::Off,
+            sequencer_allowlist: Vec::new(),
+            verify_sequencer: CheckMode::Strict,
+            verify_blob_hash: CheckMode::Off,
+            beacon_rpc: None,
+            alarm_contract: None,
+            batch_header_version: 1,
+            header_version_refresh: Duration::from_secs(3600),
+            max_inflight_commits: 1,
+            simulate_before_send: true,
+            dry_run: false,
+            circuit_revert_threshold: 3,
+            circuit_cooldown: Duration::from_secs(1800),
+            loop_failure_threshold: 10,
+            loop_backoff: Duration::from_secs(600),
+            multi_prove_max: 1,
+            commit_receipt_timeout: Duration::from_secs(300),
+            commit_fee_bumps: 3,
+            commit_fee_bump_percent: 20,
+            commit_max_fee_per_gas: 200_000_000_000,
+            private_tx_rpc: None,
+            private_tx_timeout: Duration::from_secs(360),
+            receipt_webhook_url: None,
+            receipt_webhook_secret: None,
+            receipt_queue_size: 100,
+            receipt_retries: 5,
+            alert_webhook: None,
+            alert_webhook_debounce: Duration::from_secs(300),
+            commit_failure_retries: 3,
+            commit_failure_cooldown: Duration::ZERO,
+            commit_failure_severity: Severity::Warning,
+            prove_failure_retries: 0,
+            prove_failure_cooldown: Duration::ZERO,
+            prove_failure_severity: Severity::Critical,
+            role: Role::All,
+            queue_url: None,
+            queue_key: String::from("shadow-proving:batches"),
+            queue_size: 16,
+        }
+    }
+}
+
+impl Config {
+    /// Whether the batch is within `min_batch..=max_batch`.
+    pub fn batch_in_range(&self, batch_index: u64) -> bool {
+        self.min_batch.map_or(true, |min| batch_index >= min) &&
+            self.max_batch.map_or(true, |max| batch_index <= max)
+    }
+
+    /// Retry policy of the L1 reads.
+    pub fn rpc_backoff(&self) -> Backoff {
+        Backoff { retries: self.rpc_retries, base: self.rpc_retry_backoff }
+    }
+
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        let default = Self::default();
+        let config = Self {
+            loop_interval: poll_interval(
+                read_env_var("SHADOW_PROVING_POLL_INTERVAL_SECS", default.loop_interval.as_secs()),
+                default.loop_interval,
+            ),
+            l1_ws: std::env::var("SHADOW_PROVING_L1_WS").ok(),
+            l1_chain_id: parse_chain_id(
+                "SHADOW_PROVING_L1_CHAIN_ID",
+                std::env::var("SHADOW_PROVING_L1_CHAIN_ID").ok(),
+            )?,
+            verify_l1_chain_id: parse_chain_id(
+                "SHADOW_PROVING_VERIFY_L1_CHAIN_ID",
+                std::env::var("SHADOW_PROVING_VERIFY_L1_CHAIN_ID").ok(),
+            )?,
+            min_batch: parse_batch_index(
+                "SHADOW_PROVING_MIN_BATCH",
+                std::env::var("SHADOW_PROVING_MIN_BATCH").ok(),
+            )?,
+            max_batch: parse_batch_index(
+                "SHADOW_PROVING_MAX_BATCH",
+                std::env::var("SHADOW_PROVING_MAX_BATCH").ok(),
+            )?,
+            max_block: read_env_var("SHADOW_PROVING_MAX_BLOCK", default.max_block),
+            max_txn: read_env_var("SHADOW_PROVING_MAX_TXN", default.max_txn),
+            medium_batch_blocks: read_env_var(
+                "SHADOW_PROVING_MEDIUM_BATCH_BLOCKS",
+                default.medium_batch_blocks,
+            ),
+            medium_batch_txns: read_env_var(
+                "SHADOW_PROVING_MEDIUM_BATCH_TXNS",
+                default.medium_batch_txns,
+            ),
+            large_batch_blocks: read_env_var(
+                "SHADOW_PROVING_LARGE_BATCH_BLOCKS",
+                default.large_batch_blocks,
+            ),
+            large_batch_txns: read_env_var(
+                "SHADOW_PROVING_LARGE_BATCH_TXNS",
+                default.large_batch_txns,
+            ),
+            stale_block_tolerance: read_env_var(
+                "SHADOW_PROVING_STALE_BLOCK_TOLERANCE",
+                default.stale_block_tolerance,
+            ),
+            confirmations: read_env_var("SHADOW_PROVING_CONFIRMATIONS", default.confirmations),
+            batch_buffer: read_env_var(
+                "SHADOW_PROVING_BATCH_BUFFER",
+                read_env_var("SHADOW_PROVING_BATCH_LAG", default.batch_buffer),
+            ),
+            rate_limit_backoff: Duration::from_millis(read_env_var(
+                "SHADOW_PROVING_RATE_LIMIT_BACKOFF_MS",
+                default.rate_limit_backoff.as_millis() as u64,
+            )),
+            rate_limit_max_backoff: Duration::from_millis(read_env_var(
+                "SHADOW_PROVING_RATE_LIMIT_MAX_BACKOFF_MS",
+                default.rate_limit_max_backoff.as_millis() as u64,
+            )),
+            rate_limit_retries: read_env_var(
+                "SHADOW_PROVING_RATE_LIMIT_RETRIES",
+                default.rate_limit_retries,
+            ),
+            log_chunk_blocks: read_env_var(
+                "SHADOW_PROVING_LOG_CHUNK_BLOCKS",
+                default.log_chunk_blocks,
+            ),
+            rpc_retries: read_env_var("SHADOW_PROVING_RPC_RETRIES", default.rpc_retries),
+            rpc_retry_backoff: Duration::from_millis(read_env_var(
+                "SHADOW_PROVING_RPC_RETRY_BACKOFF_MS",
+                default.rpc_retry_backoff.as_millis() as u64,
+            )),
+            rpc_failover_errors: read_env_var(
+                "SHADOW_PROVING_RPC_FAILOVER_ERRORS",
+                default.rpc_failover_errors,
+            ),
+            rpc_timeout: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_RPC_TIMEOUT_SECS",
+                default.rpc_timeout.as_secs(),
+            )),
+            l1_rpc_headers: parse_header_list(
+                "SHADOW_PROVING_L1_RPC_HEADERS",
+                std::env::var("SHADOW_PROVING_L1_RPC_HEADERS").ok(),
+            )?,
+            verify_l1_rpc_headers: parse_header_list(
+                "SHADOW_PROVING_VERIFY_L1_RPC_HEADERS",
+                std::env::var("SHADOW_PROVING_VERIFY_L1_RPC_HEADERS").ok(),
+            )?,
+            l2_rpc_headers: parse_header_list(
+                "SHADOW_PROVING_L2_RPC_HEADERS",
+                std::env::var("SHADOW_PROVING_L2_RPC_HEADERS").ok(),
+            )?,
+            dedupe_commit_logs: read_env_var(
+                "SHADOW_PROVING_DEDUPE_COMMIT_LOGS",
+                default.dedupe_commit_logs,
+            ),
+            startup_catchup: read_env_var(
+                "SHADOW_PROVING_STARTUP_CATCHUP",
+                default.startup_catchup,
+            ),
+            catchup: read_env_var("SHADOW_PROVING_CATCHUP", default.catchup),
+            catchup_max_batches: read_env_var(
+                "SHADOW_PROVING_CATCHUP_MAX_BATCHES",
+                default.catchup_max_batches,
+            ),
+            warmup_timeout: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_WARMUP_TIMEOUT_SECS",
+                default.warmup_timeout.as_secs(),
+            )),
+            metric_address: parse_metric_address(
+                std::env::var("SHADOW_PROVING_METRIC_ADDRESS").ok(),
+            )?,
+            statsd_address: std::env::var("SHADOW_PROVING_STATSD_ADDR").ok(),
+            metrics_push_url: std::env::var("SHADOW_PROVING_METRICS_PUSH_URL").ok(),
+            admin_token: std::env::var("SHADOW_PROVING_ADMIN_TOKEN").ok(),
+            balance_refresh: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_BALANCE_REFRESH_SECS",
+                default.balance_refresh.as_secs(),
+            )),
+            low_balance_threshold: read_env_var(
+                "SHADOW_PROVING_LOW_BALANCE_ETH",
+                default.low_balance_threshold,
+            ),
+            checkpoint_file: std::env::var("SHADOW_PROVING_CHECKPOINT_FILE")
+                .ok()
+                .map(PathBuf::from),
+            audit_file: std::env::var("SHADOW_PROVING_AUDIT_FILE").ok().map(PathBuf::from),
+            state_retention: Duration::from_secs(
+                read_env_var(
+                    "SHADOW_PROVING_STATE_RETENTION_DAYS",
+                    default.state_retention.as_secs() / 86400,
+                ) * 86400,
+            ),
+            batch_blocks_getter: match std::env::var("SHADOW_PROVING_BATCH_BLOCKS_GETTER") {
+                Ok(address) => Some(address.parse().with_context(|| {
+                    format!("SHADOW_PROVING_BATCH_BLOCKS_GETTER is not an address: {:?}", address)
+                })?),
+                Err(_) => None,
+            },
+            block_fetch_concurrency: read_env_var(
+                "SHADOW_PROVING_BLOCK_FETCH_CONCURRENCY",
+                default.block_fetch_concurrency,
+            ),
+            multicall: match std::env::var("SHADOW_PROVING_MULTICALL") {
+                Ok(address) => Some(address.parse().with_context(|| {
+                    format!("SHADOW_PROVING_MULTICALL is not an address: {:?}", address)
+                })?),
+                Err(_) => None,
+            },
+            block_receipts: read_env_var("SHADOW_PROVING_BLOCK_RECEIPTS", default.block_receipts),
+            pause_check: read_env_var("SHADOW_PROVING_PAUSE_CHECK", default.pause_check),
+            challenge_window: read_env_var(
+                "SHADOW_PROVING_CHALLENGE_WINDOW",
+                default.challenge_window,
+            ),
+            check_authorized_signer: read_env_var(
+                "SHADOW_PROVING_CHECK_AUTHORIZED_SIGNER",
+                default.check_authorized_signer,
+            ),
+            verify_withdrawal_root: read_env_var(
+                "SHADOW_PROVING_VERIFY_WITHDRAWAL_ROOT",
+                default.verify_withdrawal_root,
+            ),
+            verify_state_chain: read_env_var(
+                "SHADOW_PROVING_VERIFY_STATE_CHAIN",
+                default.verify_state_chain,
+            ),
+            sequencer_allowlist: parse_address_list(
+                "SHADOW_PROVING_SEQUENCER_ALLOWLIST",
+                std::env::var("SHADOW_PROVING_SEQUENCER_ALLOWLIST").ok(),
+            )?,
+            verify_sequencer: read_env_var(
+                "SHADOW_PROVING_VERIFY_SEQUENCER",
+                default.verify_sequencer,
+            ),
+            verify_blob_hash: read_env_var(
+                "SHADOW_PROVING_VERIFY_BLOB_HASH",
+                default.verify_blob_hash,
+            ),
+            beacon_rpc: std::env::var("SHADOW_PROVING_BEACON_RPC").ok(),
+            alarm_contract: match std::env::var("SHADOW_PROVING_ALARM_CONTRACT") {
+                Ok(address) => Some(address.parse().with_context(|| {
+                    format!("SHADOW_PROVING_ALARM_CONTRACT is not an address: {:?}", address)
+                })?),
+                Err(_) => None,
+            },
+            batch_header_version: read_env_var(
+                "SHADOW_PROVING_BATCH_HEADER_VERSION",
+                default.batch_header_version,
+            ),
+            header_version_refresh: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_HEADER_VERSION_REFRESH_SECS",
+                default.header_version_refresh.as_secs(),
+            )),
+            max_inflight_commits: read_env_var(
+                "SHADOW_PROVING_MAX_INFLIGHT_COMMITS",
+                default.max_inflight_commits,
+            ),
+            simulate_before_send: read_env_var(
+                "SHADOW_PROVING_SIMULATE_BEFORE_SEND",
+                default.simulate_before_send,
+            ),
+            dry_run: read_env_var("SHADOW_PROVING_DRY_RUN", default.dry_run),
+            circuit_revert_threshold: read_env_var(
+                "SHADOW_PROVING_CIRCUIT_REVERT_THRESHOLD",
+                default.circuit_revert_threshold,
+            ),
+            circuit_cooldown: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_CIRCUIT_COOLDOWN_SECS",
+                default.circuit_cooldown.as_secs(),
+            )),
+            loop_failure_threshold: read_env_var(
+                "SHADOW_PROVING_LOOP_FAILURE_THRESHOLD",
+                default.loop_failure_threshold,
+            ),
+            loop_backoff: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_LOOP_BACKOFF_SECS",
+                default.loop_backoff.as_secs(),
+            )),
+            multi_prove_max: read_env_var(
+                "SHADOW_PROVING_MULTI_PROVE_MAX",
+                default.multi_prove_max,
+            ),
+            commit_receipt_timeout: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_COMMIT_RECEIPT_TIMEOUT_SECS",
+                default.commit_receipt_timeout.as_secs(),
+            )),
+            commit_fee_bumps: read_env_var(
+                "SHADOW_PROVING_COMMIT_FEE_BUMPS",
+                default.commit_fee_bumps,
+            ),
+            commit_fee_bump_percent: read_env_var(
+                "SHADOW_PROVING_COMMIT_FEE_BUMP_PERCENT",
+                default.commit_fee_bump_percent,
+            ),
+            commit_max_fee_per_gas: read_env_var(
+                "SHADOW_PROVING_COMMIT_MAX_FEE_GWEI",
+                default.commit_max_fee_per_gas / 1_000_000_000,
+            ) * 1_000_000_000,
+            private_tx_rpc: std::env::var("SHADOW_PROVING_PRIVATE_TX_RPC").ok(),
+            private_tx_timeout: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_PRIVATE_TX_TIMEOUT_SECS",
+                default.private_tx_timeout.as_secs(),
+            )),
+            receipt_webhook_url: std::env::var("SHADOW_PROVING_RECEIPT_WEBHOOK_URL").ok(),
+            receipt_webhook_secret: std::env::var("SHADOW_PROVING_RECEIPT_WEBHOOK_SECRET").ok(),
+            receipt_queue_size: read_env_var(
+                "SHADOW_PROVING_RECEIPT_QUEUE_SIZE",
+                default.receipt_queue_size,
+            ),
+            receipt_retries: read_env_var(
+                "SHADOW_PROVING_RECEIPT_RETRIES",
+                default.receipt_retries,
+            ),
+            alert_webhook: std::env::var("SHADOW_PROVING_ALERT_WEBHOOK").ok(),
+            alert_webhook_debounce: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_ALERT_WEBHOOK_DEBOUNCE_SECS",
+                default.alert_webhook_debounce.as_secs(),
+            )),
+            commit_failure_retries: read_env_var(
+                "SHADOW_PROVING_COMMIT_FAILURE_RETRIES",
+                default.commit_failure_retries,
+            ),
+            commit_failure_cooldown: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_COMMIT_FAILURE_COOLDOWN_SECS",
+                default.commit_failure_cooldown.as_secs(),
+            )),
+            commit_failure_severity: read_env_var(
+                "SHADOW_PROVING_COMMIT_FAILURE_SEVERITY",
+                default.commit_failure_severity,
+            ),
+            prove_failure_retries: read_env_var(
+                "SHADOW_PROVING_PROVE_FAILURE_RETRIES",
+                default.prove_failure_retries,
+            ),
+            prove_failure_cooldown: Duration::from_secs(read_env_var(
+                "SHADOW_PROVING_PROVE_FAILURE_COOLDOWN_SECS",
+                default.prove_failure_cooldown.as_secs(),
+            )),
+            prove_failure_severity: read_env_var(
+                "SHADOW_PROVING_PROVE_FAILURE_SEVERITY",
+                default.prove_failure_severity,
+            ),
+            role: match std::env::var("SHADOW_PROVING_ROLE") {
+                Ok(role) => role.parse().map_err(anyhow::Error::msg)?,
+                Err(_) => default.role,
+            },
+            queue_url: std::env::var("SHADOW_PROVING_QUEUE_URL").ok(),
+            queue_key: read_env_var("SHADOW_PROVING_QUEUE_KEY", default.queue_key.clone()),
+            queue_size: read_env_var("SHADOW_PROVING_QUEUE_SIZE", default.queue_size),
+            ..default
+        };
+        if let (Some(min), Some(max)) = (config.min_batch, config.max_batch) {
+            if min > max {
+                anyhow::bail!(
+                    "SHADOW_PROVING_MIN_BATCH {} exceeds SHADOW_PROVING_MAX_BATCH {}",
+                    min,
+                    max
+                );
+            }
+        }
+        if config.batch_buffer == 0 {
+            anyhow::bail!("SHADOW_PROVING_BATCH_BUFFER must be at least 1");
+        }
+        if config.medium_batch_blocks > config.large_batch_blocks ||
+            config.medium_batch_txns > config.large_batch_txns
+        {
+            anyhow::bail!("medium batch thresholds must not exceed the large ones");
+        }
+        if config.block_fetch_concurrency == 0 {
+            anyhow::bail!("SHADOW_PROVING_BLOCK_FETCH_CONCURRENCY must be at least 1");
+        }
+        if config.receipt_webhook_url.is_some() &&
+            config.receipt_webhook_secret.as_deref().unwrap_or_default().is_empty()
+        {
+            anyhow::bail!(
+                "SHADOW_PROVING_RECEIPT_WEBHOOK_URL requires SHADOW_PROVING_RECEIPT_WEBHOOK_SECRET"
+            );
+        }
+        // Nodes refuse a replacement bumping the fees by less than 10%.
+        if config.commit_fee_bumps > 0 && config.commit_fee_bump_percent < 10 {
+            anyhow::bail!("SHADOW_PROVING_COMMIT_FEE_BUMP_PERCENT must be at least 10");
+        }
+        if config.receipt_queue_size == 0 {
+            anyhow::bail!("SHADOW_PROVING_RECEIPT_QUEUE_SIZE must be at least 1");
+        }
+        if config.role != Role::All && config.queue_url.is_none() {
+            anyhow::bail!(
+                "SHADOW_PROVING_ROLE={:?} requires SHADOW_PROVING_QUEUE_URL",
+                config.role
+            );
+        }
+        Ok(config)
+    }
+}
+
+fn parse_chain_id(var_name: &str, value: Option<String>) -> Result<Option<u64>, anyhow::Error> {
+    value
+        .map(|id| {
+            id.trim().parse().with_context(|| format!("{} is not a chain id: {:?}", var_name, id))
+        })
+        .transpose()
+}
+
+fn parse_batch_index(var_name: &str, value: Option<String>) -> Result<Option<u64>, anyhow::Error> {
+    value
+        .map(|index| {
+            index
+                .trim()
+                .parse()
+                .with_context(|| format!("{} is not a batch index: {:?}", var_name, index))
+        })
+        .transpose()
+}
+
+/// Comma-separated addresses; unset or blank is an empty list.
+fn parse_address_list(
+    var_name: &str,
+    value: Option<String>,
+) -> Result<Vec<Address>, anyhow::Error> {
+    let Some(value) = value else {
+        return Ok(Vec::new());
+    };
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(|address| {
+            address
+                .parse()
+                .with_context(|| format!("{} is not an address list: {:?}", var_name, address))
+        })
+        .collect()
+}
+
+/// Comma separated `Key: Value` headers. The values carry API keys, so they are kept out of
+/// the errors and of `Debug`.
+fn parse_header_list(var_name: &str, value: Option<String>) -> Result<HeaderMap, anyhow::Error> {
+    let mut headers = HeaderMap::new();
+    let Some(value) = value else {
+        return Ok(headers);
+    };
+    for header in value.split(',').map(str::trim).filter(|header| !header.is_empty()) {
+        let (name, value) = header
+            .split_once(':')
+            .with_context(|| format!("{} is not a list of `Key: Value` headers", var_name))?;
+        let name: HeaderName = name.trim().parse().with_context(|| {
+            format!("{} has an invalid header name {:?}", var_name, name.trim())
+        })?;
+        let mut value = HeaderValue::from_str(value.trim())
+            .with_context(|| format!("{} has an invalid value of {}", var_name, name))?;
+        value.set_sensitive(true);
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// A zero interval would busy-loop against the RPCs, so it falls back to the default like an
+/// unparsable one.
+fn poll_interval(secs: u64, default: Duration) -> Duration {
+    if secs == 0 {
+        log::warn!("SHADOW_PROVING_POLL_INTERVAL_SECS must be positive, using {:?}", default);
+        return default;
+    }
+    Duration::from_secs(secs)
+}
+
+/// The default applies only when the variable is unset; a malformed value is an error.
+fn parse_metric_address(value: Option<String>) -> Result<SocketAddr, anyhow::Error> {
+    match value {
+        Some(address) => address.parse().with_context(|| {
+            format!("SHADOW_PROVING_METRIC_ADDRESS is not a valid socket address: {:?}", address)
+        }),
+        None => Ok(DEFAULT_METRIC_ADDRESS.parse()?),
+    }
+}
+
+#[test]
+fn test_parse_metric_address() {
+    assert_eq!(parse_metric_address(None).unwrap(), "0.0.0.0:6060".parse().unwrap());
+    assert_eq!(
+        parse_metric_address(Some("127.0.0.1:9090".to_string())).unwrap(),
+        "127.0.0.1:9090".parse().unwrap()
+    );
+
+    let err = parse_metric_address(Some("localhost:port".to_string())).unwrap_err();
+    assert!(err.to_string().contains("SHADOW_PROVING_METRIC_ADDRESS"));
+}
+
+#[test]
+fn test_poll_interval() {
+    let default = Duration::from_secs(12);
+    assert_eq!(poll_interval(2, default), Duration::from_secs(2));
+    assert_eq!(poll_interval(0, default), default);
+}
+
+#[test]
+fn test_batch_in_range() {
+    let config = Config { min_batch: Some(10), max_batch: Some(20), ..Config::default() };
+    assert!(!config.batch_in_range(9));
+    assert!(config.batch_in_range(10));
+    assert!(config.batch_in_range(20));
+    assert!(!config.batch_in_range(21));
+
+    let config = Config { max_batch: Some(20), ..Config::default() };
+    assert!(config.batch_in_range(0));
+    assert!(!config.batch_in_range(21));
+    assert!(Config::default().batch_in_range(u64::MAX));
+
+    let err = parse_batch_index("SHADOW_PROVING_MIN_BATCH", Some("ten".into())).unwrap_err();
+    assert!(err.to_string().contains("SHADOW_PROVING_MIN_BATCH"));
+    assert_eq!(
+        parse_batch_index("SHADOW_PROVING_MIN_BATCH", Some(" 10".into())).unwrap(),
+        Some(10)
+    );
+}
+
+#[test]
+fn test_parse_header_list() {
+    let var = "SHADOW_PROVING_L1_RPC_HEADERS";
+    assert!(parse_header_list(var, None).unwrap().is_empty());
+
+    let headers = parse_header_list(
+        var,
+        Some(String::from("Authorization: Bearer s3cret, X-Api-Key:k3y, x-api-key: k3y2,")),
+    )
+    .unwrap();
+    assert_eq!(headers["authorization"], "Bearer s3cret");
+    assert_eq!(headers.get_all("x-api-key").iter().collect::<Vec<_>>(), ["k3y", "k3y2"]);
+    assert!(!format!("{:?}", headers).contains("s3cret"));
+
+    let error = parse_header_list(var, Some(String::from("Bearer s3cret"))).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "SHADOW_PROVING_L1_RPC_HEADERS is not a list of `Key: Value` headers"
+    );
+    assert!(parse_header_list(var, Some(String::from("X Key: k3y"))).is_err());
+    let error = parse_header_list(var, Some(String::from("X-Key: s3cret\n"))).unwrap_err();
+    assert!(!error.to_string().contains("s3cret"));
+}
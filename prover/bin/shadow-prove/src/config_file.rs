@@ -0,0 +1,337 @@
+//! Configuration file, passed with `--config` or `SHADOW_PROVING_CONFIG`.
+//!
+//! The file is a flat TOML table keyed by the `SHADOW_PROVING_*` env vars, lowercase and without
+//! the prefix:
+//!
+//! ```toml
+//! l1_rpc = "http://localhost:8545"
+//! l1_shadow_rollup = "0x..."
+//! max_block = 600
+//! sequencer_allowlist = ["0x...", "0x..."]
+//! ```
+//!
+//! Each value is exported to its env var unless the var is already set, so the env overrides the
+//! file and the command line flags override both. Arrays are joined with commas.
+//!
+//! Before the daemon or a chain command starts, [`validate`] checks the settings without a
+//! default: the RPC URLs, comma separated for a failover, the rollup addresses and the signer key
+//! source, see [`crate::signer`]. Every missing or malformed one is reported at once.
+
+use alloy::{primitives::Address, signers::local::PrivateKeySigner};
+use anyhow::{anyhow, Context};
+use reqwest::Url;
+use std::{collections::BTreeMap, path::Path};
+
+const PREFIX: &str = "SHADOW_PROVING_";
+
+/// Keys of the file, the env vars read by the daemon.
+const KEYS: &[&str] = &[
+    "admin_token",
+    "alarm_contract",
+    "alert_webhook",
+    "alert_webhook_debounce_secs",
+    "audit_file",
+    "balance_refresh_secs",
+    "batch_blocks_getter",
+    "batch_buffer",
+    "batch_header_version",
+    "batch_lag",
+    "beacon_rpc",
+    "block_fetch_concurrency",
+    "block_receipts",
+    "catchup",
+    "catchup_max_batches",
+    "challenge_window",
+    "check_authorized_signer",
+    "checkpoint_file",
+    "circuit_cooldown_secs",
+    "circuit_revert_threshold",
+    "commit_failure_cooldown_secs",
+    "commit_failure_retries",
+    "commit_failure_severity",
+    "commit_fee_bump_percent",
+    "commit_fee_bumps",
+    "commit_max_fee_gwei",
+    "commit_receipt_timeout_secs",
+    "confirmations",
+    "dedupe_commit_logs",
+    "dry_run",
+    "header_version_refresh_secs",
+    "keystore",
+    "keystore_password",
+    "kms_key_id",
+    "l1_chain_id",
+    "l1_rollup",
+    "l1_rpc",
+    "l1_rpc_headers",
+    "l1_shadow_rollup",
+    "l1_ws",
+    "l2_rpc",
+    "l2_rpc_headers",
+    "large_batch_blocks",
+    "large_batch_txns",
+    "log_chunk_blocks",
+    "log_dir",
+    "log_file_size_bytes",
+    "log_files_to_keep",
+    "log_format",
+    "log_rotate_age",
+    "log_to_file",
+    "loop_backoff_secs",
+    "loop_failure_threshold",
+    "low_balance_eth",
+    "max_batch",
+    "max_block",
+    "max_inflight_commits",
+    "max_txn",
+    "medium_batch_blocks",
+    "medium_batch_txns",
+    "metric_address",
+    "metrics_push_url",
+    "min_batch",
+    "multi_prove_max",
+    "multicall",
+    "pause_check",
+    "poll_interval_secs",
+    "private_key",
+    "private_tx_rpc",
+    "private_tx_timeout_secs",
+    "prove_failure_cooldown_secs",
+    "prove_failure_retries",
+    "prove_failure_severity",
+    "prover_rpc",
+    "queue_key",
+    "queue_size",
+    "queue_url",
+    "rate_limit_backoff_ms",
+    "rate_limit_max_backoff_ms",
+    "rate_limit_retries",
+    "receipt_queue_size",
+    "receipt_retries",
+    "receipt_webhook_secret",
+    "receipt_webhook_url",
+    "remote_signer_address",
+    "remote_signer_url",
+    "role",
+    "rpc_failover_errors",
+    "rpc_retries",
+    "rpc_retry_backoff_ms",
+    "rpc_timeout_secs",
+    "sequencer_allowlist",
+    "simulate_before_send",
+    "stale_block_tolerance",
+    "startup_catchup",
+    "state_retention_days",
+    "statsd_addr",
+    "verify_blob_hash",
+    "verify_l1_chain_id",
+    "verify_l1_rpc",
+    "verify_l1_rpc_headers",
+    "verify_l2_rpc",
+    "verify_sequencer",
+    "verify_state_chain",
+    "verify_withdrawal_root",
+    "warmup_timeout_secs",
+];
+
+/// What a required setting must parse as.
+#[derive(Clone, Copy)]
+enum Kind {
+    Url,
+    /// Comma separated URLs, the endpoints of an RPC failing over, see [`crate::failover`].
+    Urls,
+    Address,
+    PrivateKey,
+}
+
+impl Kind {
+    fn check(self, value: &str) -> Result<(), String> {
+        match self {
+            Self::Url => Url::parse(value).map(|_| ()).map_err(|e| format!("is not a URL: {}", e)),
+            Self::Urls => value.split(',').try_for_each(|url| {
+                Url::parse(url.trim()).map(|_| ()).map_err(|e| format!("is not a URL list: {}", e))
+            }),
+            Self::Address => value
+                .parse::<Address>()
+                .map(|_| ())
+                .map_err(|e| format!("is not an address: {}", e)),
+            // The key itself is kept out of the message.
+            Self::PrivateKey => value
+                .parse::<PrivateKeySigner>()
+                .map(|_| ())
+                .map_err(|_| String::from("is not a private key")),
+        }
+    }
+}
+
+/// Settings without a default, needed by the daemon and the chain commands.
+const REQUIRED: &[(&str, Kind)] = &[
+    ("l1_rpc", Kind::Urls),
+    ("verify_l1_rpc", Kind::Urls),
+    ("l2_rpc", Kind::Urls),
+    ("prover_rpc", Kind::Url),
+    ("l1_rollup", Kind::Address),
+    ("l1_shadow_rollup", Kind::Address),
+];
+
+/// The env vars set by the file content `text`.
+pub fn parse(text: &str) -> Result<Vec<(String, String)>, anyhow::Error> {
+    let table: BTreeMap<String, toml::Value> = toml::from_str(text)?;
+    let mut unknown = Vec::new();
+    let mut vars = Vec::new();
+    for (key, value) in table {
+        if !KEYS.contains(&key.as_str()) {
+            unknown.push(key);
+            continue;
+        }
+        let value =
+            env_value(&value).ok_or_else(|| anyhow!("{} is not a scalar or an array", key))?;
+        vars.push((env_var(&key), value));
+    }
+    if !unknown.is_empty() {
+        anyhow::bail!("unknown keys: {}", unknown.join(", "));
+    }
+    Ok(vars)
+}
+
+fn env_value(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(i) => Some(i.to_string()),
+        toml::Value::Float(f) => Some(f.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        toml::Value::Array(values) => {
+            values.iter().map(env_value).collect::<Option<Vec<_>>>().map(|v| v.join(","))
+        }
+        toml::Value::Datetime(_) | toml::Value::Table(_) => None,
+    }
+}
+
+/// Export the file at `path` to the env vars not already set.
+pub fn load(path: &Path) -> Result<(), anyhow::Error> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("read config file {}", path.display()))?;
+    let vars = parse(&text).with_context(|| format!("invalid config file {}", path.display()))?;
+    for (name, value) in vars {
+        if std::env::var_os(&name).is_none() {
+            std::env::set_var(name, value);
+        }
+    }
+    Ok(())
+}
+
+fn env_var(key: &str) -> String {
+    format!("{}{}", PREFIX, key.to_ascii_uppercase())
+}
+
+/// The problems of the required settings `get` reads, the signer key source included.
+pub fn problems(get: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    let value = |key: &str| get(&env_var(key)).filter(|v| !v.is_empty());
+    let check = |key: &str, kind: Kind| match value(key) {
+        None => Some(format!("{} is missing", env_var(key))),
+        Some(v) => kind.check(&v).err().map(|e| format!("{} {}", env_var(key), e)),
+    };
+    let mut problems: Vec<String> =
+        REQUIRED.iter().filter_map(|&(key, kind)| check(key, kind)).collect();
+    if value("remote_signer_url").is_some() {
+        problems.extend(check("remote_signer_url", Kind::Url));
+        problems.extend(check("remote_signer_address", Kind::Address));
+    } else if value("kms_key_id").is_some() {
+        // The key is only known to KMS.
+    } else if let Some(path) = value("keystore") {
+        if !Path::new(&path).is_file() {
+            problems.push(format!("{} {} is not a file", env_var("keystore"), path));
+        }
+        // An empty password is a password.
+        if get(&env_var("keystore_password")).is_none() {
+            problems.push(format!("{} is missing", env_var("keystore_password")));
+        }
+    } else if value("private_key").is_some() {
+        problems.extend(check("private_key", Kind::PrivateKey));
+    } else {
+        problems.push(format!(
+            "{}, {}, {} or {} is missing",
+            env_var("private_key"),
+            env_var("keystore"),
+            env_var("kms_key_id"),
+            env_var("remote_signer_url")
+        ));
+    }
+    problems
+}
+
+/// Fail listing every required setting neither the file, the env nor the flags set properly.
+pub fn validate() -> Result<(), anyhow::Error> {
+    let problems = problems(|name| std::env::var(name).ok());
+    if !problems.is_empty() {
+        anyhow::bail!("invalid shadow proving config:\n  {}", problems.join("\n  "));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parse() {
+    let vars = parse(
+        r#"
+        l1_rpc = "http://localhost:8545"
+        max_block = 600
+        low_balance_eth = 0.5
+        block_receipts = true
+        sequencer_allowlist = ["0x01", "0x02"]
+        "#,
+    )
+    .unwrap();
+    let var = |name: &str| vars.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+    assert_eq!(var("SHADOW_PROVING_L1_RPC"), Some("http://localhost:8545"));
+    assert_eq!(var("SHADOW_PROVING_MAX_BLOCK"), Some("600"));
+    assert_eq!(var("SHADOW_PROVING_LOW_BALANCE_ETH"), Some("0.5"));
+    assert_eq!(var("SHADOW_PROVING_BLOCK_RECEIPTS"), Some("true"));
+    assert_eq!(var("SHADOW_PROVING_SEQUENCER_ALLOWLIST"), Some("0x01,0x02"));
+
+    let error = parse("max_blocks = 600\nl2_rcp = \"http://l2\"").unwrap_err();
+    assert_eq!(error.to_string(), "unknown keys: l2_rcp, max_blocks");
+    assert!(parse("[l1]\nrpc = \"http://l1\"").is_err());
+}
+
+#[test]
+fn test_problems() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string())
+    };
+    let found = problems(env(&[
+        ("SHADOW_PROVING_L1_RPC", "http://l1:8545, http://l1-backup:8545"),
+        ("SHADOW_PROVING_VERIFY_L1_RPC", "http://l1:8545,l1.example"),
+        ("SHADOW_PROVING_L2_RPC", ""),
+        ("SHADOW_PROVING_PROVER_RPC", "http://prover"),
+        ("SHADOW_PROVING_L1_ROLLUP", "0x01"),
+        ("SHADOW_PROVING_L1_SHADOW_ROLLUP", "0x0000000000000000000000000000000000000001"),
+        ("SHADOW_PROVING_PRIVATE_KEY", "0xsecret"),
+    ]));
+    assert_eq!(found.len(), 4);
+    assert!(found[0].starts_with("SHADOW_PROVING_VERIFY_L1_RPC is not a URL list"));
+    assert_eq!(found[1], "SHADOW_PROVING_L2_RPC is missing");
+    assert!(found[2].starts_with("SHADOW_PROVING_L1_ROLLUP is not an address"));
+    assert_eq!(found[3], "SHADOW_PROVING_PRIVATE_KEY is not a private key");
+
+    let found = problems(env(&[("SHADOW_PROVING_REMOTE_SIGNER_URL", "http://signer")]));
+    assert_eq!(found.len(), REQUIRED.len() + 1);
+    assert_eq!(found.last().unwrap(), "SHADOW_PROVING_REMOTE_SIGNER_ADDRESS is missing");
+    let found = problems(env(&[]));
+    assert_eq!(
+        found.last().unwrap(),
+        "SHADOW_PROVING_PRIVATE_KEY, SHADOW_PROVING_KEYSTORE, SHADOW_PROVING_KMS_KEY_ID or \
+         SHADOW_PROVING_REMOTE_SIGNER_URL is missing"
+    );
+    let found = problems(env(&[("SHADOW_PROVING_KEYSTORE", "/nonexistent/keystore.json")]));
+    assert_eq!(
+        found[found.len() - 2..],
+        [
+            "SHADOW_PROVING_KEYSTORE /nonexistent/keystore.json is not a file",
+            "SHADOW_PROVING_KEYSTORE_PASSWORD is missing",
+        ]
+    );
+    assert_eq!(
+        problems(env(&[("SHADOW_PROVING_KMS_KEY_ID", "alias/shadow")])).len(),
+        REQUIRED.len()
+    );
+}
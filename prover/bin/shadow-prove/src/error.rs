@@ -0,0 +1,62 @@
+//! Failure kinds of the sync and prove paths.
+//!
+//! Internal functions return a [`ShadowError`]; the stages wrap it in `anyhow` with context, and
+//! the loop reads its [`kind`] back to log and alert on.
+
+use crate::batch_header::HeaderError;
+use alloy::{contract, primitives::TxHash, transports::TransportError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShadowError {
+    /// An RPC request failed.
+    #[error("rpc error: {0}")]
+    Transport(#[from] TransportError),
+    /// A contract call failed or reverted.
+    #[error("contract call error: {0}")]
+    Contract(#[from] contract::Error),
+    /// Fetching the `CommitBatch` logs failed, retries included.
+    #[error("get commit_batch logs error: {0}")]
+    GetLogs(String),
+    /// The commit transaction a batch header is read from is unknown to the node.
+    #[error("commit tx {0:?} not found")]
+    BatchNotFound(TxHash),
+    /// The input of a commit transaction is not a `commitBatch` call.
+    #[error("decode commit tx {0:?} error: {1}")]
+    Decode(TxHash, String),
+    #[error(transparent)]
+    Header(#[from] HeaderError),
+    /// Every prove attempt of the batch failed.
+    #[error("batch {batch_index} abandoned after {attempts} prove attempts: {reason}")]
+    ProveFailed { batch_index: u64, attempts: u8, reason: String },
+}
+
+impl ShadowError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Transport(_) => "transport",
+            Self::Contract(_) => "contract",
+            Self::GetLogs(_) => "get_logs",
+            Self::BatchNotFound(_) => "batch_not_found",
+            Self::Decode(..) => "decode",
+            Self::Header(_) => "header",
+            Self::ProveFailed { .. } => "prove_failed",
+        }
+    }
+}
+
+/// Kind of the [`ShadowError`] behind `error`, `other` for the rest.
+pub fn kind(error: &anyhow::Error) -> &'static str {
+    error.downcast_ref::<ShadowError>().map_or("other", ShadowError::kind)
+}
+
+#[test]
+fn test_kind() {
+    use anyhow::Context;
+
+    let error: Result<(), _> = Err(ShadowError::GetLogs(String::from("rate limited")));
+    let error = error.context("sync batch").unwrap_err();
+    assert_eq!(kind(&error), "get_logs");
+    assert_eq!(format!("{:#}", error), "sync batch: get commit_batch logs error: rate limited");
+
+    assert_eq!(kind(&anyhow::anyhow!("nonce too low")), "other");
+}
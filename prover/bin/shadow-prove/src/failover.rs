@@ -0,0 +1,293 @@
+//! Failover across the endpoints of an RPC.
+//!
+//! `SHADOW_PROVING_L1_RPC`, `SHADOW_PROVING_VERIFY_L1_RPC` and `SHADOW_PROVING_L2_RPC` take comma
+//! separated URLs. Requests go to the active endpoint, the first one at startup. After
+//! `SHADOW_PROVING_RPC_FAILOVER_ERRORS` consecutive failed requests the transport rotates to the
+//! next endpoint, wrapping around, logs it and counts the rotation in
+//! `shadow_rpc_failovers_total`. Only a transport failure counts, a refused connection, a timeout
+//! or an HTTP error status; a JSON-RPC error such as a revert is an answer of a working node.
+//!
+//! The failed request itself is not replayed on the next endpoint, the callers retry as before.
+//!
+//! A request is bounded by `SHADOW_PROVING_RPC_TIMEOUT_SECS` so a stalled node cannot wedge the
+//! loop: it then fails as a transport error, retried like a refused connection and counted in
+//! `shadow_rpc_timeouts_total`.
+
+use crate::{
+    banner::redact_url,
+    metrics::{self, METRICS},
+};
+use alloy::{
+    providers::{ProviderBuilder, RootProvider},
+    rpc::{
+        client::RpcClient,
+        json_rpc::{RequestPacket, ResponsePacket},
+    },
+    transports::{
+        http::{reqwest::header::HeaderMap, Client, Http},
+        utils::guess_local_url,
+        TransportError, TransportErrorKind, TransportFut,
+    },
+};
+use anyhow::{anyhow, Context};
+use std::{
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context as TaskContext, Poll},
+    time::Duration,
+};
+use tower::Service;
+
+/// HTTP transport of the RPC `provider`, `l1`, `l2` or `verify`, failing over between its
+/// endpoints.
+#[derive(Clone, Debug)]
+pub struct FailoverTransport {
+    provider: &'static str,
+    endpoints: Arc<[Http<Client>]>,
+    max_errors: u32,
+    timeout: Option<Duration>,
+    active: Arc<AtomicUsize>,
+    consecutive_errors: Arc<AtomicU32>,
+}
+
+impl FailoverTransport {
+    /// Transport over the comma separated `urls`, rotating after `max_errors` consecutive failed
+    /// requests.
+    pub fn new(provider: &'static str, urls: &str, max_errors: u32) -> Result<Self, anyhow::Error> {
+        let endpoints = urls
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(|url| {
+                url.parse()
+                    .map(Http::new)
+                    .with_context(|| format!("parse {} rpc {} to Url", provider, redact_url(url)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if endpoints.is_empty() {
+            return Err(anyhow!("no {} rpc endpoint", provider));
+        }
+        let transport = Self {
+            provider,
+            endpoints: endpoints.into(),
+            max_errors: max_errors.max(1),
+            timeout: None,
+            active: Arc::default(),
+            consecutive_errors: Arc::default(),
+        };
+        log::info!(
+            "{} rpc: {} endpoint(s), active {}",
+            provider,
+            transport.endpoints.len(),
+            redact_url(transport.url())
+        );
+        Ok(transport)
+    }
+
+    /// Send `headers` with every request, e.g. the `Authorization` of a provider taking no key
+    /// in the URL.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Result<Self, anyhow::Error> {
+        if headers.is_empty() {
+            return Ok(self);
+        }
+        let client = Client::builder().default_headers(headers).build()?;
+        self.endpoints = self
+            .endpoints
+            .iter()
+            .cloned()
+            .map(|mut endpoint| {
+                endpoint.set_client(client.clone());
+                endpoint
+            })
+            .collect();
+        Ok(self)
+    }
+
+    /// Fail a request without a response after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Provider over the transport.
+    pub fn into_provider(self) -> RootProvider<Self> {
+        let is_local = guess_local_url(self.url());
+        ProviderBuilder::new().on_client(RpcClient::new(self, is_local))
+    }
+
+    /// URL of the active endpoint.
+    pub fn url(&self) -> &str {
+        self.endpoints[self.active.load(Ordering::Relaxed)].url()
+    }
+
+    /// The error of a request to the endpoint `index` unanswered after `timeout`.
+    fn timed_out(&self, index: usize, timeout: Duration) -> TransportError {
+        log::warn!(
+            "{} rpc {} request timed out after {:?}",
+            self.provider,
+            redact_url(self.endpoints[index].url()),
+            timeout
+        );
+        metrics::inc(&METRICS.shadow_rpc_timeouts_total.with_label_values(&[self.provider]));
+        TransportErrorKind::custom_str(&format!("request timed out after {:?}", timeout))
+    }
+
+    /// Count the outcome of a request sent to the endpoint `index`.
+    fn record(&self, index: usize, failed: bool) {
+        // The outcome of an endpoint rotated away from meanwhile is stale.
+        if self.active.load(Ordering::Relaxed) != index {
+            return;
+        }
+        if !failed {
+            self.consecutive_errors.store(0, Ordering::Relaxed);
+            return;
+        }
+        let errors = self.consecutive_errors.fetch_add(1, Ordering::Relaxed) + 1;
+        if errors < self.max_errors || self.endpoints.len() == 1 {
+            return;
+        }
+        let next = (index + 1) % self.endpoints.len();
+        if self.active.compare_exchange(index, next, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            self.consecutive_errors.store(0, Ordering::Relaxed);
+            metrics::inc(&METRICS.shadow_rpc_failovers_total.with_label_values(&[self.provider]));
+            log::warn!(
+                "{} rpc {} failed {} requests in a row, active endpoint now {}",
+                self.provider,
+                redact_url(self.endpoints[index].url()),
+                errors,
+                redact_url(self.endpoints[next].url())
+            );
+        }
+    }
+}
+
+impl Service<RequestPacket> for FailoverTransport {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let transport = self.clone();
+        Box::pin(async move {
+            let index = transport.active.load(Ordering::Relaxed);
+            let mut endpoint = transport.endpoints[index].clone();
+            let response = match transport.timeout {
+                Some(timeout) => {
+                    match tokio::time::timeout(timeout, endpoint.call(request)).await {
+                        Ok(response) => response,
+                        Err(_) => Err(transport.timed_out(index, timeout)),
+                    }
+                }
+                None => endpoint.call(request).await,
+            };
+            transport.record(index, response.is_err());
+            response
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_failover() {
+    use crate::mock_rpc::MockRpc;
+    use alloy::providers::Provider;
+    use serde_json::json;
+
+    let rpc = MockRpc::start(|method, _| (method == "eth_blockNumber").then(|| json!("0x10")));
+    // Nothing listens on the first endpoint.
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let closed_url = format!("http://{}", closed.local_addr().unwrap());
+    drop(closed);
+
+    let transport =
+        FailoverTransport::new("l1", &format!("{}, {}", closed_url, rpc.url), 2).unwrap();
+    let active = || transport.url().trim_end_matches('/').to_string();
+    assert_eq!(active(), closed_url);
+    let provider = transport.clone().into_provider();
+    let failovers = METRICS.shadow_rpc_failovers_total.with_label_values(&["l1"]).get();
+
+    assert!(provider.get_block_number().await.is_err());
+    assert_eq!(active(), closed_url);
+    assert!(provider.get_block_number().await.is_err());
+    assert_eq!(active(), rpc.url);
+    assert_eq!(provider.get_block_number().await.unwrap(), 16);
+    assert_eq!(METRICS.shadow_rpc_failovers_total.with_label_values(&["l1"]).get(), failovers + 1);
+
+    // A JSON-RPC error is an answer, the endpoint stays active.
+    for _ in 0..3 {
+        assert!(provider.get_chain_id().await.is_err());
+    }
+    assert_eq!(active(), rpc.url);
+
+    assert!(FailoverTransport::new("l2", " , ", 3).is_err());
+    assert!(FailoverTransport::new("l2", "http://l2:8545,not a url", 3).is_err());
+}
+
+#[tokio::test]
+async fn test_timeout() {
+    use crate::{mock_rpc::MockRpc, util::is_transient};
+    use alloy::providers::Provider;
+    use serde_json::json;
+
+    let rpc = MockRpc::start(|method, _| (method == "eth_blockNumber").then(|| json!("0x10")));
+    // Accepts connections, never answers.
+    let stalled = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let stalled_url = format!("http://{}", stalled.local_addr().unwrap());
+
+    let transport = FailoverTransport::new("l2", &format!("{},{}", stalled_url, rpc.url), 1)
+        .unwrap()
+        .with_timeout(Duration::from_millis(200));
+    let provider = transport.clone().into_provider();
+    let timeouts = METRICS.shadow_rpc_timeouts_total.with_label_values(&["l2"]).get();
+
+    let error = provider.get_block_number().await.unwrap_err();
+    assert!(is_transient(&error), "{}", error);
+    assert!(error.to_string().contains("timed out"), "{}", error);
+    assert_eq!(METRICS.shadow_rpc_timeouts_total.with_label_values(&["l2"]).get(), timeouts + 1);
+    // A timeout is a failure of the endpoint like any other.
+    assert_eq!(transport.url().trim_end_matches('/'), rpc.url);
+    assert_eq!(provider.get_block_number().await.unwrap(), 16);
+}
+
+#[tokio::test]
+async fn test_headers() {
+    use alloy::{
+        providers::Provider,
+        transports::http::reqwest::header::{HeaderValue, AUTHORIZATION},
+    };
+    use axum::{
+        http::{HeaderMap as AxumHeaderMap, StatusCode},
+        routing::post,
+        Json, Router,
+    };
+    use serde_json::{json, Value};
+
+    // Answers only the requests carrying the API key.
+    let app = Router::new().route(
+        "/",
+        post(|headers: AxumHeaderMap, Json(request): Json<Value>| async move {
+            match headers.get("authorization").and_then(|value| value.to_str().ok()) {
+                Some("Bearer s3cret") => {
+                    Ok(Json(json!({ "jsonrpc": "2.0", "id": request["id"], "result": "0x10" })))
+                }
+                _ => Err(StatusCode::UNAUTHORIZED),
+            }
+        }),
+    );
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+    let provider = FailoverTransport::new("l1", &url, 3).unwrap().into_provider();
+    assert!(provider.get_block_number().await.is_err());
+
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer s3cret"));
+    let transport = FailoverTransport::new("l1", &url, 3).unwrap().with_headers(headers).unwrap();
+    assert_eq!(transport.into_provider().get_block_number().await.unwrap(), 16);
+}
@@ -0,0 +1,219 @@
+//! Failure policy of the commit and prove stages.
+//!
+//! The stages fail for different reasons. A commit failure is usually a gas or nonce issue that
+//! heals by itself. A prove failure often means the shadow rollup rejects what was committed.
+//! Each stage counts its failures in `shadow_commit_failures_total` or
+//! `shadow_prove_failures_total` and tolerates `SHADOW_PROVING_{COMMIT,PROVE}_FAILURE_RETRIES`
+//! failures in a row. The next one raises an alert at the stage severity, counted in
+//! `shadow_stage_alerts_total{stage, severity}` and logged at the matching level. After the alert
+//! the stage sits out `SHADOW_PROVING_{COMMIT,PROVE}_FAILURE_COOLDOWN_SECS`, then starts over.
+//!
+//! By default the commit stage alerts as a warning after 3 retries and the prove stage alerts as
+//! critical on its first failure. Neither cools down.
+//!
+//! A proof rejected by the verifier ([`ProofRejected`]) is a critical alert on the spot, whatever
+//! the policy: the shadow state diverges from the rollup and no retry can fix it.
+//!
+//! Failures are also counted by the kind of their [`ShadowError`](crate::error::ShadowError) in
+//! `shadow_stage_errors_total{stage, kind}`, `other` for untyped errors.
+
+use crate::{
+    config::{Config, Severity},
+    error,
+    metrics::{self, METRICS},
+    shadow_prove::ProofRejected,
+};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Commit,
+    Prove,
+}
+
+impl Stage {
+    /// Value of the `stage` metric label.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Commit => "commit",
+            Self::Prove => "prove",
+        }
+    }
+}
+
+/// Consecutive failures of a stage, against its policy.
+#[derive(Debug)]
+pub struct StageFailures {
+    stage: Stage,
+    retries: u32,
+    cooldown: Duration,
+    severity: Severity,
+    consecutive: u32,
+    cooling_until: Option<Instant>,
+}
+
+impl StageFailures {
+    pub fn new(stage: Stage, config: &Config) -> Self {
+        let (retries, cooldown, severity) = match stage {
+            Stage::Commit => (
+                config.commit_failure_retries,
+                config.commit_failure_cooldown,
+                config.commit_failure_severity,
+            ),
+            Stage::Prove => (
+                config.prove_failure_retries,
+                config.prove_failure_cooldown,
+                config.prove_failure_severity,
+            ),
+        };
+        Self { stage, retries, cooldown, severity, consecutive: 0, cooling_until: None }
+    }
+
+    /// Whether the stage runs at `now`, false while it cools down after an alert.
+    pub fn ready(&mut self, now: Instant) -> bool {
+        match self.cooling_until {
+            Some(until) if now < until => false,
+            _ => {
+                self.cooling_until = None;
+                true
+            }
+        }
+    }
+
+    pub fn success(&mut self) {
+        self.consecutive = 0;
+    }
+
+    /// Record a failure of the stage, returning the severity of the alert it raises, if any.
+    pub fn failure(&mut self, error: &anyhow::Error, now: Instant) -> Option<Severity> {
+        metrics::inc(match self.stage {
+            Stage::Commit => &METRICS.shadow_commit_failures_total,
+            Stage::Prove => &METRICS.shadow_prove_failures_total,
+        });
+        let kind = error::kind(error);
+        metrics::inc(
+            &METRICS.shadow_stage_errors_total.with_label_values(&[self.stage.as_str(), kind]),
+        );
+        self.consecutive += 1;
+        let rejected = error.downcast_ref::<ProofRejected>().is_some();
+        let severity = if rejected { Severity::Critical } else { self.severity };
+        if self.consecutive <= self.retries && !rejected {
+            log::warn!(
+                "{} stage failed ({}/{} retries), {} error: {:#}",
+                self.stage.as_str(),
+                self.consecutive,
+                self.retries,
+                kind,
+                error
+            );
+            return None;
+        }
+
+        metrics::inc(
+            &METRICS
+                .shadow_stage_alerts_total
+                .with_label_values(&[self.stage.as_str(), severity.as_str()]),
+        );
+        let level = match severity {
+            Severity::Warning => log::Level::Warn,
+            Severity::Critical => log::Level::Error,
+        };
+        log::log!(
+            level,
+            "{} stage failed {} times in a row, alert {}, {} error: {:#}",
+            self.stage.as_str(),
+            self.consecutive,
+            severity.as_str(),
+            kind,
+            error
+        );
+        self.consecutive = 0;
+        if !self.cooldown.is_zero() {
+            log::info!("{} stage cools down for {:?}", self.stage.as_str(), self.cooldown);
+            self.cooling_until = Some(now + self.cooldown);
+        }
+        Some(severity)
+    }
+}
+
+#[test]
+fn test_stage_failures() {
+    let config = Config {
+        commit_failure_retries: 2,
+        commit_failure_cooldown: Duration::from_secs(60),
+        ..Config::default()
+    };
+    let alerts = |stage: Stage, severity: Severity| {
+        METRICS
+            .shadow_stage_alerts_total
+            .with_label_values(&[stage.as_str(), severity.as_str()])
+            .get()
+    };
+    let error = anyhow::anyhow!("nonce too low");
+    let now = Instant::now();
+
+    // Commit failures are retried, then alert as a warning and cool down.
+    let commit_failures = METRICS.shadow_commit_failures_total.get();
+    let prove_failures = METRICS.shadow_prove_failures_total.get();
+    let commit_alerts = alerts(Stage::Commit, Severity::Warning);
+    let mut commit = StageFailures::new(Stage::Commit, &config);
+    assert_eq!(commit.failure(&error, now), None);
+    commit.success();
+    assert_eq!(commit.failure(&error, now), None);
+    assert_eq!(commit.failure(&error, now), None);
+    assert!(commit.ready(now));
+    assert_eq!(commit.failure(&error, now), Some(Severity::Warning));
+    assert_eq!(METRICS.shadow_commit_failures_total.get(), commit_failures + 4);
+    assert_eq!(alerts(Stage::Commit, Severity::Warning), commit_alerts + 1);
+    assert!(!commit.ready(now + Duration::from_secs(59)));
+    assert!(commit.ready(now + Duration::from_secs(60)));
+    // Counting starts over after the alert.
+    assert_eq!(commit.failure(&error, now), None);
+
+    // The first prove failure is a critical alert, without cooldown.
+    let prove_alerts = alerts(Stage::Prove, Severity::Critical);
+    let mut prove = StageFailures::new(Stage::Prove, &config);
+    let error = anyhow::anyhow!("prove_state reverted");
+    assert_eq!(prove.failure(&error, now), Some(Severity::Critical));
+    assert!(prove.ready(now));
+    assert_eq!(prove.failure(&error, now), Some(Severity::Critical));
+    assert_eq!(METRICS.shadow_prove_failures_total.get(), prove_failures + 2);
+    assert_eq!(alerts(Stage::Prove, Severity::Critical), prove_alerts + 2);
+    assert_eq!(METRICS.shadow_commit_failures_total.get(), commit_failures + 5);
+
+    // A rejected proof is critical without retries, even on a lenient policy.
+    let config = Config {
+        prove_failure_retries: 5,
+        prove_failure_severity: Severity::Warning,
+        ..Config::default()
+    };
+    let mut prove = StageFailures::new(Stage::Prove, &config);
+    let error = anyhow::Error::new(ProofRejected {
+        batch_index: 7,
+        reason: String::from("invalid proof"),
+        roots: None,
+    });
+    assert_eq!(prove.failure(&error, now), Some(Severity::Critical));
+    assert_eq!(prove.failure(&anyhow::anyhow!("nonce too low"), now), None);
+}
+
+#[test]
+fn test_stage_failures_kind() {
+    use crate::error::ShadowError;
+    use anyhow::Context;
+
+    let errors = |kind: &str| {
+        METRICS.shadow_stage_errors_total.with_label_values(&[Stage::Prove.as_str(), kind]).get()
+    };
+    let (prove_failed, other) = (errors("prove_failed"), errors("other"));
+    let mut prove = StageFailures::new(Stage::Prove, &Config::default());
+    let error: Result<(), _> = Err(ShadowError::ProveFailed {
+        batch_index: 7,
+        attempts: 2,
+        reason: String::from("prover timeout"),
+    });
+    prove.failure(&error.context("prove batch 7").unwrap_err(), Instant::now());
+    prove.failure(&anyhow::anyhow!("nonce too low"), Instant::now());
+    assert_eq!(errors("prove_failed"), prove_failed + 1);
+    assert_eq!(errors("other"), other + 1);
+}
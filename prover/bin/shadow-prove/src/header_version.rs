@@ -0,0 +1,109 @@
+use crate::{abi::RollupVersion, failover::FailoverTransport};
+use alloy::{primitives::Address, providers::RootProvider};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Batch header codec version of the rollup contract.
+///
+/// Read from `batchHeaderVersion()` or `version()` when the rollup exposes one, otherwise the
+/// configured default. The detected value is cached and re-read after `refresh` so a codec
+/// upgrade of the contract is picked up without a redeploy. A committed header of another
+/// version is refused rather than decoded.
+#[derive(Clone, Debug)]
+pub struct HeaderVersion {
+    rollup:
+        RollupVersion::RollupVersionInstance<FailoverTransport, RootProvider<FailoverTransport>>,
+    default: u8,
+    refresh: Duration,
+    cached: Arc<Mutex<Option<(u8, Instant)>>>,
+}
+
+impl HeaderVersion {
+    pub fn new(
+        rollup_address: Address,
+        l1_provider: RootProvider<FailoverTransport>,
+        default: u8,
+        refresh: Duration,
+    ) -> Self {
+        let rollup = RollupVersion::new(rollup_address, l1_provider);
+        Self { rollup, default, refresh, cached: Arc::new(Mutex::new(None)) }
+    }
+
+    pub async fn get(&self) -> u8 {
+        if let Some((version, read_at)) = *self.cached.lock().unwrap() {
+            if read_at.elapsed() < self.refresh {
+                return version;
+            }
+        }
+
+        let version = self.detect().await;
+        let prev = self.cached.lock().unwrap().replace((version, Instant::now()));
+        match prev {
+            Some((prev, _)) if prev != version => {
+                log::warn!("rollup batch header version changed from {} to {}", prev, version)
+            }
+            None => log::info!("rollup batch header version = {}", version),
+            _ => (),
+        }
+        version
+    }
+
+    async fn detect(&self) -> u8 {
+        if let Ok(version) = self.rollup.batchHeaderVersion().call().await {
+            return version._0;
+        }
+        if let Ok(version) = self.rollup.version().call().await {
+            return version._0;
+        }
+        log::debug!("rollup exposes no header version, use default {}", self.default);
+        self.default
+    }
+}
+
+#[tokio::test]
+async fn test_header_version() {
+    use crate::mock_rpc::{call_result, call_selector, MockRpc};
+    use alloy::sol_types::{SolCall, SolValue};
+
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == RollupVersion::versionCall::SELECTOR => {
+            call_result(2u8.abi_encode())
+        }
+        _ => None,
+    });
+    let version = HeaderVersion::new(Address::ZERO, rpc.provider(), 1, Duration::from_secs(3600));
+    assert_eq!(version.get().await, 2);
+    // Cached until the refresh interval elapses.
+    assert_eq!(version.get().await, 2);
+    assert_eq!(rpc.requests("eth_call").len(), 2);
+
+    let rpc = MockRpc::start(|_, _| None);
+    let version = HeaderVersion::new(Address::ZERO, rpc.provider(), 1, Duration::ZERO);
+    assert_eq!(version.get().await, 1);
+    assert_eq!(version.get().await, 1);
+    assert_eq!(rpc.requests("eth_call").len(), 4);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_header_version_refresh() {
+    use crate::mock_rpc::{call_result, call_selector, MockRpc};
+    use alloy::sol_types::{SolCall, SolValue};
+
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == RollupVersion::batchHeaderVersionCall::SELECTOR => {
+            call_result(1u8.abi_encode())
+        }
+        _ => None,
+    });
+    let version = HeaderVersion::new(Address::ZERO, rpc.provider(), 0, Duration::from_secs(60));
+
+    // One read per refresh interval over ticks of 12s.
+    for _ in 0..10 {
+        assert_eq!(version.get().await, 1);
+        tokio::time::advance(Duration::from_secs(12)).await;
+    }
+    assert_eq!(rpc.requests("eth_call").len(), 2);
+}
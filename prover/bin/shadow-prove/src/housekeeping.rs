@@ -0,0 +1,131 @@
+//! Pruning of the auxiliary state files kept next to the checkpoint.
+//!
+//! Log files are rotated by the logger itself. Everything else the prover writes beside the
+//! checkpoint (temp files, backups, recordings) shares the checkpoint's file stem and is removed
+//! once older than `SHADOW_PROVING_STATE_RETENTION_DAYS`. The active checkpoint and the most
+//! recent auxiliary file are never removed.
+
+use crate::config::Config;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Interval between two pruning passes.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Periodically prune the state files of the configured checkpoint; no-op without a checkpoint
+/// file or with a zero retention.
+pub fn spawn(config: &Config) {
+    let Some(active) = config.checkpoint_file.clone() else {
+        return;
+    };
+    if config.state_retention.is_zero() {
+        return;
+    }
+    let retention = config.state_retention;
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = prune_state_files(&active, retention, SystemTime::now()) {
+                log::error!("prune state files error: {:#}", e);
+            }
+            tokio::time::sleep(PRUNE_INTERVAL).await;
+        }
+    });
+}
+
+/// Remove the files beside `active` sharing its stem and last modified before `now - retention`,
+/// except `active` itself and the newest of them. Returns the removed files.
+pub fn prune_state_files(
+    active: &Path,
+    retention: Duration,
+    now: SystemTime,
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let dir = match active.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let stem = match active.file_stem() {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => return Ok(Vec::new()),
+    };
+    let cutoff = now.checked_sub(retention).unwrap_or(SystemTime::UNIX_EPOCH);
+
+    let mut candidates = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() ||
+            !entry.file_name().to_string_lossy().starts_with(&stem) ||
+            is_same_file(&path, active)
+        {
+            continue;
+        }
+        candidates.push((metadata.modified()?, path));
+    }
+    // Keep the most recent one whatever its age.
+    candidates.sort();
+    candidates.pop();
+
+    let mut removed = Vec::new();
+    for (modified, path) in candidates {
+        if modified >= cutoff {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                log::info!("pruned stale state file {:?}", path);
+                removed.push(path);
+            }
+            Err(e) => log::warn!("prune stale state file {:?} error: {}", path, e),
+        }
+    }
+    Ok(removed)
+}
+
+fn is_same_file(path: &Path, active: &Path) -> bool {
+    match (fs::canonicalize(path), fs::canonicalize(active)) {
+        (Ok(path), Ok(active)) => path == active,
+        _ => path.file_name() == active.file_name(),
+    }
+}
+
+#[test]
+fn test_prune_state_files() {
+    let dir = std::env::temp_dir().join(format!("shadow-housekeeping-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let active = dir.join("checkpoint.json");
+    let now = SystemTime::now();
+    let days_ago = |days: u64| now - Duration::from_secs(days * 86400);
+    for (name, modified) in [
+        ("checkpoint.json", days_ago(30)),
+        ("checkpoint.tmp", days_ago(20)),
+        ("checkpoint.json.1.bak", days_ago(12)),
+        ("checkpoint.json.2.bak", days_ago(3)),
+        ("checkpoint.json.3.bak", now),
+        ("other.json", days_ago(30)),
+    ] {
+        let file = fs::File::create(dir.join(name)).unwrap();
+        file.set_modified(modified).unwrap();
+    }
+
+    // Only the stale auxiliary files go; the active checkpoint is kept however old.
+    let mut removed = prune_state_files(&active, Duration::from_secs(7 * 86400), now).unwrap();
+    removed.sort();
+    assert_eq!(removed, vec![dir.join("checkpoint.json.1.bak"), dir.join("checkpoint.tmp")]);
+    assert!(active.exists());
+    assert!(dir.join("checkpoint.json.2.bak").exists());
+    assert!(dir.join("other.json").exists());
+
+    // The most recent auxiliary file is kept even once stale.
+    let removed = prune_state_files(&active, Duration::from_secs(3600), now).unwrap();
+    assert_eq!(removed, vec![dir.join("checkpoint.json.2.bak")]);
+    assert!(dir.join("checkpoint.json.3.bak").exists());
+    let later = now + Duration::from_secs(86400);
+    assert!(prune_state_files(&active, Duration::from_secs(3600), later).unwrap().is_empty());
+    assert!(dir.join("checkpoint.json.3.bak").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -1,14 +1,76 @@
 use abi::{Rollup, ShadowRollup};
+use serde::{Deserialize, Serialize};
+use size_class::SizeClass;
+use std::fmt;
 
 pub mod abi;
+pub mod alarm;
+pub mod alert_webhook;
+pub mod artifact;
+pub mod audit;
+pub mod balance;
+pub mod banner;
+pub mod batch_header;
+pub mod blob;
+pub mod block_number;
+pub mod challenge_window;
+pub mod checkpoint;
+pub mod circuit;
+pub mod config;
+pub mod config_file;
+pub mod error;
+pub mod failover;
+pub mod failure;
+pub mod header_version;
+pub mod housekeeping;
+pub mod manual_prove;
 pub mod metrics;
+pub mod pause;
+pub mod private_tx;
+pub mod queue;
+pub mod rate_limit;
+pub mod readiness;
+pub mod receipt;
+pub mod revert;
 pub mod shadow_prove;
 pub mod shadow_rollup;
+pub mod shutdown;
+pub mod signer;
+pub mod size_class;
+pub mod status;
+pub mod subscription;
+pub mod topology;
 pub mod util;
 
-#[derive(Clone, Debug)]
+#[cfg(test)]
+mod mock_rpc;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct BatchInfo {
     pub batch_index: u64,
     pub start_block: u64,
     pub end_block: u64,
+    #[serde(default)]
+    pub size_class: SizeClass,
+}
+
+impl fmt::Display for BatchInfo {
+    /// `batch 7 [101..150]`, the L2 blocks inclusive.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "batch {} [{}..{}]", self.batch_index, self.start_block, self.end_block)
+    }
+}
+
+#[test]
+fn test_batch_info_display() {
+    let batch = BatchInfo {
+        batch_index: 7,
+        start_block: 101,
+        end_block: 150,
+        size_class: SizeClass::default(),
+    };
+    assert_eq!(batch.to_string(), "batch 7 [101..150]");
+
+    let json = serde_json::to_string(&batch).unwrap();
+    assert_eq!(serde_json::from_str::<BatchInfo>(&json).unwrap(), batch);
 }
@@ -1,110 +1,1252 @@
-use std::{str::FromStr, time::Duration};
+use std::{
+    io::BufRead,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use alloy::{
-    network::EthereumWallet,
-    primitives::Address,
-    providers::{ProviderBuilder, RootProvider},
-    signers::local::PrivateKeySigner,
-    transports::http::{Client, Http},
+    network::Network,
+    primitives::{Address, Bytes, TxHash},
+    providers::{Provider, ProviderBuilder, RootProvider},
+    rpc::types::BlockId,
+    transports::Transport,
+};
+use anyhow::Context;
+use axum::{
+    extract,
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
 };
-use axum::{routing::get, Router};
+use clap::{Args, Parser, Subcommand};
 use dotenv::dotenv;
-use flexi_logger::{Cleanup, Criterion, Duplicate, FileSpec, Logger, Naming, WriteMode};
+use flexi_logger::{
+    Age, Cleanup, Criterion, DeferredNow, Duplicate, FileSpec, Logger, LoggerHandle, Naming,
+    WriteMode,
+};
 use log::Record;
-use prometheus::{Encoder, TextEncoder};
 use shadow_proving::{
-    metrics::{METRICS, REGISTRY},
-    shadow_prove::ShadowProver,
-    shadow_rollup::BatchSyncer,
-    util::{read_env_var, read_parse_env},
+    alert_webhook::AlertWebhook,
+    artifact::ProveArtifact,
+    audit::{self, AuditLog, Decision, Outcome, Tick},
+    balance,
+    banner::{self, Deployment},
+    challenge_window::{ChallengeWindowTracker, WindowStatus, WindowStatuses},
+    checkpoint::{Checkpoint, CheckpointStore, DeadLetter},
+    circuit::{CircuitBreaker, LoopBreaker},
+    config::{Config, Role},
+    config_file,
+    failover::FailoverTransport,
+    failure::{Stage, StageFailures},
+    housekeeping,
+    manual_prove::{self, ManualProve, ProveRequest, ProveResponse},
+    metrics::{self, METRICS, REGISTRY},
+    pause::PauseCheck,
+    private_tx::PrivateTxProvider,
+    queue::{self, BatchQueue},
+    readiness::{self, Liveness, Readiness, ReadyState},
+    receipt::Receipts,
+    shadow_prove::{ProofRejected, ShadowProver},
+    shadow_rollup::{check_authorized_signer, BatchSyncer, BatchSyncerBuilder, SyncOutcome},
+    shutdown::Shutdown,
+    signer,
+    status::{Status, StatusReport},
+    subscription::Commits,
+    topology,
+    util::{self, read_env_var, read_parse_env},
+    BatchInfo,
 };
 
-use tokio::time::sleep;
+use tokio::sync::mpsc;
 use tower_http::trace::TraceLayer;
 
+#[derive(Parser)]
+#[command(version, about = "Shadow proving of morph rollup batches")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Run a single tick and exit, non-zero when a stage failed, e.g. as a CronJob. Bounded by
+    /// SHADOW_PROVING_MAX_BATCH, tick until that batch is proven instead.
+    #[arg(long, env = "SHADOW_PROVING_RUN_ONCE")]
+    once: bool,
+    /// TOML file of the config, overridden by the env vars and the flags.
+    #[arg(long, global = true, env = "SHADOW_PROVING_CONFIG")]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    overrides: Overrides,
+}
+
+/// Flags overriding their env var, which overrides the default.
+#[derive(Args, Debug)]
+struct Overrides {
+    /// L1 the rollup batches are read from [env: SHADOW_PROVING_L1_RPC].
+    #[arg(long, global = true)]
+    l1_rpc: Option<String>,
+    /// L1 the shadow rollup is committed to and proven on [env: SHADOW_PROVING_VERIFY_L1_RPC].
+    #[arg(long, global = true)]
+    verify_l1_rpc: Option<String>,
+    /// L2 the batch blocks are read from [env: SHADOW_PROVING_L2_RPC].
+    #[arg(long, global = true)]
+    l2_rpc: Option<String>,
+    /// Rollup contract [env: SHADOW_PROVING_L1_ROLLUP].
+    #[arg(long, global = true)]
+    l1_rollup: Option<String>,
+    /// Shadow rollup contract [env: SHADOW_PROVING_L1_SHADOW_ROLLUP].
+    #[arg(long, global = true)]
+    l1_shadow_rollup: Option<String>,
+    /// Blocks of the largest batch shadow proven [env: SHADOW_PROVING_MAX_BLOCK].
+    #[arg(long, global = true)]
+    max_block: Option<u64>,
+    /// Transactions of the largest batch shadow proven [env: SHADOW_PROVING_MAX_TXN].
+    #[arg(long, global = true)]
+    max_txn: Option<u64>,
+    /// Sleep between two ticks [env: SHADOW_PROVING_POLL_INTERVAL_SECS].
+    #[arg(long, global = true)]
+    poll_interval_secs: Option<u64>,
+}
+
+impl Overrides {
+    /// The env vars the given flags override.
+    fn vars(&self) -> Vec<(&'static str, String)> {
+        let text = |name, value: &Option<String>| value.clone().map(|v| (name, v));
+        let number = |name, value: Option<u64>| value.map(|v| (name, v.to_string()));
+        [
+            text("SHADOW_PROVING_L1_RPC", &self.l1_rpc),
+            text("SHADOW_PROVING_VERIFY_L1_RPC", &self.verify_l1_rpc),
+            text("SHADOW_PROVING_L2_RPC", &self.l2_rpc),
+            text("SHADOW_PROVING_L1_ROLLUP", &self.l1_rollup),
+            text("SHADOW_PROVING_L1_SHADOW_ROLLUP", &self.l1_shadow_rollup),
+            number("SHADOW_PROVING_MAX_BLOCK", self.max_block),
+            number("SHADOW_PROVING_MAX_TXN", self.max_txn),
+            number("SHADOW_PROVING_POLL_INTERVAL_SECS", self.poll_interval_secs),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Export the flags to the environment, where the config and the providers read them.
+    fn export(&self) {
+        for (name, value) in self.vars() {
+            std::env::set_var(name, value);
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Inspect or override the checkpoint file.
+    Checkpoint {
+        #[arg(long, env = "SHADOW_PROVING_CHECKPOINT_FILE")]
+        file: PathBuf,
+        #[command(subcommand)]
+        action: CheckpointAction,
+    },
+    /// List or clear batches abandoned after exhausting their prove attempts.
+    DeadLetter {
+        #[arg(long, env = "SHADOW_PROVING_CHECKPOINT_FILE")]
+        file: PathBuf,
+        #[command(subcommand)]
+        action: DeadLetterAction,
+    },
+    /// Prove a batch already committed to the shadow rollup, skipping the commit step.
+    ResumeProve {
+        index: u64,
+        /// Read the batch from the L1 state at this block instead of the latest one.
+        #[arg(long)]
+        at_block: Option<u64>,
+    },
+    /// Write the proof-request artifact of a committed batch, for proving offline.
+    ExportRequest {
+        index: u64,
+        /// Artifact file to write.
+        #[arg(long, short, default_value = "prove-request.json")]
+        output: PathBuf,
+        /// Read the batch from the L1 state at this block instead of the latest one.
+        #[arg(long)]
+        at_block: Option<u64>,
+    },
+    /// Prove a batch on the shadow rollup with a proof generated offline.
+    SubmitProof {
+        /// Artifact written by export-request.
+        artifact: PathBuf,
+        /// Proof file, raw bytes or 0x-prefixed hex.
+        proof: PathBuf,
+    },
+    /// Check the hash chain of the audit log and print its head.
+    VerifyAudit {
+        #[arg(env = "SHADOW_PROVING_AUDIT_FILE")]
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum CheckpointAction {
+    /// Print the last committed/proven batch and the committed-but-unproven set.
+    Show,
+    /// Mark every batch up to INDEX as committed and proven, for incident recovery.
+    Set {
+        index: u64,
+        /// Skip the confirmation prompt.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DeadLetterAction {
+    /// Print every dead-letter entry.
+    List,
+    /// Remove the entry of INDEX, or all entries when omitted.
+    Clear {
+        index: Option<u64>,
+        /// Skip the confirmation prompt.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
 #[tokio::main]
 async fn main() {
     // Prepare environment.
     dotenv().ok();
-    setup_logging();
+    let cli = Cli::parse();
+    if let Some(path) = &cli.config {
+        if let Err(e) = config_file::load(path) {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+    cli.overrides.export();
+    // Commands running against the chain share the daemon setup.
+    let oneshot = match cli.command {
+        Some(
+            command @ (Command::ResumeProve { .. } |
+            Command::ExportRequest { .. } |
+            Command::SubmitProof { .. }),
+        ) => Some(command),
+        Some(command) => {
+            if let Err(e) = run_command(command) {
+                eprintln!("error: {:#}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => None,
+    };
+
+    let logger = setup_logging();
     log::info!("Starting shadow proving...");
 
-    // Start metric management.
-    metric_mng().await;
+    if let Err(e) = config_file::validate() {
+        log::error!("{:#}", e);
+        std::process::exit(1);
+    }
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("invalid shadow proving config: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Start metric management, leaving the port to the daemon for one-shot commands.
+    let window_statuses = WindowStatuses::default();
+    let readiness = Readiness::default();
+    let liveness = Liveness::default();
+    let status = Status::new(util::unix_timestamp());
+    let circuit = CircuitBreaker::new(&config);
+    let (manual_prove, prove_requests) = manual_prove::channel(config.admin_token.clone());
+    if oneshot.is_none() {
+        metric_mng(
+            &config,
+            window_statuses.clone(),
+            readiness.clone(),
+            liveness.clone(),
+            circuit.clone(),
+            manual_prove,
+            status.clone(),
+        )
+        .await;
+    }
+    if let Some(address) = &config.statsd_address {
+        if let Err(e) = metrics::init_statsd(address) {
+            log::error!("invalid statsd address {:?}: {:#}", address, e);
+            std::process::exit(1);
+        }
+        log::info!("Mirroring metrics to statsd at {}", address);
+    }
 
     let l1_verify_rpc: String = read_parse_env("SHADOW_PROVING_VERIFY_L1_RPC");
     let l1_rpc: String = read_parse_env("SHADOW_PROVING_L1_RPC");
     let l2_rpc: String = read_parse_env("SHADOW_PROVING_L2_RPC");
 
-    let private_key: String = read_parse_env("SHADOW_PROVING_PRIVATE_KEY");
     let rollup: String = read_parse_env("SHADOW_PROVING_L1_ROLLUP");
     let shadow_rollup: String = read_parse_env("SHADOW_PROVING_L1_SHADOW_ROLLUP");
 
-    let signer: PrivateKeySigner = private_key.parse().expect("parse PrivateKeySigner");
-    let wallet: EthereumWallet = EthereumWallet::from(signer.clone());
-    let l1_provider: RootProvider<Http<Client>> =
-        ProviderBuilder::new().on_http(l1_rpc.parse().expect("parse l1_rpc to Url"));
+    let (wallet, wallet_address) = match signer::wallet_from_env().await {
+        Ok(wallet) => wallet,
+        Err(e) => {
+            log::error!("load signer error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let l1_provider: RootProvider<FailoverTransport> =
+        FailoverTransport::new("l1", &l1_rpc, config.rpc_failover_errors)
+            .and_then(|transport| transport.with_headers(config.l1_rpc_headers.clone()))
+            .expect("parse l1_rpc to Url")
+            .with_timeout(config.rpc_timeout)
+            .into_provider();
 
-    let l2_provider: RootProvider<Http<Client>> =
-        ProviderBuilder::new().on_http(l2_rpc.parse().expect("parse l2_rpc to Url"));
+    let l2_provider: RootProvider<FailoverTransport> =
+        FailoverTransport::new("l2", &l2_rpc, config.rpc_failover_errors)
+            .and_then(|transport| transport.with_headers(config.l2_rpc_headers.clone()))
+            .expect("parse l2_rpc to Url")
+            .with_timeout(config.rpc_timeout)
+            .into_provider();
 
-    let verify_provider: RootProvider<Http<Client>> =
-        ProviderBuilder::new().on_http(l1_verify_rpc.parse().expect("parse l1_rpc to Url"));
+    let verify_provider: RootProvider<FailoverTransport> =
+        FailoverTransport::new("verify", &l1_verify_rpc, config.rpc_failover_errors)
+            .and_then(|transport| transport.with_headers(config.verify_l1_rpc_headers.clone()))
+            .expect("parse l1_verify_rpc to Url")
+            .with_timeout(config.rpc_timeout)
+            .into_provider();
+    readiness.probe_rpc(l1_provider.clone(), l2_provider.clone());
 
-    let l1_signer = ProviderBuilder::new()
-        .with_recommended_fillers()
-        .wallet(wallet)
-        .on_provider(verify_provider.clone());
+    let l1_signer = ProviderBuilder::new().with_recommended_fillers().wallet(wallet).on_provider(
+        PrivateTxProvider::new(
+            verify_provider.clone(),
+            config.private_tx_rpc.as_deref(),
+            config.private_tx_timeout,
+        )
+        .expect("parse private_tx_rpc to Url"),
+    );
 
-    let batch_syncer = BatchSyncer::new(
+    // Reading one L1 and writing another is fine, reaching an unintended chain is not.
+    let topology = match topology::check_topology(
+        &l1_provider,
+        &verify_provider,
+        &l1_signer,
         Address::from_str(&rollup).unwrap(),
         Address::from_str(&shadow_rollup).unwrap(),
-        l1_provider.clone(),
-        l2_provider.clone(),
-        l1_signer.clone(),
+        &config,
+    )
+    .await
+    {
+        Ok(topology) => topology,
+        Err(e) => {
+            log::error!("invalid chain topology: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if topology.cross_chain() {
+        log::info!(
+            "Reading batches from chain {}, shadow proving on chain {}",
+            topology.read_chain_id,
+            topology.write_chain_id
+        );
+    }
+
+    // Only commits are restricted to the owner.
+    if config.check_authorized_signer && config.role.syncs() && oneshot.is_none() {
+        let shadow_rollup_address = Address::from_str(&shadow_rollup).unwrap();
+        if let Err(e) =
+            check_authorized_signer(shadow_rollup_address, &verify_provider, wallet_address).await
+        {
+            log::error!("{:#}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if oneshot.is_none() {
+        let deployment = Deployment {
+            l1_chain_id: Some(topology.read_chain_id),
+            verify_l1_chain_id: Some(topology.write_chain_id),
+            l2_chain_id: l2_provider.get_chain_id().await.ok(),
+            l1_rpc: l1_rpc.clone(),
+            l1_verify_rpc: l1_verify_rpc.clone(),
+            l2_rpc: l2_rpc.clone(),
+            rollup: Address::from_str(&rollup).unwrap(),
+            shadow_rollup: Address::from_str(&shadow_rollup).unwrap(),
+            signer: wallet_address,
+        };
+        log::info!("{}", banner::startup_banner(&config, &deployment));
+    }
+
+    let checkpoint = config.checkpoint_file.clone().map(CheckpointStore::new);
+    let resumed = checkpoint.as_ref().and_then(|store| store.load().ok());
+    // A chain command acts on the batch it is given, proven or not.
+    let proven_through =
+        resumed.as_ref().and_then(|c| c.last_proven_index).filter(|_| oneshot.is_none());
+    let committed_unproven =
+        resumed.as_ref().map(|c| c.committed_unproven.clone()).unwrap_or_default();
+    let batch_syncer = BatchSyncerBuilder::default()
+        .rollup(Address::from_str(&rollup).unwrap())
+        .shadow_rollup(Address::from_str(&shadow_rollup).unwrap())
+        .l1_provider(l1_provider.clone())
+        .l2_provider(l2_provider.clone())
+        .wallet(l1_signer.clone())
+        .config(config.clone())
+        .build()
+        .expect("every field of the builder is set")
+        .with_sender(wallet_address)
+        .with_circuit_breaker(circuit)
+        .with_proven_through(proven_through)
+        .with_committed_unproven(committed_unproven);
+    let challenge_window = ChallengeWindowTracker::new(
+        Address::from_str(&shadow_rollup).unwrap(),
+        verify_provider.clone(),
+        window_statuses,
     );
+    if let Some(c) = resumed {
+        METRICS.shadow_dead_letter_count.set(c.dead_letter.len() as i64);
+        if let Some(batch_index) = c.last_proven_index {
+            challenge_window.track(batch_index);
+        }
+    }
 
     let shadow_prover = ShadowProver::new(
-        signer.address(),
+        wallet_address,
         Address::from_str(&shadow_rollup).unwrap(),
-        verify_provider,
+        verify_provider.clone(),
         l1_signer,
+    )
+    .with_multi_prove(config.multi_prove_max)
+    .with_dry_run(config.dry_run);
+
+    if let Some(command) = oneshot {
+        if let Err(e) =
+            run_chain_command(command, &batch_syncer, &shadow_prover, checkpoint.as_ref()).await
+        {
+            log::error!("{:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    tokio::spawn(serve_prove_requests(
+        prove_requests,
+        batch_syncer.clone(),
+        shadow_prover.clone(),
+        checkpoint.clone(),
+    ));
+
+    let audit_log = match config.audit_file.clone().map(AuditLog::open).transpose() {
+        Ok(audit_log) => audit_log,
+        Err(e) => {
+            log::error!("{:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let pause_check =
+        PauseCheck::new(Address::from_str(&shadow_rollup).unwrap(), verify_provider.clone());
+
+    let queue =
+        match queue::from_url(config.queue_url.as_deref(), &config.queue_key, config.queue_size) {
+            Ok(queue) => queue,
+            Err(e) => {
+                log::error!("invalid batch queue: {:#}", e);
+                std::process::exit(1);
+            }
+        };
+    log::info!("Running as {:?}, batch queue: {:?}", config.role, config.queue_url);
+    log::info!("Poll interval: {:?}", config.loop_interval);
+    housekeeping::spawn(&config);
+    balance::spawn(&config, verify_provider.clone(), wallet_address);
+    let receipts = Receipts::spawn(&config);
+    let alert_webhook = AlertWebhook::spawn(&config);
+
+    // Only the syncer reads batches from the rollup.
+    if config.role.syncs() {
+        readiness::warmup(&readiness, config.warmup_timeout, batch_syncer.dry_run()).await;
+    } else {
+        readiness.set_ready();
+    }
+
+    // Every unproven batch of the lookback window, before entering the loop.
+    if config.startup_catchup && config.role.syncs() {
+        if let Err(e) = catch_up(
+            &batch_syncer,
+            &shadow_prover,
+            checkpoint.as_ref(),
+            &config,
+            queue.as_ref(),
+            usize::MAX,
+        )
+        .await
+        {
+            log::error!("startup catch-up error: {:#}", e);
+        }
+    }
+
+    let shutdown = Shutdown::listen();
+    // Only the syncer reads batches from the rollup.
+    let commits = if config.role.syncs() {
+        Commits::spawn(
+            config.l1_ws.clone(),
+            Address::from_str(&rollup).unwrap(),
+            config.loop_interval,
+        )
+    } else {
+        Commits::default()
+    };
+    let stages = Stages {
+        config: config.clone(),
+        batch_syncer,
+        shadow_prover,
+        checkpoint,
+        queue: Arc::from(queue),
+        pause_check,
+        challenge_window,
+        receipts,
+        alert_webhook,
+        status,
+        audit_log: audit_log.map(|audit_log| Arc::new(Mutex::new(audit_log))),
+    };
+    liveness.set_started();
+    // A slow prove does not hold back the commit of the next batch, the bounded queue holds
+    // the batches in between. A run-once process ticks both in turn.
+    let failed = if config.role == Role::All && !cli.once {
+        let prover = tokio::spawn(stages.clone().run(
+            Role::Prover,
+            false,
+            Commits::default(),
+            shutdown.clone(),
+        ));
+        let sync_failed = stages.run(Role::Syncer, false, commits, shutdown).await;
+        let prove_failed = prover.await.unwrap_or(true);
+        sync_failed || prove_failed
+    } else {
+        stages.run(config.role, cli.once, commits, shutdown).await
+    };
+
+    log::info!("shutting down");
+    if let Some(url) = &config.metrics_push_url {
+        if let Err(e) = metrics::push(url).await {
+            log::error!("push metrics error: {:#}", e);
+        }
+    }
+    logger.flush();
+    logger.shutdown();
+    if cli.once && failed {
+        std::process::exit(1);
+    }
+}
+
+// What the loops of the daemon tick; with both stages in one process, the sync and prove stages
+// run as loops of their own, connected by the batch queue.
+#[derive(Clone)]
+struct Stages<T, P, N> {
+    config: Config,
+    batch_syncer: BatchSyncer<T, P, N>,
+    shadow_prover: ShadowProver<T, P, N>,
+    checkpoint: Option<CheckpointStore>,
+    queue: Arc<dyn BatchQueue>,
+    pause_check: PauseCheck,
+    challenge_window: ChallengeWindowTracker,
+    receipts: Option<Receipts>,
+    alert_webhook: Option<AlertWebhook>,
+    status: Status,
+    audit_log: Option<Arc<Mutex<AuditLog>>>,
+}
+
+impl<T, P, N> Stages<T, P, N>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    // Tick the stages of `role` until a shutdown, or just once with `once`. Returns whether a
+    // stage failed.
+    async fn run(self, role: Role, once: bool, commits: Commits, mut shutdown: Shutdown) -> bool {
+        let config = &self.config;
+        let mut commit_failures = StageFailures::new(Stage::Commit, config);
+        let mut prove_failures = StageFailures::new(Stage::Prove, config);
+        let mut loop_breaker = LoopBreaker::new(config);
+        let (mut ticked, mut failed) = (false, false);
+        loop {
+            if once && ticked && !awaits_max_batch(&self.batch_syncer, config.max_batch).await {
+                break;
+            }
+            // A run-once process ticks right away, a prover loop waits on the queue instead.
+            if !once || ticked {
+                let stop = match loop_breaker.backoff() {
+                    // New commits do not wake a loop backing off.
+                    Some(backoff) => shutdown.sleep(backoff).await,
+                    None if role.syncs() => {
+                        shutdown.sleep_until(config.loop_interval, commits.wait()).await
+                    }
+                    None => false,
+                };
+                if stop {
+                    break;
+                }
+            }
+            ticked = true;
+            // A tick in progress finishes, but no new one starts.
+            if shutdown.requested() {
+                break;
+            }
+            // Heartbeat
+            METRICS.shadow_loop_iterations_total.inc();
+            let now = util::unix_timestamp();
+            METRICS.shadow_last_tick_timestamp.set(now as i64);
+            METRICS.shadow_last_iteration_timestamp.set(now as i64);
+            let mut tick = Tick { timestamp: now, ..Tick::default() };
+
+            if config.pause_check && self.pause_check.is_paused().await {
+                let paused = || Decision {
+                    reason: Some("shadow rollup paused".into()),
+                    ..Default::default()
+                };
+                tick.commit = role.syncs().then(paused);
+                tick.prove = role.proves().then(paused);
+                append_audit(self.audit_log.as_deref(), tick);
+                // A prover loop did not sleep before the tick.
+                if !role.syncs() {
+                    shutdown.sleep(config.loop_interval).await;
+                }
+                continue;
+            }
+
+            // Sync
+            if role.syncs() {
+                let mut decision = Decision::default();
+                if !commit_failures.ready(Instant::now()) {
+                    decision.skip("commit stage cooling down");
+                } else {
+                    let synced = if config.catchup {
+                        catch_up_stage(
+                            &self.batch_syncer,
+                            &self.shadow_prover,
+                            self.checkpoint.as_ref(),
+                            config,
+                            self.queue.as_ref(),
+                            &mut decision,
+                        )
+                        .await
+                    } else {
+                        sync_stage(
+                            &self.batch_syncer,
+                            self.checkpoint.as_ref(),
+                            self.queue.as_ref(),
+                            &mut decision,
+                        )
+                        .await
+                    };
+                    match synced {
+                        Ok(()) => commit_failures.success(),
+                        Err(e) => {
+                            failed = true;
+                            metrics::inc(&METRICS.shadow_exec_error_total);
+                            commit_failures.failure(&e, Instant::now());
+                            decision.fail(format!("{:#}", e));
+                        }
+                    }
+                }
+                tick.commit = Some(decision);
+            }
+
+            // Prove
+            if role.proves() {
+                let mut decision = Decision::default();
+                if !prove_failures.ready(Instant::now()) {
+                    decision.skip("prove stage cooling down");
+                    // A prover loop waits on the queue otherwise.
+                    if !role.syncs() {
+                        shutdown.sleep(config.loop_interval).await;
+                    }
+                } else {
+                    let wait = if role.syncs() { Duration::ZERO } else { config.loop_interval };
+                    match prove_stage(
+                        &self.shadow_prover,
+                        self.checkpoint.as_ref(),
+                        self.queue.as_ref(),
+                        wait,
+                        &mut decision,
+                    )
+                    .await
+                    {
+                        Ok(Some(batch_index)) => {
+                            self.challenge_window.track(batch_index);
+                            prove_failures.success();
+                        }
+                        Ok(None) => prove_failures.success(),
+                        Err(e) => {
+                            failed = true;
+                            metrics::inc(&METRICS.shadow_exec_error_total);
+                            prove_failures.failure(&e, Instant::now());
+                            decision.fail(format!("{:#}", e));
+                        }
+                    }
+                }
+                tick.prove = Some(decision);
+            }
+            if let Some(receipts) = &self.receipts {
+                receipts.record_tick(&tick);
+            }
+            if let Some(alert_webhook) = &self.alert_webhook {
+                alert_webhook.record_tick(&tick);
+            }
+            self.status.record_tick(&tick);
+            let tick_failed = [&tick.commit, &tick.prove]
+                .into_iter()
+                .flatten()
+                .any(|decision| decision.outcome == Outcome::Failed);
+            loop_breaker.tick(tick_failed);
+            append_audit(self.audit_log.as_deref(), tick);
+
+            // Post-prove lifecycle, in the loop proving if any.
+            if config.challenge_window && role.proves() == config.role.proves() {
+                self.challenge_window.refresh(util::unix_timestamp()).await;
+            }
+        }
+        failed
+    }
+}
+
+// Commit the latest batch to the shadow rollup and queue it for proving.
+async fn sync_stage<T, P, N>(
+    batch_syncer: &BatchSyncer<T, P, N>,
+    checkpoint: Option<&CheckpointStore>,
+    queue: &dyn BatchQueue,
+    decision: &mut Decision,
+) -> Result<(), anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let outcome = batch_syncer.sync_batch(decision).await?;
+    METRICS.shadow_sync_outcomes_total.with_label_values(&[outcome.as_str()]).inc();
+    match outcome {
+        SyncOutcome::Committed(batch) | SyncOutcome::AlreadyCommitted(batch) => {
+            update_checkpoint(checkpoint, |c| c.record_committed(batch.batch_index));
+            queue.push(&batch).await?;
+        }
+        // The prover simulates its proof in turn.
+        SyncOutcome::Simulated(batch) => queue.push(&batch).await?,
+        outcome => log::info!(
+            "no batch committed ({}): {}",
+            outcome.as_str(),
+            decision.reason.as_deref().unwrap_or_default()
+        ),
+    }
+    Ok(())
+}
+
+// Serve the forced proves of `POST /prove/{index}` one at a time.
+async fn serve_prove_requests<T, P, N>(
+    mut requests: mpsc::Receiver<ProveRequest>,
+    batch_syncer: BatchSyncer<T, P, N>,
+    shadow_prover: ShadowProver<T, P, N>,
+    checkpoint: Option<CheckpointStore>,
+) where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    while let Some(request) = requests.recv().await {
+        let batch_index = request.batch_index;
+        let response =
+            force_prove(&batch_syncer, &shadow_prover, checkpoint.as_ref(), batch_index).await;
+        match &response {
+            Ok(response) => log::info!("forced prove of batch {:?}: {:?}", batch_index, response),
+            Err(e) => log::error!("forced prove of batch {:?} error: {:#}", batch_index, e),
+        }
+        let _ = request.reply.send(response.map_err(|e| format!("{:#}", e)));
+    }
+}
+
+// Commit the batch to the shadow rollup unless it is already, then prove it.
+async fn force_prove<T, P, N>(
+    batch_syncer: &BatchSyncer<T, P, N>,
+    shadow_prover: &ShadowProver<T, P, N>,
+    checkpoint: Option<&CheckpointStore>,
+    batch_index: u64,
+) -> Result<ProveResponse, anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let mut response = ProveResponse { batch_index, ..ProveResponse::default() };
+    if batch_syncer.is_proven(batch_index).await? {
+        response.result = String::from("already_proven");
+        return Ok(response);
+    }
+
+    let batch = if batch_syncer.is_committed(batch_index).await? {
+        batch_syncer
+            .committed_unproven_batch(batch_index)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("batch {} is already proven", batch_index))?
+    } else {
+        let target = batch_syncer.batch_target(batch_index).await?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "batch {} and its successor are not in the lookback window",
+                batch_index
+            )
+        })?;
+        let mut decision = Decision::default();
+        match batch_syncer.sync_target(&target, &mut decision).await? {
+            SyncOutcome::Committed(batch) | SyncOutcome::AlreadyCommitted(batch) => {
+                update_checkpoint(checkpoint, |c| c.record_committed(batch_index));
+                response.commit_tx_hash = decision.tx_hash;
+                batch
+            }
+            outcome => {
+                response.result = outcome.as_str().to_string();
+                response.reason = decision.reason;
+                return Ok(response);
+            }
+        }
+    };
+
+    response.prove_tx_hash = prove_batch(shadow_prover, checkpoint, batch).await?;
+    response.result =
+        String::from(if response.prove_tx_hash.is_some() { "proven" } else { "simulated" });
+    Ok(response)
+}
+
+// Whether a run-once process bounded by `SHADOW_PROVING_MAX_BATCH` keeps ticking, until that
+// batch is proven.
+async fn awaits_max_batch<T, P, N>(
+    batch_syncer: &BatchSyncer<T, P, N>,
+    max_batch: Option<u64>,
+) -> bool
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let Some(max_batch) = max_batch else {
+        return false;
+    };
+    match batch_syncer.is_proven(max_batch).await {
+        Ok(proven) => {
+            if proven {
+                log::info!("batch {:?}, the last of the batch range, is proven", max_batch);
+            }
+            !proven
+        }
+        // Asked again after the next tick.
+        Err(e) => {
+            log::error!("query prove state of batch {:?} error: {:#}", max_batch, e);
+            true
+        }
+    }
+}
+
+// Catch up on the oldest unproven batches of the lookback window in place of the latest one, up
+// to `catchup_max_batches` per tick.
+async fn catch_up_stage<T, P, N>(
+    batch_syncer: &BatchSyncer<T, P, N>,
+    shadow_prover: &ShadowProver<T, P, N>,
+    checkpoint: Option<&CheckpointStore>,
+    config: &Config,
+    queue: &dyn BatchQueue,
+    decision: &mut Decision,
+) -> Result<(), anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let (proved, skipped, failed) = catch_up(
+        batch_syncer,
+        shadow_prover,
+        checkpoint,
+        config,
+        queue,
+        config.catchup_max_batches,
+    )
+    .await?;
+    if failed > 0 {
+        anyhow::bail!("catch-up of {} batches failed", failed);
+    }
+    decision.skip(format!("catch-up: proved {}, skipped {}", proved, skipped));
+    Ok(())
+}
+
+// Prove the next batch of the queue, waiting up to `wait` for one. Returns the batch index once
+// proven.
+async fn prove_stage<T, P, N>(
+    shadow_prover: &ShadowProver<T, P, N>,
+    checkpoint: Option<&CheckpointStore>,
+    queue: &dyn BatchQueue,
+    wait: Duration,
+    decision: &mut Decision,
+) -> Result<Option<u64>, anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let Some(batch) = queue.pop(wait).await? else {
+        decision.skip("no batch to prove");
+        return Ok(None);
+    };
+    let batch_index = batch.batch_index;
+    decision.batch_index = Some(batch_index);
+    match prove_batch(shadow_prover, checkpoint, batch).await? {
+        Some(tx_hash) => {
+            decision.prove(tx_hash);
+            Ok(Some(batch_index))
+        }
+        None => {
+            decision.skip("prove not attempted");
+            Ok(None)
+        }
+    }
+}
+
+// Prove up to `limit` unproven batches of the lookback window, oldest first; a syncer-only
+// process publishes them to the batch queue instead. Dead-lettered batches are left to the
+// operator: `POST /prove/{index}` or the `resume-prove` command, then `dead-letter clear`.
+// Returns the number of batches proven, skipped and failed.
+async fn catch_up<T, P, N>(
+    batch_syncer: &BatchSyncer<T, P, N>,
+    shadow_prover: &ShadowProver<T, P, N>,
+    checkpoint: Option<&CheckpointStore>,
+    config: &Config,
+    queue: &dyn BatchQueue,
+    limit: usize,
+) -> Result<(usize, usize, usize), anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let mut targets =
+        batch_syncer.unproven_batches().await.context("list unproven batches error")?;
+    if let Some(c) = checkpoint.and_then(|store| store.load().ok()) {
+        targets.retain(|t| c.dead_letter.iter().all(|d| d.batch_index != t.batch_index));
+    }
+    targets.truncate(limit);
+    log::info!(
+        "catch-up: unproven batches = {:?}",
+        targets.iter().map(|t| t.batch_index).collect::<Vec<_>>()
     );
 
-    loop {
-        sleep(Duration::from_secs(12)).await;
-        // Sync & Prove
-        let result = match batch_syncer.sync_batch().await {
-            Ok(Some(batch)) => shadow_prover.prove(batch).await,
-            Ok(None) => Ok(()),
+    // Contiguous batches are proven together once all are committed.
+    let prove_grouped = config.role.proves() && config.multi_prove_max > 1;
+    let mut results = Vec::new();
+    let mut grouped = Vec::new();
+    for target in &targets {
+        let result = match batch_syncer.sync_target(target, &mut Decision::default()).await {
+            Ok(SyncOutcome::Committed(batch) | SyncOutcome::AlreadyCommitted(batch)) => {
+                update_checkpoint(checkpoint, |c| c.record_committed(batch.batch_index));
+                if prove_grouped {
+                    grouped.push(batch);
+                    continue;
+                } else if config.role.proves() {
+                    prove_batch(shadow_prover, checkpoint, batch).await.map(|tx| tx.is_some())
+                } else {
+                    queue.push(&batch).await.map(|_| false)
+                }
+            }
+            Ok(_) => Ok(false),
             Err(e) => Err(e),
         };
+        results.push((target.batch_index, result));
+    }
+    for (batch_index, outcome) in shadow_prover.prove_group(&grouped).await {
+        results.push((batch_index, record_prove_outcome(checkpoint, batch_index, outcome)));
+    }
 
-        // Handle result.
+    let (mut proved, mut skipped, mut failed) = (0, 0, 0);
+    for (batch_index, result) in results {
         match result {
-            Ok(()) => (),
+            Ok(true) => proved += 1,
+            Ok(false) => skipped += 1,
             Err(e) => {
-                log::error!("shadow proving exec error: {:#?}", e);
+                log::error!("catch-up of batch {:?} error: {:#?}", batch_index, e);
+                failed += 1;
+            }
+        }
+    }
+    log::info!(
+        "catch-up finished: unproven = {}, proved = {}, skipped = {}, failed = {}",
+        targets.len(),
+        proved,
+        skipped,
+        failed
+    );
+    Ok((proved, skipped, failed))
+}
+
+async fn run_chain_command<T, P, N>(
+    command: Command,
+    batch_syncer: &BatchSyncer<T, P, N>,
+    shadow_prover: &ShadowProver<T, P, N>,
+    checkpoint: Option<&CheckpointStore>,
+) -> Result<(), anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    // Reproducing a past run needs the batch as the rollup held it then.
+    let pinned = |at_block: Option<u64>| match at_block {
+        Some(number) => batch_syncer.clone().at_block(BlockId::number(number)),
+        None => batch_syncer.clone(),
+    };
+    match command {
+        Command::ResumeProve { index, at_block } => {
+            resume_prove(&pinned(at_block), shadow_prover, checkpoint, index)
+                .await
+                .with_context(|| format!("resume-prove of batch {} failed", index))?;
+        }
+        Command::ExportRequest { index, output, at_block } => {
+            let artifact = pinned(at_block).prove_artifact(index).await?;
+            artifact.write(&output)?;
+            log::info!("prove request of batch {} written to {:?}", index, output);
+        }
+        Command::SubmitProof { artifact, proof } => {
+            let artifact = ProveArtifact::read(&artifact)?;
+            let batch_index = artifact.batch.batch_index;
+            // The proof only verifies against the very commit it was requested for.
+            if batch_syncer.prove_artifact(batch_index).await? != artifact {
+                anyhow::bail!("artifact does not match batch {} on the shadow rollup", batch_index);
+            }
+            let proof = read_proof(&proof)?;
+            shadow_prover.submit_proof(batch_index, proof).await?;
+            update_checkpoint(checkpoint, |c| c.record_proven(batch_index));
+            log::info!("batch {} proven with the submitted proof", batch_index);
+        }
+        _ => unreachable!("not a chain command"),
+    }
+    Ok(())
+}
+
+fn read_proof(path: &Path) -> Result<Bytes, anyhow::Error> {
+    let content = std::fs::read(path).with_context(|| format!("read proof {:?}", path))?;
+    match std::str::from_utf8(&content).map(str::trim) {
+        Ok(hex) if hex.starts_with("0x") => {
+            hex.parse().with_context(|| format!("proof {:?} is not valid hex", path))
+        }
+        _ => Ok(Bytes::from(content)),
+    }
+}
+
+// Prove a batch committed to the shadow rollup before a crash, without committing it again.
+async fn resume_prove<T, P, N>(
+    batch_syncer: &BatchSyncer<T, P, N>,
+    shadow_prover: &ShadowProver<T, P, N>,
+    checkpoint: Option<&CheckpointStore>,
+    batch_index: u64,
+) -> Result<(), anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let batch = match batch_syncer.committed_unproven_batch(batch_index).await? {
+        Some(batch) => batch,
+        None => {
+            log::info!("batch {} is already proven, nothing to resume", batch_index);
+            return Ok(());
+        }
+    };
+    log::info!(
+        "resume prove of batch {}, blocks {}..={}",
+        batch_index,
+        batch.start_block,
+        batch.end_block
+    );
+    update_checkpoint(checkpoint, |c| c.record_committed(batch_index));
+    if prove_batch(shadow_prover, checkpoint, batch).await?.is_none() {
+        anyhow::bail!("batch {} is not proven", batch_index);
+    }
+    log::info!("batch {} proven", batch_index);
+    Ok(())
+}
+
+// Prove a batch committed to the shadow rollup, keeping the checkpoint up to date and timing a
+// successful prove in `shadow_prove_duration_seconds`. Returns the prove tx hash once proven.
+async fn prove_batch<T, P, N>(
+    shadow_prover: &ShadowProver<T, P, N>,
+    checkpoint: Option<&CheckpointStore>,
+    batch: BatchInfo,
+) -> Result<Option<TxHash>, anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let batch_index = batch.batch_index;
+    let size_class = [batch.size_class.as_str()];
+    let start = Instant::now();
+    let outcome = shadow_prover.prove(batch).await;
+    if let Ok(Some(_)) = outcome {
+        metrics::observe_duration(
+            &METRICS.shadow_prove_duration_seconds.with_label_values(&size_class),
+            start.elapsed(),
+        );
+    }
+    let tx_hash = outcome.as_ref().ok().copied().flatten();
+    record_prove_outcome(checkpoint, batch_index, outcome.map(|tx| tx.is_some())).map(|_| tx_hash)
+}
+
+// Record a proven batch, or a dead letter for an abandoned one.
+fn record_prove_outcome(
+    checkpoint: Option<&CheckpointStore>,
+    batch_index: u64,
+    outcome: Result<bool, anyhow::Error>,
+) -> Result<bool, anyhow::Error> {
+    let proven = match outcome {
+        Ok(proven) => proven,
+        Err(e) => {
+            let error = format!("{:#}", e);
+            // A rejected proof is kept with the roots it was checked against.
+            let roots = e.downcast_ref::<ProofRejected>().and_then(|r| r.roots.clone());
+            update_checkpoint(checkpoint, |c| {
+                c.record_dead_letter(batch_index, error, roots, util::unix_timestamp())
+            });
+            return Err(e);
+        }
+    };
+    if proven {
+        update_checkpoint(checkpoint, |c| c.record_proven(batch_index));
+    }
+    Ok(proven)
+}
+
+// Append the decisions of a tick to the audit log, if any.
+fn append_audit(audit_log: Option<&Mutex<AuditLog>>, tick: Tick) {
+    if let Some(audit_log) = audit_log {
+        if let Err(e) = audit_log.lock().unwrap().append(tick) {
+            log::error!("append audit log error: {:#}", e);
+        }
+    }
+}
+
+fn update_checkpoint(checkpoint: Option<&CheckpointStore>, f: impl FnOnce(&mut Checkpoint)) {
+    if let Some(store) = checkpoint {
+        match store.update(f) {
+            Ok(c) => METRICS.shadow_dead_letter_count.set(c.dead_letter.len() as i64),
+            Err(e) => log::error!("update checkpoint error: {:#?}", e),
+        }
+    }
+}
+
+fn run_command(command: Command) -> Result<(), anyhow::Error> {
+    match command {
+        Command::Checkpoint { file, action } => {
+            let store = CheckpointStore::new(file);
+            match action {
+                CheckpointAction::Show => {
+                    let checkpoint = store.load()?;
+                    println!("checkpoint {:?}\n{}", store.path(), checkpoint);
+                }
+                CheckpointAction::Set { index, yes } => {
+                    let mut checkpoint = store.load().unwrap_or_else(|e| {
+                        eprintln!("{:#}, it will be replaced", e);
+                        Checkpoint::default()
+                    });
+                    println!("checkpoint {:?}\n{}", store.path(), checkpoint);
+                    if !yes && !confirm(&format!("Set last committed/proven batch to {}?", index))?
+                    {
+                        println!("aborted");
+                        return Ok(());
+                    }
+                    checkpoint.reset_to(index);
+                    store.save(&checkpoint)?;
+                    println!("checkpoint updated\n{}", checkpoint);
+                }
+            }
+        }
+        Command::DeadLetter { file, action } => {
+            let store = CheckpointStore::new(file);
+            let mut checkpoint = store.load()?;
+            match action {
+                DeadLetterAction::List => {
+                    println!(
+                        "dead letter of {:?}: {} batches",
+                        store.path(),
+                        checkpoint.dead_letter.len()
+                    );
+                    for dead_letter in &checkpoint.dead_letter {
+                        println!("{}", dead_letter);
+                    }
+                }
+                DeadLetterAction::Clear { index, yes } => {
+                    let question = match index {
+                        Some(index) => format!("Clear the dead-letter entry of batch {}?", index),
+                        None => format!(
+                            "Clear all {} dead-letter entries?",
+                            checkpoint.dead_letter.len()
+                        ),
+                    };
+                    if !yes && !confirm(&question)? {
+                        println!("aborted");
+                        return Ok(());
+                    }
+                    let cleared = checkpoint.clear_dead_letter(index);
+                    store.save(&checkpoint)?;
+                    println!("cleared {} dead-letter entries", cleared);
+                }
             }
         }
+        Command::VerifyAudit { file } => {
+            let head = audit::verify_file(&file)?;
+            println!("audit log {:?} verified: {} entries, head {:?}", file, head.len, head.hash);
+        }
+        Command::ResumeProve { .. } |
+        Command::ExportRequest { .. } |
+        Command::SubmitProof { .. } => {
+            unreachable!("chain commands run with the daemon setup")
+        }
     }
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool, anyhow::Error> {
+    println!("{} [y/N]", question);
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
 }
 
 // Metric management
-async fn metric_mng() {
-    register_metrics();
-    let metric_address = read_env_var("SHADOW_PROVING_METRIC_ADDRESS", "0.0.0.0:6060".to_string());
+async fn metric_mng(
+    config: &Config,
+    window_statuses: WindowStatuses,
+    readiness: Readiness,
+    liveness: Liveness,
+    circuit: CircuitBreaker,
+    manual_prove: ManualProve,
+    status: Status,
+) {
+    register_metrics(config);
+    let metric_address = config.metric_address;
+    let checkpoint = config.checkpoint_file.clone().map(CheckpointStore::new);
+    let window_statuses = config.challenge_window.then_some(window_statuses);
     tokio::spawn(async move {
-        let metrics =
-            Router::new().route("/metrics", get(handle_metrics)).layer(TraceLayer::new_for_http());
-        axum::Server::bind(&metric_address.parse().unwrap())
-            .serve(metrics.into_make_service())
-            .await
-            .unwrap();
+        let metrics = Router::new()
+            .route("/metrics", get(handle_metrics))
+            .route("/ready", get(move || handle_ready(readiness.clone())))
+            .route("/health", get(move || handle_health(liveness.clone())))
+            .route("/status", get(move || handle_status(status.clone())))
+            .route("/debug/dead-letter", get(move || handle_dead_letter(checkpoint.clone())))
+            .route(
+                "/debug/challenge-window",
+                get(move || handle_challenge_window(window_statuses.clone())),
+            )
+            .route("/admin/reset-circuit", post(move || handle_reset_circuit(circuit.clone())))
+            .route(
+                "/prove/:index",
+                post(move |index, headers| handle_prove(index, headers, manual_prove.clone())),
+            )
+            .layer(TraceLayer::new_for_http());
+        axum::Server::bind(&metric_address).serve(metrics.into_make_service()).await.unwrap();
     });
 }
 
-fn register_metrics() {
+fn register_metrics(config: &Config) {
     // detected batch index.
     REGISTRY.register(Box::new(METRICS.shadow_batch_index.clone())).unwrap();
     // chunks len.
@@ -115,22 +1257,152 @@ fn register_metrics() {
     REGISTRY.register(Box::new(METRICS.shadow_verify_result.clone())).unwrap();
     // wallet balance.
     REGISTRY.register(Box::new(METRICS.shadow_wallet_balance.clone())).unwrap();
+    // loop heartbeat.
+    REGISTRY.register(Box::new(METRICS.shadow_loop_iterations_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_last_tick_timestamp.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_last_iteration_timestamp.clone())).unwrap();
+    // withdrawal root check.
+    REGISTRY.register(Box::new(METRICS.shadow_withdrawal_root_mismatch_total.clone())).unwrap();
+    // state root chain check.
+    REGISTRY.register(Box::new(METRICS.shadow_state_root_chain_break_total.clone())).unwrap();
+    // commit transaction sender check.
+    REGISTRY.register(Box::new(METRICS.shadow_unexpected_sequencer_total.clone())).unwrap();
+    // blob versioned hash check.
+    REGISTRY.register(Box::new(METRICS.shadow_blob_hash_mismatch_total.clone())).unwrap();
+    // unconfirmed commits.
+    REGISTRY.register(Box::new(METRICS.shadow_inflight_commits.clone())).unwrap();
+    // abandoned batches.
+    REGISTRY.register(Box::new(METRICS.shadow_dead_letter_count.clone())).unwrap();
+    // empty batches.
+    REGISTRY.register(Box::new(METRICS.shadow_empty_batch_total.clone())).unwrap();
+    // sync outcomes.
+    REGISTRY.register(Box::new(METRICS.shadow_sync_outcomes_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_no_logs_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_too_few_logs_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_too_many_blocks_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_too_many_txn_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_already_proven_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_sync_duration_seconds.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_commit_duration_seconds.clone())).unwrap();
+    // prove outcomes.
+    REGISTRY.register(Box::new(METRICS.shadow_prove_duration_seconds.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_prove_success_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_prove_failure_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_proof_verification_failed_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_exec_error_total.clone())).unwrap();
+    // stage failure policy.
+    REGISTRY.register(Box::new(METRICS.shadow_commit_failures_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_prove_failures_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_stage_alerts_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_stage_errors_total.clone())).unwrap();
+    // commit send guards.
+    REGISTRY.register(Box::new(METRICS.shadow_commit_simulation_reverted_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_commit_receipt_failures_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_commit_replacements_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_circuit_open.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_loop_backoff.clone())).unwrap();
+    // batch receipts.
+    REGISTRY.register(Box::new(METRICS.shadow_receipts_sent_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_receipts_dropped_total.clone())).unwrap();
+    // stage failure alerts.
+    REGISTRY.register(Box::new(METRICS.shadow_alerts_sent_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_alerts_dropped_total.clone())).unwrap();
+    // provider throttling.
+    REGISTRY.register(Box::new(METRICS.shadow_rpc_rate_limited_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_rpc_errors_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_rpc_failovers_total.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_rpc_timeouts_total.clone())).unwrap();
+    // lagging nodes behind a load balancer.
+    REGISTRY.register(Box::new(METRICS.shadow_stale_node_total.clone())).unwrap();
+    // maintenance.
+    REGISTRY.register(Box::new(METRICS.shadow_rollup_paused.clone())).unwrap();
+    // post-prove lifecycle.
+    REGISTRY.register(Box::new(METRICS.shadow_batch_challenge_remaining_secs.clone())).unwrap();
+
+    // build.
+    REGISTRY.register(Box::new(METRICS.shadow_build_info.clone())).unwrap();
+    METRICS
+        .shadow_build_info
+        .with_label_values(&[env!("CARGO_PKG_VERSION"), env!("SHADOW_PROVING_GIT_SHA")])
+        .set(1);
+
+    // effective config.
+    REGISTRY.register(Box::new(METRICS.shadow_config_loop_interval_secs.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_config_max_block.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_config_max_txn.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_config_lookback_blocks.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_config_log_chunk_blocks.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_config_batch_buffer.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_config_confirmations.clone())).unwrap();
+    REGISTRY.register(Box::new(METRICS.shadow_config_catchup_max_batches.clone())).unwrap();
+    METRICS.shadow_config_loop_interval_secs.set(config.loop_interval.as_secs() as i64);
+    METRICS.shadow_config_max_block.set(config.max_block as i64);
+    METRICS.shadow_config_max_txn.set(config.max_txn as i64);
+    METRICS.shadow_config_lookback_blocks.set(config.lookback_blocks as i64);
+    METRICS.shadow_config_log_chunk_blocks.set(config.log_chunk_blocks as i64);
+    METRICS.shadow_config_batch_buffer.set(config.batch_buffer as i64);
+    METRICS.shadow_config_confirmations.set(config.confirmations as i64);
+    METRICS.shadow_config_catchup_max_batches.set(config.catchup_max_batches as i64);
 }
 
 async fn handle_metrics() -> String {
-    let mut buffer = Vec::new();
-    let encoder = TextEncoder::new();
+    metrics::encode()
+}
+
+async fn handle_dead_letter(
+    checkpoint: Option<CheckpointStore>,
+) -> Result<Json<Vec<DeadLetter>>, (StatusCode, String)> {
+    let store = checkpoint.ok_or_else(|| {
+        (StatusCode::NOT_FOUND, String::from("SHADOW_PROVING_CHECKPOINT_FILE is not set"))
+    })?;
+    match store.load() {
+        Ok(checkpoint) => Ok(Json(checkpoint.dead_letter)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e))),
+    }
+}
 
-    // Gather the metrics.
-    let mut metric_families = REGISTRY.gather();
-    metric_families.extend(prometheus::gather());
+async fn handle_ready(readiness: Readiness) -> (StatusCode, Json<ReadyState>) {
+    let state = readiness.check().await;
+    let status = if state.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(state))
+}
 
-    // Encode metrics to send.
-    match encoder.encode(&metric_families, &mut buffer) {
-        Ok(()) => String::from_utf8(buffer.clone()).unwrap(),
-        Err(e) => {
-            log::error!("encode metrics error: {:#?}", e);
-            String::from("")
+async fn handle_health(liveness: Liveness) -> (StatusCode, Json<serde_json::Value>) {
+    match liveness.started() {
+        true => (StatusCode::OK, Json(serde_json::json!({ "status": "ok" }))),
+        false => {
+            (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "status": "starting" })))
+        }
+    }
+}
+
+async fn handle_reset_circuit(circuit: CircuitBreaker) -> Json<serde_json::Value> {
+    let was_open = circuit.reset("reset by POST /admin/reset-circuit");
+    Json(serde_json::json!({ "was_open": was_open }))
+}
+
+async fn handle_prove(
+    extract::Path(index): extract::Path<u64>,
+    headers: HeaderMap,
+    manual_prove: ManualProve,
+) -> Result<Json<ProveResponse>, (StatusCode, String)> {
+    let authorization = headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok());
+    manual_prove.authorize(authorization)?;
+    log::info!("forced prove of batch {:?} requested", index);
+    Ok(Json(manual_prove.prove(index).await?))
+}
+
+async fn handle_status(status: Status) -> Json<StatusReport> {
+    Json(status.get(util::unix_timestamp()))
+}
+
+async fn handle_challenge_window(
+    window_statuses: Option<WindowStatuses>,
+) -> Result<Json<Vec<WindowStatus>>, (StatusCode, String)> {
+    match window_statuses {
+        Some(statuses) => Ok(Json(statuses.get())),
+        None => {
+            Err((StatusCode::NOT_FOUND, String::from("SHADOW_PROVING_CHALLENGE_WINDOW is off")))
         }
     }
 }
@@ -142,10 +1414,25 @@ const LOG_FILE_SIZE_LIMIT: u64 = 200 * 10u64.pow(6); // 200MB
                                                      // const LOG_FILE_SIZE_LIMIT: u64 = 10u64.pow(3); // 1kB
 const LOG_FILES_TO_KEEP: usize = 3;
 
-fn setup_logging() {
+// The returned handle flushes the buffered log lines; keep it until exit.
+fn setup_logging() -> LoggerHandle {
+    // Text by default, one JSON object per record for log pipelines.
+    let format: fn(&mut dyn std::io::Write, &mut DeferredNow, &Record) -> std::io::Result<()> =
+        match read_env_var("SHADOW_PROVING_LOG_FORMAT", String::from("text")).as_str() {
+            "json" => json_log_format,
+            "text" => log_format,
+            other => {
+                eprintln!("unknown SHADOW_PROVING_LOG_FORMAT {:?}, logging as text", other);
+                log_format
+            }
+        };
+    let logger = Logger::try_with_env_or_str(LOG_LEVEL).unwrap().format(format);
+    // A container platform collects stdout, and its filesystem may be read-only.
+    if !read_env_var("SHADOW_PROVING_LOG_TO_FILE", true) {
+        return logger.log_to_stdout().write_mode(WriteMode::BufferAndFlush).start().unwrap();
+    }
     //configure the logger
-    Logger::try_with_env_or_str(LOG_LEVEL)
-        .unwrap()
+    logger
         .log_to_file(
             FileSpec::default()
                 .directory(read_env_var(
@@ -154,24 +1441,43 @@ fn setup_logging() {
                 ))
                 .basename(LOG_FILE_BASENAME),
         )
-        .format(log_format)
         .duplicate_to_stdout(Duplicate::All)
         .rotate(
-            Criterion::Size(LOG_FILE_SIZE_LIMIT), // Scroll when file size reaches 200MB
+            log_rotation(
+                &read_env_var("SHADOW_PROVING_LOG_ROTATE_AGE", String::from("off")),
+                read_env_var("SHADOW_PROVING_LOG_FILE_SIZE_BYTES", LOG_FILE_SIZE_LIMIT),
+            ),
             Naming::TimestampsCustomFormat {
                 current_infix: Some(""),
                 format: "r%Y-%m-%d_%H-%M-%S",
             }, // Using timestamps as part of scrolling files
-            Cleanup::KeepLogFiles(LOG_FILES_TO_KEEP), // Keep the latest 3 scrolling files
+            // Keep the latest scrolling files, 3 by default
+            Cleanup::KeepLogFiles(read_env_var(
+                "SHADOW_PROVING_LOG_FILES_TO_KEEP",
+                LOG_FILES_TO_KEEP,
+            )),
         )
         .write_mode(WriteMode::BufferAndFlush)
         .start()
-        .unwrap();
+        .unwrap()
+}
+
+// Scroll when the file reaches `size` bytes, or also once a day or an hour per `age`.
+fn log_rotation(age: &str, size: u64) -> Criterion {
+    match age {
+        "day" => Criterion::AgeOrSize(Age::Day, size),
+        "hour" => Criterion::AgeOrSize(Age::Hour, size),
+        "off" => Criterion::Size(size),
+        other => {
+            eprintln!("unknown SHADOW_PROVING_LOG_ROTATE_AGE {:?}, rotating by size only", other);
+            Criterion::Size(size)
+        }
+    }
 }
 
 fn log_format(
     w: &mut dyn std::io::Write,
-    now: &mut flexi_logger::DeferredNow,
+    now: &mut DeferredNow,
     record: &Record,
 ) -> Result<(), std::io::Error> {
     write!(
@@ -184,6 +1490,44 @@ fn log_format(
     )
 }
 
+fn json_log_format(
+    w: &mut dyn std::io::Write,
+    now: &mut DeferredNow,
+    record: &Record,
+) -> Result<(), std::io::Error> {
+    let message = record.args().to_string();
+    let mut line = serde_json::json!({
+        "timestamp": now.now().format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string(),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": message,
+    });
+    if let Some(batch_index) = log_batch_index(&message) {
+        line["batch_index"] = batch_index.into();
+    }
+    write!(w, "{}", line)
+}
+
+// Index of the batch a log message is about: the number after "batch", "batch index",
+// "batch_index" or "batch of", e.g. "sync batch of 7".
+fn log_batch_index(message: &str) -> Option<u64> {
+    message.match_indices("batch").find_map(|(start, _)| {
+        let mut rest = &message[start + "batch".len()..];
+        loop {
+            let trimmed = rest.trim_start_matches([' ', ':', '=', '#']);
+            match ["_index", "index", "of"].iter().find_map(|word| trimmed.strip_prefix(word)) {
+                Some(after) => rest = after,
+                None => {
+                    rest = trimmed;
+                    break;
+                }
+            }
+        }
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        rest[..digits].parse().ok()
+    })
+}
+
 #[tokio::test]
 async fn test_prove_batch() {
     use alloy::{
@@ -191,9 +1535,8 @@ async fn test_prove_batch() {
         primitives::{Address, B256},
         providers::{ProviderBuilder, RootProvider},
         signers::local::PrivateKeySigner,
-        transports::http::{Client, Http},
     };
-    use shadow_proving::{abi::ShadowRollup, BatchInfo};
+    use shadow_proving::{abi::ShadowRollup, size_class::SizeClass, BatchInfo};
     use std::{env::var, str::FromStr};
 
     dotenv().ok();
@@ -207,11 +1550,11 @@ async fn test_prove_batch() {
 
     let signer: PrivateKeySigner = private_key.parse().unwrap();
     let wallet: EthereumWallet = EthereumWallet::from(signer.clone());
-    let provider: RootProvider<Http<Client>> =
-        ProviderBuilder::new().on_http(l1_rpc.parse().unwrap());
+    let provider: RootProvider<FailoverTransport> =
+        FailoverTransport::new("l1", &l1_rpc, 3).unwrap().into_provider();
 
-    let verify_provider: RootProvider<Http<Client>> =
-        ProviderBuilder::new().on_http(l1_verify_rpc.parse().unwrap());
+    let verify_provider: RootProvider<FailoverTransport> =
+        FailoverTransport::new("verify", &l1_verify_rpc, 3).unwrap().into_provider();
 
     let shadow_rollup =
         var("SHADOW_PROVING_L1_SHADOW_ROLLUP").expect("Cannot detect L1_SHADOW_ROLLUP env var");
@@ -232,41 +1575,89 @@ async fn test_prove_batch() {
     );
 
     let tx_hash = B256::from_str(&next_tx_hash).unwrap();
-    let batch_header = shadow_proving::shadow_rollup::batch_header_inspect(&provider, tx_hash)
-        .await
-        .ok_or_else(|| "Failed to inspect batch header".to_string())
-        .unwrap();
+    let batch_header =
+        shadow_proving::shadow_rollup::batch_header_inspect(&provider, tx_hash).await.unwrap();
 
-    let batch_store = ShadowRollup::BatchStore {
-        prevStateRoot: batch_header.get(89..121).unwrap_or_default().try_into().unwrap_or_default(),
-        postStateRoot: batch_header
-            .get(121..153)
-            .unwrap_or_default()
-            .try_into()
-            .unwrap_or_default(),
-        withdrawalRoot: batch_header
-            .get(153..185)
-            .unwrap_or_default()
-            .try_into()
-            .unwrap_or_default(),
-        dataHash: batch_header.get(25..57).unwrap_or_default().try_into().unwrap_or_default(),
-        blobVersionedHash: batch_header
-            .get(57..89)
-            .unwrap_or_default()
-            .try_into()
-            .unwrap_or_default(),
-        sequencerSetVerifyHash: batch_header
-            .get(185..217)
-            .unwrap_or_default()
-            .try_into()
-            .unwrap_or_default(),
-    };
+    let batch_store =
+        shadow_proving::shadow_rollup::batch_store_from_header(&batch_header).unwrap();
 
     let shadow_tx = l1_shadow_rollup.commitBatch(batch_index, batch_store);
     let rt = shadow_tx.send().await.unwrap();
     println!("commitBatch success: {:?}", rt.tx_hash());
 
-    let batch_info = BatchInfo { batch_index, start_block: 1000001, end_block: 1000002 };
+    let batch_info = BatchInfo {
+        batch_index,
+        start_block: 1000001,
+        end_block: 1000002,
+        size_class: SizeClass::Small,
+    };
 
     shadow_prover.prove(batch_info).await.unwrap();
 }
+
+#[test]
+fn test_cli_overrides() {
+    let cli = Cli::try_parse_from([
+        "shadow-proving",
+        "--l1-rpc",
+        "http://l1:8545",
+        "--max-block",
+        "200",
+        "resume-prove",
+        "7",
+        "--poll-interval-secs",
+        "30",
+    ])
+    .unwrap();
+    assert!(matches!(cli.command, Some(Command::ResumeProve { index: 7, at_block: None })));
+    assert_eq!(
+        cli.overrides.vars(),
+        vec![
+            ("SHADOW_PROVING_L1_RPC", String::from("http://l1:8545")),
+            ("SHADOW_PROVING_MAX_BLOCK", String::from("200")),
+            ("SHADOW_PROVING_POLL_INTERVAL_SECS", String::from("30")),
+        ]
+    );
+    assert!(Cli::try_parse_from(["shadow-proving"]).unwrap().overrides.vars().is_empty());
+    assert!(Cli::try_parse_from(["shadow-proving", "--max-txn", "many"]).is_err());
+}
+
+#[test]
+fn test_json_log_format() {
+    let record = |message: &str| {
+        let mut line = Vec::new();
+        json_log_format(
+            &mut line,
+            &mut DeferredNow::new(),
+            &Record::builder()
+                .args(format_args!("{}", message))
+                .level(log::Level::Warn)
+                .target("shadow_proving::shadow_rollup")
+                .build(),
+        )
+        .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&line).unwrap()
+    };
+
+    let line = record("sync batch of 7, header version = 1");
+    assert_eq!(line["level"], "WARN");
+    assert_eq!(line["target"], "shadow_proving::shadow_rollup");
+    assert_eq!(line["message"], "sync batch of 7, header version = 1");
+    assert_eq!(line["batch_index"], 7);
+    assert!(line["timestamp"].as_str().unwrap().contains('T'));
+    assert!(record("no batch to prove").get("batch_index").is_none());
+
+    assert_eq!(log_batch_index("batch 12 abandoned after 2 prove attempts"), Some(12));
+    assert_eq!(log_batch_index("Start prove batch of: 12, blocks.len = 3"), Some(12));
+    assert_eq!(log_batch_index("batch_index = 12"), Some(12));
+    assert_eq!(log_batch_index("decode batch header version 1 of batch 12"), Some(12));
+    assert_eq!(log_batch_index("batch header version 1"), None);
+}
+
+#[test]
+fn test_log_rotation() {
+    assert!(matches!(log_rotation("off", 100), Criterion::Size(100)));
+    assert!(matches!(log_rotation("day", 100), Criterion::AgeOrSize(Age::Day, 100)));
+    assert!(matches!(log_rotation("hour", 100), Criterion::AgeOrSize(Age::Hour, 100)));
+    assert!(matches!(log_rotation("weekly", 100), Criterion::Size(100)));
+}
@@ -0,0 +1,134 @@
+//! Forced proving of a batch, served at `POST /prove/{index}`.
+//!
+//! During an incident an operator can commit and prove a given batch without waiting for the
+//! loop to select it. The endpoint is off until `SHADOW_PROVING_ADMIN_TOKEN` is set, and then
+//! requires it as `Authorization: Bearer <token>`. The requests are served one at a time by a
+//! task of the daemon; the response tells the commit and prove transactions, or why the batch
+//! was not proven.
+
+use alloy::primitives::TxHash;
+use axum::http::StatusCode;
+use serde::Serialize;
+use tokio::sync::{mpsc, oneshot};
+
+/// Forced prove requests waiting to be served, beyond which the endpoint answers busy.
+const QUEUE_SIZE: usize = 4;
+
+/// The outcome of a forced prove.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ProveResponse {
+    pub batch_index: u64,
+    /// `proven`, `already_proven`, or the outcome of the commit that did not go through.
+    pub result: String,
+    pub reason: Option<String>,
+    /// The `commitBatch` transaction, unless the batch was committed before.
+    pub commit_tx_hash: Option<TxHash>,
+    pub prove_tx_hash: Option<TxHash>,
+}
+
+/// A forced prove of `batch_index`, answered on `reply`.
+#[derive(Debug)]
+pub struct ProveRequest {
+    pub batch_index: u64,
+    pub reply: oneshot::Sender<Result<ProveResponse, String>>,
+}
+
+/// The endpoint side, handing the requests over to the task serving them.
+#[derive(Clone, Debug)]
+pub struct ManualProve {
+    token: Option<String>,
+    requests: mpsc::Sender<ProveRequest>,
+}
+
+/// The endpoint, guarded by `token`, and the requests it receives.
+pub fn channel(token: Option<String>) -> (ManualProve, mpsc::Receiver<ProveRequest>) {
+    let (requests, received) = mpsc::channel(QUEUE_SIZE);
+    (ManualProve { token: token.filter(|t| !t.is_empty()), requests }, received)
+}
+
+impl ManualProve {
+    /// Check the `Authorization` header of a request.
+    pub fn authorize(&self, authorization: Option<&str>) -> Result<(), (StatusCode, String)> {
+        let Some(token) = &self.token else {
+            return Err((
+                StatusCode::NOT_FOUND,
+                String::from("SHADOW_PROVING_ADMIN_TOKEN is not set"),
+            ));
+        };
+        let bearer = authorization.and_then(|value| value.strip_prefix("Bearer "));
+        if !bearer.is_some_and(|bearer| constant_time_eq(bearer.as_bytes(), token.as_bytes())) {
+            return Err((StatusCode::UNAUTHORIZED, String::from("invalid bearer token")));
+        }
+        Ok(())
+    }
+
+    /// Commit and prove `batch_index`, waiting for the outcome.
+    pub async fn prove(&self, batch_index: u64) -> Result<ProveResponse, (StatusCode, String)> {
+        let (reply, response) = oneshot::channel();
+        let request = ProveRequest { batch_index, reply };
+        self.requests.try_send(request).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => {
+                (StatusCode::TOO_MANY_REQUESTS, String::from("forced proves are queued already"))
+            }
+            mpsc::error::TrySendError::Closed(_) => {
+                (StatusCode::SERVICE_UNAVAILABLE, String::from("forced proves are not served"))
+            }
+        })?;
+        match response.await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+            Err(_) => Err((StatusCode::SERVICE_UNAVAILABLE, String::from("forced prove dropped"))),
+        }
+    }
+}
+
+// The token is not leaked by the time a comparison takes.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[test]
+fn test_authorize() {
+    let (manual_prove, _requests) = channel(None);
+    assert_eq!(manual_prove.authorize(Some("Bearer ")).unwrap_err().0, StatusCode::NOT_FOUND);
+
+    let (manual_prove, _requests) = channel(Some(String::from("s3cret")));
+    assert!(manual_prove.authorize(Some("Bearer s3cret")).is_ok());
+    for authorization in [None, Some("s3cret"), Some("Bearer s3cre"), Some("Bearer s3cret2")] {
+        assert_eq!(
+            manual_prove.authorize(authorization).unwrap_err().0,
+            StatusCode::UNAUTHORIZED,
+            "{:?}",
+            authorization
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_prove() {
+    let (manual_prove, mut requests) = channel(Some(String::from("s3cret")));
+    tokio::spawn(async move {
+        while let Some(request) = requests.recv().await {
+            let response = match request.batch_index {
+                7 => Ok(ProveResponse {
+                    batch_index: 7,
+                    result: String::from("proven"),
+                    ..ProveResponse::default()
+                }),
+                _ => Err(String::from("batch not found")),
+            };
+            let _ = request.reply.send(response);
+        }
+    });
+
+    assert_eq!(manual_prove.prove(7).await.unwrap().result, "proven");
+    assert_eq!(
+        manual_prove.prove(8).await.unwrap_err(),
+        (StatusCode::INTERNAL_SERVER_ERROR, String::from("batch not found"))
+    );
+
+    // Not served, e.g. by a one-shot command.
+    let (manual_prove, requests) = channel(Some(String::from("s3cret")));
+    drop(requests);
+    assert_eq!(manual_prove.prove(7).await.unwrap_err().0, StatusCode::SERVICE_UNAVAILABLE);
+}
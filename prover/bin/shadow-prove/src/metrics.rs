@@ -1,5 +1,9 @@
-use once_cell::sync::Lazy;
-use prometheus::{Gauge, IntGauge, Registry};
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus::{
+    core::Collector, Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::{net::UdpSocket, time::Duration};
 
 pub struct Metrics {
     pub shadow_batch_index: IntGauge,
@@ -7,6 +11,68 @@ pub struct Metrics {
     pub shadow_txn_len: IntGauge,
     pub shadow_verify_result: IntGauge,
     pub shadow_wallet_balance: Gauge,
+    /// Always 1, labelled by the crate `version` and the `git_sha` it was built from.
+    pub shadow_build_info: IntGaugeVec,
+    pub shadow_config_loop_interval_secs: IntGauge,
+    pub shadow_config_max_block: IntGauge,
+    pub shadow_config_max_txn: IntGauge,
+    pub shadow_config_lookback_blocks: IntGauge,
+    pub shadow_config_log_chunk_blocks: IntGauge,
+    pub shadow_config_batch_buffer: IntGauge,
+    pub shadow_config_confirmations: IntGauge,
+    pub shadow_config_catchup_max_batches: IntGauge,
+    pub shadow_loop_iterations_total: IntCounter,
+    pub shadow_last_tick_timestamp: IntGauge,
+    pub shadow_last_iteration_timestamp: IntGauge,
+    pub shadow_withdrawal_root_mismatch_total: IntCounter,
+    pub shadow_state_root_chain_break_total: IntCounter,
+    pub shadow_unexpected_sequencer_total: IntCounter,
+    pub shadow_blob_hash_mismatch_total: IntCounter,
+    pub shadow_inflight_commits: IntGauge,
+    pub shadow_dead_letter_count: IntGauge,
+    pub shadow_empty_batch_total: IntCounter,
+    pub shadow_no_logs_total: IntCounter,
+    pub shadow_too_few_logs_total: IntCounter,
+    pub shadow_too_many_blocks_total: IntCounter,
+    pub shadow_too_many_txn_total: IntCounter,
+    pub shadow_already_proven_total: IntCounter,
+    /// Labelled by `outcome`, see [`crate::shadow_rollup::SyncOutcome`].
+    pub shadow_sync_outcomes_total: IntCounterVec,
+    /// Whole sync stage, from the commit logs to the commit receipt.
+    pub shadow_sync_duration_seconds: Histogram,
+    /// From the `commitBatch` send to its receipt, replacements included.
+    pub shadow_commit_duration_seconds: Histogram,
+    /// Labelled by `size_class`, see [`crate::size_class`].
+    pub shadow_prove_duration_seconds: HistogramVec,
+    pub shadow_prove_success_total: IntCounterVec,
+    pub shadow_prove_failure_total: IntCounterVec,
+    pub shadow_proof_verification_failed_total: IntCounter,
+    pub shadow_exec_error_total: IntCounter,
+    pub shadow_commit_failures_total: IntCounter,
+    pub shadow_commit_simulation_reverted_total: IntCounter,
+    pub shadow_commit_receipt_failures_total: IntCounter,
+    pub shadow_commit_replacements_total: IntCounter,
+    pub shadow_receipts_sent_total: IntCounter,
+    pub shadow_receipts_dropped_total: IntCounter,
+    pub shadow_alerts_sent_total: IntCounter,
+    pub shadow_alerts_dropped_total: IntCounter,
+    pub shadow_prove_failures_total: IntCounter,
+    /// Labelled by `stage` and `severity`, see [`crate::failure`].
+    pub shadow_stage_alerts_total: IntCounterVec,
+    /// Labelled by `stage` and `kind`, see [`crate::error::ShadowError`].
+    pub shadow_stage_errors_total: IntCounterVec,
+    pub shadow_rpc_rate_limited_total: IntCounter,
+    /// Labelled by `provider`, `l1`, `l2` or `verify`, and `method`, see [`rpc_error`].
+    pub shadow_rpc_errors_total: IntCounterVec,
+    /// Labelled by `provider`, see [`crate::failover`].
+    pub shadow_rpc_failovers_total: IntCounterVec,
+    /// Labelled by `provider`.
+    pub shadow_rpc_timeouts_total: IntCounterVec,
+    pub shadow_stale_node_total: IntCounter,
+    pub shadow_rollup_paused: IntGauge,
+    pub shadow_circuit_open: IntGauge,
+    pub shadow_loop_backoff: IntGauge,
+    pub shadow_batch_challenge_remaining_secs: IntGauge,
 }
 
 pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
@@ -21,4 +87,419 @@ pub static METRICS: Lazy<Metrics> = Lazy::new(|| Metrics {
         .expect("shadow_verify_result metric can be created"),
     shadow_wallet_balance: Gauge::new("shadow_wallet_balance", "shadow wallet balance")
         .expect("shadow_wallet_balance metric can be created"),
+    shadow_build_info: IntGaugeVec::new(
+        Opts::new("shadow_build_info", "build of the running prover"),
+        &["version", "git_sha"],
+    )
+    .expect("shadow_build_info metric can be created"),
+    shadow_config_loop_interval_secs: IntGauge::new(
+        "shadow_config_loop_interval_secs",
+        "configured loop interval in seconds",
+    )
+    .expect("shadow_config_loop_interval_secs metric can be created"),
+    shadow_config_max_block: IntGauge::new("shadow_config_max_block", "configured max blocks")
+        .expect("shadow_config_max_block metric can be created"),
+    shadow_config_max_txn: IntGauge::new("shadow_config_max_txn", "configured max txn")
+        .expect("shadow_config_max_txn metric can be created"),
+    shadow_config_lookback_blocks: IntGauge::new(
+        "shadow_config_lookback_blocks",
+        "configured commit log lookback blocks",
+    )
+    .expect("shadow_config_lookback_blocks metric can be created"),
+    shadow_config_log_chunk_blocks: IntGauge::new(
+        "shadow_config_log_chunk_blocks",
+        "configured blocks of a commit log request, 0 for one request",
+    )
+    .expect("shadow_config_log_chunk_blocks metric can be created"),
+    shadow_config_batch_buffer: IntGauge::new(
+        "shadow_config_batch_buffer",
+        "configured most recent committed batches skipped by the selection",
+    )
+    .expect("shadow_config_batch_buffer metric can be created"),
+    shadow_config_confirmations: IntGauge::new(
+        "shadow_config_confirmations",
+        "configured blocks a commit log is buried under before its batch is selected",
+    )
+    .expect("shadow_config_confirmations metric can be created"),
+    shadow_config_catchup_max_batches: IntGauge::new(
+        "shadow_config_catchup_max_batches",
+        "configured max batches caught up on per tick",
+    )
+    .expect("shadow_config_catchup_max_batches metric can be created"),
+    shadow_loop_iterations_total: IntCounter::new(
+        "shadow_loop_iterations_total",
+        "shadow main loop iterations",
+    )
+    .expect("shadow_loop_iterations_total metric can be created"),
+    shadow_last_tick_timestamp: IntGauge::new(
+        "shadow_last_tick_timestamp",
+        "unix time of the last main loop iteration",
+    )
+    .expect("shadow_last_tick_timestamp metric can be created"),
+    shadow_last_iteration_timestamp: IntGauge::new(
+        "shadow_last_iteration_timestamp",
+        "unix time the last main loop iteration started, stale when the loop is wedged",
+    )
+    .expect("shadow_last_iteration_timestamp metric can be created"),
+    shadow_withdrawal_root_mismatch_total: IntCounter::new(
+        "shadow_withdrawal_root_mismatch_total",
+        "batches whose withdrawal root differs from L2 state",
+    )
+    .expect("shadow_withdrawal_root_mismatch_total metric can be created"),
+    shadow_state_root_chain_break_total: IntCounter::new(
+        "shadow_state_root_chain_break_total",
+        "batches whose prevStateRoot differs from the previous proven postStateRoot",
+    )
+    .expect("shadow_state_root_chain_break_total metric can be created"),
+    shadow_unexpected_sequencer_total: IntCounter::new(
+        "shadow_unexpected_sequencer_total",
+        "batches whose commit transaction was sent by an address off the sequencer allowlist",
+    )
+    .expect("shadow_unexpected_sequencer_total metric can be created"),
+    shadow_blob_hash_mismatch_total: IntCounter::new(
+        "shadow_blob_hash_mismatch_total",
+        "batches whose blob versioned hash matches no blob of their commit transaction",
+    )
+    .expect("shadow_blob_hash_mismatch_total metric can be created"),
+    shadow_inflight_commits: IntGauge::new(
+        "shadow_inflight_commits",
+        "commitBatch transactions sent and not yet confirmed",
+    )
+    .expect("shadow_inflight_commits metric can be created"),
+    shadow_dead_letter_count: IntGauge::new(
+        "shadow_dead_letter_count",
+        "batches abandoned after exhausting their prove attempts",
+    )
+    .expect("shadow_dead_letter_count metric can be created"),
+    shadow_empty_batch_total: IntCounter::new(
+        "shadow_empty_batch_total",
+        "committed batches with an empty block range",
+    )
+    .expect("shadow_empty_batch_total metric can be created"),
+    shadow_no_logs_total: IntCounter::new(
+        "shadow_no_logs_total",
+        "sync ticks without commit_batch logs in the lookback window",
+    )
+    .expect("shadow_no_logs_total metric can be created"),
+    shadow_too_few_logs_total: IntCounter::new(
+        "shadow_too_few_logs_total",
+        "sync ticks with too few commit_batch logs in the lookback window",
+    )
+    .expect("shadow_too_few_logs_total metric can be created"),
+    shadow_too_many_blocks_total: IntCounter::new(
+        "shadow_too_many_blocks_total",
+        "batches skipped for too many blocks",
+    )
+    .expect("shadow_too_many_blocks_total metric can be created"),
+    shadow_too_many_txn_total: IntCounter::new(
+        "shadow_too_many_txn_total",
+        "batches skipped for too many txn",
+    )
+    .expect("shadow_too_many_txn_total metric can be created"),
+    shadow_already_proven_total: IntCounter::new(
+        "shadow_already_proven_total",
+        "batches skipped as already proven",
+    )
+    .expect("shadow_already_proven_total metric can be created"),
+    shadow_sync_duration_seconds: Histogram::with_opts(
+        HistogramOpts::new("shadow_sync_duration_seconds", "duration of a batch sync")
+            .buckets(vec![1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0]),
+    )
+    .expect("shadow_sync_duration_seconds metric can be created"),
+    shadow_commit_duration_seconds: Histogram::with_opts(
+        HistogramOpts::new(
+            "shadow_commit_duration_seconds",
+            "duration from the commitBatch send to its receipt",
+        )
+        .buckets(vec![5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0]),
+    )
+    .expect("shadow_commit_duration_seconds metric can be created"),
+    shadow_prove_duration_seconds: HistogramVec::new(
+        HistogramOpts::new("shadow_prove_duration_seconds", "duration of a batch prove")
+            .buckets(vec![5.0, 15.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0, 14400.0]),
+        &["size_class"],
+    )
+    .expect("shadow_prove_duration_seconds metric can be created"),
+    shadow_prove_success_total: IntCounterVec::new(
+        Opts::new("shadow_prove_success_total", "batches proven on the shadow rollup"),
+        &["size_class"],
+    )
+    .expect("shadow_prove_success_total metric can be created"),
+    shadow_prove_failure_total: IntCounterVec::new(
+        Opts::new(
+            "shadow_prove_failure_total",
+            "batches abandoned after exhausting their prove attempts",
+        ),
+        &["size_class"],
+    )
+    .expect("shadow_prove_failure_total metric can be created"),
+    shadow_proof_verification_failed_total: IntCounter::new(
+        "shadow_proof_verification_failed_total",
+        "proofs rejected by the on-chain verifier",
+    )
+    .expect("shadow_proof_verification_failed_total metric can be created"),
+    shadow_exec_error_total: IntCounter::new(
+        "shadow_exec_error_total",
+        "errors of the sync & prove loop",
+    )
+    .expect("shadow_exec_error_total metric can be created"),
+    shadow_commit_failures_total: IntCounter::new(
+        "shadow_commit_failures_total",
+        "failed commit stages of the loop",
+    )
+    .expect("shadow_commit_failures_total metric can be created"),
+    shadow_commit_simulation_reverted_total: IntCounter::new(
+        "shadow_commit_simulation_reverted_total",
+        "commitBatch sends skipped because their simulation reverted",
+    )
+    .expect("shadow_commit_simulation_reverted_total metric can be created"),
+    shadow_commit_receipt_failures_total: IntCounter::new(
+        "shadow_commit_receipt_failures_total",
+        "commitBatch sends whose receipt could not be fetched in time",
+    )
+    .expect("shadow_commit_receipt_failures_total metric can be created"),
+    shadow_commit_replacements_total: IntCounter::new(
+        "shadow_commit_replacements_total",
+        "commitBatch txs replaced with bumped fees after no receipt in time",
+    )
+    .expect("shadow_commit_replacements_total metric can be created"),
+    shadow_receipts_sent_total: IntCounter::new(
+        "shadow_receipts_sent_total",
+        "batch receipts delivered to the webhook",
+    )
+    .expect("shadow_receipts_sent_total metric can be created"),
+    shadow_receipts_dropped_total: IntCounter::new(
+        "shadow_receipts_dropped_total",
+        "batch receipts dropped on a full queue or after all retries",
+    )
+    .expect("shadow_receipts_dropped_total metric can be created"),
+    shadow_alerts_sent_total: IntCounter::new(
+        "shadow_alerts_sent_total",
+        "stage failures posted to the alert webhook",
+    )
+    .expect("shadow_alerts_sent_total metric can be created"),
+    shadow_alerts_dropped_total: IntCounter::new(
+        "shadow_alerts_dropped_total",
+        "stage failure alerts dropped on a full queue or a failed post",
+    )
+    .expect("shadow_alerts_dropped_total metric can be created"),
+    shadow_prove_failures_total: IntCounter::new(
+        "shadow_prove_failures_total",
+        "failed prove stages of the loop",
+    )
+    .expect("shadow_prove_failures_total metric can be created"),
+    shadow_sync_outcomes_total: IntCounterVec::new(
+        Opts::new("shadow_sync_outcomes_total", "sync stages by outcome"),
+        &["outcome"],
+    )
+    .expect("shadow_sync_outcomes_total metric can be created"),
+    shadow_stage_alerts_total: IntCounterVec::new(
+        Opts::new("shadow_stage_alerts_total", "stages failing beyond their retries"),
+        &["stage", "severity"],
+    )
+    .expect("shadow_stage_alerts_total metric can be created"),
+    shadow_stage_errors_total: IntCounterVec::new(
+        Opts::new("shadow_stage_errors_total", "failed stages by error kind"),
+        &["stage", "kind"],
+    )
+    .expect("shadow_stage_errors_total metric can be created"),
+    shadow_rpc_rate_limited_total: IntCounter::new(
+        "shadow_rpc_rate_limited_total",
+        "rpc requests rejected by provider rate limiting",
+    )
+    .expect("shadow_rpc_rate_limited_total metric can be created"),
+    shadow_rpc_errors_total: IntCounterVec::new(
+        Opts::new("shadow_rpc_errors_total", "rpc requests failed, retries exhausted"),
+        &["provider", "method"],
+    )
+    .expect("shadow_rpc_errors_total metric can be created"),
+    shadow_rpc_failovers_total: IntCounterVec::new(
+        Opts::new("shadow_rpc_failovers_total", "rotations to the next rpc endpoint"),
+        &["provider"],
+    )
+    .expect("shadow_rpc_failovers_total metric can be created"),
+    shadow_rpc_timeouts_total: IntCounterVec::new(
+        Opts::new("shadow_rpc_timeouts_total", "rpc requests timed out"),
+        &["provider"],
+    )
+    .expect("shadow_rpc_timeouts_total metric can be created"),
+    shadow_stale_node_total: IntCounter::new(
+        "shadow_stale_node_total",
+        "ticks skipped on a latest block behind one seen before",
+    )
+    .expect("shadow_stale_node_total metric can be created"),
+    shadow_rollup_paused: IntGauge::new("shadow_rollup_paused", "shadow rollup is paused")
+        .expect("shadow_rollup_paused metric can be created"),
+    shadow_circuit_open: IntGauge::new(
+        "shadow_circuit_open",
+        "commits are not sent after repeated reverts",
+    )
+    .expect("shadow_circuit_open metric can be created"),
+    shadow_loop_backoff: IntGauge::new(
+        "shadow_loop_backoff",
+        "loops ticking every SHADOW_PROVING_LOOP_BACKOFF_SECS after repeated failed ticks",
+    )
+    .expect("shadow_loop_backoff metric can be created"),
+    shadow_batch_challenge_remaining_secs: IntGauge::new(
+        "shadow_batch_challenge_remaining_secs",
+        "seconds until the latest proven batch leaves the challenge window",
+    )
+    .expect("shadow_batch_challenge_remaining_secs metric can be created"),
 });
+
+/// The registered and process metrics, in the Prometheus text format.
+pub fn encode() -> String {
+    let mut metric_families = REGISTRY.gather();
+    metric_families.extend(prometheus::gather());
+
+    let mut buffer = Vec::new();
+    match TextEncoder::new().encode(&metric_families, &mut buffer) {
+        Ok(()) => String::from_utf8(buffer).unwrap(),
+        Err(e) => {
+            log::error!("encode metrics error: {:#?}", e);
+            String::new()
+        }
+    }
+}
+
+/// Push the metrics to the Pushgateway at `url`, replacing the ones of the previous push; for a
+/// process that is gone before it is scraped.
+pub async fn push(url: &str) -> Result<(), anyhow::Error> {
+    let url = format!("{}/metrics/job/shadow_proving", url.trim_end_matches('/'));
+    reqwest::Client::new()
+        .put(&url)
+        .timeout(Duration::from_secs(10))
+        .body(encode())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Optional StatsD/DogStatsD mirror of the Prometheus metrics updated through [`set`], [`inc`]
+/// and [`observe_duration`].
+static STATSD: OnceCell<StatsdSink> = OnceCell::new();
+
+/// Mirror metric updates to the StatsD daemon at `address`, in addition to Prometheus.
+pub fn init_statsd(address: &str) -> Result<(), anyhow::Error> {
+    let sink = StatsdSink::new(address)?;
+    STATSD.set(sink).map_err(|_| anyhow::anyhow!("statsd sink is already initialized"))
+}
+
+/// Set a gauge.
+pub fn set(gauge: &IntGauge, value: i64) {
+    gauge.set(value);
+    statsd(gauge, |name| format!("{}:{}|g", name, value));
+}
+
+/// Increment a counter.
+pub fn inc(counter: &IntCounter) {
+    counter.inc();
+    statsd(counter, |name| format!("{}:1|c", name));
+}
+
+/// Count a failed `method` request to the `provider` RPC: `l1` the rollup is read from, `l2` or
+/// `verify`, the L1 of the shadow rollup. A reverted call is an answer, not a failure.
+pub fn rpc_error(provider: &str, method: &str) {
+    inc(&METRICS.shadow_rpc_errors_total.with_label_values(&[provider, method]));
+}
+
+/// Record a duration, as seconds in Prometheus and as a millisecond timer in StatsD.
+pub fn observe_duration(histogram: &Histogram, duration: Duration) {
+    histogram.observe(duration.as_secs_f64());
+    statsd(histogram, |name| format!("{}:{}|ms", name, duration.as_millis()));
+}
+
+fn statsd(metric: &impl Collector, packet: impl FnOnce(&str) -> String) {
+    if let Some(sink) = STATSD.get() {
+        if let Some(desc) = metric.desc().first() {
+            sink.send(&packet(&desc.fq_name));
+        }
+    }
+}
+
+/// Fire-and-forget UDP client of a StatsD daemon.
+#[derive(Debug)]
+pub struct StatsdSink {
+    socket: UdpSocket,
+}
+
+impl StatsdSink {
+    pub fn new(address: &str) -> Result<Self, anyhow::Error> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket })
+    }
+
+    /// Losing a packet must never affect proving, so errors are only logged.
+    pub fn send(&self, packet: &str) {
+        if let Err(e) = self.socket.send(packet.as_bytes()) {
+            log::debug!("send statsd packet {:?} error: {}", packet, e);
+        }
+    }
+}
+
+#[test]
+fn test_statsd_sink() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    listener.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    init_statsd(&listener.local_addr().unwrap().to_string()).unwrap();
+
+    set(&METRICS.shadow_batch_index, 42);
+    inc(&METRICS.shadow_prove_success_total.with_label_values(&["small"]));
+    observe_duration(
+        &METRICS.shadow_prove_duration_seconds.with_label_values(&["small"]),
+        Duration::from_millis(1500),
+    );
+    assert_eq!(METRICS.shadow_batch_index.get(), 42);
+
+    // Other tests may update metrics concurrently, look for ours among the packets.
+    let mut expected = vec![
+        "shadow_batch_index:42|g",
+        "shadow_prove_success_total:1|c",
+        "shadow_prove_duration_seconds:1500|ms",
+    ];
+    let mut buf = [0u8; 512];
+    while !expected.is_empty() {
+        let len = listener.recv(&mut buf).expect("statsd packet");
+        let packet = std::str::from_utf8(&buf[..len]).unwrap();
+        expected.retain(|p| *p != packet);
+    }
+}
+
+#[tokio::test]
+async fn test_push() {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let gateway = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        // Read the head and the body it announces before answering.
+        loop {
+            let len = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..len]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            let Some((head, body)) = text.split_once("\r\n\r\n") else { continue };
+            let content_length = head
+                .lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length: ")?.parse().ok())
+                .unwrap_or(0usize);
+            if len == 0 || body.len() >= content_length {
+                break;
+            }
+        }
+        stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").unwrap();
+        String::from_utf8_lossy(&request).to_string()
+    });
+
+    push(&url).await.unwrap();
+    let request = gateway.join().unwrap();
+    assert!(request.starts_with("PUT /metrics/job/shadow_proving HTTP/1.1"), "{}", request);
+}
@@ -0,0 +1,186 @@
+//! Minimal JSON-RPC endpoint for exercising provider and contract calls in tests.
+
+use crate::{abi::Rollup, failover::FailoverTransport};
+use alloy::{
+    primitives::{Address, Bloom, Bytes, FixedBytes, B256, U256},
+    providers::RootProvider,
+    sol_types::{SolCall, SolValue},
+};
+use axum::{routing::post, Json, Router};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+type Handler = dyn Fn(&str, &Value) -> Option<Value> + Send + Sync;
+
+/// JSON-RPC server answering each request through a handler, recording every request received.
+///
+/// The handler gets the method and params and returns the `result`; `None` answers with a
+/// JSON-RPC error, which is how a missing contract getter shows up to the caller, [`error`] with
+/// a given error and [`revert`] with a revert carrying data.
+#[derive(Clone)]
+pub struct MockRpc {
+    pub url: String,
+    requests: Arc<Mutex<Vec<(String, Value)>>>,
+}
+
+impl MockRpc {
+    pub fn start(handler: impl Fn(&str, &Value) -> Option<Value> + Send + Sync + 'static) -> Self {
+        let handler: Arc<Handler> = Arc::new(handler);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<Value>| async move {
+                let method = request["method"].as_str().unwrap_or_default().to_string();
+                let params = request["params"].clone();
+                recorded.lock().unwrap().push((method.clone(), params.clone()));
+                let response = match handler(&method, &params) {
+                    Some(result) if result.get(ERROR_KEY).is_some() => json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "error": result[ERROR_KEY],
+                    }),
+                    Some(result) => {
+                        json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+                    }
+                    None => json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "error": { "code": -32000, "message": "execution reverted" },
+                    }),
+                };
+                Json(response)
+            }),
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+        Self { url, requests }
+    }
+
+    pub fn provider(&self) -> RootProvider<FailoverTransport> {
+        FailoverTransport::new("l1", &self.url, 1).unwrap().into_provider()
+    }
+
+    /// Requests received so far for the given method.
+    pub fn requests(&self, method: &str) -> Vec<Value> {
+        let requests = self.requests.lock().unwrap();
+        requests.iter().filter(|(m, _)| m == method).map(|(_, params)| params.clone()).collect()
+    }
+}
+
+const ERROR_KEY: &str = "__mock_rpc_error";
+
+/// JSON-RPC error with `code` and `message`, e.g. a provider throttling.
+pub fn error(code: i64, message: &str) -> Option<Value> {
+    Some(json!({ ERROR_KEY: { "code": code, "message": message } }))
+}
+
+/// Revert with ABI-encoded `data`, e.g. a custom error, as answered to `eth_call` or
+/// `eth_sendTransaction`.
+pub fn revert(data: Vec<u8>) -> Option<Value> {
+    Some(json!({
+        ERROR_KEY: {
+            "code": 3,
+            "message": "execution reverted",
+            "data": alloy::hex::encode_prefixed(data),
+        }
+    }))
+}
+
+/// Function selector of an `eth_call` request.
+pub fn call_selector(params: &Value) -> FixedBytes<4> {
+    let tx = &params[0];
+    let input = tx["input"].as_str().or_else(|| tx["data"].as_str()).unwrap_or_default();
+    let input: Bytes = input.parse().unwrap_or_default();
+    FixedBytes::from_slice(input.get(..4).unwrap_or(&[0u8; 4]))
+}
+
+/// Hex-encode ABI return data as an `eth_call` result.
+pub fn call_result(data: Vec<u8>) -> Option<Value> {
+    Some(json!(alloy::hex::encode_prefixed(data)))
+}
+
+/// `CommitBatch` logs as an `eth_getLogs` result: batches 1 to 20, batch n committed at
+/// `block(n)`, those within the requested `fromBlock..=toBlock`.
+pub fn commit_logs(params: &Value, block: impl Fn(u64) -> u64) -> Option<Value> {
+    let param = |key: &str| {
+        let hex = params[0][key].as_str().unwrap().trim_start_matches("0x");
+        u64::from_str_radix(hex, 16).unwrap()
+    };
+    let range = param("fromBlock")..=param("toBlock");
+    Some(json!((1u64..=20)
+        .filter(|index| range.contains(&block(*index)))
+        .map(|index| json!({
+            "address": Address::ZERO,
+            "topics": [B256::ZERO, B256::from(U256::from(index).to_be_bytes::<32>())],
+            "data": "0x",
+            "blockNumber": format!("{:#x}", block(index)),
+            "transactionHash": B256::from(U256::from(index).to_be_bytes::<32>()),
+            "logIndex": "0x0",
+            "removed": false,
+        }))
+        .collect::<Vec<_>>()))
+}
+
+/// Rollup `batchDataStore` as an `eth_call` result: batch n ends at L2 block `last_block(n)`,
+/// reverting for `None`.
+pub fn batch_data_store(params: &Value, last_block: impl Fn(u64) -> Option<u64>) -> Option<Value> {
+    let tx = &params[0];
+    let input = tx["input"].as_str().or_else(|| tx["data"].as_str()).unwrap_or_default();
+    let call =
+        Rollup::batchDataStoreCall::abi_decode(&input.parse::<Bytes>().unwrap(), false).unwrap();
+    match last_block(call.batchIndex.to()) {
+        Some(block) => {
+            call_result((U256::ZERO, U256::ZERO, U256::from(block), U256::ZERO).abi_encode())
+        }
+        None => revert(Vec::new()),
+    }
+}
+
+/// Mined EIP-1559 transaction as an `eth_getTransactionByHash` result.
+pub fn transaction(tx_hash: B256, from: Address, input: Bytes) -> Option<Value> {
+    Some(json!({
+        "hash": tx_hash,
+        "nonce": "0x0",
+        "blockHash": B256::repeat_byte(0xbb),
+        "blockNumber": "0x1",
+        "transactionIndex": "0x0",
+        "from": from,
+        "to": Address::ZERO,
+        "value": "0x0",
+        "gas": "0x5208",
+        "gasPrice": "0x1",
+        "maxFeePerGas": "0x1",
+        "maxPriorityFeePerGas": "0x1",
+        "input": input,
+        "accessList": [],
+        "chainId": "0x1",
+        "type": "0x2",
+        "v": "0x0",
+        "yParity": "0x0",
+        "r": "0x1",
+        "s": "0x1",
+    }))
+}
+
+/// Receipt of a mined transaction as an `eth_getTransactionReceipt` result.
+pub fn receipt(tx_hash: B256, status: bool) -> Option<Value> {
+    Some(json!({
+        "transactionHash": tx_hash,
+        "transactionIndex": "0x0",
+        "blockHash": B256::repeat_byte(0xbb),
+        "blockNumber": "0x1",
+        "from": Address::ZERO,
+        "to": Address::ZERO,
+        "contractAddress": null,
+        "cumulativeGasUsed": "0x5208",
+        "gasUsed": "0x5208",
+        "effectiveGasPrice": "0x1",
+        "logs": [],
+        "logsBloom": Bloom::ZERO,
+        "type": "0x2",
+        "status": if status { "0x1" } else { "0x0" },
+    }))
+}
@@ -0,0 +1,107 @@
+use crate::{
+    abi::Pausable,
+    failover::FailoverTransport,
+    metrics::{self, METRICS},
+};
+use alloy::{primitives::Address, providers::RootProvider};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Interval to repeat the "paused" log while the shadow rollup stays paused.
+const PAUSED_LOG_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Pause state of the shadow rollup, checked before each tick so no commit/prove is sent while
+/// every one of them would revert. A rollup without `paused()` is never paused.
+#[derive(Clone, Debug)]
+pub struct PauseCheck {
+    shadow_rollup: Pausable::PausableInstance<FailoverTransport, RootProvider<FailoverTransport>>,
+    paused_logged_at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl PauseCheck {
+    pub fn new(
+        shadow_rollup_address: Address,
+        l1_provider: RootProvider<FailoverTransport>,
+    ) -> Self {
+        let shadow_rollup = Pausable::new(shadow_rollup_address, l1_provider);
+        Self { shadow_rollup, paused_logged_at: Arc::new(Mutex::new(None)) }
+    }
+
+    pub async fn is_paused(&self) -> bool {
+        let paused = match self.shadow_rollup.paused().call().await {
+            Ok(paused) => paused._0,
+            Err(e) => {
+                log::debug!("shadow_rollup.paused unavailable, treat as unpaused: {}", e);
+                false
+            }
+        };
+        metrics::set(&METRICS.shadow_rollup_paused, paused as i64);
+
+        let mut logged_at = self.paused_logged_at.lock().unwrap();
+        if paused {
+            if logged_at.map_or(true, |at| at.elapsed() >= PAUSED_LOG_INTERVAL) {
+                log::info!("shadow rollup is paused, skip sync & prove until it is unpaused");
+                *logged_at = Some(Instant::now());
+            }
+        } else if logged_at.take().is_some() {
+            log::info!("shadow rollup is unpaused, resume sync & prove");
+        }
+        paused
+    }
+}
+
+#[tokio::test]
+async fn test_pause_check() {
+    use crate::mock_rpc::{call_result, call_selector, MockRpc};
+    use alloy::sol_types::{SolCall, SolValue};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let paused = Arc::new(AtomicBool::new(true));
+    let state = paused.clone();
+    let rpc = MockRpc::start(move |method, params| match method {
+        "eth_call" if call_selector(params) == Pausable::pausedCall::SELECTOR => {
+            call_result(state.load(Ordering::SeqCst).abi_encode())
+        }
+        _ => None,
+    });
+    let check = PauseCheck::new(Address::ZERO, rpc.provider());
+
+    assert!(check.is_paused().await);
+    assert!(check.is_paused().await);
+    paused.store(false, Ordering::SeqCst);
+    // Resumes automatically once unpaused.
+    assert!(!check.is_paused().await);
+    assert_eq!(rpc.requests("eth_call").len(), 3);
+
+    // No paused() getter.
+    let rpc = MockRpc::start(|_, _| None);
+    assert!(!PauseCheck::new(Address::ZERO, rpc.provider()).is_paused().await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_pause_check_log_interval() {
+    use crate::mock_rpc::{call_result, call_selector, MockRpc};
+    use alloy::sol_types::{SolCall, SolValue};
+
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == Pausable::pausedCall::SELECTOR => {
+            call_result(true.abi_encode())
+        }
+        _ => None,
+    });
+    let check = PauseCheck::new(Address::ZERO, rpc.provider());
+
+    // Ticks of 12s: the paused log is repeated once the interval has elapsed.
+    let mut logged = Vec::new();
+    for _ in 0..60 {
+        assert!(check.is_paused().await);
+        logged.push(check.paused_logged_at.lock().unwrap().unwrap());
+        tokio::time::advance(Duration::from_secs(12)).await;
+    }
+    logged.dedup();
+    assert_eq!(logged.len(), 2);
+    assert_eq!(logged[1] - logged[0], Duration::from_secs(600));
+}
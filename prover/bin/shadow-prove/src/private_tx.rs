@@ -0,0 +1,110 @@
+//! Optional submission of the commit/prove transactions through a private relay.
+//!
+//! With `SHADOW_PROVING_PRIVATE_TX_RPC` set (Flashbots Protect or any private RPC accepting
+//! `eth_sendRawTransaction`), signed transactions are sent to the relay instead of the public
+//! mempool; every other request, including nonce, fee and receipt lookups, still goes to the
+//! L1 RPC.
+//!
+//! Confirmation differs from a public send:
+//!
+//! - The transaction is invisible to the L1 RPC until it is included, so there is no pending state
+//!   to observe; the receipt simply appears once mined.
+//! - A relay may drop a transaction it could not include (Flashbots Protect gives up after about 25
+//!   blocks) without reporting an error. Waiting for the receipt is therefore bounded by
+//!   `SHADOW_PROVING_PRIVATE_TX_TIMEOUT_SECS`, after which the send counts as failed and is retried
+//!   by the next iteration.
+//! - Pending nonces are read from the L1 RPC, which does not see relayed transactions, so keep
+//!   `SHADOW_PROVING_MAX_INFLIGHT_COMMITS` at 1.
+
+use crate::failover::FailoverTransport;
+use alloy::{
+    network::Ethereum,
+    providers::{PendingTransactionBuilder, Provider, ProviderBuilder, RootProvider},
+    transports::{
+        http::{Client, Http},
+        TransportResult,
+    },
+};
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// Provider sending raw transactions to the private relay when configured, and everything else
+/// to the underlying L1 provider.
+#[derive(Clone, Debug)]
+pub struct PrivateTxProvider {
+    inner: RootProvider<FailoverTransport>,
+    relay: Option<RootProvider<Http<Client>>>,
+    timeout: Duration,
+}
+
+impl PrivateTxProvider {
+    pub fn new(
+        inner: RootProvider<FailoverTransport>,
+        relay_url: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Self, anyhow::Error> {
+        let relay = match relay_url {
+            Some(url) => Some(ProviderBuilder::new().on_http(url.parse()?)),
+            None => None,
+        };
+        Ok(Self { inner, relay, timeout })
+    }
+}
+
+#[async_trait]
+impl Provider<FailoverTransport> for PrivateTxProvider {
+    fn root(&self) -> &RootProvider<FailoverTransport> {
+        &self.inner
+    }
+
+    async fn send_raw_transaction(
+        &self,
+        encoded_tx: &[u8],
+    ) -> TransportResult<PendingTransactionBuilder<FailoverTransport, Ethereum>> {
+        let relay = match &self.relay {
+            Some(relay) => relay,
+            None => return self.inner.send_raw_transaction(encoded_tx).await,
+        };
+        let rlp_hex = alloy::hex::encode_prefixed(encoded_tx);
+        let tx_hash = relay.client().request("eth_sendRawTransaction", (rlp_hex,)).await?;
+        log::info!("sent tx {:?} through the private relay", tx_hash);
+
+        // Watch for inclusion on the L1 RPC; a dropped tx must not block forever.
+        Ok(PendingTransactionBuilder::new(self.inner.clone(), tx_hash)
+            .with_timeout(Some(self.timeout)))
+    }
+}
+
+#[tokio::test]
+async fn test_private_tx_provider() {
+    use crate::mock_rpc::MockRpc;
+    use alloy::primitives::B256;
+    use serde_json::json;
+
+    let tx_hash = B256::repeat_byte(0x77);
+    let public = MockRpc::start(move |method, _| match method {
+        "eth_sendRawTransaction" => Some(json!(B256::repeat_byte(0x11))),
+        _ => None,
+    });
+    let relay = MockRpc::start(move |method, _| match method {
+        "eth_sendRawTransaction" => Some(json!(tx_hash)),
+        _ => None,
+    });
+    let encoded_tx = [0x02, 0xf8, 0x6e];
+
+    // Relay configured: the raw tx goes there only.
+    let provider =
+        PrivateTxProvider::new(public.provider(), Some(&relay.url), Duration::from_secs(60))
+            .unwrap();
+    let pending = provider.send_raw_transaction(&encoded_tx).await.unwrap();
+    assert_eq!(*pending.tx_hash(), tx_hash);
+    assert_eq!(relay.requests("eth_sendRawTransaction"), vec![json!(["0x02f86e"])]);
+    assert!(public.requests("eth_sendRawTransaction").is_empty());
+
+    // No relay: the public mempool.
+    let provider =
+        PrivateTxProvider::new(public.provider(), None, Duration::from_secs(60)).unwrap();
+    let pending = provider.send_raw_transaction(&encoded_tx).await.unwrap();
+    assert_eq!(*pending.tx_hash(), B256::repeat_byte(0x11));
+    assert_eq!(public.requests("eth_sendRawTransaction").len(), 1);
+}
@@ -0,0 +1,278 @@
+//! Hand-off of committed batches from the sync stage to the prove stage.
+//!
+//! In the default single-process mode both stages run as tasks of the process and share a
+//! [`MemoryQueue`], a channel of `SHADOW_PROVING_QUEUE_SIZE` batches: the sync stage commits the
+//! next batch while the prove stage proves the previous one, and waits for room once the prove
+//! stage falls that far behind. With `SHADOW_PROVING_QUEUE_URL` set, a `syncer` process publishes
+//! the batches it committed to an external queue and any number of `prover` processes consume them,
+//! so the light L1-bound stage and the heavy prover-bound stage scale independently. The URL scheme
+//! selects the backend; `redis://` uses a Redis list named by `SHADOW_PROVING_QUEUE_KEY`.
+//!
+//! Delivery guarantees of the external queue:
+//!
+//! - At most once per consumer: a batch is removed from the list when a prover takes it. If that
+//!   prover dies before the batch is proven, the batch stays committed but unproven on the shadow
+//!   rollup, where the startup catch-up of a syncer picks it up again.
+//! - Possibly more than once overall: a batch re-committed by the syncer is published again.
+//!   Proving is idempotent on chain; a duplicate only costs prover time.
+//! - FIFO order for a single producer; consumers race for the head of the list.
+
+use crate::BatchInfo;
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+
+/// Longest wait of the sync stage for room in the in-process queue. A batch not queued stays
+/// committed but unproven, left to the catch-up.
+const PUSH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Queue of committed batches waiting to be proven.
+#[async_trait]
+pub trait BatchQueue: Send + Sync {
+    async fn push(&self, batch: &BatchInfo) -> Result<(), anyhow::Error>;
+
+    /// Take the oldest batch, waiting up to `timeout` for one to arrive.
+    async fn pop(&self, timeout: Duration) -> Result<Option<BatchInfo>, anyhow::Error>;
+}
+
+/// Build the queue selected by `queue_url`, the in-process queue of `size` batches if none.
+pub fn from_url(
+    queue_url: Option<&str>,
+    key: &str,
+    size: usize,
+) -> Result<Box<dyn BatchQueue>, anyhow::Error> {
+    match queue_url {
+        None => Ok(Box::new(MemoryQueue::new(size))),
+        Some(url) if url.starts_with("redis://") || url.starts_with("rediss://") => {
+            Ok(Box::new(RedisQueue::new(url, key)?))
+        }
+        Some(url) => Err(anyhow!("unsupported batch queue url: {:?}", url)),
+    }
+}
+
+/// In-process queue of the single-process mode, bounded to apply backpressure on the sync stage.
+#[derive(Debug)]
+pub struct MemoryQueue {
+    sender: mpsc::Sender<BatchInfo>,
+    receiver: Mutex<mpsc::Receiver<BatchInfo>>,
+}
+
+impl MemoryQueue {
+    pub fn new(size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(size.max(1));
+        Self { sender, receiver: Mutex::new(receiver) }
+    }
+}
+
+#[async_trait]
+impl BatchQueue for MemoryQueue {
+    async fn push(&self, batch: &BatchInfo) -> Result<(), anyhow::Error> {
+        self.sender
+            .send_timeout(batch.clone(), PUSH_TIMEOUT)
+            .await
+            .map_err(|_| anyhow!("batch queue full, {} not queued for proving", batch))
+    }
+
+    async fn pop(&self, timeout: Duration) -> Result<Option<BatchInfo>, anyhow::Error> {
+        let mut receiver = self.receiver.lock().await;
+        if timeout.is_zero() {
+            return Ok(receiver.try_recv().ok());
+        }
+        Ok(tokio::time::timeout(timeout, receiver.recv()).await.ok().flatten())
+    }
+}
+
+/// Redis list shared by syncer and prover processes; pushed at the head, popped at the tail.
+#[derive(Clone, Debug)]
+pub struct RedisQueue {
+    client: redis::Client,
+    key: String,
+}
+
+impl RedisQueue {
+    pub fn new(url: &str, key: &str) -> Result<Self, anyhow::Error> {
+        let client = redis::Client::open(url).context("open redis batch queue")?;
+        Ok(Self { client, key: key.to_string() })
+    }
+
+    async fn connection(&self) -> Result<redis::aio::MultiplexedConnection, anyhow::Error> {
+        self.client.get_multiplexed_tokio_connection().await.context("connect redis batch queue")
+    }
+}
+
+#[async_trait]
+impl BatchQueue for RedisQueue {
+    async fn push(&self, batch: &BatchInfo) -> Result<(), anyhow::Error> {
+        let mut con = self.connection().await?;
+        let _: i64 = redis::cmd("LPUSH")
+            .arg(&self.key)
+            .arg(serde_json::to_string(batch)?)
+            .query_async(&mut con)
+            .await
+            .context("push batch to redis")?;
+        Ok(())
+    }
+
+    async fn pop(&self, timeout: Duration) -> Result<Option<BatchInfo>, anyhow::Error> {
+        let mut con = self.connection().await?;
+        // BRPOP treats a zero timeout as "block forever".
+        let item: redis::RedisResult<Option<String>> = if timeout.is_zero() {
+            redis::cmd("RPOP").arg(&self.key).query_async(&mut con).await
+        } else {
+            let popped: redis::RedisResult<Option<(String, String)>> = redis::cmd("BRPOP")
+                .arg(&self.key)
+                .arg(timeout.as_secs_f64())
+                .query_async(&mut con)
+                .await;
+            popped.map(|popped| popped.map(|(_, item)| item))
+        };
+        let item = item.context("pop batch from redis")?;
+
+        match item {
+            Some(item) => Ok(Some(
+                serde_json::from_str(&item)
+                    .with_context(|| format!("malformed batch in redis queue: {:?}", item))?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_memory_queue() {
+    use crate::size_class::SizeClass;
+
+    let queue = MemoryQueue::new(2);
+    assert!(queue.pop(Duration::ZERO).await.unwrap().is_none());
+
+    for batch_index in 1..=2 {
+        let batch =
+            BatchInfo { batch_index, start_block: 0, end_block: 0, size_class: SizeClass::Small };
+        queue.push(&batch).await.unwrap();
+    }
+    assert_eq!(queue.pop(Duration::ZERO).await.unwrap().unwrap().batch_index, 1);
+    assert_eq!(queue.pop(Duration::ZERO).await.unwrap().unwrap().batch_index, 2);
+
+    // A waiting pop is woken up by a push.
+    let queue = std::sync::Arc::new(queue);
+    let consumer = queue.clone();
+    let pop = tokio::spawn(async move { consumer.pop(Duration::from_secs(5)).await });
+    let batch =
+        BatchInfo { batch_index: 3, start_block: 0, end_block: 0, size_class: SizeClass::Small };
+    queue.push(&batch).await.unwrap();
+    assert_eq!(pop.await.unwrap().unwrap().unwrap().batch_index, 3);
+
+    // A full queue holds the push back until a pop makes room.
+    for batch_index in 4..=5 {
+        let batch =
+            BatchInfo { batch_index, start_block: 0, end_block: 0, size_class: SizeClass::Small };
+        queue.push(&batch).await.unwrap();
+    }
+    let producer = queue.clone();
+    let push = tokio::spawn(async move {
+        let batch = BatchInfo {
+            batch_index: 6,
+            start_block: 0,
+            end_block: 0,
+            size_class: SizeClass::Small,
+        };
+        producer.push(&batch).await
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!push.is_finished());
+    assert_eq!(queue.pop(Duration::ZERO).await.unwrap().unwrap().batch_index, 4);
+    push.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_redis_queue() {
+    use crate::size_class::SizeClass;
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::{Arc, Mutex},
+    };
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    // Embedded backend speaking just enough RESP for LPUSH/RPOP/BRPOP on lists.
+    type Lists = Arc<Mutex<HashMap<String, VecDeque<String>>>>;
+    fn pop(lists: &Lists, key: &str) -> Option<String> {
+        lists.lock().unwrap().get_mut(key).and_then(|list| list.pop_back())
+    }
+
+    let lists: Lists = Arc::default();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("redis://{}", listener.local_addr().unwrap());
+    let backend = lists.clone();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let lists = backend.clone();
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+                let mut line = String::new();
+                while stream.read_line(&mut line).await.unwrap_or(0) > 0 {
+                    let argc: usize = line.trim()[1..].parse().unwrap();
+                    let mut args = Vec::with_capacity(argc);
+                    for _ in 0..argc {
+                        line.clear();
+                        stream.read_line(&mut line).await.unwrap();
+                        let mut arg = vec![0u8; line.trim()[1..].parse::<usize>().unwrap() + 2];
+                        stream.read_exact(&mut arg).await.unwrap();
+                        args.push(String::from_utf8_lossy(&arg[..arg.len() - 2]).to_string());
+                    }
+                    line.clear();
+
+                    let bulk = |s: &str| format!("${}\r\n{}\r\n", s.len(), s);
+                    let reply = match args[0].to_uppercase().as_str() {
+                        "LPUSH" => {
+                            let mut lists = lists.lock().unwrap();
+                            let list = lists.entry(args[1].clone()).or_default();
+                            list.push_front(args[2].clone());
+                            format!(":{}\r\n", list.len())
+                        }
+                        "RPOP" => match pop(&lists, &args[1]) {
+                            Some(item) => bulk(&item),
+                            None => "$-1\r\n".to_string(),
+                        },
+                        "BRPOP" => match pop(&lists, &args[1]) {
+                            Some(item) => format!("*2\r\n{}{}", bulk(&args[1]), bulk(&item)),
+                            None => "*-1\r\n".to_string(),
+                        },
+                        _ => "+OK\r\n".to_string(),
+                    };
+                    stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+                }
+            });
+        }
+    });
+
+    // A syncer publishes, a separate prover instance consumes in order.
+    let syncer = from_url(Some(&url), "shadow:batches").unwrap();
+    let prover = from_url(Some(&url), "shadow:batches").unwrap();
+    for batch_index in 10..13 {
+        let batch = BatchInfo {
+            batch_index,
+            start_block: batch_index * 5,
+            end_block: 0,
+            size_class: SizeClass::Medium,
+        };
+        syncer.push(&batch).await.unwrap();
+    }
+    assert_eq!(lists.lock().unwrap()["shadow:batches"].len(), 3);
+
+    let first = prover.pop(Duration::from_secs(1)).await.unwrap().unwrap();
+    assert_eq!(
+        (first.batch_index, first.start_block, first.size_class),
+        (10, 50, SizeClass::Medium)
+    );
+    assert_eq!(prover.pop(Duration::ZERO).await.unwrap().unwrap().batch_index, 11);
+    assert_eq!(prover.pop(Duration::from_secs(1)).await.unwrap().unwrap().batch_index, 12);
+    assert!(prover.pop(Duration::from_secs(1)).await.unwrap().is_none());
+    assert!(prover.pop(Duration::ZERO).await.unwrap().is_none());
+
+    assert!(from_url(Some("nats://127.0.0.1:4222"), "shadow:batches").is_err());
+}
@@ -0,0 +1,218 @@
+//! Rate-limit aware `eth_getLogs`.
+//!
+//! The log scan is the heaviest request we send, and providers throttle it with HTTP 429 or a
+//! JSON-RPC error. Retrying right away only prolongs the throttling, so a rate-limited request is
+//! retried after the server's `Retry-After` (in seconds) when present, otherwise after an
+//! exponential, jittered backoff. Any other failure is returned at once to the generic handling,
+//! which retries it if [`is_transient`].
+//!
+//! The request is sent directly rather than through the provider because the provider's
+//! transport drops the response headers.
+
+use crate::{
+    config::Config,
+    failover::FailoverTransport,
+    metrics::{self, METRICS},
+};
+use alloy::{
+    providers::{Provider, RootProvider},
+    rpc::types::{Filter, Log},
+};
+use anyhow::{anyhow, Context};
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use serde_json::{json, Value};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+/// JSON-RPC error codes providers use for throttling.
+const RATE_LIMIT_CODES: [i64; 2] = [429, -32005];
+
+enum Outcome {
+    Logs(Vec<Log>),
+    RateLimited { retry_after: Option<Duration> },
+}
+
+/// `eth_getLogs` retrying rate-limited responses up to `config.rate_limit_retries` times.
+pub async fn get_logs(
+    provider: &RootProvider<FailoverTransport>,
+    filter: &Filter,
+    config: &Config,
+) -> Result<Vec<Log>, anyhow::Error> {
+    let url = provider.client().transport().url().to_string();
+    let client = reqwest::Client::builder().timeout(config.rpc_timeout).build()?;
+    let mut attempt = 0;
+    loop {
+        let retry_after = match request_logs(&client, &url, filter).await? {
+            Outcome::Logs(logs) => return Ok(logs),
+            Outcome::RateLimited { retry_after } => retry_after,
+        };
+        metrics::inc(&METRICS.shadow_rpc_rate_limited_total);
+        if attempt >= config.rate_limit_retries {
+            return Err(anyhow!("eth_getLogs still rate limited after {} retries", attempt));
+        }
+        let delay = backoff(config, attempt, retry_after);
+        log::warn!("eth_getLogs rate limited, retry in {:?}", delay);
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Whether a failed `eth_getLogs` did not reach the node or hit a server error, as opposed to an
+/// error answered by the node.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<reqwest::Error>().is_some_and(|e| {
+        e.is_connect() ||
+            e.is_timeout() ||
+            e.is_request() ||
+            e.status().is_some_and(|status| status.is_server_error())
+    })
+}
+
+async fn request_logs(
+    client: &reqwest::Client,
+    url: &str,
+    filter: &Filter,
+) -> Result<Outcome, anyhow::Error> {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_getLogs", "params": [filter] });
+    let response = client.post(url).json(&request).send().await.context("eth_getLogs")?;
+
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        return Ok(Outcome::RateLimited { retry_after });
+    }
+    let response: Value = response
+        .error_for_status()
+        .context("eth_getLogs")?
+        .json()
+        .await
+        .context("eth_getLogs response")?;
+
+    if let Some(error) = response.get("error") {
+        let code = error["code"].as_i64().unwrap_or_default();
+        let message = error["message"].as_str().unwrap_or_default();
+        let lowercase = message.to_lowercase();
+        if RATE_LIMIT_CODES.contains(&code) ||
+            lowercase.contains("rate limit") ||
+            lowercase.contains("too many requests")
+        {
+            return Ok(Outcome::RateLimited { retry_after: None });
+        }
+        return Err(anyhow!("eth_getLogs error {}: {}", code, message));
+    }
+    let logs = serde_json::from_value(response["result"].clone()).context("eth_getLogs result")?;
+    Ok(Outcome::Logs(logs))
+}
+
+/// Delay before retry `attempt` (from 0): `Retry-After` if given, otherwise the exponential
+/// backoff capped at `rate_limit_max_backoff`; either plus up to half of it as jitter.
+fn backoff(config: &Config, attempt: u32, retry_after: Option<Duration>) -> Duration {
+    let delay = retry_after.unwrap_or_else(|| {
+        config
+            .rate_limit_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(config.rate_limit_max_backoff)
+    });
+    let jitter = RandomState::new().build_hasher().finish() % (delay.as_millis() as u64 / 2 + 1);
+    delay + Duration::from_millis(jitter)
+}
+
+#[test]
+fn test_backoff() {
+    let config = Config {
+        rate_limit_backoff: Duration::from_secs(2),
+        rate_limit_max_backoff: Duration::from_secs(10),
+        ..Config::default()
+    };
+    for (attempt, base) in [(0, 2), (1, 4), (2, 8), (3, 10), (30, 10)] {
+        let delay = backoff(&config, attempt, None);
+        let base = Duration::from_secs(base);
+        assert!(delay >= base && delay <= base + base / 2, "attempt {}: {:?}", attempt, delay);
+    }
+
+    // Retry-After wins over the exponential backoff and its cap.
+    let delay = backoff(&config, 0, Some(Duration::from_secs(30)));
+    assert!(delay >= Duration::from_secs(30) && delay <= Duration::from_secs(45));
+}
+
+#[tokio::test]
+async fn test_get_logs_rate_limited() {
+    use axum::{
+        http::{header::HeaderMap, StatusCode as HttpStatus},
+        routing::post,
+        Router,
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Instant,
+    };
+
+    // Throttle the first request, with or without Retry-After, then answer.
+    async fn start(retry_after: Option<&'static str>) -> (String, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let count = requests.clone();
+        let app = Router::new().route(
+            "/",
+            post(move || {
+                let count = count.clone();
+                async move {
+                    let mut headers = HeaderMap::new();
+                    if count.fetch_add(1, Ordering::SeqCst) == 0 {
+                        if let Some(retry_after) = retry_after {
+                            headers.insert("retry-after", retry_after.parse().unwrap());
+                        }
+                        return (HttpStatus::TOO_MANY_REQUESTS, headers, String::new());
+                    }
+                    let body = json!({ "jsonrpc": "2.0", "id": 1, "result": [] }).to_string();
+                    headers.insert("content-type", "application/json".parse().unwrap());
+                    (HttpStatus::OK, headers, body)
+                }
+            }),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+        (url, requests)
+    }
+
+    let config = Config {
+        rate_limit_backoff: Duration::from_millis(10),
+        rate_limit_max_backoff: Duration::from_millis(100),
+        ..Config::default()
+    };
+    let filter = Filter::new();
+
+    // Without Retry-After: short jittered backoff.
+    let (url, requests) = start(None).await;
+    let provider = FailoverTransport::new("l1", &url, 1).unwrap().into_provider();
+    let limited = METRICS.shadow_rpc_rate_limited_total.get();
+    let start_at = Instant::now();
+    assert!(get_logs(&provider, &filter, &config).await.unwrap().is_empty());
+    assert!(start_at.elapsed() < Duration::from_secs(1));
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert!(METRICS.shadow_rpc_rate_limited_total.get() > limited);
+
+    // With Retry-After: the server's delay is honored.
+    let (url, requests) = start(Some("1")).await;
+    let provider = FailoverTransport::new("l1", &url, 1).unwrap().into_provider();
+    let start_at = Instant::now();
+    assert!(get_logs(&provider, &filter, &config).await.unwrap().is_empty());
+    assert!(start_at.elapsed() >= Duration::from_secs(1));
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    // Retries exhausted.
+    let (url, _) = start(None).await;
+    let provider = FailoverTransport::new("l1", &url, 1).unwrap().into_provider();
+    let config = Config { rate_limit_retries: 0, ..config };
+    assert!(get_logs(&provider, &filter, &config).await.is_err());
+}
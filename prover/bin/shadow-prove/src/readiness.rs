@@ -0,0 +1,260 @@
+//! Readiness of the prover, served at `/ready`.
+//!
+//! A syncing process is ready once a warmup dry run of the read path succeeded against the real
+//! endpoints: fetch the commit logs, inspect the header and blocks of the batch to sync and
+//! decode its `BatchStore`. ABI, RPC or contract misconfiguration then keeps the pod out of
+//! rotation instead of failing on the first tick. The warmup runs once at startup, bounded by
+//! `SHADOW_PROVING_WARMUP_TIMEOUT_SECS`; its failure reason is served with the state.
+//!
+//! Every `/ready` request also asks the L1 and L2 endpoints for their block number, bounded by
+//! [`RPC_PROBE_TIMEOUT`]: an unreachable endpoint takes the pod out of rotation until it answers
+//! again. The last block numbers observed are served with the state for debugging.
+//!
+//! Liveness, served at `/health`, only tells that the main loop has started. It reads no endpoint
+//! so a load balancer can probe it as often as it likes.
+
+use crate::{failover::FailoverTransport, BatchInfo};
+use alloy::providers::{Provider, RootProvider};
+use serde::Serialize;
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+/// Time an endpoint has to answer the readiness probe.
+pub const RPC_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ReadyState {
+    pub ready: bool,
+    /// Why the prover is not ready.
+    pub reason: Option<String>,
+    /// Last block number the L1 endpoint answered the probe with.
+    pub l1_block_number: Option<u64>,
+    /// Last block number the L2 endpoint answered the probe with.
+    pub l2_block_number: Option<u64>,
+}
+
+impl Default for ReadyState {
+    fn default() -> Self {
+        Self {
+            ready: false,
+            reason: Some(String::from("warmup in progress")),
+            l1_block_number: None,
+            l2_block_number: None,
+        }
+    }
+}
+
+/// Endpoints probed by the `/ready` endpoint.
+#[derive(Clone, Debug)]
+struct RpcProbe {
+    l1_provider: RootProvider<FailoverTransport>,
+    l2_provider: RootProvider<FailoverTransport>,
+}
+
+/// Readiness state, shared with the `/ready` endpoint.
+#[derive(Clone, Debug, Default)]
+pub struct Readiness {
+    state: Arc<Mutex<ReadyState>>,
+    rpc: Arc<OnceLock<RpcProbe>>,
+}
+
+impl Readiness {
+    pub fn get(&self) -> ReadyState {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub fn set_ready(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.ready = true;
+        state.reason = None;
+    }
+
+    pub fn set_failed(&self, reason: String) {
+        let mut state = self.state.lock().unwrap();
+        state.ready = false;
+        state.reason = Some(reason);
+    }
+
+    /// Probe the endpoints on every check from now on; the metrics server starts before they
+    /// are built.
+    pub fn probe_rpc(
+        &self,
+        l1_provider: RootProvider<FailoverTransport>,
+        l2_provider: RootProvider<FailoverTransport>,
+    ) {
+        let _ = self.rpc.set(RpcProbe { l1_provider, l2_provider });
+    }
+
+    /// The state, not ready while an endpoint does not answer the probe.
+    pub async fn check(&self) -> ReadyState {
+        let Some(rpc) = self.rpc.get() else {
+            return self.get();
+        };
+        let (l1, l2) = tokio::join!(
+            block_number(&rpc.l1_provider, "l1"),
+            block_number(&rpc.l2_provider, "l2")
+        );
+        let mut state = self.state.lock().unwrap();
+        if let Ok(number) = &l1 {
+            state.l1_block_number = Some(*number);
+        }
+        if let Ok(number) = &l2 {
+            state.l2_block_number = Some(*number);
+        }
+        let mut checked = state.clone();
+        // A failed warmup is the reason that matters.
+        if let (true, Err(reason)) = (checked.ready, l1.and(l2)) {
+            checked.ready = false;
+            checked.reason = Some(reason);
+        }
+        checked
+    }
+}
+
+async fn block_number(
+    provider: &RootProvider<FailoverTransport>,
+    name: &str,
+) -> Result<u64, String> {
+    match tokio::time::timeout(RPC_PROBE_TIMEOUT, provider.get_block_number()).await {
+        Ok(Ok(number)) => Ok(number),
+        Ok(Err(e)) => Err(format!("{} rpc unreachable: {}", name, e)),
+        Err(_) => Err(format!("{} rpc did not answer within {:?}", name, RPC_PROBE_TIMEOUT)),
+    }
+}
+
+/// Whether the main loop has started, shared with the `/health` endpoint.
+#[derive(Clone, Debug, Default)]
+pub struct Liveness(Arc<AtomicBool>);
+
+impl Liveness {
+    pub fn started(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set_started(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Await the `dry_run` for at most `timeout`, flipping `readiness` to ready only on success.
+pub async fn warmup<F>(readiness: &Readiness, timeout: Duration, dry_run: F)
+where
+    F: Future<Output = Result<BatchInfo, anyhow::Error>>,
+{
+    match tokio::time::timeout(timeout, dry_run).await {
+        Ok(Ok(batch)) => {
+            log::info!("warmup decoded batch {}, ready", batch.batch_index);
+            readiness.set_ready();
+        }
+        Ok(Err(e)) => {
+            log::error!("warmup failed, not ready: {:#}", e);
+            readiness.set_failed(format!("warmup failed: {:#}", e));
+        }
+        Err(_) => {
+            log::error!("warmup timed out after {:?}, not ready", timeout);
+            readiness.set_failed(format!("warmup timed out after {:?}", timeout));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_warmup() {
+    use crate::{config::Config, mock_rpc::MockRpc, shadow_rollup::BatchSyncer};
+    use alloy::primitives::Address;
+    use serde_json::json;
+
+    // The rollup answers no commit logs.
+    let rpc = MockRpc::start(|method, _| match method {
+        "eth_blockNumber" => Some(json!("0x64")),
+        "eth_getLogs" => Some(json!([])),
+        _ => None,
+    });
+    let syncer = BatchSyncer::new(
+        Address::ZERO,
+        Address::ZERO,
+        rpc.provider(),
+        rpc.provider(),
+        rpc.provider(),
+        Config::default(),
+    );
+    let readiness = Readiness::default();
+    assert!(!readiness.get().ready);
+    warmup(&readiness, Duration::from_secs(10), syncer.dry_run()).await;
+    let state = readiness.get();
+    assert!(!state.ready);
+    assert_eq!(
+        state.reason.as_deref(),
+        Some("warmup failed: no batch to inspect: no commit_batch logs in the lookback window")
+    );
+
+    // A hanging endpoint is bounded.
+    warmup(&readiness, Duration::from_millis(10), std::future::pending()).await;
+    assert!(!readiness.get().ready);
+    assert_eq!(readiness.get().reason.as_deref(), Some("warmup timed out after 10ms"));
+
+    let batch = BatchInfo {
+        batch_index: 7,
+        start_block: 101,
+        end_block: 110,
+        size_class: crate::size_class::SizeClass::Unknown,
+    };
+    warmup(&readiness, Duration::from_secs(10), async { Ok(batch) }).await;
+    assert_eq!(
+        readiness.get(),
+        ReadyState { ready: true, reason: None, l1_block_number: None, l2_block_number: None }
+    );
+}
+
+#[tokio::test]
+async fn test_check_rpc() {
+    use crate::mock_rpc::{error, MockRpc};
+    use serde_json::json;
+
+    let l1 = MockRpc::start(|method, _| match method {
+        "eth_blockNumber" => Some(json!("0x64")),
+        _ => None,
+    });
+    // The L2 node is warming up until told otherwise.
+    let l2_up = Arc::new(AtomicBool::new(false));
+    let l2 = MockRpc::start({
+        let l2_up = l2_up.clone();
+        move |method, _| match method {
+            "eth_blockNumber" if l2_up.load(Ordering::SeqCst) => Some(json!("0xc8")),
+            "eth_blockNumber" => error(-32603, "node is syncing"),
+            _ => None,
+        }
+    });
+    let readiness = Readiness::default();
+    readiness.set_ready();
+    // No endpoint to probe yet.
+    assert!(readiness.check().await.ready);
+
+    readiness.probe_rpc(l1.provider(), l2.provider());
+    let state = readiness.check().await;
+    assert!(!state.ready);
+    assert!(state.reason.unwrap().starts_with("l2 rpc unreachable: "));
+    assert_eq!((state.l1_block_number, state.l2_block_number), (Some(100), None));
+
+    l2_up.store(true, Ordering::SeqCst);
+    assert_eq!(
+        readiness.check().await,
+        ReadyState {
+            ready: true,
+            reason: None,
+            l1_block_number: Some(100),
+            l2_block_number: Some(200)
+        }
+    );
+
+    // A failed warmup keeps its reason.
+    readiness.set_failed(String::from("warmup timed out after 60s"));
+    let state = readiness.check().await;
+    assert_eq!(state.reason.as_deref(), Some("warmup timed out after 60s"));
+    assert_eq!(state.l2_block_number, Some(200));
+}
@@ -0,0 +1,292 @@
+//! Signed receipts of the processed batches, posted to an integrator's webhook.
+//!
+//! With `SHADOW_PROVING_RECEIPT_WEBHOOK_URL` set, every batch committed, proven or failed by a
+//! stage is posted as a JSON [`BatchReceipt`]. Unlike the alerts, receipts are meant to feed an
+//! attestation system downstream, so each one is authenticated: the body is signed with
+//! HMAC-SHA256 keyed by `SHADOW_PROVING_RECEIPT_WEBHOOK_SECRET`, sent hex encoded in the
+//! `X-Shadow-Proving-Signature: sha256=<hex>` header. The receiver recomputes the HMAC of the raw
+//! body and compares it in constant time, see [`verify`].
+//!
+//! Receipts are numbered by `seq`, starting over at 0 with each `session`, the unix timestamp the
+//! process started at. A gap in `seq` within a session is a receipt that was not delivered.
+//!
+//! Delivery never blocks the loop. Receipts wait in a queue of
+//! `SHADOW_PROVING_RECEIPT_QUEUE_SIZE` and are posted in order by a background task, each retried
+//! `SHADOW_PROVING_RECEIPT_RETRIES` times with a doubling backoff. A receipt that finds the
+//! queue full or runs out of retries is dropped and counted in `shadow_receipts_dropped_total`.
+
+use crate::{
+    artifact::BatchStoreFields,
+    audit::{Decision, Outcome, Tick},
+    config::Config,
+    failure::Stage,
+    metrics::{self, METRICS},
+    util,
+};
+use alloy::primitives::B256;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+/// Header carrying the signature of the body.
+pub const SIGNATURE_HEADER: &str = "X-Shadow-Proving-Signature";
+
+/// First backoff of a failed delivery, doubled per retry.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Committed batches remembered to complete the receipt of their proof.
+const MAX_COMMITTED: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BatchReceipt {
+    pub seq: u64,
+    /// Unix timestamp the emitting process started at; `seq` starts over with it.
+    pub session: u64,
+    pub batch_index: u64,
+    pub stage: &'static str,
+    pub outcome: Outcome,
+    /// Why the stage failed.
+    pub reason: Option<String>,
+    /// Roots of the batch as committed to the shadow rollup, if committed by this process.
+    pub roots: Option<BatchStoreFields>,
+    pub commit_tx: Option<B256>,
+    pub prove_tx: Option<B256>,
+    /// Unix timestamp of the tick that processed the batch.
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    seq: u64,
+    /// Roots and commit tx of the recently committed batches.
+    committed: BTreeMap<u64, (Option<BatchStoreFields>, Option<B256>)>,
+}
+
+/// Emitter of the receipts of the processed batches.
+#[derive(Clone, Debug)]
+pub struct Receipts {
+    session: u64,
+    state: Arc<Mutex<State>>,
+    queue: mpsc::Sender<BatchReceipt>,
+}
+
+impl Receipts {
+    /// Start delivering to the configured webhook; None when no webhook is configured.
+    pub fn spawn(config: &Config) -> Option<Self> {
+        let url = config.receipt_webhook_url.clone()?;
+        let secret = config.receipt_webhook_secret.clone().unwrap_or_default();
+        let retries = config.receipt_retries;
+        let (receipts, mut queue) = Self::new(config.receipt_queue_size, util::unix_timestamp());
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            while let Some(receipt) = queue.recv().await {
+                deliver(&client, &url, secret.as_bytes(), &receipt, retries).await;
+            }
+        });
+        Some(receipts)
+    }
+
+    fn new(queue_size: usize, session: u64) -> (Self, mpsc::Receiver<BatchReceipt>) {
+        let (queue, receiver) = mpsc::channel(queue_size.max(1));
+        (Self { session, state: Arc::default(), queue }, receiver)
+    }
+
+    /// Queue the receipts of the batches the stages of `tick` processed.
+    pub fn record_tick(&self, tick: &Tick) {
+        let stages = [(Stage::Commit, &tick.commit), (Stage::Prove, &tick.prove)];
+        for (stage, decision) in stages {
+            if let Some(receipt) = decision.as_ref().and_then(|d| self.receipt(stage, d, tick)) {
+                if self.queue.try_send(receipt).is_err() {
+                    log::warn!(
+                        "receipt queue full, drop the receipt of a {} stage",
+                        stage.as_str()
+                    );
+                    metrics::inc(&METRICS.shadow_receipts_dropped_total);
+                }
+            }
+        }
+    }
+
+    /// Receipt of a decision, numbered; None for a stage that processed no batch.
+    fn receipt(&self, stage: Stage, decision: &Decision, tick: &Tick) -> Option<BatchReceipt> {
+        let batch_index = decision.batch_index?;
+        if decision.outcome == Outcome::Skipped {
+            return None;
+        }
+        let mut state = self.state.lock().unwrap();
+        let (roots, commit_tx, prove_tx) = match stage {
+            Stage::Commit => {
+                if decision.outcome == Outcome::Committed {
+                    state.committed.insert(batch_index, (decision.roots.clone(), decision.tx_hash));
+                    while state.committed.len() > MAX_COMMITTED {
+                        state.committed.pop_first();
+                    }
+                }
+                (decision.roots.clone(), decision.tx_hash, None)
+            }
+            Stage::Prove => {
+                let (roots, commit_tx) =
+                    state.committed.get(&batch_index).cloned().unwrap_or_default();
+                (roots, commit_tx, decision.tx_hash)
+            }
+        };
+        let seq = state.seq;
+        state.seq += 1;
+        Some(BatchReceipt {
+            seq,
+            session: self.session,
+            batch_index,
+            stage: stage.as_str(),
+            outcome: decision.outcome,
+            reason: decision.reason.clone(),
+            roots,
+            commit_tx,
+            prove_tx,
+            timestamp: tick.timestamp,
+        })
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of `body` keyed by `secret`.
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(body);
+    alloy::hex::encode(mac.finalize().into_bytes())
+}
+
+/// Whether `signature`, the hex value of the signature header without its `sha256=` prefix,
+/// authenticates `body`.
+pub fn verify(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    let Ok(signature) = alloy::hex::decode(signature) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &[u8],
+    receipt: &BatchReceipt,
+    retries: u32,
+) {
+    let body = serde_json::to_vec(receipt).expect("receipt serializes");
+    let signature = format!("sha256={}", sign(secret, &body));
+    let mut backoff = RETRY_BACKOFF;
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+        let response = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status().is_success() => {
+                metrics::inc(&METRICS.shadow_receipts_sent_total);
+                return;
+            }
+            Ok(response) => {
+                log::warn!("receipt {} rejected by the webhook: {}", receipt.seq, response.status())
+            }
+            Err(e) => log::warn!("post receipt {} error: {}", receipt.seq, e),
+        }
+    }
+    log::error!(
+        "receipt {} of batch {} dropped after {} retries",
+        receipt.seq,
+        receipt.batch_index,
+        retries
+    );
+    metrics::inc(&METRICS.shadow_receipts_dropped_total);
+}
+
+#[test]
+fn test_sign() {
+    // RFC 4231, test case 2.
+    let signature = sign(b"Jefe", b"what do ya want for nothing?");
+    assert_eq!(signature, "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+
+    let body = br#"{"seq":0,"batch_index":7}"#;
+    let signature = sign(b"secret", body);
+    assert!(verify(b"secret", body, &signature));
+    assert!(!verify(b"other secret", body, &signature));
+    assert!(!verify(b"secret", br#"{"seq":0,"batch_index":8}"#, &signature));
+    assert!(!verify(b"secret", body, "not hex"));
+}
+
+#[test]
+fn test_record_tick() {
+    let (receipts, mut queue) = Receipts::new(2, 1_700_000_000);
+    let roots = BatchStoreFields {
+        prev_state_root: B256::repeat_byte(1),
+        post_state_root: B256::repeat_byte(2),
+        withdrawal_root: B256::repeat_byte(3),
+        data_hash: B256::repeat_byte(4),
+        blob_versioned_hash: B256::repeat_byte(5),
+        sequencer_set_verify_hash: B256::repeat_byte(6),
+    };
+    let (commit_tx, prove_tx) = (B256::repeat_byte(0x11), B256::repeat_byte(0x22));
+
+    let mut commit =
+        Decision { batch_index: Some(7), roots: Some(roots.clone()), ..Default::default() };
+    commit.commit(commit_tx);
+    receipts.record_tick(&Tick { timestamp: 100, commit: Some(commit), prove: None });
+    // Skipped stages make no receipt.
+    let skipped = Decision { batch_index: Some(8), ..Default::default() };
+    receipts.record_tick(&Tick { timestamp: 110, commit: Some(skipped), prove: None });
+    let mut prove = Decision { batch_index: Some(7), ..Default::default() };
+    prove.prove(prove_tx);
+    receipts.record_tick(&Tick { timestamp: 120, commit: None, prove: Some(prove) });
+
+    let committed = queue.try_recv().unwrap();
+    assert_eq!(
+        (committed.seq, committed.stage, committed.commit_tx),
+        (0, "commit", Some(commit_tx))
+    );
+    // The proof receipt carries the roots and tx of the commit.
+    let proven = queue.try_recv().unwrap();
+    assert_eq!(
+        proven,
+        BatchReceipt {
+            seq: 1,
+            session: 1_700_000_000,
+            batch_index: 7,
+            stage: "prove",
+            outcome: Outcome::Proven,
+            reason: None,
+            roots: Some(roots),
+            commit_tx: Some(commit_tx),
+            prove_tx: Some(prove_tx),
+            timestamp: 120,
+        }
+    );
+
+    // A full queue drops the receipt, leaving a gap in seq.
+    let dropped = METRICS.shadow_receipts_dropped_total.get();
+    let failed = |index: u64| {
+        let mut decision = Decision { batch_index: Some(index), ..Default::default() };
+        decision.fail("commit_batch tx failed");
+        Tick { timestamp: 130, commit: Some(decision), prove: None }
+    };
+    for index in 9..12 {
+        receipts.record_tick(&failed(index));
+    }
+    assert_eq!(METRICS.shadow_receipts_dropped_total.get(), dropped + 1);
+    let seqs: Vec<u64> = std::iter::from_fn(|| queue.try_recv().ok()).map(|r| r.seq).collect();
+    assert_eq!(seqs, vec![2, 3]);
+    receipts.record_tick(&failed(12));
+    assert_eq!(queue.try_recv().unwrap().seq, 5);
+}
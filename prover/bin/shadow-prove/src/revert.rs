@@ -0,0 +1,79 @@
+//! Reasons of reverted contract calls.
+//!
+//! A node answers a call or send that reverts with a JSON-RPC error whose `data` is the revert
+//! data: a custom error of the contract ABIs, an `Error(string)` as raised by `require`, or a
+//! `Panic(uint256)`. [`revert_reason`] decodes it into a readable reason, e.g. `Pausable: paused`
+//! or `InvalidProof()`; unknown custom errors are shown as their raw data.
+//!
+//! A receipt only tells a mined transaction failed. [`replay_reason`] reads the reason from a
+//! replay of its call as an `eth_call` at the block it was mined in.
+
+use crate::abi::{Rollup::RollupErrors, SP1Verifier::SP1VerifierErrors};
+use alloy::{primitives::Bytes, rpc::types::BlockId, sol_types::SolInterface};
+
+/// Revert data of a failed call, None for a failure other than a revert, e.g. a transport error.
+pub fn revert_data(error: &alloy::contract::Error) -> Option<Bytes> {
+    let alloy::contract::Error::TransportError(e) = error else {
+        return None;
+    };
+    e.as_error_resp()?.as_revert_data()
+}
+
+/// Readable reason of a reverted call, None for a failure other than a revert.
+pub fn revert_reason(error: &alloy::contract::Error) -> Option<String> {
+    let alloy::contract::Error::TransportError(e) = error else {
+        return None;
+    };
+    let payload = e.as_error_resp()?;
+    match payload.as_revert_data() {
+        Some(data) => Some(decode(&data)),
+        // Some nodes only answer with the message.
+        None if payload.message.contains("revert") => Some(payload.message.to_string()),
+        None => None,
+    }
+}
+
+/// Block to replay a transaction mined in `block_number` at.
+pub fn replay_block(block_number: Option<u64>) -> BlockId {
+    block_number.map_or(BlockId::latest(), BlockId::number)
+}
+
+/// Reason of a failed mined transaction, from the `replay` of its call.
+pub fn replay_reason<R>(replay: &Result<R, alloy::contract::Error>) -> String {
+    match replay {
+        // The tx ran on the state before it in its block, the replay on the state after the block.
+        Ok(_) => String::from("unknown, the replay succeeded"),
+        Err(e) => revert_reason(e).unwrap_or_else(|| format!("replay error: {}", e)),
+    }
+}
+
+fn decode(data: &[u8]) -> String {
+    if let Ok(e) = SP1VerifierErrors::abi_decode(data, false) {
+        return match e {
+            SP1VerifierErrors::InvalidProof(_) => String::from("InvalidProof()"),
+            SP1VerifierErrors::WrongVerifierSelector(s) => format!(
+                "WrongVerifierSelector(expected: {:?}, received: {:?})",
+                s.expected, s.received
+            ),
+        };
+    }
+    if let Ok(RollupErrors::ErrZeroAddress(_)) = RollupErrors::abi_decode(data, false) {
+        return String::from("ErrZeroAddress()");
+    }
+    if data.is_empty() {
+        return String::from("execution reverted");
+    }
+    alloy::sol_types::decode_revert_reason(data)
+        .unwrap_or_else(|| format!("unknown revert data {}", alloy::hex::encode_prefixed(data)))
+}
+
+#[test]
+fn test_decode() {
+    use crate::abi::SP1Verifier;
+    use alloy::sol_types::{Revert, SolError};
+
+    assert_eq!(decode(&Revert::from("Pausable: paused").abi_encode()), "revert: Pausable: paused");
+    assert_eq!(decode(&SP1Verifier::InvalidProof {}.abi_encode()), "InvalidProof()");
+    assert_eq!(decode(&[]), "execution reverted");
+    assert_eq!(decode(&[0xde, 0xad, 0xbe, 0xef]), "unknown revert data 0xdeadbeef");
+}
@@ -1,15 +1,27 @@
-use crate::{metrics::METRICS, util, BatchInfo, ShadowRollup::ShadowRollupInstance};
+use crate::{
+    abi::{
+        MultiProve::{self, MultiProveInstance},
+        SP1Verifier::SP1VerifierErrors,
+    },
+    artifact::BatchStoreFields,
+    balance,
+    error::ShadowError,
+    failover::FailoverTransport,
+    metrics::{self, METRICS},
+    revert,
+    size_class::SizeClass,
+    util, BatchInfo,
+    ShadowRollup::ShadowRollupInstance,
+};
 use alloy::{
     network::{Network, ReceiptResponse},
-    primitives::{Address, Bytes},
+    primitives::{Address, Bytes, TxHash, U256},
     providers::{Provider, RootProvider},
-    transports::{
-        http::{Client, Http},
-        Transport,
-    },
+    sol_types::SolInterface,
+    transports::Transport,
 };
 use serde::{Deserialize, Serialize};
-use std::{env::var, time::Duration};
+use std::{env::var, fmt, time::Duration};
 use tokio::time::sleep;
 
 const MAX_RETRY_TIMES: u8 = 2;
@@ -32,6 +44,58 @@ pub struct ProveResult {
     pub batch_header: Vec<u8>,
 }
 
+/// Why a `proveState` did not prove the batch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ProveError {
+    /// The verifier rejected the proof; sending it again cannot succeed.
+    Rejected(String),
+    /// No proof, or a transaction or RPC failure.
+    Failed(String),
+}
+
+impl fmt::Display for ProveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected(reason) => write!(f, "proof rejected by the verifier: {}", reason),
+            Self::Failed(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// A proof of the batch the on-chain verifier rejected.
+///
+/// The shadow state diverges from what the rollup claims for the batch, either in the committed
+/// roots or in the prover. The batch is not retried and goes to the dead letter list with the
+/// roots it was checked against, and the prove stage alerts as critical whatever its policy.
+///
+/// Runbook:
+///
+/// 1. Look up the batch with `shadow-proving checkpoint <file> dead-letter list`; the roots are the
+///    public inputs of the rejected proof.
+/// 2. Compare them with the batch header committed to the rollup. A `postStateRoot` or
+///    `withdrawalRoot` that differs from what the L2 nodes compute is a fault of the sequencer:
+///    escalate before the challenge window of the batch closes.
+/// 3. If the roots match, suspect the prover: a `WrongVerifierSelector` reason means the prover and
+///    the verifier run different circuit versions, an `invalid proof` one a prover bug. Run
+///    `shadow-proving export-request <index>` and reproduce the proof offline.
+/// 4. Once explained, prove the batch again with `resume-prove <index>` or `submit-proof`, then
+///    clear it with `dead-letter clear <index>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofRejected {
+    pub batch_index: u64,
+    pub reason: String,
+    /// Roots of the batch on the shadow rollup, if they could be read.
+    pub roots: Option<BatchStoreFields>,
+}
+
+impl fmt::Display for ProofRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "proof of batch {} rejected by the verifier: {}", self.batch_index, self.reason)
+    }
+}
+
+impl std::error::Error for ProofRejected {}
+
 mod task_status {
     pub const STARTED: &str = "Started";
     pub const PROVING: &str = "Proving";
@@ -40,9 +104,12 @@ mod task_status {
 
 #[derive(Clone, Debug)]
 pub struct ShadowProver<T, P, N> {
-    l1_provider: RootProvider<Http<Client>>,
+    l1_provider: RootProvider<FailoverTransport>,
     l1_shadow_rollup: ShadowRollupInstance<T, P, N>,
+    multi_prove: MultiProveInstance<T, P, N>,
+    max_group: usize,
     wallet_address: Address,
+    dry_run: bool,
 }
 
 impl<T, P, N> ShadowProver<T, P, N>
@@ -54,50 +121,221 @@ where
     pub fn new(
         wallet_address: Address,
         shadow_rollup_address: Address,
-        provider: RootProvider<Http<Client>>,
+        provider: RootProvider<FailoverTransport>,
         wallet: P,
     ) -> Self {
+        let multi_prove = MultiProve::new(shadow_rollup_address, wallet.clone());
         let l1_shadow_rollup = ShadowRollupInstance::new(shadow_rollup_address, wallet);
 
-        Self { l1_provider: provider, l1_shadow_rollup, wallet_address }
+        Self {
+            l1_provider: provider,
+            l1_shadow_rollup,
+            multi_prove,
+            max_group: 1,
+            wallet_address,
+            dry_run: false,
+        }
+    }
+
+    /// Simulate `proveState` with `eth_call` instead of sending it; nothing gets proven.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
     }
 
-    pub async fn prove(&self, batch_info: BatchInfo) -> Result<(), anyhow::Error> {
-        log::info!(">Start shadow prove for batch: {:#?}", batch_info.batch_index);
+    /// Have the prover generate the proof of the batch and simulate its `proveState`, logging
+    /// whether it would succeed or the revert reason.
+    async fn simulate(&self, batch_info: &BatchInfo) -> Result<(), anyhow::Error> {
+        let batch_index = batch_info.batch_index;
+        wait_for_proof(batch_info).await.map_err(anyhow::Error::msg)?;
+        let proof = fetch_proof(batch_index).await.map_err(anyhow::Error::msg)?;
+        match self.l1_shadow_rollup.proveState(batch_index, proof).call().await {
+            Ok(_) => log::info!("dry run: prove_state {:?} would succeed", batch_index),
+            Err(e) => log::warn!(
+                "dry run: prove_state {:?} would revert: {}",
+                batch_index,
+                revert::revert_reason(&e).unwrap_or_else(|| format!("{:#}", e))
+            ),
+        }
+        Ok(())
+    }
+
+    /// Let [`Self::prove_group`] prove up to `max_group` contiguous batches per transaction.
+    pub fn with_multi_prove(mut self, max_group: usize) -> Self {
+        self.max_group = max_group.max(1);
+        self
+    }
+
+    /// Prove the batch on the shadow rollup, returning the `proveState` tx hash once its state
+    /// got proven on chain, None if no prove was attempted.
+    ///
+    /// A batch still unproven after all attempts is abandoned with an error holding the last
+    /// failure.
+    pub async fn prove(&self, batch_info: BatchInfo) -> Result<Option<TxHash>, anyhow::Error> {
+        log::info!(">Start shadow prove for {}", batch_info);
+
+        if !self.record_wallet_balance().await {
+            return Ok(None);
+        }
+        if self.dry_run {
+            return self.simulate(&batch_info).await.map(|_| None);
+        }
+
+        let size_class = [batch_info.size_class.as_str()];
+        match handle_with_prover(&batch_info, &self.l1_shadow_rollup).await {
+            Ok(tx_hash) => {
+                metrics::inc(&METRICS.shadow_prove_success_total.with_label_values(&size_class));
+                Ok(Some(tx_hash))
+            }
+            Err(ProveError::Rejected(reason)) => {
+                metrics::inc(&METRICS.shadow_prove_failure_total.with_label_values(&size_class));
+                Err(self.rejected(batch_info.batch_index, reason).await)
+            }
+            Err(ProveError::Failed(e)) => {
+                metrics::inc(&METRICS.shadow_prove_failure_total.with_label_values(&size_class));
+                Err(ShadowError::ProveFailed {
+                    batch_index: batch_info.batch_index,
+                    attempts: MAX_RETRY_TIMES,
+                    reason: e,
+                }
+                .into())
+            }
+        }
+    }
+
+    /// Prove several batches, sending the proofs of contiguous batches together.
+    ///
+    /// Each proof is generated as by [`Self::prove`]. The batches whose proof is ready are then
+    /// split into runs of contiguous indexes, at most `max_group` long, each proven in a single
+    /// `proveStates` transaction; the contract only accepts a run without gaps, in ascending
+    /// order. A run whose transaction fails, e.g. on a rollup without `proveStates`, falls back
+    /// to one `proveState` per batch. Returns the outcome of each batch as `prove` would.
+    pub async fn prove_group(
+        &self,
+        batches: &[BatchInfo],
+    ) -> Vec<(u64, Result<bool, anyhow::Error>)> {
+        if batches.is_empty() {
+            return Vec::new();
+        }
+        log::info!(
+            ">Start shadow prove for batches: {:?}",
+            batches.iter().map(|b| b.batch_index).collect::<Vec<_>>()
+        );
+        if !self.record_wallet_balance().await {
+            return batches.iter().map(|b| (b.batch_index, Ok(false))).collect();
+        }
+        if self.dry_run {
+            let mut outcomes = Vec::new();
+            for batch in batches {
+                outcomes.push((batch.batch_index, self.simulate(batch).await.map(|_| false)));
+            }
+            return outcomes;
+        }
+
+        let mut outcomes = Vec::new();
+        let mut proofs = Vec::new();
+        for batch_info in batches {
+            let proof = match wait_for_proof(batch_info).await {
+                Ok(()) => fetch_proof(batch_info.batch_index).await,
+                Err(e) => Err(e),
+            };
+            match proof {
+                Ok(proof) => proofs.push((batch_info.batch_index, proof)),
+                Err(e) => outcomes.push((batch_info.batch_index, Err(ProveError::Failed(e)))),
+            }
+        }
+        for group in contiguous_groups(proofs, self.max_group) {
+            outcomes.extend(submit_group(group, &self.l1_shadow_rollup, &self.multi_prove).await);
+        }
+        outcomes.sort_by_key(|(batch_index, _)| *batch_index);
 
-        // Record wallet balance.
-        let balance = match self.l1_provider.get_balance(self.wallet_address).await {
-            Ok(b) => b,
+        // The group has no per-batch prove duration to observe.
+        let size_class = |batch_index: u64| {
+            let batch = batches.iter().find(|b| b.batch_index == batch_index);
+            [batch.map_or(SizeClass::Unknown, |b| b.size_class).as_str()]
+        };
+        let mut results = Vec::new();
+        for (batch_index, outcome) in outcomes {
+            let counter = match outcome {
+                Ok(()) => &METRICS.shadow_prove_success_total,
+                Err(_) => &METRICS.shadow_prove_failure_total,
+            };
+            metrics::inc(&counter.with_label_values(&size_class(batch_index)));
+            let result = match outcome {
+                Ok(()) => Ok(true),
+                Err(ProveError::Rejected(reason)) => Err(self.rejected(batch_index, reason).await),
+                Err(ProveError::Failed(e)) => Err(ShadowError::ProveFailed {
+                    batch_index,
+                    attempts: MAX_RETRY_TIMES,
+                    reason: e,
+                }
+                .into()),
+            };
+            results.push((batch_index, result));
+        }
+        results
+    }
+
+    /// The error of a batch whose proof got rejected, with the roots it was checked against.
+    async fn rejected(&self, batch_index: u64, reason: String) -> anyhow::Error {
+        let roots = match self
+            .l1_shadow_rollup
+            .committedBatchStores(U256::from(batch_index))
+            .call()
+            .await
+        {
+            Ok(store) => Some(BatchStoreFields::from(store)),
             Err(e) => {
-                log::error!("shadow_proving_wallet.get_balance error: {:#?}", e);
-                return Ok(());
+                log::error!("read roots of rejected batch {} error: {:#?}", batch_index, e);
+                None
             }
         };
-        METRICS
-            .shadow_wallet_balance
-            .set(alloy::primitives::utils::format_ether(balance).parse().unwrap_or(0.0));
+        anyhow::Error::new(ProofRejected { batch_index, reason, roots })
+    }
 
-        handle_with_prover(&batch_info, &self.l1_shadow_rollup).await;
+    async fn record_wallet_balance(&self) -> bool {
+        match balance::record_wallet_balance(&self.l1_provider, self.wallet_address).await {
+            Ok(_) => true,
+            Err(e) => {
+                log::error!("shadow_proving_wallet.get_balance error: {:#?}", e);
+                false
+            }
+        }
+    }
 
-        Ok(())
+    /// Prove the batch with a proof generated elsewhere, e.g. by an offline prover.
+    pub async fn submit_proof(&self, batch_index: u64, proof: Bytes) -> Result<(), anyhow::Error> {
+        match send_prove_state(batch_index, proof, &self.l1_shadow_rollup).await {
+            Ok(_) => Ok(()),
+            Err(ProveError::Rejected(reason)) => Err(self.rejected(batch_index, reason).await),
+            Err(ProveError::Failed(e)) => Err(anyhow::Error::msg(e)),
+        }
     }
 }
 
 async fn handle_with_prover<T, P, N>(
     batch_info: &BatchInfo,
     l1_shadow_rollup: &ShadowRollupInstance<T, P, N>,
-) where
+) -> Result<TxHash, ProveError>
+where
     P: Provider<T, N> + Clone,
     T: Transport + Clone,
     N: Network,
 {
+    wait_for_proof(batch_info).await.map_err(ProveError::Failed)?;
+    prove_state(batch_info.batch_index, l1_shadow_rollup).await
+}
+
+/// Have the prover server generate the proof of the batch, returning once it is available.
+async fn wait_for_proof(batch_info: &BatchInfo) -> Result<(), String> {
     let l2_rpc = var("SHADOW_PROVING_L2_RPC").expect("Cannot detect L2_RPC env var");
     let batch_index = batch_info.batch_index;
     let blocks_len = batch_info.end_block - batch_info.start_block + 1;
 
-    METRICS.shadow_blocks_len.set(blocks_len as i64);
-    METRICS.shadow_batch_index.set(batch_index as i64);
+    metrics::set(&METRICS.shadow_blocks_len, blocks_len as i64);
+    metrics::set(&METRICS.shadow_batch_index, batch_index as i64);
 
+    let mut last_error = String::new();
     for _ in 0..MAX_RETRY_TIMES {
         sleep(Duration::from_secs(12)).await;
 
@@ -112,8 +350,7 @@ async fn handle_with_prover<T, P, N>(
         if let Some(prove_result) = query_proof(batch_index).await {
             if !prove_result.proof_data.is_empty() {
                 log::info!("query proof and prove state: {:?}", batch_index);
-                prove_state(batch_index, l1_shadow_rollup).await;
-                break;
+                return Ok(());
             }
         }
 
@@ -139,22 +376,24 @@ async fn handle_with_prover<T, P, N>(
                 task_status::PROVING => log::info!("waiting for prev proof to be generated"),
                 task_status::PROVED => {
                     log::info!("proof already generated");
-                    prove_state(batch_index, l1_shadow_rollup).await;
-                    break;
+                    return Ok(());
                 }
                 _ => {
                     log::error!("submit prove task failed: {:#?}", info);
+                    last_error = format!("submit prove task failed: {}", info);
                     continue;
                 }
             },
             None => {
                 log::error!("submit prove task failed");
+                last_error = String::from("submit prove task failed");
                 continue;
             }
         }
 
         // Step5. query proof and prove onchain state.
         let mut max_waiting_time: usize = 1600 * blocks_len as usize; //block_prove_time =30min
+        last_error = String::from("proof not generated in time");
         while max_waiting_time > 300 {
             sleep(Duration::from_secs(300)).await;
             max_waiting_time -= 300; // Query results every 5 minutes.
@@ -162,61 +401,227 @@ async fn handle_with_prover<T, P, N>(
                 Some(prove_result) => {
                     log::debug!("query proof and prove state: {:#?}", batch_index);
                     if !prove_result.proof_data.is_empty() {
-                        prove_state(batch_index, l1_shadow_rollup).await;
-                        return;
+                        return Ok(());
                     }
                 }
                 None => {
                     log::error!("prover status unknown, resubmit task");
+                    last_error = String::from("prover status unknown");
                     break;
                 }
             }
         }
     }
+    Err(last_error)
 }
 
 async fn prove_state<T, P, N>(
     batch_index: u64,
     shadow_rollup: &ShadowRollupInstance<T, P, N>,
-) -> bool
+) -> Result<TxHash, ProveError>
 where
     P: Provider<T, N> + Clone,
     T: Transport + Clone,
     N: Network,
 {
+    let mut last_error = ProveError::Failed(String::new());
     for _ in 0..MAX_RETRY_TIMES {
         sleep(Duration::from_secs(12)).await;
         let prove_result = match query_proof(batch_index).await {
             Some(pr) => pr,
-            None => continue,
+            None => {
+                last_error = ProveError::Failed(String::from("query proof failed"));
+                continue;
+            }
         };
 
         if prove_result.proof_data.is_empty() {
             log::warn!("query proof of {:#?}, proof_data is empty", batch_index);
+            last_error = ProveError::Failed(String::from("proof_data is empty"));
             continue;
         }
 
-        log::info!(">Starting prove state onchain, batch index = {:#?}", batch_index);
-        let aggr_proof = Bytes::from(prove_result.proof_data);
-        let shadow_tx = shadow_rollup.proveState(batch_index, aggr_proof);
-        let send = shadow_tx.send().await;
+        match send_prove_state(batch_index, Bytes::from(prove_result.proof_data), shadow_rollup)
+            .await
+        {
+            Ok(tx_hash) => return Ok(tx_hash),
+            // The same proof is rejected again.
+            Err(e @ ProveError::Rejected(_)) => return Err(e),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
 
-        let pending_tx = match send {
-            Ok(pending_tx) => pending_tx,
-            Err(e) => {
-                log::error!("send tx of prove_state error: {:#?}", e);
-                METRICS.shadow_verify_result.set(2);
-                continue;
+/// Read the generated proof of the batch from the prover server.
+async fn fetch_proof(batch_index: u64) -> Result<Bytes, String> {
+    let mut last_error = String::new();
+    for _ in 0..MAX_RETRY_TIMES {
+        sleep(Duration::from_secs(12)).await;
+        match query_proof(batch_index).await {
+            Some(prove_result) if !prove_result.proof_data.is_empty() => {
+                return Ok(Bytes::from(prove_result.proof_data));
             }
-        };
-        let receipt = pending_tx.get_receipt().await.unwrap();
-        if receipt.status() {
-            log::info!("tx of prove_state success, tx hash: {:?}", receipt.transaction_hash());
-            return true;
+            Some(_) => {
+                log::warn!("query proof of {:#?}, proof_data is empty", batch_index);
+                last_error = String::from("proof_data is empty");
+            }
+            None => last_error = String::from("query proof failed"),
+        }
+    }
+    Err(last_error)
+}
+
+/// Split proofs sorted by batch index into runs of contiguous batches, at most `max_group` long.
+fn contiguous_groups(proofs: Vec<(u64, Bytes)>, max_group: usize) -> Vec<Vec<(u64, Bytes)>> {
+    let mut groups: Vec<Vec<(u64, Bytes)>> = Vec::new();
+    for (batch_index, proof) in proofs {
+        match groups.last_mut() {
+            Some(group)
+                if group.len() < max_group &&
+                    group.last().map(|(last, _)| last + 1) == Some(batch_index) =>
+            {
+                group.push((batch_index, proof))
+            }
+            _ => groups.push(vec![(batch_index, proof)]),
+        }
+    }
+    groups
+}
+
+/// Prove a run of contiguous batches, together if there are several, one by one if that fails.
+async fn submit_group<T, P, N>(
+    group: Vec<(u64, Bytes)>,
+    shadow_rollup: &ShadowRollupInstance<T, P, N>,
+    multi_prove: &MultiProveInstance<T, P, N>,
+) -> Vec<(u64, Result<(), ProveError>)>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    if group.len() > 1 {
+        match send_prove_states(&group, multi_prove).await {
+            Ok(()) => {
+                return group.into_iter().map(|(batch_index, _)| (batch_index, Ok(()))).collect()
+            }
+            Err(e) => log::warn!(
+                "prove states of {} batches failed, prove them one by one: {}",
+                group.len(),
+                e
+            ),
+        }
+    }
+
+    let mut outcomes = Vec::new();
+    for (batch_index, proof) in group {
+        let outcome = send_prove_state(batch_index, proof, shadow_rollup).await;
+        outcomes.push((batch_index, outcome.map(|_| ())));
+    }
+    outcomes
+}
+
+/// Send `proveStates` for a run of contiguous batches and wait for its receipt.
+async fn send_prove_states<T, P, N>(
+    group: &[(u64, Bytes)],
+    multi_prove: &MultiProveInstance<T, P, N>,
+) -> Result<(), String>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let (batch_indexes, proofs): (Vec<u64>, Vec<Bytes>) = group.iter().cloned().unzip();
+    log::info!(">Starting prove states onchain, batch indexes = {:?}", batch_indexes);
+    let shadow_tx = multi_prove.proveStates(batch_indexes, proofs);
+    let pending_tx = match shadow_tx.send().await {
+        Ok(pending_tx) => pending_tx,
+        Err(e) => return Err(format!("send tx of prove_states error: {}", e)),
+    };
+    let receipt = match pending_tx.get_receipt().await {
+        Ok(receipt) => receipt,
+        Err(e) => return Err(format!("get receipt of prove_states error: {}", e)),
+    };
+    if receipt.status() {
+        log::info!("tx of prove_states success, tx hash: {:?}", receipt.transaction_hash());
+        return Ok(());
+    }
+    let replay = shadow_tx.block(revert::replay_block(receipt.block_number())).call().await;
+    Err(format!(
+        "tx of prove_states failed, tx hash: {:?}: {}",
+        receipt.transaction_hash(),
+        revert::replay_reason(&replay)
+    ))
+}
+
+/// Send `proveState` with the given proof and wait for its receipt, returning the tx hash.
+///
+/// The transaction is estimated before it is accepted, so a proof the verifier rejects fails the
+/// send with the verifier error as revert data; it is told apart as [`ProveError::Rejected`] and
+/// counted in `shadow_proof_verification_failed_total`.
+async fn send_prove_state<T, P, N>(
+    batch_index: u64,
+    proof: Bytes,
+    shadow_rollup: &ShadowRollupInstance<T, P, N>,
+) -> Result<TxHash, ProveError>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    log::info!(">Starting prove state onchain, batch index = {:#?}", batch_index);
+    let shadow_tx = shadow_rollup.proveState(batch_index, proof);
+    let send = shadow_tx.send().await;
+
+    let pending_tx = match send {
+        Ok(pending_tx) => pending_tx,
+        Err(e) => {
+            metrics::set(&METRICS.shadow_verify_result, 2);
+            if let Some(reason) = verifier_rejection(&e) {
+                log::error!("proof of batch {} rejected by the verifier: {}", batch_index, reason);
+                metrics::inc(&METRICS.shadow_proof_verification_failed_total);
+                return Err(ProveError::Rejected(reason));
+            }
+            log::error!("send tx of prove_state error: {:#?}", e);
+            return Err(ProveError::Failed(format!("send tx of prove_state error: {}", e)));
         }
-        log::error!("tx of prove_state failed, tx hash: {:?}", receipt.transaction_hash());
+    };
+    let receipt = match pending_tx.get_receipt().await {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            log::error!("get receipt of prove_state error: {:#?}", e);
+            return Err(ProveError::Failed(format!("get receipt of prove_state error: {}", e)));
+        }
+    };
+    if receipt.status() {
+        log::info!("tx of prove_state success, tx hash: {:?}", receipt.transaction_hash());
+        return Ok(receipt.transaction_hash());
+    }
+    let replay = shadow_tx.block(revert::replay_block(receipt.block_number())).call().await;
+    if let Some(reason) = replay.as_ref().err().and_then(verifier_rejection) {
+        log::error!("proof of batch {} rejected by the verifier: {}", batch_index, reason);
+        metrics::inc(&METRICS.shadow_proof_verification_failed_total);
+        return Err(ProveError::Rejected(reason));
+    }
+    let reason = revert::replay_reason(&replay);
+    log::error!("tx of prove_state {:?} failed: {}", receipt.transaction_hash(), reason);
+    Err(ProveError::Failed(format!(
+        "tx of prove_state failed, tx hash: {:?}: {}",
+        receipt.transaction_hash(),
+        reason
+    )))
+}
+
+/// The reason of a verifier error in the revert data of a failed send, None for other failures.
+fn verifier_rejection(error: &alloy::contract::Error) -> Option<String> {
+    let data = revert::revert_data(error)?;
+    match SP1VerifierErrors::abi_decode(&data, false).ok()? {
+        SP1VerifierErrors::InvalidProof(_) => Some(String::from("invalid proof")),
+        SP1VerifierErrors::WrongVerifierSelector(s) => Some(format!(
+            "wrong verifier selector, expected {:?}, received {:?}",
+            s.expected, s.received
+        )),
     }
-    false
 }
 
 /**
@@ -270,3 +675,125 @@ fn test() {
         SP1Verifier::SP1VerifierErrors::InvalidProof(_) => println!("WrongVerifierSelector"),
     }
 }
+
+#[test]
+fn test_contiguous_groups() {
+    let proofs = [7, 8, 9, 11, 12, 14].map(|index| (index, Bytes::from(vec![index as u8])));
+    let indexes = |groups: Vec<Vec<(u64, Bytes)>>| -> Vec<Vec<u64>> {
+        groups.into_iter().map(|g| g.into_iter().map(|(index, _)| index).collect()).collect()
+    };
+    assert_eq!(
+        indexes(contiguous_groups(proofs.to_vec(), 10)),
+        vec![vec![7, 8, 9], vec![11, 12], vec![14]]
+    );
+    assert_eq!(
+        indexes(contiguous_groups(proofs.to_vec(), 2)),
+        vec![vec![7, 8], vec![9], vec![11, 12], vec![14]]
+    );
+    assert_eq!(indexes(contiguous_groups(proofs.to_vec(), 1)).len(), 6);
+}
+
+#[tokio::test]
+async fn test_submit_group() {
+    use crate::{
+        abi::ShadowRollup,
+        mock_rpc::{call_selector, receipt, MockRpc},
+    };
+    use alloy::{primitives::B256, sol_types::SolCall};
+    use serde_json::json;
+
+    // Proven together when the rollup supports proveStates, one by one otherwise.
+    let start = |supports_multi_prove: bool| {
+        MockRpc::start(move |method, params| match method {
+            "eth_sendTransaction" => match call_selector(params) {
+                selector if selector == MultiProve::proveStatesCall::SELECTOR => {
+                    supports_multi_prove.then(|| json!(B256::repeat_byte(0x55)))
+                }
+                _ => Some(json!(B256::repeat_byte(0x11))),
+            },
+            "eth_getTransactionReceipt" => {
+                receipt(params[0].as_str().unwrap().parse().unwrap(), true)
+            }
+            "eth_blockNumber" => Some(json!("0x1")),
+            _ => None,
+        })
+    };
+    let group = vec![(7, Bytes::from(vec![7])), (8, Bytes::from(vec![8]))];
+
+    let rpc = start(true);
+    let shadow_rollup = ShadowRollup::new(Address::ZERO, rpc.provider());
+    let multi_prove = MultiProve::new(Address::ZERO, rpc.provider());
+    let outcomes = submit_group(group.clone(), &shadow_rollup, &multi_prove).await;
+    assert_eq!(outcomes, vec![(7, Ok(())), (8, Ok(()))]);
+    let sent = rpc.requests("eth_sendTransaction");
+    assert_eq!(sent.len(), 1);
+    let input = sent[0][0]["input"].as_str().or(sent[0][0]["data"].as_str()).unwrap();
+    let call = MultiProve::proveStatesCall::abi_decode(&input.parse::<Bytes>().unwrap(), false);
+    assert_eq!(call.unwrap().batchIndexes, vec![7, 8]);
+
+    // Partial support: the group falls back to a proveState per batch.
+    let rpc = start(false);
+    let shadow_rollup = ShadowRollup::new(Address::ZERO, rpc.provider());
+    let multi_prove = MultiProve::new(Address::ZERO, rpc.provider());
+    let outcomes = submit_group(group, &shadow_rollup, &multi_prove).await;
+    assert_eq!(outcomes, vec![(7, Ok(())), (8, Ok(()))]);
+    let sent = rpc.requests("eth_sendTransaction");
+    let selectors: Vec<_> = sent.iter().map(|params| call_selector(params).0).collect();
+    assert_eq!(
+        selectors,
+        vec![
+            MultiProve::proveStatesCall::SELECTOR,
+            ShadowRollup::proveStateCall::SELECTOR,
+            ShadowRollup::proveStateCall::SELECTOR,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_verifier_rejection() {
+    use crate::{
+        abi::{SP1Verifier, ShadowRollup},
+        mock_rpc::{call_result, receipt, revert, MockRpc},
+    };
+    use alloy::{primitives::B256, sol_types::SolError};
+    use serde_json::json;
+
+    // The verifier rejects the proof when the transaction is estimated.
+    let start = |rejected: bool| {
+        MockRpc::start(move |method, params| match method {
+            "eth_estimateGas" | "eth_sendTransaction" if rejected => {
+                revert(SP1Verifier::InvalidProof {}.abi_encode())
+            }
+            "eth_sendTransaction" => None,
+            "eth_getTransactionReceipt" => {
+                receipt(params[0].as_str().unwrap().parse().unwrap(), true)
+            }
+            "eth_blockNumber" => Some(json!("0x1")),
+            // Zero roots of committedBatchStores.
+            "eth_call" => call_result(vec![0; 6 * 32]),
+            _ => None,
+        })
+    };
+
+    let rejections = METRICS.shadow_proof_verification_failed_total.get();
+    let rpc = start(true);
+    let shadow_rollup = ShadowRollup::new(Address::ZERO, rpc.provider());
+    let outcome = send_prove_state(7, Bytes::from(vec![7]), &shadow_rollup).await;
+    assert_eq!(outcome, Err(ProveError::Rejected(String::from("invalid proof"))));
+    assert_eq!(METRICS.shadow_proof_verification_failed_total.get(), rejections + 1);
+
+    // An RPC failure is not a rejection.
+    let rpc = start(false);
+    let shadow_rollup = ShadowRollup::new(Address::ZERO, rpc.provider());
+    let outcome = send_prove_state(7, Bytes::from(vec![7]), &shadow_rollup).await;
+    assert!(matches!(outcome, Err(ProveError::Failed(_))));
+    assert_eq!(METRICS.shadow_proof_verification_failed_total.get(), rejections + 1);
+
+    // A rejected batch carries its roots to the dead letter list.
+    let prover = ShadowProver::new(Address::ZERO, Address::ZERO, rpc.provider(), rpc.provider());
+    let error = prover.rejected(7, String::from("invalid proof")).await;
+    let rejected = error.downcast_ref::<ProofRejected>().unwrap();
+    assert_eq!(rejected.batch_index, 7);
+    assert_eq!(rejected.roots.as_ref().map(|r| r.post_state_root), Some(B256::ZERO));
+    assert_eq!(error.to_string(), "proof of batch 7 rejected by the verifier: invalid proof");
+}
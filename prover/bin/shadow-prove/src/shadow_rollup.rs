@@ -1,28 +1,73 @@
-use crate::{metrics::METRICS, util::read_env_var, BatchInfo};
+use crate::{
+    alarm::Alarm,
+    artifact::ProveArtifact,
+    audit::Decision,
+    batch_header::{BatchHeader, DecodeError},
+    blob::{Beacon, EMPTY_BLOB_VERSIONED_HASH},
+    block_number::{self, LatestBlock},
+    circuit::{Circuit, CircuitBreaker},
+    config::{CheckMode, Config},
+    error::ShadowError,
+    failover::FailoverTransport,
+    header_version::HeaderVersion,
+    metrics::{self, METRICS},
+    rate_limit, revert,
+    size_class::SizeClass,
+    util, BatchInfo,
+};
 use alloy::{
     consensus::Transaction,
+    contract::{self, CallBuilder, CallDecoder},
     network::{Network, ReceiptResponse},
-    primitives::{Address, Bytes, TxHash, U256, U64},
+    primitives::{address, Address, Bytes, TxHash, B256, U256},
     providers::{Provider, RootProvider},
-    rpc::types::Log,
+    rpc::types::{BlockId, BlockNumberOrTag, BlockTransactionsKind, Log},
     sol_types::SolCall,
-    transports::{
-        http::{Client, Http},
-        Transport,
-    },
+    transports::{RpcError, Transport},
 };
 
 use crate::{
+    abi::{BatchBlocksGetter, L2ToL1MessagePasser, Multicall3},
     Rollup::{self, RollupInstance},
     ShadowRollup::{self, ShadowRollupInstance},
 };
+use anyhow::Context;
+use futures::{stream, StreamExt};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Committed but unproven batches remembered, the oldest forgotten first.
+const MAX_COMMITTED_UNPROVEN: usize = 256;
 
 #[derive(Clone, Debug)]
 pub struct BatchSyncer<T, P, N> {
-    l1_provider: RootProvider<Http<Client>>,
-    l2_provider: RootProvider<Http<Client>>,
-    l1_rollup: RollupInstance<Http<Client>, RootProvider<Http<Client>>>,
+    l1_provider: RootProvider<FailoverTransport>,
+    l2_provider: RootProvider<FailoverTransport>,
+    l1_rollup: RollupInstance<FailoverTransport, RootProvider<FailoverTransport>>,
     l1_shadow_rollup: ShadowRollupInstance<T, P, N>,
+    header_version: HeaderVersion,
+    inflight_commits: InflightCommits,
+    alarm: Alarm<T, P, N>,
+    latest_block: LatestBlock,
+    commit_logs: CommitLogWindow,
+    /// L1 state the inspection of committed batches reads, latest unless pinned.
+    block: BlockId,
+    /// Account the `commitBatch` simulation is run as.
+    sender: Option<Address>,
+    /// Batches up to this index are proven per the checkpoint, skipped without a read.
+    proven_through: Option<u64>,
+    /// Batches committed to the shadow rollup and not seen proven yet, whose commit is skipped.
+    committed_unproven: Arc<Mutex<BTreeSet<u64>>>,
+    circuit: CircuitBreaker,
+    config: Config,
 }
 
 impl<T, P, N> BatchSyncer<T, P, N>
@@ -34,100 +79,419 @@ where
     pub fn new(
         rollup_address: Address,
         shadow_rollup_address: Address,
-        l1_provider: RootProvider<Http<Client>>,
-        l2_provider: RootProvider<Http<Client>>,
+        l1_provider: RootProvider<FailoverTransport>,
+        l2_provider: RootProvider<FailoverTransport>,
+        wallet: P,
+        config: Config,
+    ) -> Self {
+        BatchSyncerBuilder::default()
+            .rollup(rollup_address)
+            .shadow_rollup(shadow_rollup_address)
+            .l1_provider(l1_provider)
+            .l2_provider(l2_provider)
+            .wallet(wallet)
+            .config(config)
+            .build()
+            .expect("every field of the builder is set")
+    }
+
+    fn from_parts(
+        rollup_address: Address,
+        shadow_rollup_address: Address,
+        l1_provider: RootProvider<FailoverTransport>,
+        l2_provider: RootProvider<FailoverTransport>,
         wallet: P,
+        config: Config,
     ) -> Self {
         let l1_rollup = Rollup::RollupInstance::new(rollup_address, l1_provider.clone());
+        let alarm = Alarm::new(config.alarm_contract, wallet.clone());
         let l1_shadow_rollup = ShadowRollup::new(shadow_rollup_address, wallet);
+        let header_version = HeaderVersion::new(
+            rollup_address,
+            l1_provider.clone(),
+            config.batch_header_version,
+            config.header_version_refresh,
+        );
+
+        let inflight_commits = InflightCommits::new(config.max_inflight_commits);
+        let circuit = CircuitBreaker::new(&config);
+
+        Self {
+            l1_provider,
+            l2_provider,
+            l1_rollup,
+            l1_shadow_rollup,
+            header_version,
+            inflight_commits,
+            alarm,
+            latest_block: LatestBlock::default(),
+            commit_logs: CommitLogWindow::default(),
+            block: BlockId::latest(),
+            sender: None,
+            proven_through: None,
+            committed_unproven: Arc::default(),
+            circuit,
+            config,
+        }
+    }
+
+    /// Share the circuit breaker of the commit sends, e.g. with the admin endpoint resetting it.
+    pub fn with_circuit_breaker(mut self, circuit: CircuitBreaker) -> Self {
+        self.circuit = circuit;
+        self
+    }
+
+    /// Simulate `commitBatch` as `sender`, the signer of the sent transactions; the shadow
+    /// rollup only accepts commits of its owner.
+    pub fn with_sender(mut self, sender: Address) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// Skip the batches up to `batch_index`, the last proven one of the checkpoint, so that a
+    /// restart resumes after it.
+    pub fn with_proven_through(mut self, batch_index: Option<u64>) -> Self {
+        self.proven_through = batch_index;
+        self
+    }
+
+    /// Skip the commit of `batches`, committed but not proven per the checkpoint, as long as the
+    /// shadow rollup shows their commit; they go straight to the prove step.
+    pub fn with_committed_unproven(self, batches: impl IntoIterator<Item = u64>) -> Self {
+        for batch_index in batches {
+            self.record_committed(batch_index);
+        }
+        self
+    }
+
+    fn record_committed(&self, batch_index: u64) {
+        let mut committed = self.committed_unproven.lock().unwrap();
+        committed.insert(batch_index);
+        while committed.len() > MAX_COMMITTED_UNPROVEN {
+            committed.pop_first();
+        }
+    }
+
+    /// Pin the reads of [`Self::committed_unproven_batch`] and [`Self::prove_artifact`] to the
+    /// L1 state at `block`, to reproduce a past run. Historical state needs an archive node.
+    pub fn at_block(mut self, block: BlockId) -> Self {
+        self.block = block;
+        self
+    }
 
-        Self { l1_provider, l2_provider, l1_rollup, l1_shadow_rollup }
+    /// Latest L1 block number, retrying transient RPC errors.
+    async fn latest_block_number(&self) -> Result<u64, anyhow::Error> {
+        let latest = util::retry_with_backoff(
+            "eth_blockNumber",
+            self.config.rpc_backoff(),
+            util::is_transient,
+            || self.l1_provider.get_block_number(),
+        )
+        .await;
+        if latest.is_err() {
+            metrics::rpc_error("l1", "eth_blockNumber");
+        }
+        Ok(latest?)
     }
 
     /**
-     * Sync a latest batch to l1-shadow-rollup.
+     * Sync a latest batch to l1-shadow-rollup, recording the outcome in `decision`.
      */
-    pub async fn sync_batch(&self) -> Result<Option<BatchInfo>, anyhow::Error> {
+    pub async fn sync_batch(&self, decision: &mut Decision) -> Result<SyncOutcome, anyhow::Error> {
+        let start = Instant::now();
+        let outcome = self.sync_next_batch(decision).await;
+        metrics::observe_duration(&METRICS.shadow_sync_duration_seconds, start.elapsed());
+        outcome
+    }
+
+    async fn sync_next_batch(&self, decision: &mut Decision) -> Result<SyncOutcome, anyhow::Error> {
         log::info!("start sync_batch...");
 
-        let latest = self.l1_provider.get_block_number().await?;
+        let latest = self.latest_block_number().await?;
+        // A lagging node behind a load balancer would make the prover go backwards.
+        if let Err(e) = self.latest_block.observe(latest, self.config.stale_block_tolerance) {
+            metrics::inc(&METRICS.shadow_stale_node_total);
+            log::warn!("stale l1 node, skip the tick: {:#}", e);
+            return Ok(SyncOutcome::StaleNode.record(decision, "stale l1 node"));
+        }
 
         // Fetch a commited batch on l1 rollup.
         let (batch_info, batch_header) = match get_committed_batch(
-            U64::from(latest),
+            latest,
+            &self.commit_logs,
             &self.l1_rollup,
             &self.l1_provider,
             &self.l2_provider,
+            &self.config,
+            decision,
         )
         .await
         {
-            Ok(Some(committed_batch)) => committed_batch,
-            Ok(None) => return Ok(None),
-            Err(msg) => {
-                log::error!("get_committed_batch error: {:?}", msg);
-                return Ok(None);
+            Ok(committed_batch) => committed_batch,
+            Err(outcome) => return Ok(outcome),
+        };
+
+        self.commit_batch(batch_info, batch_header, decision).await
+    }
+
+    /// Run the read path of [`Self::sync_batch`] without committing: fetch the commit logs,
+    /// inspect the blocks and header of the batch to sync and decode its `BatchStore`. The size
+    /// limits are lifted, an oversized batch exercises the read path as well as any.
+    pub async fn dry_run(&self) -> Result<BatchInfo, anyhow::Error> {
+        let latest = self.latest_block_number().await?;
+        let config = Config { max_block: u64::MAX, max_txn: u64::MAX, ..self.config.clone() };
+        let mut decision = Decision::default();
+        let committed = get_committed_batch(
+            latest,
+            &self.commit_logs,
+            &self.l1_rollup,
+            &self.l1_provider,
+            &self.l2_provider,
+            &config,
+            &mut decision,
+        )
+        .await;
+        let Ok((batch_info, batch_header)) = committed else {
+            anyhow::bail!("no batch to inspect: {}", decision.reason.unwrap_or_default());
+        };
+        let header = BatchHeader::decode(&batch_header)
+            .with_context(|| format!("decode header of batch {}", batch_info.batch_index))?;
+        if header.fields().post_state_root.is_zero() {
+            anyhow::bail!("header of batch {} has no postStateRoot", batch_info.batch_index);
+        }
+        log::info!(
+            "dry run decoded batch {} header version {}",
+            batch_info.batch_index,
+            header.version()
+        );
+        Ok(batch_info)
+    }
+
+    /**
+     * Whether the batch is proven on l1-shadow-rollup.
+     */
+    pub async fn is_proven(&self, batch_index: u64) -> Result<bool, anyhow::Error> {
+        Ok(is_prove_success(batch_index, &self.l1_shadow_rollup, BlockId::latest()).await?)
+    }
+
+    /**
+     * Committed batches of the batch range in the lookback window not yet proven on
+     * l1-shadow-rollup, in ascending batch index order.
+     */
+    pub async fn unproven_batches(&self) -> Result<Vec<BatchTarget>, anyhow::Error> {
+        let mut unproven = Vec::new();
+        let targets = self.batch_targets().await?;
+        for target in targets.into_iter().filter(|t| self.config.batch_in_range(t.batch_index)) {
+            match is_prove_success(target.batch_index, &self.l1_shadow_rollup, BlockId::latest())
+                .await
+            {
+                Ok(false) => unproven.push(target),
+                Ok(true) => (),
+                Err(e) => log::warn!(
+                    "skip batch {:?}, query of its prove state error: {}",
+                    target.batch_index,
+                    e
+                ),
             }
+        }
+        Ok(unproven)
+    }
+
+    /**
+     * The committed batch of the given index in the lookback window, if its successor is
+     * committed too.
+     */
+    pub async fn batch_target(
+        &self,
+        batch_index: u64,
+    ) -> Result<Option<BatchTarget>, anyhow::Error> {
+        let targets = self.batch_targets().await?;
+        Ok(targets.into_iter().find(|target| target.batch_index == batch_index))
+    }
+
+    async fn batch_targets(&self) -> Result<Vec<BatchTarget>, anyhow::Error> {
+        let latest = self.latest_block_number().await?;
+        let logs = fetch_commit_logs(
+            latest,
+            &self.commit_logs,
+            &self.l1_rollup,
+            &self.l1_provider,
+            &self.config,
+        )
+        .await?;
+        Ok(batch_targets(&logs))
+    }
+
+    /**
+     * Whether the batch is committed to l1-shadow-rollup.
+     */
+    pub async fn is_committed(&self, batch_index: u64) -> Result<bool, anyhow::Error> {
+        let store =
+            self.l1_shadow_rollup.committedBatchStores(U256::from(batch_index)).call().await?;
+        Ok(!store.postStateRoot.is_zero())
+    }
+
+    /**
+     * Sync the given committed batch to l1-shadow-rollup.
+     */
+    pub async fn sync_target(
+        &self,
+        target: &BatchTarget,
+        decision: &mut Decision,
+    ) -> Result<SyncOutcome, anyhow::Error> {
+        log::info!("start sync_target of batch {:?}...", target.batch_index);
+
+        let (batch_info, batch_header) = match inspect_committed_batch(
+            target,
+            &self.l1_rollup,
+            &self.l1_provider,
+            &self.l2_provider,
+            &self.config,
+            decision,
+        )
+        .await
+        {
+            Ok(committed_batch) => committed_batch,
+            Err(outcome) => return Ok(outcome),
         };
 
-        // Batch should not have been verified yet.
-        if is_prove_success(batch_info.batch_index, &self.l1_shadow_rollup).await.unwrap_or(true) {
+        self.commit_batch(batch_info, batch_header, decision).await
+    }
+
+    /**
+     * Rebuild the batch info of a batch already committed to l1-shadow-rollup, to prove it
+     * without committing again. None if it is proven already.
+     */
+    pub async fn committed_unproven_batch(
+        &self,
+        batch_index: u64,
+    ) -> Result<Option<BatchInfo>, anyhow::Error> {
+        match is_prove_success(batch_index, &self.l1_shadow_rollup, self.block).await {
+            Ok(false) => (),
+            Ok(true) => {
+                log::info!("batch {:?} is already proven on shadow rollup", batch_index);
+                return Ok(None);
+            }
+            Err(e) => anyhow::bail!("query prove state of batch {} error: {}", batch_index, e),
+        }
+
+        let store = self
+            .l1_shadow_rollup
+            .committedBatchStores(U256::from(batch_index))
+            .block(self.block)
+            .call()
+            .await?;
+        if store.postStateRoot.is_zero() {
+            anyhow::bail!("batch {} is not committed to the shadow rollup", batch_index);
+        }
+
+        let (blocks, total_txn_count) = inspect_batch_blocks(
+            &self.l1_rollup,
+            &self.l1_provider,
+            &self.l2_provider,
+            batch_index,
+            &self.config,
+            self.block,
+        )
+        .await
+        .ok_or_else(|| anyhow::anyhow!("inspect blocks of batch {} failed", batch_index))?;
+        check_block_range(batch_index, blocks)?;
+        let size_class =
+            SizeClass::classify(blocks.1 - blocks.0 + 1, total_txn_count, &self.config);
+
+        Ok(Some(BatchInfo { batch_index, start_block: blocks.0, end_block: blocks.1, size_class }))
+    }
+
+    /**
+     * Proof-request artifact of a batch committed to l1-shadow-rollup but not proven yet.
+     */
+    pub async fn prove_artifact(&self, batch_index: u64) -> Result<ProveArtifact, anyhow::Error> {
+        let batch = self
+            .committed_unproven_batch(batch_index)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("batch {} is already proven", batch_index))?;
+        let store = self
+            .l1_shadow_rollup
+            .committedBatchStores(U256::from(batch_index))
+            .block(self.block)
+            .call()
+            .await?;
+        let layer2_chain_id =
+            self.l1_shadow_rollup.layer2ChainId().block(self.block).call().await?._0;
+
+        Ok(ProveArtifact::new(
+            *self.l1_shadow_rollup.address(),
+            layer2_chain_id,
+            batch,
+            store.into(),
+        ))
+    }
+
+    async fn commit_batch(
+        &self,
+        batch_info: BatchInfo,
+        batch_header: Bytes,
+        decision: &mut Decision,
+    ) -> Result<SyncOutcome, anyhow::Error> {
+        decision.batch_index = Some(batch_info.batch_index);
+        if self.proven_through.is_some_and(|index| batch_info.batch_index <= index) {
+            log::info!("batch {:?} is proven per the checkpoint", batch_info.batch_index);
+            metrics::inc(&METRICS.shadow_already_proven_total);
+            return Ok(SyncOutcome::AlreadyProven.record(decision, "proven per the checkpoint"));
+        }
+        // Batch should not have been verified yet. An unknown prove state is an error, so the
+        // batch is retried on the next tick rather than skipped as proven.
+        let proven =
+            is_prove_success(batch_info.batch_index, &self.l1_shadow_rollup, BlockId::latest())
+                .await
+                .with_context(|| {
+                    format!("query prove state of batch {} error", batch_info.batch_index)
+                })?;
+        if proven {
+            self.committed_unproven.lock().unwrap().remove(&batch_info.batch_index);
             log::debug!("batch of {:?} already prove state successful", batch_info.batch_index);
-            return Ok(None);
+            metrics::inc(&METRICS.shadow_already_proven_total);
+            return Ok(SyncOutcome::AlreadyProven.record(decision, "already proven"));
         };
+        // A batch whose prove failed after its commit is proven again without a second commit,
+        // unless the shadow rollup no longer shows the commit, e.g. after a reorg.
+        if self.committed_unproven.lock().unwrap().contains(&batch_info.batch_index) {
+            let committed = self.is_committed(batch_info.batch_index).await.with_context(|| {
+                format!("query commit state of batch {} error", batch_info.batch_index)
+            })?;
+            if committed {
+                log::info!("{} is committed but not proven, skip its commit", batch_info);
+                return Ok(SyncOutcome::AlreadyCommitted(batch_info)
+                    .record(decision, "committed, not proven"));
+            }
+            log::warn!(
+                "{} is no longer committed to the shadow rollup, commit it again",
+                batch_info
+            );
+            self.committed_unproven.lock().unwrap().remove(&batch_info.batch_index);
+        }
 
-        // Assembling a batche of the same commitment.
-        #[rustfmt::skip]
-        //   Below is the encoding for `BatchHeader`, reference: morph-repo/contracts/contracts/libraries/codec/BatchHeaderCodecV1.sol
-        //    
-        //   * Field                   Bytes       Type        Index   Comments
-        //   * version                 1           uint8       0       The batch version
-        //   * batchIndex              8           uint64      1       The index of the batch
-        //   * l1MessagePopped         8           uint64      9       Number of L1 messages popped in the batch
-        //   * totalL1MessagePopped    8           uint64      17      Number of total L1 messages popped after the batch
-        //   * dataHash                32          bytes32     25      The data hash of the batch
-        //   * blobVersionedHash       32          bytes32     57      The versioned hash of the blob with this batch’s data
-        //   * prevStateHash           32          bytes32     89      Preview state root
-        //   * postStateHash           32          bytes32     121     Post state root
-        //   * withdrawRootHash        32          bytes32     153     L2 withdrawal tree root hash
-        //   * sequencerSetVerifyHash  32          bytes32     185     L2 sequencers set verify hash
-        //   * parentBatchHash         32          bytes32     217     The parent batch hash
-        //   * skippedL1MessageBitmap  dynamic     uint256[]   249     A bitmap to indicate which L1 messages are skipped in the batch
-        //   @dev Below is the feilds for `BatchHeader` V1
-        //   * lastBlockNumber         8           uint64      249     The last block number in this batch
-        // ```
-        let batch_store = ShadowRollup::BatchStore {
-            prevStateRoot: batch_header
-                .get(89..121)
-                .unwrap_or_default()
-                .try_into()
-                .unwrap_or_default(),
-            postStateRoot: batch_header
-                .get(121..153)
-                .unwrap_or_default()
-                .try_into()
-                .unwrap_or_default(),
-            withdrawalRoot: batch_header
-                .get(153..185)
-                .unwrap_or_default()
-                .try_into()
-                .unwrap_or_default(),
-            dataHash: batch_header.get(25..57).unwrap_or_default().try_into().unwrap_or_default(),
-            blobVersionedHash: batch_header
-                .get(57..89)
-                .unwrap_or_default()
-                .try_into()
-                .unwrap_or_default(),
-            sequencerSetVerifyHash: batch_header
-                .get(185..217)
-                .unwrap_or_default()
-                .try_into()
-                .unwrap_or_default(),
+        // Truncated headers, newer versions and versions other than the rollup's codec are
+        // refused rather than decoded into zeroed or misplaced roots.
+        let rollup_version = self.header_version.get().await;
+        let decoded =
+            BatchHeader::check_version(&batch_header, rollup_version).and_then(|version| {
+                batch_store_from_header(&batch_header).map(|store| (version, store))
+            });
+        let (version, batch_store) = match decoded {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                log::error!("decode header of batch {:?} error: {}", batch_info.batch_index, e);
+                return Ok(SyncOutcome::CommitFailed.record(decision, e.to_string()));
+            }
         };
+        decision.header_version = Some(version);
 
         log::info!(
-            "sync batch of {:?}, prevStateRoot = {:?}, postStateRoot = {:?}, withdrawalRoot = {:?},
+            "sync batch of {:?}, header version = {}, prevStateRoot = {:?}, postStateRoot = {:?}, withdrawalRoot = {:?},
             dataHash = {:?}, blobVersionedHash = {:?}, sequencerSetVerifyHash = {:?}",
             batch_info.batch_index,
+            version,
             alloy::hex::encode_prefixed(batch_store.prevStateRoot),
             alloy::hex::encode_prefixed(batch_store.postStateRoot),
             alloy::hex::encode_prefixed(batch_store.withdrawalRoot),
@@ -135,216 +499,2218 @@ where
             alloy::hex::encode_prefixed(batch_store.blobVersionedHash),
             alloy::hex::encode_prefixed(batch_store.sequencerSetVerifyHash),
         );
+        decision.roots = Some((&batch_store).into());
 
-        // Commit the shadow batch.
-        let shadow_tx = self.l1_shadow_rollup.commitBatch(batch_info.batch_index, batch_store);
+        if !verify_withdrawal_root(
+            &self.l2_provider,
+            &self.alarm,
+            self.config.verify_withdrawal_root,
+            &batch_info,
+            batch_store.withdrawalRoot,
+        )
+        .await
+        {
+            return Ok(SyncOutcome::CheckFailed.record(decision, "withdrawal root mismatch"));
+        }
+        if !verify_state_chain(
+            &self.l1_shadow_rollup,
+            &self.alarm,
+            self.config.verify_state_chain,
+            batch_info.batch_index,
+            batch_store.prevStateRoot,
+        )
+        .await
+        {
+            return Ok(SyncOutcome::CheckFailed.record(decision, "state root chain break"));
+        }
+
+        let circuit = self.circuit.circuit(Instant::now());
+        if circuit == Circuit::Open {
+            log::warn!("circuit open, skip commit of batch {:?}", batch_info.batch_index);
+            return Ok(SyncOutcome::CircuitOpen.record(decision, "circuit open"));
+        }
+
+        // Commit the shadow batch, holding an in-flight slot until the receipt is in.
+        let _inflight = self.inflight_commits.acquire(batch_info.batch_index).await;
+        let mut shadow_tx = self.l1_shadow_rollup.commitBatch(batch_info.batch_index, batch_store);
+        if let Some(sender) = self.sender {
+            shadow_tx = shadow_tx.from(sender);
+        }
+        if self.config.dry_run {
+            return Ok(match shadow_tx.call().await {
+                Ok(_) => {
+                    log::info!("dry run: commit_batch {:?} would succeed", batch_info.batch_index);
+                    SyncOutcome::Simulated(batch_info).record(decision, "dry run, commit simulated")
+                }
+                Err(e) => {
+                    let reason = revert::revert_reason(&e).unwrap_or_else(|| format!("{:#}", e));
+                    log::warn!(
+                        "dry run: commit_batch {:?} would revert: {}",
+                        batch_info.batch_index,
+                        reason
+                    );
+                    SyncOutcome::SimulationReverted.record(
+                        decision,
+                        format!("dry run, commit simulation reverted: {}", reason),
+                    )
+                }
+            });
+        }
+        // A commit that would revert, e.g. already committed, paused or unauthorized, costs
+        // its gas for nothing. A half-open circuit only closes on a successful simulation.
+        if self.config.simulate_before_send || circuit == Circuit::HalfOpen {
+            match shadow_tx.call().await {
+                Ok(_) if circuit == Circuit::HalfOpen => {
+                    self.circuit.reset("commit simulation succeeded");
+                }
+                Ok(_) => (),
+                Err(e) if circuit == Circuit::HalfOpen => {
+                    call_error("verify", &e);
+                    log::warn!("circuit half-open, simulation failed, skip send: {:#}", e);
+                    return Ok(SyncOutcome::CircuitOpen.record(decision, "circuit open"));
+                }
+                Err(e) => match revert::revert_reason(&e) {
+                    Some(reason) => {
+                        log::error!(
+                            "simulation of shadow_rollup.commit_batch {:?} reverted, skip send: {}",
+                            batch_info.batch_index,
+                            reason
+                        );
+                        metrics::inc(&METRICS.shadow_commit_simulation_reverted_total);
+                        return Ok(SyncOutcome::SimulationReverted
+                            .record(decision, format!("commit simulation reverted: {}", reason)));
+                    }
+                    // Not conclusive, the send tells.
+                    None => {
+                        log::warn!("simulate shadow_rollup.commit_batch error: {:#}", e);
+                        metrics::rpc_error("verify", "eth_call");
+                    }
+                },
+            }
+        }
+        let sent_at = Instant::now();
         let rt = shadow_tx.send().await;
         let pending_tx = match rt {
             Ok(pending_tx) => pending_tx,
             Err(e) => {
                 log::error!("send tx of shadow_rollup.commit_batch error: {:#?}", e);
-                return Ok(None);
+                match revert::revert_reason(&e) {
+                    Some(reason) => self.circuit.revert(&reason, Instant::now()),
+                    None => metrics::rpc_error("verify", "eth_sendRawTransaction"),
+                }
+                return Ok(SyncOutcome::CommitFailed
+                    .record(decision, format!("send tx of commit_batch error: {}", e)));
+            }
+        };
+        // A tx dropped by the node or the private relay must not block the loop, one stuck
+        // under congestion is replaced with bumped fees. Any of the sent txs may get mined.
+        let timeout = self.config.commit_receipt_timeout;
+        let mut pending_tx = pending_tx;
+        let mut sent = Vec::new();
+        let receipt = loop {
+            let tx_hash = *pending_tx.tx_hash();
+            sent.push(tx_hash);
+            match tokio::time::timeout(timeout, pending_tx.get_receipt()).await {
+                Ok(Ok(receipt)) => break receipt,
+                Ok(Err(e)) => {
+                    log::error!("get receipt of shadow_rollup.commit_batch error: {:#?}", e);
+                    metrics::inc(&METRICS.shadow_commit_receipt_failures_total);
+                    metrics::rpc_error("verify", "eth_getTransactionReceipt");
+                    decision.tx_hash = Some(tx_hash);
+                    return Ok(SyncOutcome::CommitFailed
+                        .record(decision, format!("get receipt of commit_batch error: {}", e)));
+                }
+                Err(_) => log::warn!(
+                    "no receipt of shadow_rollup.commit_batch {:?} after {:?}",
+                    tx_hash,
+                    timeout
+                ),
             }
+            if let Some(receipt) = self.mined_receipt(&sent).await {
+                break receipt;
+            }
+            let replacement = if sent.len() > self.config.commit_fee_bumps as usize {
+                Err(format!("no receipt of commit_batch after {:?}", timeout))
+            } else {
+                self.bumped_fees(tx_hash).await
+            };
+            let replacement = match replacement {
+                Ok((nonce, fees)) => fees.apply(shadow_tx.clone().nonce(nonce)).send().await,
+                Err(reason) => {
+                    log::error!("shadow_rollup.commit_batch {:?}: {}", tx_hash, reason);
+                    metrics::inc(&METRICS.shadow_commit_receipt_failures_total);
+                    decision.tx_hash = Some(tx_hash);
+                    return Ok(SyncOutcome::CommitFailed.record(decision, reason));
+                }
+            };
+            pending_tx = match replacement {
+                Ok(pending_tx) => pending_tx,
+                Err(e) => {
+                    log::error!("send replacement of shadow_rollup.commit_batch error: {:#?}", e);
+                    metrics::inc(&METRICS.shadow_commit_receipt_failures_total);
+                    decision.tx_hash = Some(tx_hash);
+                    return Ok(SyncOutcome::CommitFailed.record(
+                        decision,
+                        format!("send replacement of commit_batch error: {}", e),
+                    ));
+                }
+            };
+            log::info!(
+                "replaced shadow_rollup.commit_batch {:?} by {:?}",
+                tx_hash,
+                pending_tx.tx_hash()
+            );
+            metrics::inc(&METRICS.shadow_commit_replacements_total);
         };
-        let receipt = pending_tx.get_receipt().await.unwrap();
+        metrics::observe_duration(&METRICS.shadow_commit_duration_seconds, sent_at.elapsed());
         if !receipt.status() {
-            log::error!("shadow_rollup.commit_batch check_receipt fail");
-            return Ok(None);
+            let replay = shadow_tx.block(revert::replay_block(receipt.block_number())).call().await;
+            let reason = format!("commit_batch tx failed: {}", revert::replay_reason(&replay));
+            log::error!("shadow_rollup.commit_batch {:?}: {}", receipt.transaction_hash(), reason);
+            self.circuit.revert(&reason, Instant::now());
+            decision.tx_hash = Some(receipt.transaction_hash());
+            return Ok(SyncOutcome::CommitFailed.record(decision, reason));
         }
+        self.circuit.success();
+        decision.commit(receipt.transaction_hash());
+        self.record_committed(batch_info.batch_index);
 
-        log::info!(">Sync shadow batch complete: {:#?}", batch_info.batch_index);
-        Ok(Some(batch_info))
+        log::info!(">Sync shadow batch complete: {}", batch_info);
+        Ok(SyncOutcome::Committed(batch_info))
     }
-}
 
-async fn get_committed_batch<T, P, N>(
-    latest: U64,
-    l1_rollup: &RollupInstance<T, P, N>,
-    l1_provider: &RootProvider<Http<Client>>,
-    l2_provider: &RootProvider<Http<Client>>,
-) -> Result<Option<(BatchInfo, Bytes)>, String>
-where
-    P: Provider<T, N> + Clone,
-    T: Transport + Clone,
-    N: Network,
-{
-    log::info!("latest l1 blocknum = {:#?}", latest);
-    let start = if latest > U64::from(600) { latest - U64::from(600) } else { U64::from(1) };
-    let filter =
-        l1_rollup.CommitBatch_filter().filter.from_block(start).address(*l1_rollup.address());
-    let mut logs: Vec<Log> = match l1_provider.get_logs(&filter).await {
-        Ok(logs) => logs,
-        Err(e) => {
-            log::error!("l1_rollup.commit_batch.get_logs error: {:#?}", e);
-            return Err("l1_rollup.commit_batch.get_logs provider error".to_string());
+    /// Receipt of the first mined of the `commitBatch` txs sharing a nonce.
+    async fn mined_receipt(&self, tx_hashes: &[TxHash]) -> Option<N::ReceiptResponse> {
+        for tx_hash in tx_hashes {
+            match self.l1_shadow_rollup.provider().get_transaction_receipt(*tx_hash).await {
+                Ok(Some(receipt)) => return Some(receipt),
+                Ok(None) => (),
+                Err(e) => {
+                    log::warn!("get receipt of {:?} error: {}", tx_hash, e);
+                    metrics::rpc_error("verify", "eth_getTransactionReceipt");
+                }
+            }
         }
-    };
-    if logs.is_empty() {
-        log::warn!("There have been no commit_batch logs for the last 600 blocks");
-        return Ok(None);
+        None
     }
-    if logs.len() < 3 {
-        log::warn!("No enough commit_batch logs for the last 600 blocks");
-        return Ok(None);
+
+    /// Nonce and fees of a replacement of the pending `commitBatch` tx `tx_hash`.
+    async fn bumped_fees(&self, tx_hash: TxHash) -> Result<(u64, Fees), String> {
+        let tx = self
+            .l1_shadow_rollup
+            .provider()
+            .get_transaction_by_hash(tx_hash)
+            .await
+            .map_err(|e| format!("get commit_batch tx error: {}", e))?
+            .ok_or_else(|| String::from("no receipt of commit_batch, tx dropped by the node"))?;
+        let fees = match tx.max_priority_fee_per_gas() {
+            Some(tip) => Fees::Eip1559 {
+                max_fee_per_gas: tx.max_fee_per_gas(),
+                max_priority_fee_per_gas: tip,
+            },
+            None => Fees::Legacy { gas_price: tx.gas_price().unwrap_or(tx.max_fee_per_gas()) },
+        };
+        let cap = self.config.commit_max_fee_per_gas;
+        let bumped = fees.bump(self.config.commit_fee_bump_percent, cap).ok_or_else(|| {
+            format!("no receipt of commit_batch, fees at the cap of {} gwei", cap / 1_000_000_000)
+        })?;
+        Ok((tx.nonce(), bumped))
     }
-    logs.sort_by(|a, b| a.block_number.unwrap().cmp(&b.block_number.unwrap()));
+}
 
-    let batch_index = match logs.get(logs.len() - 2) {
-        Some(log) => {
-            let _index = U256::from_be_slice(log.topics()[1].as_slice());
-            _index.to::<u64>()
-        }
-        None => {
-            return Err("find commit_batch log error".to_string());
+/// Fees per gas of a `commitBatch` tx.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Fees {
+    Eip1559 { max_fee_per_gas: u128, max_priority_fee_per_gas: u128 },
+    Legacy { gas_price: u128 },
+}
+
+impl Fees {
+    /// Fees `percent` higher, the max fee at most `cap`; None when the cap leaves no room.
+    fn bump(self, percent: u64, cap: u128) -> Option<Self> {
+        let bump = |fee: u128| fee + (fee * percent as u128 / 100).max(1);
+        match self {
+            Self::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => {
+                let max_fee = bump(max_fee_per_gas).min(cap);
+                let tip = bump(max_priority_fee_per_gas).min(max_fee);
+                (max_fee > max_fee_per_gas).then_some(Self::Eip1559 {
+                    max_fee_per_gas: max_fee,
+                    max_priority_fee_per_gas: tip,
+                })
+            }
+            Self::Legacy { gas_price } => {
+                let bumped = bump(gas_price).min(cap);
+                (bumped > gas_price).then_some(Self::Legacy { gas_price: bumped })
+            }
         }
-    };
+    }
 
-    if batch_index == 0 {
-        return Err(String::from("batch_index is 0"));
+    fn apply<T, P, D, N>(self, call: CallBuilder<T, P, D, N>) -> CallBuilder<T, P, D, N>
+    where
+        T: Transport + Clone,
+        P: Provider<T, N>,
+        D: CallDecoder,
+        N: Network,
+    {
+        match self {
+            Self::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => call
+                .max_fee_per_gas(max_fee_per_gas)
+                .max_priority_fee_per_gas(max_priority_fee_per_gas),
+            Self::Legacy { gas_price } => call.gas_price(gas_price),
+        }
     }
-    let (blocks, total_txn_count) =
-        match batch_blocks_inspect(l1_rollup, l2_provider, batch_index).await {
-            Some(block_txn) => block_txn,
-            None => return Err(String::from("batch_blocks_inspect none")),
-        };
+}
+
+/// Bounds the number of `commitBatch` transactions sent but not yet confirmed, so concurrent
+/// commits cannot run away with the wallet's nonces.
+#[derive(Clone, Debug)]
+pub struct InflightCommits {
+    slots: Arc<Semaphore>,
+}
 
-    if blocks.0 <= blocks.1 {
-        return Err(String::from("blocks is empty"));
+impl InflightCommits {
+    pub fn new(max: usize) -> Self {
+        Self { slots: Arc::new(Semaphore::new(max.max(1))) }
     }
 
-    if blocks.1 - blocks.0 + 1 > read_env_var("SHADOW_PROVING_MAX_BLOCK", 300) {
-        log::warn!("Too many blocks in the latest batch to shadow prove");
-        return Ok(None);
+    /// Wait for a free slot; it is released when the returned guard is dropped.
+    pub async fn acquire(&self, batch_index: u64) -> InflightCommit {
+        let permit = match self.slots.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                log::info!("commit of batch {} waits for an in-flight slot", batch_index);
+                self.slots.clone().acquire_owned().await.expect("commit slots are never closed")
+            }
+        };
+        METRICS.shadow_inflight_commits.inc();
+        InflightCommit { _permit: permit }
     }
+}
 
-    if total_txn_count > read_env_var("SHADOW_PROVING_MAX_TXN", 600) {
-        log::warn!("Too many txn in the latest batch to shadow prove");
-        return Ok(None);
+/// An occupied [`InflightCommits`] slot.
+#[derive(Debug)]
+pub struct InflightCommit {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for InflightCommit {
+    fn drop(&mut self) {
+        METRICS.shadow_inflight_commits.dec();
     }
+}
 
-    let batch_info: BatchInfo =
-        BatchInfo { batch_index, start_block: blocks.0, end_block: blocks.1 };
+/// What [`BatchSyncer::sync_batch`] did, labelling `shadow_sync_outcomes_total`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// The batch was committed to the shadow rollup.
+    Committed(BatchInfo),
+    /// The commit of the batch was simulated in a dry run, nothing was sent.
+    Simulated(BatchInfo),
+    /// The batch was committed to the shadow rollup before but is not proven yet.
+    AlreadyCommitted(BatchInfo),
+    /// The L1 node is behind a block seen before.
+    StaleNode,
+    /// Too few commit logs in the lookback window to select a batch.
+    NoBatch,
+    AlreadyProven,
+    TooManyBlocks,
+    TooManyTxns,
+    /// Refused by a check: sequencer, withdrawal root or state root chain.
+    CheckFailed,
+    CircuitOpen,
+    SimulationReverted,
+    /// Outside `SHADOW_PROVING_MIN_BATCH..=SHADOW_PROVING_MAX_BATCH`.
+    OutOfRange,
+    /// Reading the committed batch failed.
+    InspectFailed,
+    /// Decoding its header, sending the commit or getting its receipt failed.
+    CommitFailed,
+}
 
-    // A rollup commit_batch_input contains prev batch_header.
-    let next_tx_hash = match logs.last() {
-        Some(log) => log.transaction_hash.unwrap_or_default(),
+impl SyncOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Committed(_) => "committed",
+            Self::Simulated(_) => "simulated",
+            Self::AlreadyCommitted(_) => "already_committed",
+            Self::StaleNode => "stale_node",
+            Self::NoBatch => "no_batch",
+            Self::AlreadyProven => "already_proven",
+            Self::TooManyBlocks => "too_many_blocks",
+            Self::TooManyTxns => "too_many_txns",
+            Self::CheckFailed => "check_failed",
+            Self::CircuitOpen => "circuit_open",
+            Self::SimulationReverted => "simulation_reverted",
+            Self::OutOfRange => "out_of_range",
+            Self::InspectFailed => "inspect_failed",
+            Self::CommitFailed => "commit_failed",
+        }
+    }
 
-        None => {
-            return Err("find commit_batch log error".to_string());
+    /// Record a batch not committed in `decision`, as failed or skipped with `reason`.
+    fn record(self, decision: &mut Decision, reason: impl Into<String>) -> Self {
+        match self {
+            Self::InspectFailed | Self::CommitFailed => decision.fail(reason),
+            _ => decision.skip(reason),
         }
-    };
-    let batch_header = batch_header_inspect(l1_provider, next_tx_hash)
-        .await
-        .ok_or_else(|| "Failed to inspect batch header".to_string())?;
+        self
+    }
+}
 
-    log::info!("Found the committed batch, batch index = {:#?}", batch_index);
-    Ok(Some((batch_info, batch_header)))
+/// Named construction of a [`BatchSyncer`]: the rollup and shadow rollup addresses, like the L1
+/// and L2 providers, share a type and are easily swapped as positional arguments.
+#[derive(Debug)]
+pub struct BatchSyncerBuilder<P> {
+    rollup: Option<Address>,
+    shadow_rollup: Option<Address>,
+    l1_provider: Option<RootProvider<FailoverTransport>>,
+    l2_provider: Option<RootProvider<FailoverTransport>>,
+    wallet: Option<P>,
+    config: Option<Config>,
 }
 
-pub async fn batch_header_inspect(
-    l1_provider: &RootProvider<Http<Client>>,
-    hash: TxHash,
-) -> Option<Bytes> {
-    //Step1.  Get transaction
-    let result = l1_provider.get_transaction_by_hash(hash).await;
-    let tx = match result {
-        Ok(Some(tx)) => tx,
-        Ok(None) => {
-            log::error!("l1_provider.get_transaction is none");
-            return None;
-        }
-        Err(e) => {
-            log::error!("l1_provider.get_transaction err: {:#?}", e);
-            return None;
+impl<P> Default for BatchSyncerBuilder<P> {
+    fn default() -> Self {
+        Self {
+            rollup: None,
+            shadow_rollup: None,
+            l1_provider: None,
+            l2_provider: None,
+            wallet: None,
+            config: None,
         }
-    };
+    }
+}
 
-    //Step2. Parse transaction data
-    let data = tx.input();
+impl<P> BatchSyncerBuilder<P> {
+    /// The rollup the committed batches are read from.
+    pub fn rollup(mut self, address: Address) -> Self {
+        self.rollup = Some(address);
+        self
+    }
 
-    if data.is_empty() {
-        log::warn!("batch inspect: tx.input is empty, tx_hash =  {:#?}", hash);
-        return None;
+    /// The shadow rollup the batches are committed to.
+    pub fn shadow_rollup(mut self, address: Address) -> Self {
+        self.shadow_rollup = Some(address);
+        self
+    }
+
+    /// The L1 the rollup is read from.
+    pub fn l1_provider(mut self, provider: RootProvider<FailoverTransport>) -> Self {
+        self.l1_provider = Some(provider);
+        self
+    }
+
+    /// The L2 the blocks of the batches are read from.
+    pub fn l2_provider(mut self, provider: RootProvider<FailoverTransport>) -> Self {
+        self.l2_provider = Some(provider);
+        self
+    }
+
+    /// The signing provider the shadow rollup is written with.
+    pub fn wallet(mut self, wallet: P) -> Self {
+        self.wallet = Some(wallet);
+        self
+    }
+
+    /// The configuration the syncer runs with.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// The syncer, failing on the first field not set.
+    pub fn build<T, N>(self) -> Result<BatchSyncer<T, P, N>, anyhow::Error>
+    where
+        P: Provider<T, N> + Clone,
+        T: Transport + Clone,
+        N: Network,
+    {
+        let missing = |field: &str| anyhow::anyhow!("BatchSyncer {} is not set", field);
+        Ok(BatchSyncer::from_parts(
+            self.rollup.ok_or_else(|| missing("rollup"))?,
+            self.shadow_rollup.ok_or_else(|| missing("shadow_rollup"))?,
+            self.l1_provider.ok_or_else(|| missing("l1_provider"))?,
+            self.l2_provider.ok_or_else(|| missing("l2_provider"))?,
+            self.wallet.ok_or_else(|| missing("wallet"))?,
+            self.config.ok_or_else(|| missing("config"))?,
+        ))
     }
-    let param = if let Ok(_param) = Rollup::commitBatchCall::abi_decode(&data, false) {
-        _param
-    } else {
-        log::error!("batch inspect: decode tx.input error, tx_hash =  {:#?}", hash);
-        return None;
-    };
-    let parent_batch_header: Bytes = param.batchDataInput.parentBatchHeader;
-    Some(parent_batch_header)
 }
 
-async fn batch_blocks_inspect<T, P, N>(
+/// A committed batch and the commit tx of its successor, whose input carries the batch header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchTarget {
+    pub batch_index: u64,
+    /// The commit tx of the batch itself, carrying its blob.
+    pub tx_hash: TxHash,
+    pub next_tx_hash: TxHash,
+}
+
+async fn get_committed_batch<T, P, N>(
+    latest: u64,
+    window: &CommitLogWindow,
     l1_rollup: &RollupInstance<T, P, N>,
-    l2_provider: &RootProvider<Http<Client>>,
-    batch_index: u64,
-) -> Option<((u64, u64), u64)>
+    l1_provider: &RootProvider<FailoverTransport>,
+    l2_provider: &RootProvider<FailoverTransport>,
+    config: &Config,
+    decision: &mut Decision,
+) -> Result<(BatchInfo, Bytes), SyncOutcome>
 where
     P: Provider<T, N> + Clone,
     T: Transport + Clone,
     N: Network,
 {
-    let prev_bn = match l1_rollup.batchDataStore(U256::from(batch_index - 1)).call().await {
-        Ok(s) => s.blockNumber.to::<u64>(),
-        Err(e) => {
-            log::error!("l1_rollup.batch_data_store err: {:#?}", e);
-            return None;
-        }
+    let logs = match fetch_commit_logs(latest, window, l1_rollup, l1_provider, config).await {
+        Ok(logs) => logs,
+        Err(e) => return Err(inspect_failed(decision, e.to_string())),
     };
+    if logs.is_empty() {
+        log::warn!(
+            "There have been no commit_batch logs for the last {} blocks",
+            config.lookback_blocks
+        );
+        metrics::inc(&METRICS.shadow_no_logs_total);
+        return Err(
+            SyncOutcome::NoBatch.record(decision, "no commit_batch logs in the lookback window")
+        );
+    }
+    if (logs.len() as u64) < config.batch_buffer + 2 {
+        log::warn!(
+            "{} commit_batch logs for the last {} blocks, {} needed to select a batch {} behind \
+             the newest (SHADOW_PROVING_BATCH_BUFFER)",
+            logs.len(),
+            config.lookback_blocks,
+            config.batch_buffer + 2,
+            config.batch_buffer
+        );
+        metrics::inc(&METRICS.shadow_too_few_logs_total);
+        return Err(SyncOutcome::NoBatch
+            .record(decision, "not enough commit_batch logs in the lookback window"));
+    }
 
-    let current_bn = match l1_rollup.batchDataStore(U256::from(batch_index)).call().await {
-        Ok(s) => s.blockNumber.to::<u64>(),
-        Err(e) => {
-            log::error!("l1_rollup.batch_data_store err: {:#?}", e);
-            return None;
-        }
+    let target = match select_target(&logs, config.batch_buffer) {
+        Some(target) => target,
+        None => return Err(inspect_failed(decision, "find commit_batch log error")),
     };
-
-    let mut total_tx_count: u64 = 0;
-    for i in prev_bn + 1..current_bn + 1 {
-        total_tx_count += l2_provider
-            .get_block_transaction_count_by_number(i.into())
-            .await
-            .unwrap_or_default()
-            .unwrap_or_default();
+    if !config.batch_in_range(target.batch_index) {
+        log::info!(
+            "skip batch {:?}, outside the batch range {:?}..={:?}",
+            target.batch_index,
+            config.min_batch,
+            config.max_batch
+        );
+        decision.batch_index = Some(target.batch_index);
+        return Err(SyncOutcome::OutOfRange.record(decision, "outside the batch range"));
     }
 
-    log::info!(
-        "decode_blocks, blocks_len: {:#?}, start_block: {:#?}, txn_in_batch: {:?}",
-        current_bn - prev_bn,
-        prev_bn + 1,
-        total_tx_count
-    );
+    inspect_committed_batch(&target, l1_rollup, l1_provider, l2_provider, config, decision).await
+}
+
+/// The newest committed batch minus `buffer`, paired with its successor's commit tx.
+///
+/// A rollup commit_batch_input contains prev batch_header, so the buffer is at least 1.
+fn select_target(logs: &[Log], buffer: u64) -> Option<BatchTarget> {
+    let newest = logs.last().and_then(commit_log_batch_index)?;
+    let batch_index = newest.checked_sub(buffer.max(1))?;
+    batch_targets(logs).into_iter().find(|target| target.batch_index == batch_index)
+}
+
+/// Blocks fetched again on every scan, their logs replacing the ones kept; two epochs, the
+/// finalization depth of Ethereum.
+const RESCAN_BLOCKS: u64 = 64;
 
-    METRICS.shadow_txn_len.set(total_tx_count as i64);
+/// `CommitBatch` logs of the lookback window kept across ticks, so that a scan only fetches the
+/// blocks since the previous one, and the last [`RESCAN_BLOCKS`] again to drop reorged logs. The
+/// first scan, e.g. after a restart, fetches the whole window.
+#[derive(Clone, Debug, Default)]
+struct CommitLogWindow(Arc<Mutex<Option<ScannedLogs>>>);
 
-    Some(((prev_bn + 1, current_bn), total_tx_count))
+#[derive(Debug)]
+struct ScannedLogs {
+    to_block: u64,
+    /// Sorted, not deduped.
+    logs: Vec<Log>,
 }
 
-async fn is_prove_success<T, P, N>(
-    batch_index: u64,
-    l1_rollup: &ShadowRollupInstance<T, P, N>,
-) -> Option<bool>
-where
-    P: Provider<T, N> + Clone,
-    T: Transport + Clone,
-    N: Network,
-{
-    let is_prove_success: bool =
-        match l1_rollup.isProveSuccess(U256::from(batch_index)).call().await {
-            Ok(x) => x._0,
-            Err(e) => {
-                log::info!(
-                    "query l1_shadow_rollup.is_prove_success error, batch index = {:#?}, {:#?}",
-                    batch_index,
+impl CommitLogWindow {
+    /// First block to fetch of the window `start..=latest`, with the logs kept before it.
+    fn resume(&self, start: u64, latest: u64) -> (u64, Vec<Log>) {
+        let scanned = self.0.lock().unwrap();
+        let Some(scanned) = scanned.as_ref() else {
+            return (start, Vec::new());
+        };
+        let from = scanned.to_block.saturating_sub(RESCAN_BLOCKS).max(start).min(latest);
+        let kept = scanned
+            .logs
+            .iter()
+            .filter(|log| log.block_number.is_some_and(|bn| bn >= start && bn < from))
+            .cloned()
+            .collect();
+        (from, kept)
+    }
+
+    fn record(&self, to_block: u64, logs: &[Log]) {
+        *self.0.lock().unwrap() = Some(ScannedLogs { to_block, logs: logs.to_vec() });
+    }
+}
+
+/// Inclusive block ranges of at most `size` blocks covering `from..=to`, a single one for a
+/// zero `size`.
+fn log_chunks(from: u64, to: u64, size: u64) -> Vec<(u64, u64)> {
+    if size == 0 {
+        return vec![(from, to)];
+    }
+    (from..=to)
+        .step_by(size as usize)
+        .map(|start| (start, start.saturating_add(size - 1).min(to)))
+        .collect()
+}
+
+/// Sorted `CommitBatch` logs of the lookback window, fetched in chunks of
+/// `config.log_chunk_blocks` blocks.
+async fn fetch_commit_logs<T, P, N>(
+    latest: u64,
+    window: &CommitLogWindow,
+    l1_rollup: &RollupInstance<T, P, N>,
+    l1_provider: &RootProvider<FailoverTransport>,
+    config: &Config,
+) -> Result<Vec<Log>, ShadowError>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    log::info!("latest l1 blocknum = {:#?}", latest);
+    // The newer logs may still be reorged out.
+    let latest = latest.saturating_sub(config.confirmations);
+    let start = block_number::lookback_start(latest, config.lookback_blocks);
+    let (from, mut kept) = window.resume(start, latest);
+    let mut fetched = Vec::new();
+    for (chunk_from, chunk_to) in log_chunks(from, latest, config.log_chunk_blocks) {
+        let filter = l1_rollup
+            .CommitBatch_filter()
+            .filter
+            .from_block(chunk_from)
+            .to_block(chunk_to)
+            .address(*l1_rollup.address());
+        let get_logs = || rate_limit::get_logs(l1_provider, &filter, config);
+        match util::retry_with_backoff(
+            "eth_getLogs",
+            config.rpc_backoff(),
+            rate_limit::is_transient,
+            get_logs,
+        )
+        .await
+        {
+            Ok(logs) => fetched.extend(logs),
+            Err(e) => {
+                log::error!(
+                    "l1_rollup.commit_batch.get_logs of blocks {}..={} error: {:#?}",
+                    chunk_from,
+                    chunk_to,
+                    e
+                );
+                metrics::rpc_error("l1", "eth_getLogs");
+                // The chunks fetched are kept, the next scan resumes after them.
+                if chunk_from > from {
+                    kept.extend(sort_commit_logs(fetched));
+                    window.record(chunk_from - 1, &kept);
+                }
+                return Err(ShadowError::GetLogs(format!("{:#}", e)));
+            }
+        }
+    }
+    // The kept logs all precede the fetched ones.
+    kept.extend(sort_commit_logs(fetched));
+    let logs = kept;
+    window.record(latest, &logs);
+    if config.dedupe_commit_logs {
+        return Ok(dedupe_commit_logs(logs));
+    }
+    Ok(logs)
+}
+
+/// Inspect the blocks and header of a committed batch, applying the size limits.
+async fn inspect_committed_batch<T, P, N>(
+    target: &BatchTarget,
+    l1_rollup: &RollupInstance<T, P, N>,
+    l1_provider: &RootProvider<FailoverTransport>,
+    l2_provider: &RootProvider<FailoverTransport>,
+    config: &Config,
+    decision: &mut Decision,
+) -> Result<(BatchInfo, Bytes), SyncOutcome>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let BatchTarget { batch_index, tx_hash, next_tx_hash } = *target;
+    decision.batch_index = Some(batch_index);
+    if batch_index == 0 {
+        return Err(inspect_failed(decision, "batch_index is 0"));
+    }
+    let (blocks, total_txn_count) = match inspect_batch_blocks(
+        l1_rollup,
+        l1_provider,
+        l2_provider,
+        batch_index,
+        config,
+        BlockId::latest(),
+    )
+    .await
+    {
+        Some(block_txn) => block_txn,
+        None => return Err(inspect_failed(decision, "batch_blocks_inspect none")),
+    };
+
+    if let Err(e) = check_block_range(batch_index, blocks) {
+        metrics::inc(&METRICS.shadow_empty_batch_total);
+        log::warn!("{}", e);
+        return Err(SyncOutcome::InspectFailed.record(decision, e.to_string()));
+    }
+
+    if blocks.1 - blocks.0 + 1 > config.max_block {
+        log::warn!("Too many blocks in the latest batch to shadow prove");
+        metrics::inc(&METRICS.shadow_too_many_blocks_total);
+        return Err(SyncOutcome::TooManyBlocks.record(decision, "too many blocks"));
+    }
+
+    if total_txn_count > config.max_txn {
+        log::warn!("Too many txn in the latest batch to shadow prove");
+        metrics::inc(&METRICS.shadow_too_many_txn_total);
+        return Err(SyncOutcome::TooManyTxns.record(decision, "too many txn"));
+    }
+
+    let size_class = SizeClass::classify(blocks.1 - blocks.0 + 1, total_txn_count, config);
+    let batch_info: BatchInfo =
+        BatchInfo { batch_index, start_block: blocks.0, end_block: blocks.1, size_class };
+
+    let batch_header = match batch_header_inspect(l1_provider, next_tx_hash).await {
+        Ok(batch_header) => batch_header,
+        Err(e) => return Err(inspect_failed(decision, e.to_string())),
+    };
+
+    if !verify_sequencer(
+        l1_provider,
+        config.verify_sequencer,
+        &config.sequencer_allowlist,
+        batch_index,
+        tx_hash,
+    )
+    .await
+    {
+        return Err(SyncOutcome::CheckFailed.record(decision, "unexpected sequencer"));
+    }
+    if !verify_blob_hash(l1_provider, config, batch_index, tx_hash, &batch_header).await {
+        return Err(SyncOutcome::CheckFailed.record(decision, "blob versioned hash mismatch"));
+    }
+
+    log::info!("Found the committed {}", batch_info);
+    Ok((batch_info, batch_header))
+}
+
+/// Record a failure of the read path in `decision`.
+fn inspect_failed(decision: &mut Decision, reason: impl Into<String>) -> SyncOutcome {
+    let reason = reason.into();
+    log::error!("inspect committed batch error: {}", reason);
+    SyncOutcome::InspectFailed.record(decision, reason)
+}
+
+/// A committed batch whose L2 block range is empty: its end block precedes its start block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmptyBatch {
+    pub batch_index: u64,
+    pub start_block: u64,
+    pub end_block: u64,
+}
+
+impl fmt::Display for EmptyBatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "blocks of batch {} is empty: start_block = {}, end_block = {}",
+            self.batch_index, self.start_block, self.end_block
+        )
+    }
+}
+
+impl std::error::Error for EmptyBatch {}
+
+/// The inclusive range `start..=end` holds at least one block iff `start <= end`.
+fn check_block_range(
+    batch_index: u64,
+    (start_block, end_block): (u64, u64),
+) -> Result<(), EmptyBatch> {
+    if start_block > end_block {
+        return Err(EmptyBatch { batch_index, start_block, end_block });
+    }
+    Ok(())
+}
+
+/// Pair every committed batch of the sorted logs with the commit tx of its successor.
+fn batch_targets(logs: &[Log]) -> Vec<BatchTarget> {
+    logs.windows(2)
+        .filter_map(|pair| {
+            let batch_index = commit_log_batch_index(&pair[0])?;
+            if commit_log_batch_index(&pair[1])? != batch_index + 1 {
+                return None;
+            }
+            Some(BatchTarget {
+                batch_index,
+                tx_hash: pair[0].transaction_hash?,
+                next_tx_hash: pair[1].transaction_hash?,
+            })
+        })
+        .collect()
+}
+
+/// Order `CommitBatch` logs by block, dropping pending logs that have no block number yet.
+fn sort_commit_logs(logs: Vec<Log>) -> Vec<Log> {
+    let mut logs: Vec<Log> = logs
+        .into_iter()
+        .filter(|log| {
+            if log.block_number.is_none() {
+                log::debug!("skip pending commit_batch log, tx_hash = {:?}", log.transaction_hash);
+            }
+            log.block_number.is_some()
+        })
+        .collect();
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    logs
+}
+
+/// Keep only the newest of the sorted `CommitBatch` logs sharing a batch index, as a
+/// reorg-and-replay can surface the same index twice at different blocks within the window.
+fn dedupe_commit_logs(logs: Vec<Log>) -> Vec<Log> {
+    let indexes: Vec<Option<u64>> = logs.iter().map(commit_log_batch_index).collect();
+    let mut newest: HashMap<u64, usize> = HashMap::new();
+    for (position, index) in indexes.iter().enumerate() {
+        if let Some(index) = index {
+            if let Some(prev) = newest.insert(*index, position) {
+                log::warn!(
+                    "duplicate commit_batch logs of batch {:?} at blocks {:?} and {:?}",
+                    index,
+                    logs[prev].block_number,
+                    logs[position].block_number
+                );
+            }
+        }
+    }
+
+    logs.into_iter()
+        .zip(indexes)
+        .enumerate()
+        .filter(|(position, (_, index))| index.map_or(true, |i| newest[&i] == *position))
+        .map(|(_, (log, _))| log)
+        .collect()
+}
+
+/// Batch index carried in the first indexed topic of a `CommitBatch` log.
+fn commit_log_batch_index(log: &Log) -> Option<u64> {
+    let topic = match log.topics().get(1) {
+        Some(topic) => topic,
+        None => {
+            log::warn!(
+                "commit_batch log without batch index topic, tx_hash = {:?}",
+                log.transaction_hash
+            );
+            return None;
+        }
+    };
+    U256::from_be_slice(topic.as_slice()).try_into().ok()
+}
+
+/// Header of a batch, decoded from the input of its commit transaction. Transactions are looked
+/// up by hash, so a batch syncer pinned to an L1 block reads the same header as any other.
+pub async fn batch_header_inspect(
+    l1_provider: &RootProvider<FailoverTransport>,
+    hash: TxHash,
+) -> Result<Bytes, ShadowError> {
+    //Step1.  Get transaction
+    let tx = l1_provider.get_transaction_by_hash(hash).await;
+    if tx.is_err() {
+        metrics::rpc_error("l1", "eth_getTransactionByHash");
+    }
+    let tx = tx?.ok_or(ShadowError::BatchNotFound(hash))?;
+
+    //Step2. Parse transaction data
+    let data = tx.input();
+
+    if data.is_empty() {
+        return Err(ShadowError::Decode(hash, String::from("tx.input is empty")));
+    }
+    let param = Rollup::commitBatchCall::abi_decode(data, false)
+        .map_err(|e| ShadowError::Decode(hash, e.to_string()))?;
+    let parent_batch_header: Bytes = param.batchDataInput.parentBatchHeader;
+    Ok(parent_batch_header)
+}
+
+/// Roots of a batch committed to the shadow rollup, decoded from its header.
+pub fn batch_store_from_header(header: &Bytes) -> Result<ShadowRollup::BatchStore, DecodeError> {
+    BatchHeader::decode(header).map(|header| ShadowRollup::BatchStore::from(&header))
+}
+
+/// Block range and transaction count of a batch, read through the batched getter when one is
+/// configured and answers, otherwise block by block. The rollup is read at the L1 `block`; the
+/// L2 blocks are fetched by number, whatever the L1 state.
+async fn inspect_batch_blocks<T, P, N>(
+    l1_rollup: &RollupInstance<T, P, N>,
+    l1_provider: &RootProvider<FailoverTransport>,
+    l2_provider: &RootProvider<FailoverTransport>,
+    batch_index: u64,
+    config: &Config,
+    block: BlockId,
+) -> Option<((u64, u64), u64)>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    if let Some(getter) = config.batch_blocks_getter {
+        if let Some(inspected) =
+            batch_blocks_getter_inspect(getter, l1_provider, batch_index, block).await
+        {
+            return Some(inspected);
+        }
+    }
+    batch_blocks_inspect(l1_rollup, l2_provider, batch_index, config, block).await
+}
+
+async fn batch_blocks_getter_inspect(
+    getter: Address,
+    l1_provider: &RootProvider<FailoverTransport>,
+    batch_index: u64,
+    block: BlockId,
+) -> Option<((u64, u64), u64)> {
+    let getter = BatchBlocksGetter::new(getter, l1_provider.clone());
+    let blocks = match getter.batchBlocks(U256::from(batch_index)).block(block).call().await {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            log::warn!("batch_blocks getter unavailable, fall back to block inspection: {:#?}", e);
+            call_error("l1", &e);
+            return None;
+        }
+    };
+
+    log::info!(
+        "decode_blocks by getter, start_block: {:#?}, end_block: {:#?}, txn_in_batch: {:?}",
+        blocks.startBlock,
+        blocks.endBlock,
+        blocks.txnCount
+    );
+    metrics::set(&METRICS.shadow_txn_len, blocks.txnCount as i64);
+
+    Some(((blocks.startBlock, blocks.endBlock), blocks.txnCount))
+}
+
+/// Block range and transaction count of a batch, counted block by block.
+///
+/// The counts are fetched `block_fetch_concurrency` at a time and summed as they arrive. Counting
+/// stops once the total exceeds `max_txn`: the batch is skipped anyway, and the returned count is
+/// then only a lower bound.
+async fn batch_blocks_inspect<T, P, N>(
+    l1_rollup: &RollupInstance<T, P, N>,
+    l2_provider: &RootProvider<FailoverTransport>,
+    batch_index: u64,
+    config: &Config,
+    block: BlockId,
+) -> Option<((u64, u64), u64)>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    // The genesis batch has no predecessor to take the start block from.
+    let Some(prev_index) = batch_index.checked_sub(1) else {
+        log::error!("batch 0 has no block range to inspect");
+        return None;
+    };
+    let (prev_bn, current_bn) =
+        match batch_end_blocks(l1_rollup, prev_index, batch_index, config, block).await {
+            Ok(bns) => bns,
+            Err(e) => {
+                log::error!(
+                    "l1_rollup.batch_data_store of batches {} and {} err: {:#?}",
+                    prev_index,
+                    batch_index,
                     e
                 );
+                if let Some(e) = e.downcast_ref::<contract::Error>() {
+                    call_error("l1", e);
+                }
                 return None;
             }
         };
-    Some(is_prove_success)
+
+    let (start_block, end_block) = block_number::batch_blocks(prev_bn, current_bn);
+    // Given up at the first error answer: a node without the method then costs one failed
+    // request per batch, not one per block.
+    let receipts = AtomicBool::new(config.block_receipts);
+    let mut counts = stream::iter(start_block..=end_block)
+        .map(|i| block_transaction_count(l2_provider, i, &receipts))
+        .buffered(config.block_fetch_concurrency.max(1));
+    let mut total_tx_count: u64 = 0;
+    while let Some(count) = counts.next().await {
+        total_tx_count = total_tx_count.saturating_add(count);
+        if total_tx_count > config.max_txn {
+            log::info!(
+                "batch {} has more than {} txns, stop counting at {}",
+                batch_index,
+                config.max_txn,
+                total_tx_count
+            );
+            break;
+        }
+    }
+
+    log::info!(
+        "decode_blocks, blocks_len: {:#?}, start_block: {:#?}, txn_in_batch: {:?}",
+        current_bn.saturating_sub(prev_bn),
+        start_block,
+        total_tx_count
+    );
+
+    metrics::set(&METRICS.shadow_txn_len, total_tx_count as i64);
+
+    Some(((start_block, end_block), total_tx_count))
+}
+
+/// L2 block of the genesis batch, batch 0.
+const GENESIS_BLOCK: u64 = 0;
+
+/// End blocks of the batches `prev_index` and `batch_index`, read in one `aggregate3` call when
+/// a Multicall3 contract is configured and answers, otherwise one `batchDataStore` call each.
+async fn batch_end_blocks<T, P, N>(
+    l1_rollup: &RollupInstance<T, P, N>,
+    prev_index: u64,
+    batch_index: u64,
+    config: &Config,
+    block: BlockId,
+) -> Result<(u64, u64), anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    // The genesis batch holds only the L2 genesis block, so batch 1 starts at block 1 whatever
+    // the store of batch 0 reads.
+    if prev_index == 0 {
+        return Ok((GENESIS_BLOCK, batch_data_store_block(l1_rollup, batch_index, block).await?));
+    }
+    if let Some(multicall) = config.multicall {
+        match multicall_end_blocks(multicall, l1_rollup, [prev_index, batch_index], block).await {
+            Ok(bns) => return Ok(bns),
+            Err(e) => log::warn!("multicall unavailable, fall back to a call per batch: {:#}", e),
+        }
+    }
+    Ok((
+        batch_data_store_block(l1_rollup, prev_index, block).await?,
+        batch_data_store_block(l1_rollup, batch_index, block).await?,
+    ))
+}
+
+async fn multicall_end_blocks<T, P, N>(
+    multicall: Address,
+    l1_rollup: &RollupInstance<T, P, N>,
+    batch_indexes: [u64; 2],
+    block: BlockId,
+) -> Result<(u64, u64), anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let calls = batch_indexes
+        .iter()
+        .map(|batch_index| Multicall3::Call3 {
+            target: *l1_rollup.address(),
+            allowFailure: false,
+            callData: Rollup::batchDataStoreCall { batchIndex: U256::from(*batch_index) }
+                .abi_encode()
+                .into(),
+        })
+        .collect();
+    let multicall = Multicall3::new(multicall, l1_rollup.provider().clone());
+    let results = multicall.aggregate3(calls).block(block).call().await?.returnData;
+    let [prev, current] = results.as_slice() else {
+        anyhow::bail!("{} results of 2 calls", results.len());
+    };
+    let end_block = |result: &Multicall3::Call3Result| -> Result<u64, anyhow::Error> {
+        let store = Rollup::batchDataStoreCall::abi_decode_returns(&result.returnData, true)?;
+        block_number::from_u256(store.blockNumber)
+    };
+    Ok((end_block(prev)?, end_block(current)?))
+}
+
+/// Transactions of the L2 block `number`, 0 when unknown. While `receipts` is set, they are
+/// counted from `eth_getBlockReceipts`, read as raw JSON as Morph receipts carry transaction types
+/// foreign to Ethereum; an error answer of the node unsets it.
+async fn block_transaction_count(
+    l2_provider: &RootProvider<FailoverTransport>,
+    number: u64,
+    receipts: &AtomicBool,
+) -> u64 {
+    if receipts.load(Ordering::Relaxed) {
+        let block_receipts = l2_provider
+            .raw_request::<_, Option<Vec<serde_json::Value>>>(
+                "eth_getBlockReceipts".into(),
+                (BlockNumberOrTag::Number(number),),
+            )
+            .await;
+        match block_receipts {
+            Ok(Some(block_receipts)) => return block_receipts.len() as u64,
+            Ok(None) => (),
+            Err(RpcError::ErrorResp(e)) => {
+                metrics::rpc_error("l2", "eth_getBlockReceipts");
+                if receipts.swap(false, Ordering::Relaxed) {
+                    log::info!(
+                        "eth_getBlockReceipts of block {} refused ({}), count the transactions \
+                         of the batch by block",
+                        number,
+                        e
+                    );
+                }
+            }
+            Err(e) => {
+                log::debug!("eth_getBlockReceipts of block {} err: {}", number, e);
+                metrics::rpc_error("l2", "eth_getBlockReceipts");
+            }
+        }
+    }
+    match l2_provider.get_block_transaction_count_by_number(number.into()).await {
+        Ok(count) => count.unwrap_or_default(),
+        Err(e) => {
+            log::warn!("transaction count of block {} err: {}", number, e);
+            metrics::rpc_error("l2", "eth_getBlockTransactionCountByNumber");
+            0
+        }
+    }
+}
+
+/// Last L2 block of a batch, as recorded by the rollup at the L1 `block`.
+async fn batch_data_store_block<T, P, N>(
+    l1_rollup: &RollupInstance<T, P, N>,
+    batch_index: u64,
+    block: BlockId,
+) -> Result<u64, anyhow::Error>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    let store = l1_rollup.batchDataStore(U256::from(batch_index)).block(block).call().await?;
+    block_number::from_u256(store.blockNumber)
+}
+
+/// Address of the `L2ToL1MessagePasser` predeploy on Morph L2.
+const L2_TO_L1_MESSAGE_PASSER: Address = address!("5300000000000000000000000000000000000001");
+
+/// Check the header's withdrawal root against `L2ToL1MessagePasser.messageRoot()` at the batch end
+/// block, which is what the node's derivation compares too. Returns whether the batch may be
+/// committed under the given mode.
+async fn verify_withdrawal_root<T, P, N>(
+    l2_provider: &RootProvider<FailoverTransport>,
+    alarm: &Alarm<T, P, N>,
+    mode: CheckMode,
+    batch_info: &BatchInfo,
+    withdrawal_root: B256,
+) -> bool
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    if mode == CheckMode::Off {
+        return true;
+    }
+
+    let message_passer = L2ToL1MessagePasser::new(L2_TO_L1_MESSAGE_PASSER, l2_provider.clone());
+    let l2_root = match message_passer
+        .messageRoot()
+        .block(BlockId::number(batch_info.end_block))
+        .call()
+        .await
+    {
+        Ok(root) => root._0,
+        Err(e) => {
+            log::error!("l2_to_l1_message_passer.message_root err: {:#?}", e);
+            return mode != CheckMode::Strict;
+        }
+    };
+
+    if l2_root != withdrawal_root {
+        log::error!(
+            "withdrawal root mismatch of batch {:?}: header = {:?}, l2 at block {:?} = {:?}",
+            batch_info.batch_index,
+            withdrawal_root,
+            batch_info.end_block,
+            l2_root
+        );
+        metrics::inc(&METRICS.shadow_withdrawal_root_mismatch_total);
+        alarm.raise(batch_info.batch_index, l2_root, withdrawal_root).await;
+        return mode != CheckMode::Strict;
+    }
+    true
+}
+
+/// Check the header's prevStateRoot against the postStateRoot the previous batch was proven with
+/// on the shadow rollup. Skipped when the previous batch is not proven there, there is nothing
+/// to chain to. Returns whether the batch may be committed under the given mode.
+async fn verify_state_chain<T, P, N>(
+    shadow_rollup: &ShadowRollupInstance<T, P, N>,
+    alarm: &Alarm<T, P, N>,
+    mode: CheckMode,
+    batch_index: u64,
+    prev_state_root: B256,
+) -> bool
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    if mode == CheckMode::Off || batch_index == 0 {
+        return true;
+    }
+
+    let parent_index = batch_index - 1;
+    match is_prove_success(parent_index, shadow_rollup, BlockId::latest()).await {
+        Ok(true) => (),
+        Ok(false) => {
+            log::debug!("batch {:?} is not proven, skip state chain check", parent_index);
+            return true;
+        }
+        Err(e) => {
+            log::error!("query prove state of batch {:?} error: {}", parent_index, e);
+            return mode != CheckMode::Strict;
+        }
+    }
+    let parent_post_state_root =
+        match shadow_rollup.committedBatchStores(U256::from(parent_index)).call().await {
+            Ok(store) => store.postStateRoot,
+            Err(e) => {
+                log::error!("shadow_rollup.committed_batch_stores err: {:#?}", e);
+                return mode != CheckMode::Strict;
+            }
+        };
+
+    if parent_post_state_root != prev_state_root {
+        log::error!(
+            "CRITICAL: state root chain break at batch {:?}: prevStateRoot = {:?}, \
+             postStateRoot of proven batch {:?} = {:?}",
+            batch_index,
+            prev_state_root,
+            parent_index,
+            parent_post_state_root
+        );
+        metrics::inc(&METRICS.shadow_state_root_chain_break_total);
+        alarm.raise(batch_index, parent_post_state_root, prev_state_root).await;
+        return mode != CheckMode::Strict;
+    }
+    true
+}
+
+/// Check that the batch's own commit transaction was sent by an allowlisted sequencer, not the
+/// successor's its header is read from. Skipped without an allowlist. Returns whether the batch may
+/// be committed under the given mode.
+async fn verify_sequencer(
+    l1_provider: &RootProvider<FailoverTransport>,
+    mode: CheckMode,
+    allowlist: &[Address],
+    batch_index: u64,
+    tx_hash: TxHash,
+) -> bool {
+    if mode == CheckMode::Off || allowlist.is_empty() {
+        return true;
+    }
+
+    let sender = match l1_provider.get_transaction_by_hash(tx_hash).await {
+        Ok(Some(tx)) => tx.from,
+        Ok(None) => {
+            log::error!("commit tx {:?} of batch {:?} not found", tx_hash, batch_index);
+            return mode != CheckMode::Strict;
+        }
+        Err(e) => {
+            log::error!("l1_provider.get_transaction err: {:#?}", e);
+            return mode != CheckMode::Strict;
+        }
+    };
+
+    if !allowlist.contains(&sender) {
+        log::error!(
+            "CRITICAL: batch {:?} committed by tx {:?} sent by {:?}, not an allowlisted sequencer",
+            batch_index,
+            tx_hash,
+            sender
+        );
+        metrics::inc(&METRICS.shadow_unexpected_sequencer_total);
+        return mode != CheckMode::Strict;
+    }
+    true
+}
+
+/// Check that the header's blobVersionedHash is one of the blobs of the batch's commit
+/// transaction, or the empty blob's for a commit without any, and, given a beacon node, that it
+/// serves a sidecar with that hash for the block the transaction was mined in. Returns whether the
+/// batch may be committed under the given mode.
+async fn verify_blob_hash(
+    l1_provider: &RootProvider<FailoverTransport>,
+    config: &Config,
+    batch_index: u64,
+    tx_hash: TxHash,
+    batch_header: &Bytes,
+) -> bool {
+    let mode = config.verify_blob_hash;
+    if mode == CheckMode::Off {
+        return true;
+    }
+    // An undecodable header is refused by the commit anyway.
+    let Ok(header) = BatchHeader::decode(batch_header) else {
+        return true;
+    };
+    let blob_hash = header.fields().blob_versioned_hash;
+    let mismatch = |reason: &str| {
+        log::error!(
+            "CRITICAL: blob versioned hash {:?} of batch {:?} {}, commit tx {:?}",
+            blob_hash,
+            batch_index,
+            reason,
+            tx_hash
+        );
+        metrics::inc(&METRICS.shadow_blob_hash_mismatch_total);
+        mode != CheckMode::Strict
+    };
+
+    let tx = match l1_provider.get_transaction_by_hash(tx_hash).await {
+        Ok(Some(tx)) => tx,
+        Ok(None) => {
+            log::error!("commit tx {:?} of batch {:?} not found", tx_hash, batch_index);
+            return mode != CheckMode::Strict;
+        }
+        Err(e) => {
+            log::error!("l1_provider.get_transaction err: {:#?}", e);
+            return mode != CheckMode::Strict;
+        }
+    };
+    let tx_blob_hashes = tx.blob_versioned_hashes().unwrap_or_default();
+    if tx_blob_hashes.is_empty() {
+        if blob_hash != EMPTY_BLOB_VERSIONED_HASH {
+            return mismatch("is not the empty blob of a commit tx without blobs");
+        }
+        return true;
+    }
+    if !tx_blob_hashes.contains(&blob_hash) {
+        return mismatch(&format!("is not among the commit tx blobs {:?}", tx_blob_hashes));
+    }
+
+    let Some(beacon_rpc) = config.beacon_rpc.as_deref() else {
+        return true;
+    };
+    let block = match tx.block_number {
+        Some(number) => {
+            l1_provider.get_block_by_number(number.into(), BlockTransactionsKind::Hashes).await
+        }
+        None => Ok(None),
+    };
+    let timestamp = match block {
+        Ok(Some(block)) => block.header.timestamp,
+        Ok(None) => {
+            log::error!("block of commit tx {:?} of batch {:?} not found", tx_hash, batch_index);
+            return mode != CheckMode::Strict;
+        }
+        Err(e) => {
+            log::error!("l1_provider.get_block err: {:#?}", e);
+            return mode != CheckMode::Strict;
+        }
+    };
+    let sidecar_hashes = match Beacon::new(beacon_rpc).blob_hashes_at(timestamp).await {
+        Ok(hashes) => hashes,
+        Err(e) => {
+            log::error!("fetch blob sidecars of batch {:?} error: {:#}", batch_index, e);
+            return mode != CheckMode::Strict;
+        }
+    };
+    if !sidecar_hashes.contains(&blob_hash) {
+        return mismatch(&format!("matches none of the blob sidecars {:?}", sidecar_hashes));
+    }
+    true
+}
+
+/// Fail fast when `signer` is not the shadow rollup owner, the only account allowed to
+/// `commitBatch`. Skipped when the rollup exposes no `owner()`.
+pub async fn check_authorized_signer(
+    shadow_rollup_address: Address,
+    l1_provider: &RootProvider<FailoverTransport>,
+    signer: Address,
+) -> Result<(), anyhow::Error> {
+    let shadow_rollup = ShadowRollup::new(shadow_rollup_address, l1_provider.clone());
+    let owner = match shadow_rollup.owner().call().await {
+        Ok(owner) => owner._0,
+        Err(e) => {
+            log::debug!("shadow_rollup.owner unavailable, skip signer authorization check: {}", e);
+            return Ok(());
+        }
+    };
+    if owner != signer {
+        anyhow::bail!(
+            "signer {:?} is not authorized on shadow rollup {:?}, its owner is {:?}",
+            signer,
+            shadow_rollup_address,
+            owner
+        );
+    }
+    log::info!("signer {:?} is the shadow rollup owner", signer);
+    Ok(())
+}
+
+async fn is_prove_success<T, P, N>(
+    batch_index: u64,
+    l1_rollup: &ShadowRollupInstance<T, P, N>,
+    block: BlockId,
+) -> Result<bool, ShadowError>
+where
+    P: Provider<T, N> + Clone,
+    T: Transport + Clone,
+    N: Network,
+{
+    match l1_rollup.isProveSuccess(U256::from(batch_index)).block(block).call().await {
+        Ok(proven) => Ok(proven._0),
+        Err(e) => {
+            call_error("verify", &e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Count a contract call to the `provider` RPC failing other than by a revert.
+fn call_error(provider: &str, error: &contract::Error) {
+    if revert::revert_reason(error).is_none() {
+        metrics::rpc_error(provider, "eth_call");
+    }
+}
+
+#[test]
+fn test_dedupe_commit_logs() {
+    use alloy::primitives::{LogData, B256};
+
+    let commit_log = |block_number: u64, index: u64| Log {
+        inner: alloy::primitives::Log {
+            address: Address::ZERO,
+            data: LogData::new_unchecked(
+                vec![B256::ZERO, B256::from(U256::from(index).to_be_bytes::<32>())],
+                Bytes::new(),
+            ),
+        },
+        block_number: Some(block_number),
+        transaction_hash: Some(B256::from(U256::from(block_number).to_be_bytes::<32>())),
+        ..Default::default()
+    };
+
+    // Batch 2 was replayed at block 12 after a reorg.
+    let logs = sort_commit_logs(vec![
+        commit_log(10, 1),
+        commit_log(11, 2),
+        commit_log(13, 3),
+        commit_log(12, 2),
+    ]);
+    let logs = dedupe_commit_logs(logs);
+
+    let selected: Vec<(u64, u64)> = logs
+        .iter()
+        .map(|log| (commit_log_batch_index(log).unwrap(), log.block_number.unwrap()))
+        .collect();
+    assert_eq!(selected, vec![(1, 10), (2, 12), (3, 13)]);
+}
+
+#[tokio::test]
+async fn test_fetch_commit_logs_window() {
+    use crate::mock_rpc::{commit_logs, MockRpc};
+    use serde_json::json;
+
+    // Batch n is committed at block 100 * n.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_getLogs" => commit_logs(params, |index| 100 * index),
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+    let config = Config { lookback_blocks: 600, ..Config::default() };
+    let window = CommitLogWindow::default();
+    let indexes = |logs: Vec<Log>| {
+        logs.iter().map(|log| commit_log_batch_index(log).unwrap()).collect::<Vec<_>>()
+    };
+
+    // The first scan fetches the whole window.
+    let logs = fetch_commit_logs(1000, &window, &rollup, &rpc.provider(), &config).await.unwrap();
+    assert_eq!(indexes(logs), vec![4, 5, 6, 7, 8, 9, 10]);
+    assert_eq!(rpc.requests("eth_getLogs")[0][0]["fromBlock"], json!("0x190"));
+
+    // The next one only the new blocks and the rescanned ones, the window moving on.
+    let logs = fetch_commit_logs(1250, &window, &rollup, &rpc.provider(), &config).await.unwrap();
+    assert_eq!(indexes(logs), vec![7, 8, 9, 10, 11, 12]);
+    assert_eq!(rpc.requests("eth_getLogs")[1][0]["fromBlock"], json!("0x3a8"));
+}
+
+#[tokio::test]
+async fn test_fetch_commit_logs_confirmations() {
+    use crate::mock_rpc::{commit_logs, MockRpc};
+    use serde_json::json;
+
+    // Batch n is committed at block 100 * n.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_getLogs" => commit_logs(params, |index| 100 * index),
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+    let config = Config { lookback_blocks: 600, confirmations: 250, ..Config::default() };
+
+    // The window ends 250 blocks behind the latest.
+    let window = CommitLogWindow::default();
+    let logs = fetch_commit_logs(1000, &window, &rollup, &rpc.provider(), &config).await.unwrap();
+    let indexes = logs.iter().map(|log| commit_log_batch_index(log).unwrap()).collect::<Vec<_>>();
+    assert_eq!(indexes, vec![2, 3, 4, 5, 6, 7]);
+    assert_eq!(rpc.requests("eth_getLogs")[0][0]["toBlock"], json!("0x2ee"));
+}
+
+#[test]
+fn test_log_chunks() {
+    assert_eq!(log_chunks(400, 1000, 250), vec![(400, 649), (650, 899), (900, 1000)]);
+    assert_eq!(log_chunks(400, 899, 250), vec![(400, 649), (650, 899)]);
+    assert_eq!(log_chunks(1000, 1000, 250), vec![(1000, 1000)]);
+    assert_eq!(log_chunks(400, 1000, 0), vec![(400, 1000)]);
+    assert_eq!(log_chunks(u64::MAX - 1, u64::MAX, 250), vec![(u64::MAX - 1, u64::MAX)]);
+}
+
+#[tokio::test]
+async fn test_fetch_commit_logs_chunks() {
+    use crate::mock_rpc::{commit_logs, MockRpc};
+    use serde_json::json;
+
+    // Batch n is committed at block 100 * n; the chunk from block 900 fails while `failing`.
+    let failing = Arc::new(AtomicBool::new(true));
+    let fails = failing.clone();
+    let rpc = MockRpc::start(move |method, params| match method {
+        "eth_getLogs"
+            if params[0]["fromBlock"] == json!("0x384") && fails.load(Ordering::SeqCst) =>
+        {
+            None
+        }
+        "eth_getLogs" => commit_logs(params, |index| 100 * index),
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+    let config = Config { lookback_blocks: 600, log_chunk_blocks: 250, ..Config::default() };
+    let window = CommitLogWindow::default();
+    let ranges = || {
+        rpc.requests("eth_getLogs")
+            .iter()
+            .map(|params| (params[0]["fromBlock"].clone(), params[0]["toBlock"].clone()))
+            .collect::<Vec<_>>()
+    };
+
+    // The last chunk fails the scan, the ones before it are kept.
+    assert!(fetch_commit_logs(1000, &window, &rollup, &rpc.provider(), &config).await.is_err());
+    assert_eq!(
+        ranges(),
+        vec![
+            (json!("0x190"), json!("0x289")),
+            (json!("0x28a"), json!("0x383")),
+            (json!("0x384"), json!("0x3e8")),
+        ]
+    );
+
+    // The next scan resumes after them, rescanning the last blocks.
+    failing.store(false, Ordering::SeqCst);
+    let logs = fetch_commit_logs(1000, &window, &rollup, &rpc.provider(), &config).await.unwrap();
+    let indexes = logs.iter().map(|log| commit_log_batch_index(log).unwrap()).collect::<Vec<_>>();
+    assert_eq!(indexes, vec![4, 5, 6, 7, 8, 9, 10]);
+    assert_eq!(ranges()[3..], [(json!("0x343"), json!("0x3e8"))]);
+}
+
+#[tokio::test]
+async fn test_unproven_batches() {
+    use crate::mock_rpc::{call_result, call_selector, MockRpc};
+    use alloy::{
+        primitives::B256,
+        sol_types::{SolCall, SolValue},
+    };
+    use serde_json::json;
+
+    // Batches 5..=9 committed in the window, of which 6 and 8 are already proven.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_blockNumber" => Some(json!("0x3e8")),
+        "eth_getLogs" => Some(json!((5u64..=9)
+            .map(|index| json!({
+                "address": Address::ZERO,
+                "topics": [B256::ZERO, B256::from(U256::from(index).to_be_bytes::<32>())],
+                "data": "0x",
+                "blockNumber": format!("{:#x}", 900 + index),
+                "transactionHash": B256::from(U256::from(index).to_be_bytes::<32>()),
+                "logIndex": "0x0",
+                "removed": false,
+            }))
+            .collect::<Vec<_>>())),
+        "eth_call" if call_selector(params) == ShadowRollup::isProveSuccessCall::SELECTOR => {
+            let tx_input = params[0]["input"].as_str().or(params[0]["data"].as_str()).unwrap();
+            let call = ShadowRollup::isProveSuccessCall::abi_decode(
+                &tx_input.parse::<Bytes>().unwrap(),
+                false,
+            )
+            .unwrap();
+            let index = call._batchIndex.to::<u64>();
+            call_result((index == 6 || index == 8).abi_encode())
+        }
+        _ => None,
+    });
+
+    let syncer = BatchSyncer::new(
+        Address::ZERO,
+        Address::ZERO,
+        rpc.provider(),
+        rpc.provider(),
+        rpc.provider(),
+        Config::default(),
+    );
+    let unproven = syncer.unproven_batches().await.unwrap();
+
+    // The newest batch has no successor to read its header from yet.
+    assert_eq!(unproven.iter().map(|t| t.batch_index).collect::<Vec<_>>(), vec![5, 7]);
+    assert_eq!(unproven[1].next_tx_hash, B256::from(U256::from(8).to_be_bytes::<32>()));
+}
+
+#[tokio::test]
+async fn test_inspect_batch_blocks_getter() {
+    use crate::mock_rpc::{batch_data_store, call_result, call_selector, MockRpc};
+    use alloy::sol_types::{SolCall, SolValue};
+    use serde_json::json;
+
+    // Getter implemented: one call, no per-block requests.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == BatchBlocksGetter::batchBlocksCall::SELECTOR => {
+            call_result((101u64, 110u64, 42u64).abi_encode())
+        }
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+    let config = Config { batch_blocks_getter: Some(Address::ZERO), ..Config::default() };
+    let inspected = inspect_batch_blocks(
+        &rollup,
+        &rpc.provider(),
+        &rpc.provider(),
+        7,
+        &config,
+        BlockId::latest(),
+    )
+    .await;
+    assert_eq!(inspected, Some(((101, 110), 42)));
+    assert!(rpc.requests("eth_getBlockTransactionCountByNumber").is_empty());
+
+    // Getter missing: fall back to batchDataStore and per-block counts.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == Rollup::batchDataStoreCall::SELECTOR => {
+            batch_data_store(params, |index| Some(100 + 3 * index))
+        }
+        "eth_getBlockTransactionCountByNumber" => Some(json!("0x2")),
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+    let inspected = inspect_batch_blocks(
+        &rollup,
+        &rpc.provider(),
+        &rpc.provider(),
+        7,
+        &config,
+        BlockId::latest(),
+    )
+    .await;
+    assert_eq!(inspected, Some(((119, 121), 6)));
+    assert_eq!(rpc.requests("eth_getBlockTransactionCountByNumber").len(), 3);
+}
+
+#[tokio::test]
+async fn test_inspect_batch_blocks_at_block() {
+    use crate::mock_rpc::{batch_data_store, call_selector, MockRpc};
+    use alloy::sol_types::SolCall;
+    use serde_json::json;
+
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == Rollup::batchDataStoreCall::SELECTOR => {
+            batch_data_store(params, |index| Some(100 + 3 * index))
+        }
+        "eth_getBlockTransactionCountByNumber" => Some(json!("0x1")),
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+    let inspected = batch_blocks_inspect(
+        &rollup,
+        &rpc.provider(),
+        7,
+        &Config::default(),
+        BlockId::number(1234),
+    )
+    .await;
+    assert_eq!(inspected, Some(((119, 121), 3)));
+
+    // Both batch stores are read at the pinned L1 block.
+    let calls = rpc.requests("eth_call");
+    assert_eq!(calls.len(), 2);
+    for params in calls {
+        assert_eq!(params[1], json!("0x4d2"));
+    }
+}
+
+#[tokio::test]
+async fn test_batch_blocks_inspect_first_batch() {
+    use crate::mock_rpc::{batch_data_store, call_selector, MockRpc};
+    use alloy::sol_types::SolCall;
+    use serde_json::json;
+
+    // The store of the genesis batch reverts, batch 1 ends at block 100.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == Rollup::batchDataStoreCall::SELECTOR => {
+            batch_data_store(params, |index| (index > 0).then(|| 100 * index))
+        }
+        "eth_getBlockTransactionCountByNumber" => Some(json!("0x1")),
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+
+    let inspected =
+        batch_blocks_inspect(&rollup, &rpc.provider(), 1, &Config::default(), BlockId::latest())
+            .await;
+    assert_eq!(inspected, Some(((1, 100), 100)));
+    // Only the store of batch 1 is read.
+    assert_eq!(rpc.requests("eth_call").len(), 1);
+
+    assert_eq!(
+        batch_blocks_inspect(&rollup, &rpc.provider(), 0, &Config::default(), BlockId::latest())
+            .await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_batch_blocks_inspect_multicall() {
+    use crate::mock_rpc::{call_result, call_selector, MockRpc};
+    use alloy::sol_types::{SolCall, SolValue};
+    use serde_json::json;
+
+    // Batch n ends at block 100 * n, only read through the multicall.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == Multicall3::aggregate3Call::SELECTOR => {
+            let input = params[0]["input"].as_str().or(params[0]["data"].as_str()).unwrap();
+            let call =
+                Multicall3::aggregate3Call::abi_decode(&input.parse::<Bytes>().unwrap(), false)
+                    .unwrap();
+            let results = call
+                .calls
+                .iter()
+                .map(|call| {
+                    let store =
+                        Rollup::batchDataStoreCall::abi_decode(&call.callData, false).unwrap();
+                    let block_number = store.batchIndex * U256::from(100);
+                    Multicall3::Call3Result {
+                        success: true,
+                        returnData: (U256::ZERO, U256::ZERO, block_number, U256::ZERO)
+                            .abi_encode()
+                            .into(),
+                    }
+                })
+                .collect();
+            call_result(Multicall3::aggregate3Call::abi_encode_returns(&(results,)))
+        }
+        "eth_getBlockTransactionCountByNumber" => Some(json!("0x1")),
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+    let multicall = Address::repeat_byte(0xca);
+    let config = Config { multicall: Some(multicall), ..Config::default() };
+
+    let inspected =
+        batch_blocks_inspect(&rollup, &rpc.provider(), 7, &config, BlockId::latest()).await;
+    assert_eq!(inspected, Some(((601, 700), 100)));
+    let calls = rpc.requests("eth_call");
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0][0]["to"], json!(multicall));
+}
+
+#[tokio::test]
+async fn test_block_transaction_count_receipts() {
+    use crate::mock_rpc::MockRpc;
+    use serde_json::json;
+
+    // Block 101 is not served by eth_getBlockReceipts, block 102 is unknown to it.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_getBlockReceipts" if params[0] == json!("0x65") => None,
+        "eth_getBlockReceipts" if params[0] == json!("0x66") => Some(json!(null)),
+        "eth_getBlockReceipts" => Some(json!([{}, {}, {}])),
+        "eth_getBlockTransactionCountByNumber" => Some(json!("0x1")),
+        _ => None,
+    });
+    let provider = rpc.provider();
+    let receipts = AtomicBool::new(true);
+
+    assert_eq!(block_transaction_count(&provider, 100, &receipts).await, 3);
+    // An unknown block keeps the method on, an error answer turns it off.
+    assert_eq!(block_transaction_count(&provider, 102, &receipts).await, 1);
+    assert!(receipts.load(Ordering::Relaxed));
+    assert_eq!(block_transaction_count(&provider, 101, &receipts).await, 1);
+    assert!(!receipts.load(Ordering::Relaxed));
+    assert_eq!(block_transaction_count(&provider, 100, &receipts).await, 1);
+    assert_eq!(rpc.requests("eth_getBlockReceipts").len(), 3);
+    assert_eq!(rpc.requests("eth_getBlockTransactionCountByNumber").len(), 3);
+}
+
+#[tokio::test]
+async fn test_batch_blocks_inspect_receipts_fallback() {
+    use crate::mock_rpc::{batch_data_store, call_selector, MockRpc};
+    use alloy::sol_types::SolCall;
+    use serde_json::json;
+
+    // Batch n ends at block 100 * n; the node does not serve eth_getBlockReceipts.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == Rollup::batchDataStoreCall::SELECTOR => {
+            batch_data_store(params, |index| Some(100 * index))
+        }
+        "eth_getBlockTransactionCountByNumber" => Some(json!("0x2")),
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+    let config = Config { block_receipts: true, block_fetch_concurrency: 1, ..Config::default() };
+
+    // One refused request for the batch, then a single request per block.
+    let inspected =
+        batch_blocks_inspect(&rollup, &rpc.provider(), 7, &config, BlockId::latest()).await;
+    assert_eq!(inspected, Some(((601, 700), 200)));
+    assert_eq!(rpc.requests("eth_getBlockReceipts").len(), 1);
+    assert_eq!(rpc.requests("eth_getBlockTransactionCountByNumber").len(), 100);
+}
+
+#[tokio::test]
+async fn test_rpc_errors() {
+    use crate::mock_rpc::{error, MockRpc};
+
+    let rpc = MockRpc::start(|method, _| match method {
+        "eth_getBlockTransactionCountByNumber" => error(-32000, "header not found"),
+        _ => None,
+    });
+    let errors = METRICS
+        .shadow_rpc_errors_total
+        .with_label_values(&["l2", "eth_getBlockTransactionCountByNumber"]);
+    let before = errors.get();
+
+    // Counted as no transactions, not silently.
+    assert_eq!(block_transaction_count(&rpc.provider(), 100, &AtomicBool::new(false)).await, 0);
+    assert!(errors.get() > before);
+}
+
+#[tokio::test]
+async fn test_batch_blocks_inspect_max_txn() {
+    use crate::mock_rpc::{batch_data_store, call_selector, MockRpc};
+    use alloy::sol_types::SolCall;
+    use serde_json::json;
+
+    // Batch n ends at block 100 * n, every block holds 2 txns.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == Rollup::batchDataStoreCall::SELECTOR => {
+            batch_data_store(params, |index| Some(100 * index))
+        }
+        "eth_getBlockTransactionCountByNumber" => Some(json!("0x2")),
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+    let config = Config { max_txn: 20, block_fetch_concurrency: 4, ..Config::default() };
+
+    // The 11th block crosses the limit; at most the in-flight requests follow it.
+    let inspected =
+        batch_blocks_inspect(&rollup, &rpc.provider(), 7, &config, BlockId::latest()).await;
+    let ((start_block, end_block), total_txn_count) = inspected.unwrap();
+    assert_eq!((start_block, end_block), (601, 700));
+    assert_eq!(total_txn_count, 22);
+    let fetched = rpc.requests("eth_getBlockTransactionCountByNumber").len();
+    assert!((11..=11 + 4).contains(&fetched), "{} blocks fetched", fetched);
+
+    // Within the limit every block is counted.
+    let config = Config { max_txn: 600, ..config };
+    let inspected =
+        batch_blocks_inspect(&rollup, &rpc.provider(), 7, &config, BlockId::latest()).await;
+    assert_eq!(inspected, Some(((601, 700), 200)));
+}
+
+#[tokio::test]
+async fn test_verify_withdrawal_root() {
+    use crate::mock_rpc::{call_result, call_selector, MockRpc};
+    use alloy::sol_types::{SolCall, SolValue};
+
+    let l2_root = B256::repeat_byte(0xaa);
+    let rpc = MockRpc::start(move |method, params| match method {
+        "eth_call" if call_selector(params) == L2ToL1MessagePasser::messageRootCall::SELECTOR => {
+            call_result(l2_root.abi_encode())
+        }
+        _ => None,
+    });
+    let batch_info = BatchInfo {
+        batch_index: 7,
+        start_block: 101,
+        end_block: 110,
+        size_class: SizeClass::Small,
+    };
+    let l2 = rpc.provider();
+    let alarm = Alarm::new(None, rpc.provider());
+
+    assert!(verify_withdrawal_root(&l2, &alarm, CheckMode::Strict, &batch_info, l2_root).await);
+    // Read at the batch end block.
+    assert_eq!(rpc.requests("eth_call")[0][1], "0x6e");
+
+    let other_root = B256::repeat_byte(0xbb);
+    let mismatches = METRICS.shadow_withdrawal_root_mismatch_total.get();
+    assert!(verify_withdrawal_root(&l2, &alarm, CheckMode::Observe, &batch_info, other_root).await);
+    assert!(!verify_withdrawal_root(&l2, &alarm, CheckMode::Strict, &batch_info, other_root).await);
+    assert_eq!(METRICS.shadow_withdrawal_root_mismatch_total.get(), mismatches + 2);
+
+    // Off does not query L2 at all.
+    assert!(verify_withdrawal_root(&l2, &alarm, CheckMode::Off, &batch_info, other_root).await);
+    assert_eq!(rpc.requests("eth_call").len(), 3);
+}
+
+#[tokio::test]
+async fn test_verify_state_chain() {
+    use crate::{
+        abi::ShadowAlarm,
+        mock_rpc::{call_result, call_selector, receipt, MockRpc},
+    };
+    use alloy::sol_types::{SolCall, SolValue};
+    use serde_json::json;
+
+    // Batch 6 is proven with postStateRoot 0x66..; batch 9 is committed but not proven.
+    let rpc = MockRpc::start(|method, params| {
+        let alarm_tx = B256::repeat_byte(0xa1);
+        match method {
+            "eth_call" => (),
+            "eth_sendTransaction" => return Some(json!(alarm_tx)),
+            "eth_getTransactionReceipt" => return receipt(alarm_tx, true),
+            "eth_blockNumber" => return Some(json!("0x1")),
+            _ => return None,
+        }
+        let input = params[0]["input"].as_str().or(params[0]["data"].as_str()).unwrap();
+        let input: Bytes = input.parse().unwrap();
+        match call_selector(params) {
+            selector if selector == ShadowRollup::isProveSuccessCall::SELECTOR => {
+                let call = ShadowRollup::isProveSuccessCall::abi_decode(&input, false).unwrap();
+                call_result((call._batchIndex == U256::from(6)).abi_encode())
+            }
+            selector if selector == ShadowRollup::committedBatchStoresCall::SELECTOR => {
+                let call =
+                    ShadowRollup::committedBatchStoresCall::abi_decode(&input, false).unwrap();
+                let root = B256::repeat_byte(call._0.to::<u8>() * 0x11);
+                call_result((root, root, root, root, root, root).abi_encode())
+            }
+            _ => None,
+        }
+    });
+    let shadow_rollup = ShadowRollup::new(Address::ZERO, rpc.provider());
+    let alarm = Alarm::new(Some(Address::repeat_byte(0xa1)), rpc.provider());
+    let chained = B256::repeat_byte(0x66);
+    let broken = B256::repeat_byte(0x55);
+
+    assert!(verify_state_chain(&shadow_rollup, &alarm, CheckMode::Strict, 7, chained).await);
+
+    let breaks = METRICS.shadow_state_root_chain_break_total.get();
+    assert!(verify_state_chain(&shadow_rollup, &alarm, CheckMode::Observe, 7, broken).await);
+    assert!(!verify_state_chain(&shadow_rollup, &alarm, CheckMode::Strict, 7, broken).await);
+    assert_eq!(METRICS.shadow_state_root_chain_break_total.get(), breaks + 2);
+    // Each break is reported to the alarm contract.
+    let alarms = rpc.requests("eth_sendTransaction");
+    assert_eq!(alarms.len(), 2);
+    assert!(alarms
+        .iter()
+        .all(|params| call_selector(params) == ShadowAlarm::raiseAlarmCall::SELECTOR));
+
+    // Unproven previous batch: nothing to chain to.
+    assert!(verify_state_chain(&shadow_rollup, &alarm, CheckMode::Strict, 10, broken).await);
+    assert_eq!(METRICS.shadow_state_root_chain_break_total.get(), breaks + 2);
+
+    // Off does not query the shadow rollup at all.
+    let calls = rpc.requests("eth_call").len();
+    assert!(verify_state_chain(&shadow_rollup, &alarm, CheckMode::Off, 7, broken).await);
+    assert_eq!(rpc.requests("eth_call").len(), calls);
+}
+
+#[tokio::test]
+async fn test_verify_sequencer() {
+    use crate::mock_rpc::{transaction, MockRpc};
+
+    // Batch 7 was committed by the sequencer, batch 8 by another sender.
+    let sequencer = Address::repeat_byte(0x5e);
+    let rpc = MockRpc::start(move |method, params| match method {
+        "eth_getTransactionByHash" => {
+            let tx_hash: TxHash = params[0].as_str().unwrap().parse().unwrap();
+            let from = if tx_hash == TxHash::repeat_byte(7) { sequencer } else { Address::ZERO };
+            transaction(tx_hash, from, Bytes::new())
+        }
+        _ => None,
+    });
+    let l1 = rpc.provider();
+    let allowlist = [Address::repeat_byte(0x01), sequencer];
+    let (allowed, other) = (TxHash::repeat_byte(7), TxHash::repeat_byte(8));
+
+    assert!(verify_sequencer(&l1, CheckMode::Strict, &allowlist, 7, allowed).await);
+    let unexpected = METRICS.shadow_unexpected_sequencer_total.get();
+    assert!(!verify_sequencer(&l1, CheckMode::Strict, &allowlist, 8, other).await);
+    assert!(verify_sequencer(&l1, CheckMode::Observe, &allowlist, 8, other).await);
+    assert_eq!(METRICS.shadow_unexpected_sequencer_total.get(), unexpected + 2);
+
+    // Disabled without an allowlist or in off mode: the tx is not even fetched.
+    let fetched = rpc.requests("eth_getTransactionByHash").len();
+    assert!(verify_sequencer(&l1, CheckMode::Strict, &[], 8, other).await);
+    assert!(verify_sequencer(&l1, CheckMode::Off, &allowlist, 8, other).await);
+    assert_eq!(rpc.requests("eth_getTransactionByHash").len(), fetched);
+}
+
+#[tokio::test]
+async fn test_inspect_committed_batch_sequencer() {
+    use crate::mock_rpc::{call_result, call_selector, transaction, MockRpc};
+    use alloy::sol_types::{SolCall, SolValue};
+
+    // Batch 7 is committed by tx 7, its header read from the commit tx 8 of batch 8.
+    let (sequencer, rogue) = (Address::repeat_byte(0x5e), Address::repeat_byte(0x0b));
+    let inspect = |batch_sender: Address, next_sender: Address| async move {
+        let rpc = MockRpc::start(move |method, params| match method {
+            "eth_call" if call_selector(params) == BatchBlocksGetter::batchBlocksCall::SELECTOR => {
+                call_result((101u64, 110u64, 20u64).abi_encode())
+            }
+            "eth_getTransactionByHash" => {
+                let tx_hash: TxHash = params[0].as_str().unwrap().parse().unwrap();
+                if tx_hash == TxHash::repeat_byte(7) {
+                    return transaction(tx_hash, batch_sender, Bytes::new());
+                }
+                let mut header = vec![0u8; 257];
+                header[0] = 1;
+                let batch_data =
+                    (1u8, Bytes::from(header), 110u64, 0u16, B256::ZERO, B256::ZERO, B256::ZERO);
+                let signature = (U256::ZERO, Bytes::new(), Bytes::new());
+                let input = [
+                    &Rollup::commitBatchCall::SELECTOR[..],
+                    &(batch_data, signature).abi_encode_params(),
+                ]
+                .concat();
+                transaction(tx_hash, next_sender, input.into())
+            }
+            _ => None,
+        });
+        let config = Config {
+            batch_blocks_getter: Some(Address::ZERO),
+            sequencer_allowlist: vec![sequencer],
+            ..Config::default()
+        };
+        let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+        let target = BatchTarget {
+            batch_index: 7,
+            tx_hash: TxHash::repeat_byte(7),
+            next_tx_hash: TxHash::repeat_byte(8),
+        };
+        let mut decision = Decision::default();
+        let (l1, l2) = (rpc.provider(), rpc.provider());
+        inspect_committed_batch(&target, &rollup, &l1, &l2, &config, &mut decision).await.err()
+    };
+
+    // The sender of the batch's own commit tx decides, not the one of its successor.
+    assert_eq!(inspect(sequencer, rogue).await, None);
+    assert_eq!(inspect(rogue, sequencer).await, Some(SyncOutcome::CheckFailed));
+}
+
+#[tokio::test]
+async fn test_committed_unproven_batch() {
+    use crate::mock_rpc::{call_result, call_selector, MockRpc};
+    use alloy::sol_types::{SolCall, SolValue};
+
+    // Batch 7 covers blocks 101..=110; batches 7 and 8 are committed, 8 is proven.
+    let rpc = MockRpc::start(|method, params| {
+        if method != "eth_call" {
+            return None;
+        }
+        let input = params[0]["input"].as_str().or(params[0]["data"].as_str()).unwrap();
+        let input: Bytes = input.parse().unwrap();
+        match call_selector(params) {
+            selector if selector == ShadowRollup::isProveSuccessCall::SELECTOR => {
+                let call = ShadowRollup::isProveSuccessCall::abi_decode(&input, false).unwrap();
+                call_result((call._batchIndex == U256::from(8)).abi_encode())
+            }
+            selector if selector == ShadowRollup::committedBatchStoresCall::SELECTOR => {
+                let call =
+                    ShadowRollup::committedBatchStoresCall::abi_decode(&input, false).unwrap();
+                let root = if call._0 <= U256::from(8) { B256::repeat_byte(1) } else { B256::ZERO };
+                call_result((root, root, root, root, root, root).abi_encode())
+            }
+            selector if selector == BatchBlocksGetter::batchBlocksCall::SELECTOR => {
+                call_result((101u64, 110u64, 42u64).abi_encode())
+            }
+            _ => None,
+        }
+    });
+    let config = Config { batch_blocks_getter: Some(Address::ZERO), ..Config::default() };
+    let syncer = BatchSyncer::new(
+        Address::ZERO,
+        Address::ZERO,
+        rpc.provider(),
+        rpc.provider(),
+        rpc.provider(),
+        config,
+    );
+
+    let batch = syncer.committed_unproven_batch(7).await.unwrap();
+    let expected = BatchInfo {
+        batch_index: 7,
+        start_block: 101,
+        end_block: 110,
+        size_class: SizeClass::Small,
+    };
+    assert_eq!(batch, Some(expected));
+    // Already proven.
+    assert_eq!(syncer.committed_unproven_batch(8).await.unwrap(), None);
+    // Not committed.
+    let err = syncer.committed_unproven_batch(9).await.unwrap_err();
+    assert!(err.to_string().contains("is not committed"));
+
+    // Only reads, the commit step is skipped.
+    assert!(rpc.requests("eth_sendRawTransaction").is_empty());
+    assert!(rpc
+        .requests("eth_call")
+        .iter()
+        .all(|params| call_selector(params) != ShadowRollup::commitBatchCall::SELECTOR));
+}
+
+#[tokio::test]
+async fn test_check_authorized_signer() {
+    use crate::mock_rpc::{call_result, call_selector, MockRpc};
+    use alloy::sol_types::{SolCall, SolValue};
+
+    let owner = Address::repeat_byte(0x11);
+    let rpc = MockRpc::start(move |method, params| match method {
+        "eth_call" if call_selector(params) == ShadowRollup::ownerCall::SELECTOR => {
+            call_result(owner.abi_encode())
+        }
+        _ => None,
+    });
+    let l1 = rpc.provider();
+
+    check_authorized_signer(Address::ZERO, &l1, owner).await.unwrap();
+    let err =
+        check_authorized_signer(Address::ZERO, &l1, Address::repeat_byte(0x22)).await.unwrap_err();
+    assert!(err.to_string().contains("is not authorized"));
+
+    // No owner getter, the check is skipped.
+    let rpc = MockRpc::start(|_, _| None);
+    check_authorized_signer(Address::ZERO, &rpc.provider(), owner).await.unwrap();
+}
+
+#[test]
+fn test_check_block_range() {
+    // Batch N covers blocks (end of batch N-1) + 1 ..= end of batch N.
+    assert_eq!(check_block_range(7, (101, 150)), Ok(()));
+    // A single block batch.
+    assert_eq!(check_block_range(7, (101, 101)), Ok(()));
+    // Batch N ends where batch N-1 ended: no block.
+    let err = check_block_range(7, (101, 100)).unwrap_err();
+    assert_eq!(err, EmptyBatch { batch_index: 7, start_block: 101, end_block: 100 });
+    assert_eq!(err.to_string(), "blocks of batch 7 is empty: start_block = 101, end_block = 100");
+    assert!(check_block_range(7, (151, 100)).is_err());
+}
+
+#[test]
+fn test_select_target() {
+    use alloy::primitives::LogData;
+
+    // Batches 10..=15 committed, the commit tx hash of batch N is N.
+    let logs: Vec<Log> = (10u64..=15)
+        .map(|index| {
+            let hash = B256::from(U256::from(index).to_be_bytes::<32>());
+            Log {
+                inner: alloy::primitives::Log {
+                    address: Address::ZERO,
+                    data: LogData::new_unchecked(vec![B256::ZERO, hash], Bytes::new()),
+                },
+                block_number: Some(100 + index),
+                transaction_hash: Some(hash),
+                ..Default::default()
+            }
+        })
+        .collect();
+    let selected = |buffer| select_target(&logs, buffer).map(|t| t.batch_index);
+
+    assert_eq!(selected(1), Some(14));
+    assert_eq!(selected(3), Some(12));
+    assert_eq!(selected(5), Some(10));
+    // Older than the window.
+    assert_eq!(selected(6), None);
+    // The successor's commit tx is needed, so at least the newest batch is skipped.
+    assert_eq!(selected(0), Some(14));
+
+    let target = select_target(&logs, 2).unwrap();
+    assert_eq!(target.tx_hash, B256::from(U256::from(13).to_be_bytes::<32>()));
+    assert_eq!(target.next_tx_hash, B256::from(U256::from(14).to_be_bytes::<32>()));
+}
+
+#[tokio::test]
+async fn test_get_committed_batch_lag() {
+    use crate::mock_rpc::{commit_logs, MockRpc};
+
+    // Batch n is committed at block 100 * n, batches 4..=10 in the window.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_getLogs" => commit_logs(params, |index| 100 * index),
+        _ => None,
+    });
+    let rollup = Rollup::RollupInstance::new(Address::ZERO, rpc.provider());
+    let selected = |batch_buffer| {
+        let (rpc, rollup) = (rpc.clone(), rollup.clone());
+        async move {
+            let config = Config { lookback_blocks: 600, batch_buffer, ..Config::default() };
+            let mut decision = Decision::default();
+            let window = CommitLogWindow::default();
+            let (l1, l2) = (rpc.provider(), rpc.provider());
+            let result =
+                get_committed_batch(1000, &window, &rollup, &l1, &l2, &config, &mut decision).await;
+            (result.err(), decision.batch_index)
+        }
+    };
+
+    // The inspection of the selected batch fails against the mock, after the selection.
+    assert_eq!(selected(3).await, (Some(SyncOutcome::InspectFailed), Some(7)));
+    assert_eq!(selected(5).await, (Some(SyncOutcome::InspectFailed), Some(5)));
+    // 7 logs, a lag of 6 needs 8.
+    assert_eq!(selected(6).await, (Some(SyncOutcome::NoBatch), None));
+}
+
+#[tokio::test]
+async fn test_inflight_commits() {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    let slots = InflightCommits::new(2);
+    let current = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let commits = (0..6u64).map(|batch_index| {
+        let (slots, current, peak) = (slots.clone(), current.clone(), peak.clone());
+        tokio::spawn(async move {
+            let _inflight = slots.acquire(batch_index).await;
+            let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            current.fetch_sub(1, Ordering::SeqCst);
+        })
+    });
+    for commit in commits.collect::<Vec<_>>() {
+        commit.await.unwrap();
+    }
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+    // A zero limit still lets commits through one at a time.
+    let slots = InflightCommits::new(0);
+    let first = slots.acquire(1).await;
+    assert!(tokio::time::timeout(Duration::from_millis(20), slots.acquire(2)).await.is_err());
+    drop(first);
+    slots.acquire(2).await;
 }
 
 #[tokio::test]
@@ -354,7 +2720,6 @@ async fn test_sync_batch() {
         primitives::Address,
         providers::{ProviderBuilder, RootProvider},
         signers::local::PrivateKeySigner,
-        transports::http::{Client, Http},
     };
     use std::{env::var, str::FromStr};
 
@@ -369,10 +2734,10 @@ async fn test_sync_batch() {
 
     let signer: PrivateKeySigner = private_key.parse().unwrap();
     let wallet: EthereumWallet = EthereumWallet::from(signer.clone());
-    let l1_provider: RootProvider<Http<Client>> =
-        ProviderBuilder::new().on_http(l1_rpc.parse().unwrap());
-    let l2_provider: RootProvider<Http<Client>> =
-        ProviderBuilder::new().on_http(l2_rpc.parse().unwrap());
+    let l1_provider: RootProvider<FailoverTransport> =
+        FailoverTransport::new("l1", &l1_rpc, 3).unwrap().into_provider();
+    let l2_provider: RootProvider<FailoverTransport> =
+        FailoverTransport::new("l2", &l2_rpc, 3).unwrap().into_provider();
 
     let rollup = var("SHADOW_PROVING_L1_ROLLUP").expect("Cannot detect L1_ROLLUP env var");
     let shadow_rollup =
@@ -389,50 +2754,103 @@ async fn test_sync_batch() {
         l1_provider,
         l2_provider,
         l1_signer,
+        Config::from_env().unwrap(),
+    );
+    bs.sync_batch(&mut Decision::default()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_sync_batch_stale_node() {
+    use crate::mock_rpc::MockRpc;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // The load balancer answers from a node 5 blocks behind on the second request.
+    let calls = Arc::new(AtomicUsize::new(0));
+    let rpc = MockRpc::start(move |method, _| match method {
+        "eth_blockNumber" => {
+            let latest = [1000, 995, 999][calls.fetch_add(1, Ordering::SeqCst)];
+            Some(json!(format!("{:#x}", latest)))
+        }
+        "eth_getLogs" => Some(json!([])),
+        _ => None,
+    });
+    let config = Config { stale_block_tolerance: 2, ..Config::default() };
+    let bs = BatchSyncer::new(
+        Address::ZERO,
+        Address::ZERO,
+        rpc.provider(),
+        rpc.provider(),
+        rpc.provider(),
+        config,
     );
-    bs.sync_batch().await.unwrap();
+
+    let no_logs = METRICS.shadow_no_logs_total.get();
+    let synced = METRICS.shadow_sync_duration_seconds.get_sample_count();
+    let mut decision = Decision::default();
+    assert_eq!(bs.sync_batch(&mut decision).await.unwrap(), SyncOutcome::NoBatch);
+    assert_eq!(rpc.requests("eth_getLogs").len(), 1);
+    // Other tests sync without logs concurrently.
+    assert!(METRICS.shadow_no_logs_total.get() > no_logs);
+    assert!(METRICS.shadow_sync_duration_seconds.get_sample_count() > synced);
+
+    // Regressed beyond the tolerance: nothing is read at the stale block.
+    let stale = METRICS.shadow_stale_node_total.get();
+    let mut decision = Decision::default();
+    assert_eq!(bs.sync_batch(&mut decision).await.unwrap(), SyncOutcome::StaleNode);
+    assert_eq!(decision.reason.as_deref(), Some("stale l1 node"));
+    assert_eq!(rpc.requests("eth_getLogs").len(), 1);
+    assert_eq!(METRICS.shadow_stale_node_total.get(), stale + 1);
+
+    // Within the tolerance.
+    let mut decision = Decision::default();
+    bs.sync_batch(&mut decision).await.unwrap();
+    assert_eq!(rpc.requests("eth_getLogs").len(), 2);
+    let to_block = &rpc.requests("eth_getLogs")[1][0]["toBlock"];
+    assert_eq!(to_block, &json!(format!("{:#x}", 999)));
+}
+
+#[test]
+fn test_batch_store_from_header() {
+    use crate::batch_header::{HeaderError, V1_LEN};
+
+    // Each root filled with a byte of its own.
+    let mut header = vec![0u8; V1_LEN];
+    header[0] = 1;
+    header[25..57].fill(0xda);
+    header[57..89].fill(0xb1);
+    header[89..121].fill(0x01);
+    header[121..153].fill(0x02);
+    header[153..185].fill(0x3d);
+    header[185..217].fill(0x5e);
+
+    let store = batch_store_from_header(&Bytes::from(header.clone())).unwrap();
+    assert_eq!(store.dataHash, B256::repeat_byte(0xda));
+    assert_eq!(store.blobVersionedHash, B256::repeat_byte(0xb1));
+    assert_eq!(store.prevStateRoot, B256::repeat_byte(0x01));
+    assert_eq!(store.postStateRoot, B256::repeat_byte(0x02));
+    assert_eq!(store.withdrawalRoot, B256::repeat_byte(0x3d));
+    assert_eq!(store.sequencerSetVerifyHash, B256::repeat_byte(0x5e));
+
+    let err = batch_store_from_header(&Bytes::from(header[..200].to_vec())).unwrap_err();
+    assert_eq!(err, HeaderError::TooShort { version: 1, len: 200, min_len: V1_LEN });
 }
 
 #[tokio::test]
 async fn test_inspect_batch_header() {
-    use alloy::{primitives::B256, providers::ProviderBuilder};
+    use alloy::primitives::B256;
     use std::str::FromStr;
 
-    let provider: RootProvider<Http<Client>> = ProviderBuilder::new().on_http(
-        "https://eth-holesky.g.alchemy.com/v2/xxxxxxx".parse().expect("parse l1_rpc to Url"),
-    );
+    let provider: RootProvider<FailoverTransport> =
+        FailoverTransport::new("l1", "https://eth-holesky.g.alchemy.com/v2/xxxxxxx", 3)
+            .expect("parse l1_rpc to Url")
+            .into_provider();
     let next_tx_hash =
         B256::from_str("0x2bdfb2bd0b8c9210bfb593cc5734e3f092fcdd54fe74c46a938448b0422089f7")
             .unwrap();
-    let batch_header = batch_header_inspect(&provider, next_tx_hash)
-        .await
-        .ok_or_else(|| "Failed to inspect batch header".to_string())
-        .unwrap();
+    let batch_header = batch_header_inspect(&provider, next_tx_hash).await.unwrap();
 
-    let batch_store = ShadowRollup::BatchStore {
-        prevStateRoot: batch_header.get(89..121).unwrap_or_default().try_into().unwrap_or_default(),
-        postStateRoot: batch_header
-            .get(121..153)
-            .unwrap_or_default()
-            .try_into()
-            .unwrap_or_default(),
-        withdrawalRoot: batch_header
-            .get(153..185)
-            .unwrap_or_default()
-            .try_into()
-            .unwrap_or_default(),
-        dataHash: batch_header.get(25..57).unwrap_or_default().try_into().unwrap_or_default(),
-        blobVersionedHash: batch_header
-            .get(57..89)
-            .unwrap_or_default()
-            .try_into()
-            .unwrap_or_default(),
-        sequencerSetVerifyHash: batch_header
-            .get(185..217)
-            .unwrap_or_default()
-            .try_into()
-            .unwrap_or_default(),
-    };
+    let batch_store = batch_store_from_header(&batch_header).unwrap();
 
     println!(
         "sync batch of {:?}, prevStateRoot = {:?}, postStateRoot = {:?}, withdrawalRoot = {:?},
@@ -453,3 +2871,661 @@ async fn test_inspect_batch_header() {
     // "013f8fabf23fba03c52572d3403d175d952937cdd78bb8e9e06eb6ffa751fd2a", sequencerSetVerifyHash =
     // "60f10881edf25485d6d9db1c3a634c002bf4da64cce0f9a0f528e00f1ead3dec"
 }
+
+#[test]
+fn test_sort_commit_logs() {
+    use alloy::primitives::{LogData, B256};
+
+    let commit_log = |block_number: Option<u64>, topics: Vec<B256>| Log {
+        inner: alloy::primitives::Log {
+            address: Address::ZERO,
+            data: LogData::new_unchecked(topics, Bytes::new()),
+        },
+        block_number,
+        ..Default::default()
+    };
+    let topics = |index: u64| vec![B256::ZERO, B256::from(U256::from(index).to_be_bytes::<32>())];
+
+    let logs = sort_commit_logs(vec![
+        commit_log(Some(12), topics(3)),
+        commit_log(None, topics(4)),
+        commit_log(Some(10), topics(1)),
+        commit_log(Some(11), vec![]),
+    ]);
+    assert_eq!(logs.len(), 3);
+    assert_eq!(logs.iter().map(|l| l.block_number.unwrap()).collect::<Vec<_>>(), vec![10, 11, 12]);
+
+    assert_eq!(commit_log_batch_index(&logs[0]), Some(1));
+    assert_eq!(commit_log_batch_index(&logs[1]), None);
+    assert_eq!(commit_log_batch_index(&logs[2]), Some(3));
+}
+
+/// Syncer of the `commit_batch` tests, every RPC answered by `rpc`, with batch 7 of blocks
+/// 101..=110 and a V1 header of zeroes.
+#[cfg(test)]
+fn commit_batch_fixture(
+    rpc: &crate::mock_rpc::MockRpc,
+    config: Config,
+) -> (
+    BatchSyncer<FailoverTransport, RootProvider<FailoverTransport>, alloy::network::Ethereum>,
+    BatchInfo,
+    Vec<u8>,
+) {
+    let syncer = BatchSyncer::new(
+        Address::ZERO,
+        Address::ZERO,
+        rpc.provider(),
+        rpc.provider(),
+        rpc.provider(),
+        config,
+    );
+    let batch = BatchInfo {
+        batch_index: 7,
+        start_block: 101,
+        end_block: 110,
+        size_class: SizeClass::Small,
+    };
+    let mut header = vec![0u8; crate::batch_header::V1_LEN];
+    header[0] = 1;
+    (syncer, batch, header)
+}
+
+#[tokio::test]
+async fn test_commit_batch_simulation() {
+    use crate::{
+        audit::Outcome,
+        mock_rpc::{call_result, call_selector, receipt, revert, MockRpc},
+    };
+    use alloy::sol_types::{Revert, SolError, SolValue};
+    use serde_json::json;
+
+    // The shadow rollup is paused, which only the simulation tells.
+    let start = |paused: bool| {
+        MockRpc::start(move |method, params| match method {
+            "eth_call" => match call_selector(params) {
+                selector if selector == ShadowRollup::isProveSuccessCall::SELECTOR => {
+                    call_result(false.abi_encode())
+                }
+                selector if selector == ShadowRollup::commitBatchCall::SELECTOR && paused => {
+                    revert(Revert::from("Pausable: paused").abi_encode())
+                }
+                selector if selector == ShadowRollup::commitBatchCall::SELECTOR => {
+                    call_result(Vec::new())
+                }
+                _ => None,
+            },
+            "eth_sendTransaction" => Some(json!(B256::repeat_byte(0x11))),
+            "eth_getTransactionReceipt" => {
+                receipt(params[0].as_str().unwrap().parse().unwrap(), true)
+            }
+            "eth_blockNumber" => Some(json!("0x1")),
+            _ => None,
+        })
+    };
+    let syncer = |rpc: &MockRpc, config: Config| {
+        commit_batch_fixture(rpc, config).0.with_sender(Address::repeat_byte(0x22))
+    };
+    let rpc = start(true);
+    let (_, batch, mut header) = commit_batch_fixture(&rpc, Config::default());
+    header[121..153].fill(1);
+
+    let reverted = METRICS.shadow_commit_simulation_reverted_total.get();
+    let mut decision = Decision::default();
+    let committed = syncer(&rpc, Config::default())
+        .commit_batch(batch.clone(), Bytes::from(header.clone()), &mut decision)
+        .await
+        .unwrap();
+    assert_eq!(committed, SyncOutcome::SimulationReverted);
+    assert_eq!(decision.outcome, Outcome::Skipped);
+    assert_eq!(
+        decision.reason.as_deref(),
+        Some("commit simulation reverted: revert: Pausable: paused")
+    );
+    assert_eq!(METRICS.shadow_commit_simulation_reverted_total.get(), reverted + 1);
+    // Simulated as the signer, never sent.
+    let simulated = rpc.requests("eth_call");
+    let simulation = simulated
+        .iter()
+        .find(|params| call_selector(params) == ShadowRollup::commitBatchCall::SELECTOR)
+        .unwrap();
+    assert_eq!(simulation[0]["from"], json!(Address::repeat_byte(0x22)));
+    assert!(rpc.requests("eth_sendTransaction").is_empty());
+
+    // Without the simulation, the commit is sent.
+    let config = Config { simulate_before_send: false, ..Config::default() };
+    let mut decision = Decision::default();
+    let committed = syncer(&rpc, config)
+        .commit_batch(batch.clone(), Bytes::from(header.clone()), &mut decision)
+        .await
+        .unwrap();
+    assert_eq!(committed, SyncOutcome::Committed(batch.clone()));
+    assert_eq!(rpc.requests("eth_sendTransaction").len(), 1);
+
+    // A successful simulation goes on with the send.
+    let rpc = start(false);
+    let mut decision = Decision::default();
+    let committed = syncer(&rpc, Config::default())
+        .commit_batch(batch.clone(), Bytes::from(header.clone()), &mut decision)
+        .await
+        .unwrap();
+    assert_eq!(committed, SyncOutcome::Committed(batch.clone()));
+    assert_eq!(decision.outcome, Outcome::Committed);
+
+    // A dry run only simulates, whatever the simulation tells.
+    let config = Config { dry_run: true, ..Config::default() };
+    for paused in [false, true] {
+        let rpc = start(paused);
+        let mut decision = Decision::default();
+        let committed = syncer(&rpc, config.clone())
+            .commit_batch(batch.clone(), Bytes::from(header.clone()), &mut decision)
+            .await
+            .unwrap();
+        if paused {
+            assert_eq!(committed, SyncOutcome::SimulationReverted);
+            assert_eq!(
+                decision.reason.as_deref(),
+                Some("dry run, commit simulation reverted: revert: Pausable: paused")
+            );
+        } else {
+            assert_eq!(committed, SyncOutcome::Simulated(batch.clone()));
+        }
+        assert_eq!(decision.outcome, Outcome::Skipped);
+        assert!(rpc.requests("eth_sendTransaction").is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_commit_batch_failed_receipt() {
+    use crate::mock_rpc::{call_result, call_selector, receipt, revert, MockRpc};
+    use alloy::sol_types::{Revert, SolError, SolValue};
+    use serde_json::json;
+
+    // Mined in block 1, where the replay reverts.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" => match call_selector(params) {
+            selector if selector == ShadowRollup::isProveSuccessCall::SELECTOR => {
+                call_result(false.abi_encode())
+            }
+            selector if selector == ShadowRollup::commitBatchCall::SELECTOR => {
+                assert_eq!(params[1], json!("0x1"));
+                revert(Revert::from("incorrect parent batch hash").abi_encode())
+            }
+            _ => None,
+        },
+        "eth_sendTransaction" => Some(json!(B256::repeat_byte(0x11))),
+        "eth_getTransactionReceipt" => receipt(B256::repeat_byte(0x11), false),
+        "eth_blockNumber" => Some(json!("0x1")),
+        _ => None,
+    });
+    let config = Config { simulate_before_send: false, ..Config::default() };
+    let (syncer, batch, mut header) = commit_batch_fixture(&rpc, config);
+    header[121..153].fill(1);
+    let syncer = syncer.with_sender(Address::repeat_byte(0x22));
+
+    let commits = METRICS.shadow_commit_duration_seconds.get_sample_count();
+    let mut decision = Decision::default();
+    let committed = syncer.commit_batch(batch, Bytes::from(header), &mut decision).await.unwrap();
+    assert_eq!(committed, SyncOutcome::CommitFailed);
+    // Timed up to the failed receipt.
+    assert!(METRICS.shadow_commit_duration_seconds.get_sample_count() > commits);
+    assert_eq!(
+        decision.reason.as_deref(),
+        Some("commit_batch tx failed: revert: incorrect parent batch hash")
+    );
+    assert_eq!(decision.tx_hash, Some(B256::repeat_byte(0x11)));
+}
+
+#[tokio::test]
+async fn test_commit_batch_circuit_breaker() {
+    use crate::{
+        audit::Outcome,
+        mock_rpc::{call_result, call_selector, receipt, MockRpc},
+    };
+    use alloy::sol_types::SolValue;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Every commit reverts on chain until the deployment is fixed.
+    let fixed = Arc::new(AtomicBool::new(false));
+    let rpc = MockRpc::start({
+        let fixed = fixed.clone();
+        move |method, params| match method {
+            "eth_call" if call_selector(params) == ShadowRollup::isProveSuccessCall::SELECTOR => {
+                call_result(false.abi_encode())
+            }
+            "eth_sendTransaction" => Some(json!(B256::repeat_byte(0x11))),
+            "eth_getTransactionReceipt" => {
+                receipt(params[0].as_str().unwrap().parse().unwrap(), fixed.load(Ordering::SeqCst))
+            }
+            "eth_blockNumber" => Some(json!("0x1")),
+            _ => None,
+        }
+    });
+    let config =
+        Config { simulate_before_send: false, circuit_revert_threshold: 2, ..Config::default() };
+    let circuit = CircuitBreaker::new(&config);
+    let (syncer, batch, header) = commit_batch_fixture(&rpc, config);
+    let syncer = syncer.with_circuit_breaker(circuit.clone());
+    let commit = |batch_index: u64| {
+        let batch = BatchInfo { batch_index, ..batch.clone() };
+        let header = Bytes::from(header.clone());
+        let syncer = &syncer;
+        async move {
+            let mut decision = Decision::default();
+            syncer.commit_batch(batch, header, &mut decision).await.unwrap();
+            decision
+        }
+    };
+
+    // Reverts across batches open the circuit.
+    assert_eq!(commit(7).await.outcome, Outcome::Failed);
+    assert_eq!(commit(8).await.outcome, Outcome::Failed);
+    assert_eq!(rpc.requests("eth_sendTransaction").len(), 2);
+    let skipped = commit(9).await;
+    assert_eq!(skipped.reason.as_deref(), Some("circuit open"));
+    assert_eq!(rpc.requests("eth_sendTransaction").len(), 2);
+
+    // Reset by the admin endpoint once the deployment is fixed.
+    fixed.store(true, Ordering::SeqCst);
+    assert!(circuit.reset("reset by POST /admin/reset-circuit"));
+    assert_eq!(commit(9).await.outcome, Outcome::Committed);
+    assert_eq!(rpc.requests("eth_sendTransaction").len(), 3);
+}
+
+#[tokio::test]
+async fn test_commit_batch_short_header() {
+    use crate::{
+        audit::Outcome,
+        mock_rpc::{call_result, call_selector, MockRpc},
+    };
+    use alloy::sol_types::SolValue;
+
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" if call_selector(params) == ShadowRollup::isProveSuccessCall::SELECTOR => {
+            call_result(false.abi_encode())
+        }
+        _ => None,
+    });
+    let (syncer, batch, _) = commit_batch_fixture(&rpc, Config::default());
+    // Truncated in the middle of the post state root.
+    let mut header = vec![0x11u8; 130];
+    header[0] = 1;
+
+    let mut decision = Decision::default();
+    let committed = syncer.commit_batch(batch, Bytes::from(header), &mut decision).await.unwrap();
+    assert_eq!(committed, SyncOutcome::CommitFailed);
+    assert_eq!(decision.outcome, Outcome::Failed);
+    assert_eq!(
+        decision.reason.as_deref(),
+        Some("batch header version 1 is 130 bytes, at least 257 expected")
+    );
+    assert!(rpc.requests("eth_sendTransaction").is_empty());
+}
+
+#[tokio::test]
+async fn test_commit_batch_header_version_mismatch() {
+    use crate::{
+        abi::RollupVersion,
+        audit::Outcome,
+        mock_rpc::{call_result, call_selector, MockRpc},
+    };
+    use alloy::sol_types::SolValue;
+
+    // The rollup decodes V0 headers, the committed one is V1.
+    let rpc = MockRpc::start(|method, params| match method {
+        "eth_call" => match call_selector(params) {
+            selector if selector == ShadowRollup::isProveSuccessCall::SELECTOR => {
+                call_result(false.abi_encode())
+            }
+            selector if selector == RollupVersion::batchHeaderVersionCall::SELECTOR => {
+                call_result(0u8.abi_encode())
+            }
+            _ => None,
+        },
+        _ => None,
+    });
+    let (syncer, batch, header) = commit_batch_fixture(&rpc, Config::default());
+
+    let mut decision = Decision::default();
+    let committed = syncer.commit_batch(batch, Bytes::from(header), &mut decision).await.unwrap();
+    assert_eq!(committed, SyncOutcome::CommitFailed);
+    assert_eq!(decision.outcome, Outcome::Failed);
+    assert_eq!(
+        decision.reason.as_deref(),
+        Some("batch header version 1 differs from rollup header version 0")
+    );
+    assert_eq!(decision.header_version, None);
+    assert!(rpc.requests("eth_sendTransaction").is_empty());
+}
+
+#[tokio::test]
+async fn test_commit_batch_proven_through() {
+    use crate::{audit::Outcome, mock_rpc::MockRpc};
+
+    let rpc = MockRpc::start(|_, _| None);
+    let (syncer, batch, header) = commit_batch_fixture(&rpc, Config::default());
+    let syncer = syncer.with_proven_through(Some(7));
+
+    // Skipped without reading the shadow rollup.
+    let mut decision = Decision::default();
+    let committed = syncer.commit_batch(batch, Bytes::from(header), &mut decision).await.unwrap();
+    assert_eq!(committed, SyncOutcome::AlreadyProven);
+    assert_eq!(decision.outcome, Outcome::Skipped);
+    assert_eq!(decision.reason.as_deref(), Some("proven per the checkpoint"));
+    assert!(rpc.requests("eth_call").is_empty());
+}
+
+#[tokio::test]
+async fn test_commit_batch_prove_state_unknown() {
+    use crate::mock_rpc::MockRpc;
+
+    // Every call fails, isProveSuccess included.
+    let rpc = MockRpc::start(|_, _| None);
+    let (syncer, batch, header) = commit_batch_fixture(&rpc, Config::default());
+
+    // An error to retry on the next tick, not a batch skipped as proven.
+    let mut decision = Decision::default();
+    let err = syncer.commit_batch(batch, Bytes::from(header), &mut decision).await.unwrap_err();
+    assert_eq!(err.to_string(), "query prove state of batch 7 error");
+    assert_eq!(decision.reason, None);
+    assert!(rpc.requests("eth_sendTransaction").is_empty());
+}
+
+#[tokio::test]
+async fn test_commit_batch_receipt_timeout() {
+    use crate::{
+        audit::Outcome,
+        mock_rpc::{call_result, call_selector, MockRpc},
+    };
+    use alloy::sol_types::SolValue;
+    use serde_json::json;
+    use std::time::Duration;
+
+    // The node accepts the tx, then drops it: its receipt never comes.
+    let tx_hash = B256::repeat_byte(0x11);
+    let rpc = MockRpc::start(move |method, params| match method {
+        "eth_call" if call_selector(params) == ShadowRollup::isProveSuccessCall::SELECTOR => {
+            call_result(false.abi_encode())
+        }
+        "eth_sendTransaction" => Some(json!(tx_hash)),
+        "eth_getTransactionReceipt" => Some(json!(null)),
+        "eth_blockNumber" => Some(json!("0x1")),
+        _ => None,
+    });
+    let config = Config {
+        simulate_before_send: false,
+        commit_receipt_timeout: Duration::from_millis(200),
+        commit_fee_bumps: 0,
+        ..Config::default()
+    };
+    let (syncer, batch, header) = commit_batch_fixture(&rpc, config);
+
+    let failures = METRICS.shadow_commit_receipt_failures_total.get();
+    let mut decision = Decision::default();
+    let committed = syncer.commit_batch(batch, Bytes::from(header), &mut decision).await.unwrap();
+    assert_eq!(committed, SyncOutcome::CommitFailed);
+    assert_eq!(decision.outcome, Outcome::Failed);
+    assert_eq!(decision.reason.as_deref(), Some("no receipt of commit_batch after 200ms"));
+    assert_eq!(decision.tx_hash, Some(tx_hash));
+    assert_eq!(METRICS.shadow_commit_receipt_failures_total.get(), failures + 1);
+}
+
+#[tokio::test]
+async fn test_commit_batch_replacement() {
+    use crate::{
+        audit::Outcome,
+        mock_rpc::{call_result, call_selector, receipt, transaction, MockRpc},
+    };
+    use alloy::sol_types::SolValue;
+    use serde_json::json;
+    use std::{
+        sync::atomic::{AtomicU8, Ordering},
+        time::Duration,
+    };
+
+    // The first tx is stuck at 10 gwei, its replacement gets mined.
+    let (stuck, replacement) = (B256::repeat_byte(0x11), B256::repeat_byte(0x12));
+    let sends = Arc::new(AtomicU8::new(0x11));
+    let rpc = MockRpc::start(move |method, params| match method {
+        "eth_call" if call_selector(params) == ShadowRollup::isProveSuccessCall::SELECTOR => {
+            call_result(false.abi_encode())
+        }
+        "eth_sendTransaction" => {
+            Some(json!(B256::repeat_byte(sends.fetch_add(1, Ordering::SeqCst))))
+        }
+        "eth_getTransactionByHash" => {
+            let mut tx = transaction(stuck, Address::ZERO, Bytes::new()).unwrap();
+            tx["nonce"] = json!("0x7");
+            tx["maxFeePerGas"] = json!("0x2540be400");
+            tx["maxPriorityFeePerGas"] = json!("0x3b9aca00");
+            Some(tx)
+        }
+        "eth_getTransactionReceipt" if params[0] == json!(replacement) => {
+            receipt(replacement, true)
+        }
+        "eth_getTransactionReceipt" => Some(json!(null)),
+        "eth_blockNumber" => Some(json!("0x1")),
+        _ => None,
+    });
+    let config = Config {
+        simulate_before_send: false,
+        commit_receipt_timeout: Duration::from_millis(200),
+        ..Config::default()
+    };
+    let (syncer, batch, header) = commit_batch_fixture(&rpc, config);
+
+    let replacements = METRICS.shadow_commit_replacements_total.get();
+    let mut decision = Decision::default();
+    let committed =
+        syncer.commit_batch(batch.clone(), Bytes::from(header), &mut decision).await.unwrap();
+    assert_eq!(committed, SyncOutcome::Committed(batch));
+    assert_eq!((decision.outcome, decision.tx_hash), (Outcome::Committed, Some(replacement)));
+    assert_eq!(METRICS.shadow_commit_replacements_total.get(), replacements + 1);
+    // Same nonce, fees 20% higher.
+    let sent = rpc.requests("eth_sendTransaction");
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[1][0]["nonce"], json!("0x7"));
+    assert_eq!(sent[1][0]["maxFeePerGas"], json!("0x2cb417800"));
+    assert_eq!(sent[1][0]["maxPriorityFeePerGas"], json!("0x47868c00"));
+}
+
+#[test]
+fn test_bump_fees() {
+    let fees = Fees::Eip1559 { max_fee_per_gas: 10_000, max_priority_fee_per_gas: 1_000 };
+    assert_eq!(
+        fees.bump(20, 100_000),
+        Some(Fees::Eip1559 { max_fee_per_gas: 12_000, max_priority_fee_per_gas: 1_200 })
+    );
+    // Capped, the tip never above the max fee.
+    assert_eq!(
+        Fees::Eip1559 { max_fee_per_gas: 10_000, max_priority_fee_per_gas: 10_000 }
+            .bump(20, 11_000),
+        Some(Fees::Eip1559 { max_fee_per_gas: 11_000, max_priority_fee_per_gas: 11_000 })
+    );
+    assert_eq!(fees.bump(20, 10_000), None);
+    assert_eq!(Fees::Legacy { gas_price: 0 }.bump(20, 100), Some(Fees::Legacy { gas_price: 1 }));
+}
+
+#[tokio::test]
+async fn test_sync_batch_retry() {
+    use crate::mock_rpc::{error, MockRpc};
+    use serde_json::json;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    // The node is throttled on the first eth_blockNumber, then has no batch to sync.
+    let calls = Arc::new(AtomicUsize::new(0));
+    let rpc = MockRpc::start(move |method, _| match method {
+        "eth_blockNumber" if calls.fetch_add(1, Ordering::SeqCst) == 0 => {
+            error(429, "rate limit exceeded")
+        }
+        "eth_blockNumber" => Some(json!("0x3e8")),
+        "eth_getLogs" => Some(json!([])),
+        _ => None,
+    });
+    let config = Config { rpc_retry_backoff: Duration::from_millis(1), ..Config::default() };
+    let bs = BatchSyncer::new(
+        Address::ZERO,
+        Address::ZERO,
+        rpc.provider(),
+        rpc.provider(),
+        rpc.provider(),
+        config,
+    );
+
+    let mut decision = Decision::default();
+    assert_eq!(bs.sync_batch(&mut decision).await.unwrap(), SyncOutcome::NoBatch);
+    assert_eq!(rpc.requests("eth_blockNumber").len(), 2);
+    // No batch is a result, not retried.
+    assert_eq!(rpc.requests("eth_getLogs").len(), 1);
+
+    // An error of the call itself is not retried either.
+    let rpc = MockRpc::start(|_, _| error(-32602, "invalid argument"));
+    let config = Config { rpc_retry_backoff: Duration::from_millis(1), ..Config::default() };
+    let bs = BatchSyncer::new(
+        Address::ZERO,
+        Address::ZERO,
+        rpc.provider(),
+        rpc.provider(),
+        rpc.provider(),
+        config,
+    );
+    assert!(bs.sync_batch(&mut Decision::default()).await.is_err());
+    assert_eq!(rpc.requests("eth_blockNumber").len(), 1);
+}
+
+#[tokio::test]
+async fn test_verify_blob_hash() {
+    use crate::{
+        batch_header::V0_LEN,
+        mock_rpc::{transaction, MockRpc},
+    };
+    use serde_json::json;
+
+    // Commit tx 0x01 carries blob 0x01..., commit tx 0x02 no blob.
+    let blob_hash = B256::repeat_byte(1);
+    let rpc = MockRpc::start(move |method, params| match method {
+        "eth_getTransactionByHash" if params[0] == json!(B256::repeat_byte(1)) => {
+            let mut tx = transaction(B256::repeat_byte(1), Address::ZERO, Bytes::new())?;
+            tx["type"] = json!("0x3");
+            tx["maxFeePerBlobGas"] = json!("0x1");
+            tx["blobVersionedHashes"] = json!([blob_hash]);
+            Some(tx)
+        }
+        "eth_getTransactionByHash" => {
+            transaction(B256::repeat_byte(2), Address::ZERO, Bytes::new())
+        }
+        _ => None,
+    });
+    let provider = rpc.provider();
+    let header = |blob_hash: B256| {
+        let mut header = vec![0u8; V0_LEN];
+        header[57..89].copy_from_slice(blob_hash.as_slice());
+        Bytes::from(header)
+    };
+    let strict = Config { verify_blob_hash: CheckMode::Strict, ..Config::default() };
+    let mismatches = METRICS.shadow_blob_hash_mismatch_total.get();
+
+    let blob_tx = B256::repeat_byte(1);
+    assert!(verify_blob_hash(&provider, &strict, 7, blob_tx, &header(blob_hash)).await);
+    assert!(!verify_blob_hash(&provider, &strict, 7, blob_tx, &header(B256::ZERO)).await);
+    assert!(METRICS.shadow_blob_hash_mismatch_total.get() > mismatches);
+
+    // A commit without blobs carries the empty blob.
+    let calldata_tx = B256::repeat_byte(2);
+    let empty = header(EMPTY_BLOB_VERSIONED_HASH);
+    assert!(verify_blob_hash(&provider, &strict, 7, calldata_tx, &empty).await);
+    assert!(!verify_blob_hash(&provider, &strict, 7, calldata_tx, &header(blob_hash)).await);
+
+    // Observed only, and off.
+    let observe = Config { verify_blob_hash: CheckMode::Observe, ..Config::default() };
+    assert!(verify_blob_hash(&provider, &observe, 7, blob_tx, &header(B256::ZERO)).await);
+    let requests = rpc.requests("eth_getTransactionByHash").len();
+    assert!(verify_blob_hash(&provider, &Config::default(), 7, blob_tx, &header(B256::ZERO)).await);
+    assert_eq!(rpc.requests("eth_getTransactionByHash").len(), requests);
+}
+
+#[tokio::test]
+async fn test_batch_syncer_builder() {
+    use crate::mock_rpc::MockRpc;
+    use alloy::network::Ethereum;
+
+    let rpc = MockRpc::start(|_, _| None);
+    let builder = || {
+        BatchSyncerBuilder::default()
+            .rollup(Address::repeat_byte(1))
+            .shadow_rollup(Address::repeat_byte(2))
+            .l1_provider(rpc.provider())
+            .l2_provider(rpc.provider())
+            .wallet(rpc.provider())
+    };
+
+    let err = builder().build::<FailoverTransport, Ethereum>().unwrap_err();
+    assert_eq!(err.to_string(), "BatchSyncer config is not set");
+
+    let syncer =
+        builder().config(Config::default()).build::<FailoverTransport, Ethereum>().unwrap();
+    assert_eq!(*syncer.l1_rollup.address(), Address::repeat_byte(1));
+    assert_eq!(*syncer.l1_shadow_rollup.address(), Address::repeat_byte(2));
+}
+
+#[tokio::test]
+async fn test_commit_batch_committed_unproven() {
+    use crate::{
+        audit::Outcome,
+        mock_rpc::{call_result, call_selector, MockRpc},
+    };
+    use alloy::sol_types::{SolCall, SolValue};
+
+    // Batch 7 is committed, batch 9 is not.
+    let rpc = MockRpc::start(|method, params| {
+        if method != "eth_call" {
+            return None;
+        }
+        let input = params[0]["input"].as_str().or(params[0]["data"].as_str()).unwrap();
+        let input: Bytes = input.parse().unwrap();
+        match call_selector(params) {
+            selector if selector == ShadowRollup::isProveSuccessCall::SELECTOR => {
+                call_result(false.abi_encode())
+            }
+            selector if selector == ShadowRollup::committedBatchStoresCall::SELECTOR => {
+                let call =
+                    ShadowRollup::committedBatchStoresCall::abi_decode(&input, false).unwrap();
+                let root = if call._0 == U256::from(7) { B256::repeat_byte(1) } else { B256::ZERO };
+                call_result((root, root, root, root, root, root).abi_encode())
+            }
+            _ => None,
+        }
+    });
+    let syncer = BatchSyncer::new(
+        Address::ZERO,
+        Address::ZERO,
+        rpc.provider(),
+        rpc.provider(),
+        rpc.provider(),
+        Config::default(),
+    )
+    .with_committed_unproven([7, 9]);
+    let batch = |batch_index| BatchInfo {
+        batch_index,
+        start_block: 101,
+        end_block: 110,
+        size_class: SizeClass::Small,
+    };
+    // Refused by the decoding, were it committed.
+    let header = Bytes::from(vec![1u8; 130]);
+
+    let mut decision = Decision::default();
+    let committed = syncer.commit_batch(batch(7), header.clone(), &mut decision).await.unwrap();
+    assert_eq!(committed, SyncOutcome::AlreadyCommitted(batch(7)));
+    assert_eq!(decision.outcome, Outcome::Skipped);
+    assert_eq!(decision.reason.as_deref(), Some("committed, not proven"));
+
+    // No longer committed, committed again.
+    let mut decision = Decision::default();
+    let committed = syncer.commit_batch(batch(9), header, &mut decision).await.unwrap();
+    assert_eq!(committed, SyncOutcome::CommitFailed);
+    assert_eq!(*syncer.committed_unproven.lock().unwrap(), BTreeSet::from([7]));
+}
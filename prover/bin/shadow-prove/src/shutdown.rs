@@ -0,0 +1,110 @@
+//! Graceful shutdown on SIGTERM and SIGINT.
+//!
+//! A signal never interrupts the tick in progress: a `commitBatch` or `proveState` it sent is
+//! followed to its receipt and recorded in the checkpoint. The loop only stops between ticks,
+//! where its sleep is cut short by the signal. Keep the pod's `terminationGracePeriodSeconds`
+//! above the longest tick, which waits up to `SHADOW_PROVING_COMMIT_RECEIPT_TIMEOUT_SECS` for a
+//! commit receipt, or `SHADOW_PROVING_PRIVATE_TX_TIMEOUT_SECS` with a private relay if shorter,
+//! once more per `SHADOW_PROVING_COMMIT_FEE_BUMPS` replacement.
+
+use std::{future::Future, time::Duration};
+use tokio::sync::watch;
+
+/// Whether a shutdown was requested, shared with the loop.
+#[derive(Clone, Debug)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    /// Listen to SIGTERM and SIGINT.
+    pub fn listen() -> Self {
+        let (sender, shutdown) = Self::channel();
+        tokio::spawn(async move {
+            let signal = wait_for_signal().await;
+            log::info!("received {}, stopping after the current tick", signal);
+            sender.send_replace(true);
+        });
+        shutdown
+    }
+
+    fn channel() -> (watch::Sender<bool>, Self) {
+        let (sender, receiver) = watch::channel(false);
+        (sender, Self(receiver))
+    }
+
+    pub fn requested(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Sleep for `duration` unless a shutdown is requested meanwhile; returns whether it was.
+    pub async fn sleep(&mut self, duration: Duration) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => self.requested(),
+            // A dropped sender leaves only the sleep.
+            Ok(_) = self.0.wait_for(|requested| *requested) => true,
+        }
+    }
+
+    /// Like [`Self::sleep`], also cut short once `wake` completes.
+    pub async fn sleep_until(
+        &mut self,
+        duration: Duration,
+        wake: impl Future<Output = ()>,
+    ) -> bool {
+        tokio::select! {
+            requested = self.sleep(duration) => requested,
+            _ = wake => self.requested(),
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).expect("SIGTERM handler can be installed");
+    tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> &'static str {
+    let _ = tokio::signal::ctrl_c().await;
+    "SIGINT"
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_shutdown() {
+    let (sender, mut shutdown) = Shutdown::channel();
+    assert!(!shutdown.requested());
+    assert!(!shutdown.sleep(Duration::from_secs(12)).await);
+
+    // A request cuts the sleep short.
+    let start = tokio::time::Instant::now();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        sender.send_replace(true);
+    });
+    assert!(shutdown.sleep(Duration::from_secs(12)).await);
+    assert_eq!(start.elapsed(), Duration::from_secs(3));
+    assert!(shutdown.requested());
+    assert!(shutdown.sleep(Duration::from_secs(12)).await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_shutdown_sleep_until() {
+    let (sender, mut shutdown) = Shutdown::channel();
+
+    // A wake cuts the sleep short without requesting a shutdown.
+    let start = tokio::time::Instant::now();
+    let wake = tokio::time::sleep(Duration::from_secs(2));
+    assert!(!shutdown.sleep_until(Duration::from_secs(12), wake).await);
+    assert_eq!(start.elapsed(), Duration::from_secs(2));
+
+    assert!(!shutdown.sleep_until(Duration::from_secs(12), std::future::pending()).await);
+    assert_eq!(start.elapsed(), Duration::from_secs(14));
+
+    sender.send_replace(true);
+    assert!(shutdown.sleep_until(Duration::from_secs(12), std::future::pending()).await);
+}
@@ -0,0 +1,209 @@
+//! Transaction signers for the commit/prove transactions.
+//!
+//! The local `PrivateKeySigner` (from `SHADOW_PROVING_PRIVATE_KEY`) is the default. The first of
+//! these set takes precedence over it:
+//!
+//! - `SHADOW_PROVING_REMOTE_SIGNER_URL` switches to [`RemoteSigner`], which keeps the key out of
+//!   this process and delegates signing to a Web3Signer-compatible HTTP service.
+//! - `SHADOW_PROVING_KMS_KEY_ID` signs with an AWS KMS key, credentials and region taken from the
+//!   standard AWS environment. Needs the `aws-kms` feature.
+//! - `SHADOW_PROVING_KEYSTORE` decrypts an encrypted JSON keystore with
+//!   `SHADOW_PROVING_KEYSTORE_PASSWORD`.
+//!
+//! Expected remote API:
+//!
+//! ```text
+//! POST {SHADOW_PROVING_REMOTE_SIGNER_URL}/api/v1/eth1/sign/{address}
+//! Content-Type: application/json
+//!
+//! {"data": "0x<32 bytes digest>"}
+//! ```
+//!
+//! The service must sign the digest as-is (no extra hashing or EIP-191 prefix) and answer `200`
+//! with the 65-byte `r || s || v` signature as a 0x-prefixed hex string, optionally JSON-quoted.
+//! `{address}` is `SHADOW_PROVING_REMOTE_SIGNER_ADDRESS`, the account the service signs for.
+
+use alloy::{
+    consensus::SignableTransaction,
+    network::{EthereumWallet, TxSigner},
+    primitives::{Address, ChainId, B256},
+    signers::{local::PrivateKeySigner, Error, Result, Signature, Signer},
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use std::path::Path;
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+/// Build the wallet used to sign shadow rollup transactions, together with its address.
+pub async fn wallet_from_env() -> Result<(EthereumWallet, Address), anyhow::Error> {
+    if let Some(url) = env("SHADOW_PROVING_REMOTE_SIGNER_URL") {
+        let address: Address = env("SHADOW_PROVING_REMOTE_SIGNER_ADDRESS")
+            .context("SHADOW_PROVING_REMOTE_SIGNER_ADDRESS is missing")?
+            .parse()
+            .context("parse SHADOW_PROVING_REMOTE_SIGNER_ADDRESS")?;
+        log::info!("Using remote signer for {:?} at {}", address, url);
+        return Ok((EthereumWallet::from(RemoteSigner::new(url, address)), address));
+    }
+    if let Some(key_id) = env("SHADOW_PROVING_KMS_KEY_ID") {
+        return kms_wallet(key_id).await;
+    }
+    if let Some(path) = env("SHADOW_PROVING_KEYSTORE") {
+        let password = std::env::var("SHADOW_PROVING_KEYSTORE_PASSWORD")
+            .context("SHADOW_PROVING_KEYSTORE_PASSWORD is missing")?;
+        let signer = keystore_signer(Path::new(&path), &password)?;
+        let address = signer.address();
+        log::info!("Using keystore signer {:?} from {}", address, path);
+        return Ok((EthereumWallet::from(signer), address));
+    }
+    let signer: PrivateKeySigner = env("SHADOW_PROVING_PRIVATE_KEY")
+        .context("SHADOW_PROVING_PRIVATE_KEY is missing")?
+        .parse()
+        .context("parse SHADOW_PROVING_PRIVATE_KEY")?;
+    let address = signer.address();
+    Ok((EthereumWallet::from(signer), address))
+}
+
+/// Decrypt the JSON keystore at `path`.
+pub fn keystore_signer(path: &Path, password: &str) -> Result<PrivateKeySigner, anyhow::Error> {
+    PrivateKeySigner::decrypt_keystore(path, password)
+        .with_context(|| format!("decrypt keystore {}", path.display()))
+}
+
+#[cfg(feature = "aws-kms")]
+async fn kms_wallet(key_id: String) -> Result<(EthereumWallet, Address), anyhow::Error> {
+    use alloy::signers::aws::AwsSigner;
+
+    let aws = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+    let signer = AwsSigner::new(aws_sdk_kms::Client::new(&aws), key_id.clone(), None)
+        .await
+        .with_context(|| format!("load KMS key {}", key_id))?;
+    let address = signer.address();
+    log::info!("Using KMS signer {:?} with key {}", address, key_id);
+    Ok((EthereumWallet::from(signer), address))
+}
+
+#[cfg(not(feature = "aws-kms"))]
+async fn kms_wallet(_key_id: String) -> Result<(EthereumWallet, Address), anyhow::Error> {
+    anyhow::bail!("SHADOW_PROVING_KMS_KEY_ID is set but this build lacks the aws-kms feature")
+}
+
+/// Signer delegating to an external Web3Signer-compatible signing service.
+#[derive(Clone, Debug)]
+pub struct RemoteSigner {
+    client: reqwest::Client,
+    url: String,
+    address: Address,
+    chain_id: Option<ChainId>,
+}
+
+impl RemoteSigner {
+    pub fn new(url: String, address: Address) -> Self {
+        Self { client: reqwest::Client::new(), url, address, chain_id: None }
+    }
+
+    fn sign_url(&self) -> String {
+        format!("{}/api/v1/eth1/sign/{:?}", self.url.trim_end_matches('/'), self.address)
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    async fn sign_hash(&self, hash: &B256) -> Result<Signature> {
+        let body = serde_json::json!({ "data": alloy::hex::encode_prefixed(hash) });
+        let response = self
+            .client
+            .post(self.sign_url())
+            .json(&body)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(Error::other)?;
+        let text = response.text().await.map_err(Error::other)?;
+
+        text.trim().trim_matches('"').parse::<Signature>().map_err(Error::other)
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> Option<ChainId> {
+        self.chain_id
+    }
+
+    fn set_chain_id(&mut self, chain_id: Option<ChainId>) {
+        self.chain_id = chain_id;
+    }
+}
+
+#[async_trait]
+impl TxSigner<Signature> for RemoteSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> Result<Signature> {
+        if let Some(chain_id) = self.chain_id {
+            if !tx.set_chain_id_checked(chain_id) {
+                return Err(Error::TransactionChainIdMismatch {
+                    signer: chain_id,
+                    tx: tx.chain_id().unwrap_or_default(),
+                });
+            }
+        }
+        self.sign_hash(&tx.signature_hash()).await
+    }
+}
+
+#[tokio::test]
+async fn test_remote_signer() {
+    use alloy::signers::SignerSync;
+    use axum::{extract::Json, routing::post, Router};
+
+    // Mock signing service backed by a local key.
+    let key = PrivateKeySigner::random();
+    let address = key.address();
+    let service = key.clone();
+    let app = Router::new().route(
+        "/api/v1/eth1/sign/:identifier",
+        post(move |Json(body): Json<serde_json::Value>| async move {
+            let hash: B256 = body["data"].as_str().unwrap().parse().unwrap();
+            service.sign_hash_sync(&hash).unwrap().to_string()
+        }),
+    );
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+    let signer = RemoteSigner::new(url, address);
+    let hash = B256::repeat_byte(0x11);
+    let signature = signer.sign_hash(&hash).await.unwrap();
+
+    assert_eq!(signature.recover_address_from_prehash(&hash).unwrap(), address);
+    assert_eq!(signature, key.sign_hash_sync(&hash).unwrap());
+}
+
+#[test]
+fn test_keystore_signer() {
+    let dir = std::env::temp_dir().join(format!("shadow-keystore-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (key, name) = PrivateKeySigner::encrypt_keystore(
+        &dir,
+        &mut rand::thread_rng(),
+        B256::repeat_byte(0x11),
+        "secret",
+        None,
+    )
+    .unwrap();
+
+    let path = dir.join(name);
+    assert_eq!(keystore_signer(&path, "secret").unwrap().address(), key.address());
+    assert!(keystore_signer(&path, "wrong").is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,78 @@
+//! Size classes of batches, labelling the prove outcome and duration metrics.
+//!
+//! A batch is `large` from `SHADOW_PROVING_LARGE_BATCH_BLOCKS` blocks or
+//! `SHADOW_PROVING_LARGE_BATCH_TXNS` transactions, `medium` from the medium thresholds and
+//! `small` below them. Batches queued by a prover without size classes are `unknown`. The label
+//! never takes another value, whatever the batch.
+
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeClass {
+    Small,
+    Medium,
+    Large,
+    #[default]
+    Unknown,
+}
+
+impl SizeClass {
+    /// Class of a batch of `blocks` blocks holding `txns` transactions.
+    pub fn classify(blocks: u64, txns: u64, config: &Config) -> Self {
+        if blocks >= config.large_batch_blocks || txns >= config.large_batch_txns {
+            Self::Large
+        } else if blocks >= config.medium_batch_blocks || txns >= config.medium_batch_txns {
+            Self::Medium
+        } else {
+            Self::Small
+        }
+    }
+
+    /// Value of the `size_class` metric label.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Small => "small",
+            Self::Medium => "medium",
+            Self::Large => "large",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+#[test]
+fn test_classify() {
+    let config = Config {
+        medium_batch_blocks: 100,
+        medium_batch_txns: 200,
+        large_batch_blocks: 200,
+        large_batch_txns: 400,
+        ..Config::default()
+    };
+    for (blocks, txns, class) in [
+        (1, 0, SizeClass::Small),
+        (99, 199, SizeClass::Small),
+        (100, 0, SizeClass::Medium),
+        (1, 200, SizeClass::Medium),
+        (199, 399, SizeClass::Medium),
+        (200, 0, SizeClass::Large),
+        (1, 400, SizeClass::Large),
+        // Either dimension is enough.
+        (99, 400, SizeClass::Large),
+        (300, 600, SizeClass::Large),
+    ] {
+        assert_eq!(
+            SizeClass::classify(blocks, txns, &config),
+            class,
+            "{} blocks, {} txns",
+            blocks,
+            txns
+        );
+    }
+
+    // Older queue entries have no class.
+    let batch: crate::BatchInfo =
+        serde_json::from_str(r#"{"batch_index":7,"start_block":101,"end_block":110}"#).unwrap();
+    assert_eq!(batch.size_class, SizeClass::Unknown);
+}
@@ -0,0 +1,99 @@
+//! What the prover is doing, served as JSON at `/status`.
+//!
+//! The main loop records the decisions of every tick: the last batch a stage processed, the last
+//! successful prove and the last error. Unlike `/metrics` it tells the error itself.
+
+use crate::audit::{Outcome, Tick};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StatusReport {
+    /// Last batch a stage committed, proved, skipped or failed.
+    pub last_batch_index: Option<u64>,
+    pub last_proven_batch_index: Option<u64>,
+    /// Unix time of the tick the last batch was proven in.
+    pub last_proven_timestamp: Option<u64>,
+    pub last_error: Option<String>,
+    /// Unix time of the tick the last error happened in.
+    pub last_error_timestamp: Option<u64>,
+    /// Unix time the process started.
+    pub started_at: u64,
+    pub uptime_secs: u64,
+}
+
+/// Status of the prover, shared with the `/status` endpoint.
+#[derive(Clone, Debug)]
+pub struct Status(Arc<Mutex<StatusReport>>);
+
+impl Status {
+    /// Status of a process started at the unix time `started_at`.
+    pub fn new(started_at: u64) -> Self {
+        Self(Arc::new(Mutex::new(StatusReport { started_at, ..StatusReport::default() })))
+    }
+
+    /// The status at the unix time `now`.
+    pub fn get(&self, now: u64) -> StatusReport {
+        let mut report = self.0.lock().unwrap().clone();
+        report.uptime_secs = now.saturating_sub(report.started_at);
+        report
+    }
+
+    /// Record the decisions of the stages of `tick`.
+    pub fn record_tick(&self, tick: &Tick) {
+        let mut report = self.0.lock().unwrap();
+        for decision in [&tick.commit, &tick.prove].into_iter().flatten() {
+            if decision.batch_index.is_some() {
+                report.last_batch_index = decision.batch_index;
+            }
+            match decision.outcome {
+                Outcome::Proven => {
+                    report.last_proven_batch_index = decision.batch_index;
+                    report.last_proven_timestamp = Some(tick.timestamp);
+                }
+                Outcome::Failed => {
+                    report.last_error = decision.reason.clone();
+                    report.last_error_timestamp = Some(tick.timestamp);
+                }
+                Outcome::Committed | Outcome::Skipped => (),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_record_tick() {
+    use crate::audit::Decision;
+    use alloy::primitives::B256;
+
+    let status = Status::new(1000);
+    assert_eq!(
+        status.get(1060),
+        StatusReport { started_at: 1000, uptime_secs: 60, ..StatusReport::default() }
+    );
+
+    let mut committed = Decision { batch_index: Some(7), ..Decision::default() };
+    committed.commit(B256::repeat_byte(1));
+    let mut proven = Decision { batch_index: Some(6), ..Decision::default() };
+    proven.prove(B256::repeat_byte(2));
+    status.record_tick(&Tick { timestamp: 1010, commit: Some(committed), prove: Some(proven) });
+
+    let mut failed = Decision { batch_index: Some(7), ..Decision::default() };
+    failed.fail("prove batch 7 error: timeout");
+    // A stage that processed no batch keeps the last one.
+    let idle = Decision::default();
+    status.record_tick(&Tick { timestamp: 1020, commit: Some(idle), prove: Some(failed) });
+
+    assert_eq!(
+        status.get(1030),
+        StatusReport {
+            last_batch_index: Some(7),
+            last_proven_batch_index: Some(6),
+            last_proven_timestamp: Some(1010),
+            last_error: Some(String::from("prove batch 7 error: timeout")),
+            last_error_timestamp: Some(1020),
+            started_at: 1000,
+            uptime_secs: 30,
+        }
+    );
+}
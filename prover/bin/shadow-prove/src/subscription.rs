@@ -0,0 +1,75 @@
+//! Wake-up of the sync loop on the `CommitBatch` logs of the rollup, subscribed to over
+//! `SHADOW_PROVING_L1_WS`.
+//!
+//! The subscription only cuts the sleep between ticks short: batches are still read over
+//! `SHADOW_PROVING_L1_RPC`, in the lookback window. Without the endpoint, or while the
+//! subscription is down, the loop polls every `SHADOW_PROVING_POLL_INTERVAL_SECS`.
+
+use crate::Rollup;
+use alloy::{
+    primitives::Address,
+    providers::{Provider, ProviderBuilder, WsConnect},
+    rpc::types::Filter,
+    sol_types::SolEvent,
+};
+use futures::StreamExt;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::Notify;
+
+/// Commits seen since the loop last waited, coalesced into a single wake-up.
+#[derive(Clone, Debug, Default)]
+pub struct Commits(Arc<Notify>);
+
+impl Commits {
+    /// Subscribe to the commits of `rollup` at `ws_url`, resubscribing `retry` after the
+    /// subscription drops; never wakes without an endpoint.
+    pub fn spawn(ws_url: Option<String>, rollup: Address, retry: Duration) -> Self {
+        let commits = Self::default();
+        let Some(ws_url) = ws_url else {
+            return commits;
+        };
+        let notify = commits.0.clone();
+        tokio::spawn(async move {
+            loop {
+                match subscribe(&ws_url, rollup, &notify).await {
+                    Ok(()) => log::warn!("commit_batch subscription closed, polling meanwhile"),
+                    Err(e) => {
+                        log::warn!("commit_batch subscription error, polling meanwhile: {:#}", e)
+                    }
+                }
+                tokio::time::sleep(retry).await;
+            }
+        });
+        commits
+    }
+
+    /// Wait for a commit seen since the last wait.
+    pub async fn wait(&self) {
+        self.0.notified().await
+    }
+}
+
+async fn subscribe(ws_url: &str, rollup: Address, notify: &Notify) -> Result<(), anyhow::Error> {
+    let provider = ProviderBuilder::new().on_ws(WsConnect::new(ws_url)).await?;
+    let filter = Filter::new().address(rollup).event_signature(Rollup::CommitBatch::SIGNATURE_HASH);
+    let mut logs = provider.subscribe_logs(&filter).await?.into_stream();
+    log::info!("subscribed to the commit_batch logs of {:?}", rollup);
+    while let Some(log) = logs.next().await {
+        log::debug!("commit_batch log in block {:?}, waking the loop", log.block_number);
+        notify.notify_one();
+    }
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_commits() {
+    // Without an endpoint the loop only polls.
+    let commits = Commits::spawn(None, Address::ZERO, Duration::from_secs(12));
+    assert!(tokio::time::timeout(Duration::from_secs(60), commits.wait()).await.is_err());
+
+    // Commits seen during a tick wake the next wait once.
+    commits.0.notify_one();
+    commits.0.notify_one();
+    commits.wait().await;
+    assert!(tokio::time::timeout(Duration::from_secs(60), commits.wait()).await.is_err());
+}
@@ -0,0 +1,184 @@
+//! Chains the prover reads batches from and writes shadow batches to.
+//!
+//! The rollup whose batches are shadow proven is read through `SHADOW_PROVING_L1_RPC`, the read
+//! chain. The shadow rollup is committed to and proven through `SHADOW_PROVING_VERIFY_L1_RPC`, the
+//! write chain, by the signer. Both are usually the same L1, but the shadow rollup may live on
+//! another one, e.g. a cheaper chain. At startup each endpoint is checked to answer the chain id
+//! pinned by `SHADOW_PROVING_L1_CHAIN_ID` and `SHADOW_PROVING_VERIFY_L1_CHAIN_ID` when set. The
+//! signer must sign for the write chain, and each rollup must have code on its own chain.
+//!
+//! Confirmation depth is per chain:
+//!
+//! - A batch is read as soon as its `CommitBatch` log is on the read chain. A reorg there may
+//!   replace the original commit while its shadow copy stays on the write chain.
+//!   `SHADOW_PROVING_BATCH_BUFFER` keeps the proving behind the newest batches and should cover the
+//!   reorg depth of the read chain.
+//! - Commits and proofs are taken as done on their write chain receipt. On a write chain with
+//!   deeper reorgs, a dropped commit or proof is only noticed when the batch is read back from the
+//!   shadow rollup.
+
+use crate::{config::Config, failover::FailoverTransport};
+use alloy::{
+    network::Network,
+    primitives::Address,
+    providers::{Provider, RootProvider},
+    transports::Transport,
+};
+use anyhow::Context;
+
+/// Chain ids of the read and write chains, as answered by their endpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Topology {
+    pub read_chain_id: u64,
+    pub write_chain_id: u64,
+}
+
+impl Topology {
+    /// Whether the shadow rollup lives on another chain than the rollup.
+    pub fn cross_chain(&self) -> bool {
+        self.read_chain_id != self.write_chain_id
+    }
+}
+
+/// Check that every component targets its intended chain.
+pub async fn check_topology<T, P, N>(
+    l1_provider: &RootProvider<FailoverTransport>,
+    verify_provider: &RootProvider<FailoverTransport>,
+    signer: &P,
+    rollup: Address,
+    shadow_rollup: Address,
+    config: &Config,
+) -> Result<Topology, anyhow::Error>
+where
+    P: Provider<T, N>,
+    T: Transport + Clone,
+    N: Network,
+{
+    let read_chain_id = chain_id(l1_provider, "SHADOW_PROVING_L1_RPC", config.l1_chain_id).await?;
+    let write_chain_id =
+        chain_id(verify_provider, "SHADOW_PROVING_VERIFY_L1_RPC", config.verify_l1_chain_id)
+            .await?;
+    let signer_chain_id = signer.get_chain_id().await.context("read the signer chain id")?;
+    if signer_chain_id != write_chain_id {
+        anyhow::bail!(
+            "signer targets chain {} instead of the shadow rollup chain {}",
+            signer_chain_id,
+            write_chain_id
+        );
+    }
+    expect_code(l1_provider, rollup, "rollup", read_chain_id).await?;
+    expect_code(verify_provider, shadow_rollup, "shadow rollup", write_chain_id).await?;
+    Ok(Topology { read_chain_id, write_chain_id })
+}
+
+async fn chain_id(
+    provider: &RootProvider<FailoverTransport>,
+    rpc_var: &str,
+    expected: Option<u64>,
+) -> Result<u64, anyhow::Error> {
+    let chain_id =
+        provider.get_chain_id().await.with_context(|| format!("read the {} chain id", rpc_var))?;
+    if let Some(expected) = expected.filter(|expected| *expected != chain_id) {
+        anyhow::bail!("{} is on chain {}, {} was expected", rpc_var, chain_id, expected);
+    }
+    Ok(chain_id)
+}
+
+async fn expect_code(
+    provider: &RootProvider<FailoverTransport>,
+    address: Address,
+    name: &str,
+    chain_id: u64,
+) -> Result<(), anyhow::Error> {
+    let code = provider
+        .get_code_at(address)
+        .await
+        .with_context(|| format!("read the {} code on chain {}", name, chain_id))?;
+    if code.is_empty() {
+        anyhow::bail!("{} {:?} has no code on chain {}", name, address, chain_id);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_check_topology() {
+    use crate::mock_rpc::MockRpc;
+    use serde_json::json;
+
+    let rollup = Address::repeat_byte(0x11);
+    let shadow_rollup = Address::repeat_byte(0x22);
+    // Each chain only holds its own contract.
+    let chain = |chain_id: u64, contract: Address| {
+        MockRpc::start(move |method, params| match method {
+            "eth_chainId" => Some(json!(format!("0x{:x}", chain_id))),
+            "eth_getCode" if params[0] == json!(contract) => Some(json!("0x6080")),
+            "eth_getCode" => Some(json!("0x")),
+            _ => None,
+        })
+    };
+    let read = chain(1, rollup);
+    let write = chain(10, shadow_rollup);
+
+    let config = Config { l1_chain_id: Some(1), verify_l1_chain_id: Some(10), ..Config::default() };
+    let topology = check_topology(
+        &read.provider(),
+        &write.provider(),
+        &write.provider(),
+        rollup,
+        shadow_rollup,
+        &config,
+    )
+    .await
+    .unwrap();
+    assert_eq!(topology, Topology { read_chain_id: 1, write_chain_id: 10 });
+    assert!(topology.cross_chain());
+    // Each contract was looked up on its own chain only.
+    assert_eq!(read.requests("eth_getCode").len(), 1);
+    assert_eq!(read.requests("eth_getCode")[0][0], json!(rollup));
+    assert_eq!(write.requests("eth_getCode").len(), 1);
+    assert_eq!(write.requests("eth_getCode")[0][0], json!(shadow_rollup));
+
+    let check = |l1: &MockRpc, verify: &MockRpc, signer: &MockRpc, config: Config| {
+        let (l1, verify, signer) = (l1.provider(), verify.provider(), signer.provider());
+        async move {
+            check_topology(&l1, &verify, &signer, rollup, shadow_rollup, &config)
+                .await
+                .unwrap_err()
+                .to_string()
+        }
+    };
+    // Endpoints swapped.
+    assert_eq!(
+        check(&write, &read, &read, config.clone()).await,
+        "SHADOW_PROVING_L1_RPC is on chain 10, 1 was expected"
+    );
+    // Signer on the read chain.
+    assert_eq!(
+        check(&read, &write, &read, config.clone()).await,
+        "signer targets chain 1 instead of the shadow rollup chain 10"
+    );
+    // Shadow rollup not deployed on the write chain.
+    let other = chain(10, rollup);
+    assert_eq!(
+        check(&read, &other, &other, config).await,
+        format!("shadow rollup {:?} has no code on chain 10", shadow_rollup)
+    );
+
+    // Without pinned chain ids, the same chain serves both.
+    let both = MockRpc::start(|method, _| match method {
+        "eth_chainId" => Some(json!("0x1")),
+        "eth_getCode" => Some(json!("0x6080")),
+        _ => None,
+    });
+    let topology = check_topology(
+        &both.provider(),
+        &both.provider(),
+        &both.provider(),
+        rollup,
+        shadow_rollup,
+        &Config::default(),
+    )
+    .await
+    .unwrap();
+    assert!(!topology.cross_chain());
+}
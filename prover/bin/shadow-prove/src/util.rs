@@ -1,4 +1,16 @@
-use std::{env::var, str::FromStr};
+use alloy::transports::{RpcError, TransportError};
+use std::{
+    collections::hash_map::RandomState,
+    env::var,
+    fmt,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Upper bound of a single retry delay of [`retry_with_backoff`].
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
 
 pub fn call_prover(param: String, function: &str) -> Option<String> {
     let prover_rpc = var("SHADOW_PROVING_PROVER_RPC").expect("Cannot detect PROVER_RPC env var");
@@ -38,6 +50,10 @@ pub fn read_env_var<T: Clone + FromStr>(var_name: &'static str, default: T) -> T
         .unwrap_or(default)
 }
 
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
 pub fn read_parse_env<T: Clone + FromStr>(var_name: &'static str) -> T {
     let var_value =
         std::env::var(var_name).unwrap_or_else(|_| panic!("Can not read env of {}", var_name));
@@ -47,6 +63,109 @@ pub fn read_parse_env<T: Clone + FromStr>(var_name: &'static str) -> T {
     }
 }
 
+/// Retries of a failed call and delay before the first retry, doubled per retry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Backoff {
+    pub retries: u32,
+    pub base: Duration,
+}
+
+/// Run `op` until it succeeds, retrying a failure `retriable` accepts up to `backoff.retries`
+/// times after an exponential, jittered delay. Other failures are returned at once.
+pub async fn retry_with_backoff<T, E, F, Fut>(
+    name: &str,
+    backoff: Backoff,
+    retriable: impl Fn(&E) -> bool,
+    mut op: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: fmt::Display,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < backoff.retries && retriable(&e) => {
+                let delay = retry_delay(backoff.base, attempt);
+                log::warn!(
+                    "{} failed ({}/{} retries), retry in {:?}: {}",
+                    name,
+                    attempt + 1,
+                    backoff.retries,
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// `base * 2^attempt`, capped, plus up to half of it as jitter.
+fn retry_delay(base: Duration, attempt: u32) -> Duration {
+    let delay = base.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_RETRY_DELAY);
+    let jitter = RandomState::new().build_hasher().finish() % (delay.as_millis() as u64 / 2 + 1);
+    delay + Duration::from_millis(jitter)
+}
+
+/// Whether a provider call may succeed if sent again: the request did not get through, or the
+/// node answered with an error it flags as temporary. Errors of the call itself are final.
+pub fn is_transient(error: &TransportError) -> bool {
+    match error {
+        RpcError::Transport(_) | RpcError::NullResp => true,
+        RpcError::ErrorResp(payload) => payload.is_retry_err(),
+        _ => false,
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_retry_with_backoff() {
+    use std::cell::Cell;
+
+    let backoff = Backoff { retries: 3, base: Duration::from_millis(100) };
+    let retriable = |e: &String| e.starts_with("connection");
+    // Fails `failures` times with `error`, then answers the number of calls.
+    let calls = Cell::new(0);
+    let flaky = |failures: u32, error: &'static str| {
+        let calls = &calls;
+        move || {
+            calls.set(calls.get() + 1);
+            let result =
+                if calls.get() <= failures { Err(error.to_string()) } else { Ok(calls.get()) };
+            async move { result }
+        }
+    };
+
+    // Recovers from transient failures, after 100ms then 200ms plus jitter.
+    let start = tokio::time::Instant::now();
+    let result =
+        retry_with_backoff("eth_blockNumber", backoff, retriable, flaky(2, "connection reset"));
+    assert_eq!(result.await, Ok(3));
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert!(start.elapsed() <= Duration::from_millis(450));
+
+    // Gives up after the retries.
+    calls.set(0);
+    let result =
+        retry_with_backoff("eth_blockNumber", backoff, retriable, flaky(10, "connection reset"));
+    assert_eq!(result.await, Err(String::from("connection reset")));
+    assert_eq!(calls.get(), 4);
+
+    // A final error is not retried.
+    calls.set(0);
+    let result =
+        retry_with_backoff("eth_call", backoff, retriable, flaky(10, "execution reverted"));
+    assert_eq!(result.await, Err(String::from("execution reverted")));
+    assert_eq!(calls.get(), 1);
+
+    // The delay is capped.
+    assert!(retry_delay(Duration::from_secs(20), 5) <= MAX_RETRY_DELAY * 3 / 2);
+}
+
 #[tokio::test]
 async fn test_call_prover() {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();